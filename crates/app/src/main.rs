@@ -6,11 +6,14 @@ mod state;
 use args::Args;
 use clap::{Parser, Subcommand};
 use op::Op;
-use ops::{Bucket, Init, Service, Version};
+use ops::{Bucket, Gc, Init, Mount, Service, Version};
+use tracing_subscriber::EnvFilter;
 
 command_enum! {
     (Bucket, Bucket),
+    (Gc, Gc),
     (Init, Init),
+    (Mount, Mount),
     (Service, Service),
     (Version, Version),
 }
@@ -19,6 +22,19 @@ command_enum! {
 async fn main() {
     let args = Args::parse();
 
+    // Commands that keep running report what they do through tracing, while
+    //  the service sets up its own logging
+    if !matches!(args.command, Command::Service(_)) {
+        let filter =
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,jax=info"));
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_target(false)
+            .without_time()
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
     // Build context - always has API client initialized
     let ctx = match op::OpContext::new(args.remote, args.config_path) {
         Ok(ctx) => ctx,
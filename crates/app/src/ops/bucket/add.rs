@@ -2,10 +2,14 @@ use clap::Args;
 use reqwest::multipart;
 use service::http_server::api::client::ApiError;
 use service::http_server::api::v0::bucket::add::AddResponse;
+use service::http_server::api::v0::bucket::add_dir::IgnoreGlobs;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
+use super::local::{upload_dir, walk_dir};
+
 #[derive(Args, Debug, Clone)]
 pub struct Add {
     /// Bucket ID (or use --name)
@@ -16,13 +20,23 @@ pub struct Add {
     #[arg(long, group = "bucket_identifier")]
     pub name: Option<String>,
 
-    /// Absolute path to file on filesystem
+    /// Path to a file or directory on the filesystem
     #[arg(long)]
     pub path: String,
 
-    /// Path in bucket where file should be mounted
+    /// Path in bucket where the file should be mounted, or for a
+    ///  directory, the directory its contents should be added under
     #[arg(long)]
     pub mount_path: String,
+
+    /// Glob of files to leave out when adding a directory, matched against
+    ///  paths relative to it and each of their components (repeatable)
+    #[arg(long = "ignore", value_name = "GLOB")]
+    pub ignore: Vec<String>,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -35,6 +49,12 @@ pub enum BucketAddError {
     Reqwest(#[from] reqwest::Error),
     #[error("Either --bucket-id or --name must be provided")]
     NoBucketIdentifier,
+    #[error("Invalid ignore glob: {0}")]
+    InvalidGlob(#[from] glob::PatternError),
+    #[error("Failed to read directory: {0}")]
+    Walk(#[from] walkdir::Error),
+    #[error("No files to add in {0}")]
+    NoFiles(String),
 }
 
 #[async_trait::async_trait]
@@ -62,14 +82,27 @@ impl crate::op::Op for Add {
             env::current_dir()?.join(&path)
         };
 
-        // Read the file
-        let file_data = std::fs::read(&absolute_path)?;
+        if tokio::fs::metadata(&absolute_path).await?.is_dir() {
+            return self.add_dir(ctx, bucket_id, &absolute_path).await;
+        }
 
-        // Build multipart form
+        // Stream the file rather than reading it into memory
+        let file = tokio::fs::File::open(&absolute_path).await?;
+        let file_len = file.metadata().await?.len();
+        let file_part = multipart::Part::stream_with_length(
+            reqwest::Body::wrap_stream(ReaderStream::new(file)),
+            file_len,
+        );
+
+        // Build multipart form (the server expects the file last)
         let form = multipart::Form::new()
             .text("bucket_id", bucket_id.to_string())
-            .text("mount_path", self.mount_path.clone())
-            .part("file", multipart::Part::bytes(file_data));
+            .text("mount_path", self.mount_path.clone());
+        let form = match &self.message {
+            Some(message) => form.text("message", message.clone()),
+            None => form,
+        };
+        let form = form.part("file", file_part);
 
         // Send multipart request
         let url = client.base_url().join("/api/v0/bucket/add").unwrap();
@@ -95,3 +128,39 @@ impl crate::op::Op for Add {
         ))
     }
 }
+
+impl Add {
+    /// Add every file under a directory, keeping their relative paths,
+    ///  as a single version of the bucket
+    async fn add_dir(
+        &self,
+        ctx: &crate::op::OpContext,
+        bucket_id: Uuid,
+        dir: &Path,
+    ) -> Result<String, BucketAddError> {
+        let globs = IgnoreGlobs::new(&self.ignore)?;
+        let tree = walk_dir(dir, &globs)?;
+        if tree.files.is_empty() {
+            return Err(BucketAddError::NoFiles(dir.display().to_string()));
+        }
+
+        let response = upload_dir(
+            &ctx.client,
+            bucket_id,
+            &self.mount_path,
+            self.message.as_deref(),
+            tree.files,
+            &[],
+            &[],
+        )
+        .await?;
+
+        Ok(format!(
+            "Added {} files to bucket under {}, ignoring {} (link: {})",
+            response.added.len(),
+            response.mount_path,
+            tree.ignored,
+            response.link.hash()
+        ))
+    }
+}
@@ -1,5 +1,6 @@
 use base64::Engine;
 use clap::Args;
+use common::linked_data::Hash;
 use service::http_server::api::client::ApiError;
 use service::http_server::api::v0::bucket::cat::{CatRequest, CatResponse};
 use uuid::Uuid;
@@ -17,6 +18,11 @@ pub struct Cat {
     /// Path in bucket to read
     #[arg(long)]
     pub path: String,
+
+    /// Version of the bucket to read from, as shown by `jax bucket log`
+    ///  (defaults to the current version)
+    #[arg(long)]
+    pub version: Option<Hash>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -50,6 +56,7 @@ impl crate::op::Op for Cat {
         let request = CatRequest {
             bucket_id,
             path: self.path.clone(),
+            version: self.version,
         };
 
         // Call API
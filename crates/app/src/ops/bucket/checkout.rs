@@ -0,0 +1,296 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use clap::Args;
+use common::linked_data::Hash;
+use service::http_server::api::client::{ApiClient, ApiError};
+use service::http_server::api::v0::bucket::add_dir::IgnoreGlobs;
+use service::http_server::api::v0::bucket::log::LogRequest;
+use service::http_server::api::v0::bucket::ls::PathInfo;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use super::local::{list_bucket_dir, mtime_ms, same_contents, walk_dir, BucketPath, Progress};
+
+#[derive(Args, Debug, Clone)]
+pub struct Checkout {
+    /// Directory of a bucket to check out, as `<bucket>:<path>`, where the
+    ///  bucket is a name or an ID
+    pub source: BucketPath,
+
+    /// Local directory to write it to, created if it doesn't exist
+    pub local: PathBuf,
+
+    /// Version of the bucket to check out, as shown by `jax bucket log`
+    ///  (defaults to the current version)
+    #[arg(long)]
+    pub version: Option<Hash>,
+
+    /// Also delete local files and directories that aren't in the bucket
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketCheckoutError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Failed to read directory: {0}")]
+    Walk(#[from] walkdir::Error),
+    #[error("{0} not found in the bucket")]
+    NotFound(String),
+    #[error("{0} is in the way, use --delete to replace it")]
+    InTheWay(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Checkout {
+    type Error = BucketCheckoutError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+        let bucket_id = self.source.bucket_id(&mut client).await?;
+
+        let dir = if self.local.is_absolute() {
+            self.local.clone()
+        } else {
+            env::current_dir()?.join(&self.local)
+        };
+
+        // Pin the version, so every file comes from the one that was listed
+        let version = match self.version {
+            Some(version) => version,
+            None => {
+                let request = LogRequest {
+                    bucket_id,
+                    limit: Some(1),
+                };
+                let log = client.call(request).await?;
+                let current = log
+                    .versions
+                    .first()
+                    .ok_or_else(|| BucketCheckoutError::NotFound(self.source.to_string()))?;
+                *current.link.hash()
+            }
+        };
+        let remote =
+            list_bucket_dir(&mut client, bucket_id, &self.source.path, Some(version)).await?;
+        if remote.is_empty() && self.source.path != "/" {
+            return Err(BucketCheckoutError::NotFound(self.source.to_string()));
+        }
+
+        // Local files and directories in the way of what's in the bucket,
+        //  or not in it at all
+        let mut delete: Vec<String> = Vec::new();
+        let mut fetch = Vec::new();
+        let mut unchanged = 0;
+        for (name, info) in &remote {
+            let path = dir.join(name);
+            let metadata = match tokio::fs::symlink_metadata(&path).await {
+                Ok(metadata) => metadata,
+                // Not there, or under a file that's in the way
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+                    ) =>
+                {
+                    if !info.is_dir {
+                        fetch.push((name, info));
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let same_type = if info.is_dir {
+                metadata.is_dir()
+            } else {
+                metadata.is_file()
+            };
+            if !same_type {
+                if !self.delete {
+                    return Err(BucketCheckoutError::InTheWay(path.display().to_string()));
+                }
+                delete.push(name.clone());
+                if !info.is_dir {
+                    fetch.push((name, info));
+                }
+                continue;
+            }
+            if info.is_dir {
+                continue;
+            }
+            let mtime = mtime_ms(&metadata);
+            if same_contents(&path, metadata.len(), mtime, info).await? {
+                // Record the bucket's modification time, so the file isn't
+                //  hashed again next time
+                if !self.dry_run && info.mtime.is_some() && info.mtime != mtime {
+                    set_mtime(&path, info)?;
+                }
+                unchanged += 1;
+            } else {
+                fetch.push((name, info));
+            }
+        }
+        if self.delete && tokio::fs::metadata(&dir).await.is_ok() {
+            let local = walk_dir(&dir, &IgnoreGlobs::default())?;
+            let names: BTreeSet<&String> = local
+                .dirs
+                .iter()
+                .chain(local.files.iter().map(|file| &file.name))
+                .collect();
+            for name in names {
+                let under_deleted = delete
+                    .iter()
+                    .any(|dir| name.starts_with(&format!("{}/", dir)));
+                if !under_deleted && !remote.contains_key(name) && !delete.contains(name) {
+                    delete.push(name.clone());
+                }
+            }
+        }
+
+        if self.dry_run {
+            let mut lines: Vec<String> = Vec::new();
+            lines.extend(
+                delete
+                    .iter()
+                    .map(|name| format!("D {}", dir.join(name).display())),
+            );
+            lines.extend(fetch.iter().map(|(name, _)| {
+                let path = dir.join(name);
+                let change = if path.exists() { "M" } else { "A" };
+                format!("{} {}", change, path.display())
+            }));
+            if lines.is_empty() {
+                return Ok(format!(
+                    "{} is up to date with {}",
+                    dir.display(),
+                    self.source
+                ));
+            }
+            return Ok(lines.join("\n"));
+        }
+
+        for name in &delete {
+            let path = dir.join(name);
+            match tokio::fs::symlink_metadata(&path).await {
+                Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&path).await?,
+                Ok(_) => tokio::fs::remove_file(&path).await?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        for (name, info) in &remote {
+            if info.is_dir {
+                tokio::fs::create_dir_all(dir.join(name)).await?;
+            }
+        }
+
+        let total_bytes = fetch.iter().filter_map(|(_, info)| info.size).sum();
+        let progress = Progress::new("Fetched", fetch.len(), total_bytes);
+        let fetched = fetch.len();
+        if fetched > 0 {
+            progress
+                .report(async {
+                    for (name, info) in &fetch {
+                        let path = self.source.join(name);
+                        fetch_file(
+                            &client,
+                            bucket_id,
+                            &path,
+                            version,
+                            &dir.join(name),
+                            info,
+                            &progress,
+                        )
+                        .await?;
+                        progress.add_file();
+                    }
+                    Ok::<_, BucketCheckoutError>(())
+                })
+                .await?;
+        }
+
+        Ok(format!(
+            "Checked out {} at {} to {}: {} fetched, {} unchanged, {} deleted",
+            self.source,
+            version,
+            dir.display(),
+            fetched,
+            unchanged,
+            delete.len()
+        ))
+    }
+}
+
+/// Download a file of the bucket to `dest`, through a partial file next to
+///  it so an interrupted download never leaves a truncated file behind
+async fn fetch_file(
+    client: &ApiClient,
+    bucket_id: Uuid,
+    path: &str,
+    version: Hash,
+    dest: &Path,
+    info: &PathInfo,
+    progress: &Progress,
+) -> Result<(), BucketCheckoutError> {
+    let url = client.base_url().join("/api/v0/bucket/cat").unwrap();
+    let query = [
+        ("bucket_id", bucket_id.to_string()),
+        ("path", path.to_string()),
+        ("download", "true".to_string()),
+        ("version", version.to_string()),
+    ];
+    let mut response = client.http_client().get(url).query(&query).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await?;
+        return Err(BucketCheckoutError::Api(ApiError::HttpStatus(status, body)));
+    }
+
+    let file_name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let partial = dest.with_file_name(format!(".{}.partial", file_name));
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let written: Result<(), BucketCheckoutError> = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            progress.add_bytes(chunk.len() as u64);
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    drop(file);
+    if let Err(e) = written {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+
+    tokio::fs::rename(&partial, dest).await?;
+    set_mtime(dest, info)?;
+    Ok(())
+}
+
+/// Set a local file's modification time to the one the bucket recorded
+fn set_mtime(path: &Path, info: &PathInfo) -> std::io::Result<()> {
+    if let Some(mtime) = info.mtime {
+        let file = std::fs::File::options().write(true).open(path)?;
+        file.set_modified(UNIX_EPOCH + Duration::from_millis(mtime))?;
+    }
+    Ok(())
+}
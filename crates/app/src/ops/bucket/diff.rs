@@ -0,0 +1,80 @@
+use clap::Args;
+use common::linked_data::Hash;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::diff::{DiffRequest, DiffResponse};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Diff {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Version to compare from (defaults to the version before --to)
+    #[arg(long)]
+    pub from: Option<Hash>,
+
+    /// Version to compare to (defaults to the current version)
+    #[arg(long)]
+    pub to: Option<Hash>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketDiffError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Diff {
+    type Error = BucketDiffError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketDiffError::NoBucketIdentifier);
+        };
+
+        let request = DiffRequest {
+            bucket_id,
+            from: self.from,
+            to: self.to,
+        };
+        let response: DiffResponse = client.call(request).await?;
+
+        let header = format!("{} -> {}", response.from.hash(), response.to.hash());
+
+        // One change per line, marked like `git status --short`
+        let changes: Vec<String> = response
+            .added
+            .iter()
+            .map(|path| format!("A  {}", path))
+            .chain(response.removed.iter().map(|path| format!("D  {}", path)))
+            .chain(response.modified.iter().map(|path| format!("M  {}", path)))
+            .chain(
+                response
+                    .moved
+                    .iter()
+                    .map(|moved| format!("R  {} -> {}", moved.from, moved.to)),
+            )
+            .collect();
+
+        if changes.is_empty() {
+            return Ok(format!("{}\nNo changes", header));
+        }
+        Ok(format!("{}\n{}", header, changes.join("\n")))
+    }
+}
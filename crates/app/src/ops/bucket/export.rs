@@ -0,0 +1,101 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::export::ExportQuery;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Export {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Write a CAR archive of the bucket's encrypted blobs
+    #[arg(long, required = true)]
+    pub car: bool,
+
+    /// File to write the archive to (defaults to <name>.car)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketExportError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Export {
+    type Error = BucketExportError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketExportError::NoBucketIdentifier);
+        };
+        let output = self.output.clone().unwrap_or_else(|| {
+            PathBuf::from(format!(
+                "{}.car",
+                self.name.clone().unwrap_or_else(|| bucket_id.to_string())
+            ))
+        });
+
+        let url = client.base_url().join("/api/v0/bucket/export").unwrap();
+        let mut response = client
+            .http_client()
+            .get(url)
+            .query(&ExportQuery { bucket_id })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(BucketExportError::Api(ApiError::HttpStatus(status, body)));
+        }
+
+        // Stream the archive to disk, removing it if the export fails part way
+        let mut file = tokio::fs::File::create(&output).await?;
+        let mut size = 0;
+        let written: Result<(), BucketExportError> = async {
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                size += chunk.len();
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            drop(file);
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(e);
+        }
+
+        Ok(format!(
+            "Exported bucket {} to {} ({} bytes)",
+            bucket_id,
+            output.display(),
+            size
+        ))
+    }
+}
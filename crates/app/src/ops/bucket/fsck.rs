@@ -0,0 +1,104 @@
+use clap::Args;
+use common::bucket::{FsckBlob, FsckIssue, FsckProblem};
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::fsck::{FsckRequest, FsckResponse};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Fsck {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Fetch missing or corrupt blobs again from the bucket's peers
+    #[arg(long)]
+    pub repair: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketFsckError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Fsck {
+    type Error = BucketFsckError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketFsckError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = FsckRequest {
+            bucket_id,
+            repair: self.repair,
+        };
+
+        // Call API
+        let response: FsckResponse = client.call(request).await?;
+
+        let report = &response.report;
+        let mut output = format!(
+            "Checked {} directories, {} files and {} blobs of bucket {} (link: {})",
+            report.dirs,
+            report.files,
+            report.blobs,
+            response.bucket_id,
+            response.link.hash()
+        );
+        for problem in &report.problems {
+            output.push_str(&format!("\n  {}", describe(problem)));
+        }
+        if self.repair {
+            output.push_str(&format!(
+                "\nRepaired {} blobs from peers",
+                response.repaired.len()
+            ));
+            for problem in &response.remaining {
+                output.push_str(&format!("\n  still {}", describe(problem)));
+            }
+        }
+        output.push_str(&match response.remaining.len() {
+            0 => "\nNo problems found".to_string(),
+            n => format!("\n{} problems remain", n),
+        });
+        Ok(output)
+    }
+}
+
+fn describe(problem: &FsckProblem) -> String {
+    let blob = match problem.blob {
+        FsckBlob::Manifest => "manifest",
+        FsckBlob::Pins => "pin set",
+        FsckBlob::Dir => "directory",
+        FsckBlob::File => "file",
+        FsckBlob::ChunkIndex => "chunk index of",
+        FsckBlob::Chunk => "chunk of",
+    };
+    let issue = match &problem.issue {
+        FsckIssue::Missing => "missing".to_string(),
+        FsckIssue::Corrupt => "corrupt".to_string(),
+        FsckIssue::Undecryptable(e) => format!("undecryptable ({})", e),
+        FsckIssue::Undecodable(e) => format!("undecodable ({})", e),
+    };
+    match &problem.path {
+        Some(path) => format!("{}: {} {} {}", issue, blob, path.display(), problem.hash),
+        None => format!("{}: {} {}", issue, blob, problem.hash),
+    }
+}
@@ -0,0 +1,143 @@
+use clap::Args;
+use common::linked_data::Hash;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::archive::{ArchiveFormat, ArchiveQuery};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Get {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Path in bucket to download
+    #[arg(long, default_value = "/")]
+    pub path: String,
+
+    /// Download the path, a directory or a file, as a tar or zip archive
+    ///  of its decrypted contents
+    #[arg(long)]
+    pub archive: Option<ArchiveFormat>,
+
+    /// Version of the bucket to read from, as shown by `jax bucket log`
+    ///  (defaults to the current version)
+    #[arg(long)]
+    pub version: Option<Hash>,
+
+    /// File to write to (defaults to the name of the path, or of the bucket
+    ///  for its root)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketGetError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+    #[error("{0} is a directory, use --archive to download it")]
+    IsDirectory(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Get {
+    type Error = BucketGetError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketGetError::NoBucketIdentifier);
+        };
+
+        let file_name = Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+
+        let request = match self.archive {
+            Some(format) => {
+                let url = client.base_url().join("/api/v0/bucket/archive").unwrap();
+                client.http_client().get(url).query(&ArchiveQuery {
+                    bucket_id,
+                    path: self.path.clone(),
+                    version: self.version,
+                    format,
+                })
+            }
+            None => {
+                if file_name.is_none() {
+                    return Err(BucketGetError::IsDirectory(self.path.clone()));
+                }
+                let url = client.base_url().join("/api/v0/bucket/cat").unwrap();
+                let mut query = vec![
+                    ("bucket_id", bucket_id.to_string()),
+                    ("path", self.path.clone()),
+                    ("download", "true".to_string()),
+                ];
+                if let Some(version) = self.version {
+                    query.push(("version", version.to_string()));
+                }
+                client.http_client().get(url).query(&query)
+            }
+        };
+
+        let output = self.output.clone().unwrap_or_else(|| {
+            let name = file_name
+                .clone()
+                .unwrap_or_else(|| self.name.clone().unwrap_or_else(|| bucket_id.to_string()));
+            match self.archive {
+                Some(format) => PathBuf::from(format!("{}.{}", name, format.extension())),
+                None => PathBuf::from(name),
+            }
+        });
+
+        let mut response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(BucketGetError::Api(ApiError::HttpStatus(status, body)));
+        }
+
+        // Stream the download to disk, removing it if it fails part way
+        let mut file = tokio::fs::File::create(&output).await?;
+        let mut size = 0;
+        let written: Result<(), BucketGetError> = async {
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                size += chunk.len();
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            drop(file);
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(e);
+        }
+
+        Ok(format!(
+            "Downloaded {} to {} ({} bytes)",
+            self.path,
+            output.display(),
+            size
+        ))
+    }
+}
@@ -0,0 +1,71 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::export::CAR_CONTENT_TYPE;
+use service::http_server::api::v0::bucket::import::ImportResponse;
+use std::path::PathBuf;
+use tokio_util::io::ReaderStream;
+
+#[derive(Args, Debug, Clone)]
+pub struct Import {
+    /// CAR archive written by `jax bucket export --car`
+    #[arg(long)]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketImportError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Import {
+    type Error = BucketImportError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let client = ctx.client.clone();
+
+        // Stream the archive rather than reading it into memory
+        let file = tokio::fs::File::open(&self.path).await?;
+        let file_len = file.metadata().await?.len();
+
+        let url = client.base_url().join("/api/v0/bucket/import").unwrap();
+        let response = client
+            .http_client()
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, CAR_CONTENT_TYPE)
+            .header(reqwest::header::CONTENT_LENGTH, file_len)
+            .body(reqwest::Body::wrap_stream(ReaderStream::new(file)))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(BucketImportError::Api(ApiError::HttpStatus(status, body)));
+        }
+
+        let response: ImportResponse = response.json().await?;
+
+        Ok(if response.created {
+            format!(
+                "Imported bucket '{}' ({}) at {}",
+                response.name,
+                response.bucket_id,
+                response.link.hash()
+            )
+        } else {
+            format!(
+                "Bucket '{}' ({}) already exists, imported version {} is adopted if it follows the current one",
+                response.name,
+                response.bucket_id,
+                response.link.hash()
+            )
+        })
+    }
+}
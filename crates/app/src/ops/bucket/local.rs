@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use common::linked_data::Hash;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::multipart;
+use reqwest::StatusCode;
+use service::http_server::api::client::{ApiClient, ApiError};
+use service::http_server::api::v0::bucket::add_dir::{AddDirResponse, IgnoreGlobs};
+use service::http_server::api::v0::bucket::ls::{LsRequest, PathInfo};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+/// A directory in a bucket, written `<bucket>:<path>`, where the bucket is
+///  a name or an ID
+#[derive(Debug, Clone)]
+pub struct BucketPath {
+    pub bucket: String,
+    pub path: String,
+}
+
+impl FromStr for BucketPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (bucket, path) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <bucket>:<path>, got {}", s))?;
+        if bucket.is_empty() {
+            return Err("the bucket name is empty".to_string());
+        }
+        let path = match path {
+            "" => "/".to_string(),
+            path if path.starts_with('/') => path.to_string(),
+            path => format!("/{}", path),
+        };
+        Ok(Self {
+            bucket: bucket.to_string(),
+            path,
+        })
+    }
+}
+
+impl std::fmt::Display for BucketPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.bucket, self.path)
+    }
+}
+
+impl BucketPath {
+    /// Resolve the bucket to its ID, taking it as a name unless it is one
+    pub async fn bucket_id(&self, client: &mut ApiClient) -> Result<Uuid, ApiError> {
+        match Uuid::parse_str(&self.bucket) {
+            Ok(id) => Ok(id),
+            Err(_) => client.resolve_bucket_name(&self.bucket).await,
+        }
+    }
+
+    /// Path in the bucket of `name`, relative to this directory
+    pub fn join(&self, name: &str) -> String {
+        format!("{}/{}", self.path.trim_end_matches('/'), name)
+    }
+}
+
+/// A file found under a local directory
+pub struct LocalFile {
+    pub path: PathBuf,
+    /// Path relative to the directory, separated by `/`
+    pub name: String,
+    pub len: u64,
+    /// Modification time as a unix timestamp in milliseconds
+    pub mtime: Option<u64>,
+}
+
+/// What was found under a local directory
+#[derive(Default)]
+pub struct LocalTree {
+    pub files: Vec<LocalFile>,
+    /// Relative paths of the directories under it, separated by `/`
+    pub dirs: BTreeSet<String>,
+    /// Number of files and directories left out by an ignore glob
+    pub ignored: usize,
+}
+
+/// Walk `dir`, leaving out what's matched by `globs`
+///
+/// Directories matched by a glob aren't descended into, and links aren't
+///  followed.
+pub fn walk_dir(dir: &Path, globs: &IgnoreGlobs) -> Result<LocalTree, walkdir::Error> {
+    let mut tree = LocalTree::default();
+    let mut ignored = 0;
+    let walker = walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            let skip = !relative.as_os_str().is_empty() && globs.is_ignored(relative);
+            if skip {
+                ignored += 1;
+            }
+            !skip
+        });
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let name = relative_name(relative);
+        if entry.file_type().is_dir() {
+            tree.dirs.insert(name);
+        } else if entry.file_type().is_file() {
+            let metadata = entry.metadata()?;
+            tree.files.push(LocalFile {
+                path: entry.path().to_path_buf(),
+                name,
+                len: metadata.len(),
+                mtime: mtime_ms(&metadata),
+            });
+        }
+    }
+    tree.ignored = ignored;
+    Ok(tree)
+}
+
+/// A relative path joined with `/`, as names are sent to the API
+pub fn relative_name(path: &Path) -> String {
+    path.iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Modification time of a file as a unix timestamp in milliseconds
+pub fn mtime_ms(metadata: &std::fs::Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_millis()).ok()
+}
+
+/// BLAKE3 hash of a local file, to compare with the plaintext hash a
+///  bucket records for its files
+pub async fn hash_file(path: &Path) -> std::io::Result<Hash> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(std::fs::File::open(path)?)?;
+        Ok(Hash::from_bytes(*hasher.finalize().as_bytes()))
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Whether a local file has the same contents as a file in a bucket
+///
+/// Files with the same size and modification time are taken to be the
+///  same, otherwise the local file is hashed and compared with the hash
+///  the bucket recorded. Files without a recorded hash are never the same.
+pub async fn same_contents(
+    path: &Path,
+    len: u64,
+    mtime: Option<u64>,
+    info: &PathInfo,
+) -> std::io::Result<bool> {
+    if info.size.is_some_and(|size| size != len) {
+        return Ok(false);
+    }
+    if info.size.is_some() && info.mtime.is_some() && info.mtime == mtime {
+        return Ok(true);
+    }
+    match info.hash {
+        Some(hash) => Ok(hash_file(path).await? == hash),
+        None => Ok(false),
+    }
+}
+
+/// Every file and directory under a directory of a bucket, by its path
+///  relative to the directory, or nothing if the directory doesn't exist
+pub async fn list_bucket_dir(
+    client: &mut ApiClient,
+    bucket_id: Uuid,
+    path: &str,
+    version: Option<Hash>,
+) -> Result<BTreeMap<String, PathInfo>, ApiError> {
+    let request = LsRequest {
+        bucket_id,
+        path: Some(path.to_string()),
+        deep: Some(true),
+        version,
+    };
+    match client.call(request).await {
+        // Deep listings are relative to the listed directory
+        Ok(response) => Ok(response
+            .items
+            .into_iter()
+            .map(|item| (item.path.trim_start_matches('/').to_string(), item))
+            .collect()),
+        Err(ApiError::HttpStatus(StatusCode::NOT_FOUND, body))
+            if body.starts_with("Path not found") =>
+        {
+            Ok(BTreeMap::new())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Files and bytes transferred so far, reported on stderr
+#[derive(Clone)]
+pub struct Progress {
+    verb: &'static str,
+    files: Arc<AtomicU64>,
+    bytes: Arc<AtomicU64>,
+    total_files: usize,
+    total_bytes: u64,
+}
+
+impl Progress {
+    pub fn new(verb: &'static str, total_files: usize, total_bytes: u64) -> Self {
+        Self {
+            verb,
+            files: Arc::new(AtomicU64::new(0)),
+            bytes: Arc::new(AtomicU64::new(0)),
+            total_files,
+            total_bytes,
+        }
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_file(&self) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn print(&self) {
+        eprint!(
+            "\r{} {}/{} files ({}/{} bytes)",
+            self.verb,
+            self.files.load(Ordering::Relaxed),
+            self.total_files,
+            self.bytes.load(Ordering::Relaxed),
+            self.total_bytes
+        );
+        let _ = std::io::stderr().flush();
+    }
+
+    /// Report progress until `task` finishes, then end the line
+    pub async fn report<T>(&self, task: impl std::future::Future<Output = T>) -> T {
+        let reporter = {
+            let progress = self.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(200));
+                loop {
+                    interval.tick().await;
+                    progress.print();
+                }
+            })
+        };
+        let output = task.await;
+        reporter.abort();
+        self.print();
+        eprintln!();
+        output
+    }
+}
+
+/// Upload files under a local directory to a directory of a bucket,
+///  removing the relative paths in `remove` and moving those in `moves`,
+///  as a single version
+///
+/// Files are only opened as their part is sent, so a large tree doesn't
+///  hold a descriptor open for every file.
+pub async fn upload_dir(
+    client: &ApiClient,
+    bucket_id: Uuid,
+    mount_path: &str,
+    message: Option<&str>,
+    files: Vec<LocalFile>,
+    remove: &[String],
+    moves: &[(String, String)],
+) -> Result<AddDirResponse, ApiError> {
+    // Build multipart form (the server expects the files last)
+    let mut form = multipart::Form::new()
+        .text("bucket_id", bucket_id.to_string())
+        .text("mount_path", mount_path.to_string());
+    if let Some(message) = message {
+        form = form.text("message", message.to_string());
+    }
+    for name in remove {
+        form = form.text("remove", name.clone());
+    }
+    for (from, to) in moves {
+        form = form
+            .text("move_from", from.clone())
+            .text("move_to", to.clone());
+    }
+
+    let total_bytes = files.iter().map(|file| file.len).sum();
+    let progress = Progress::new("Uploaded", files.len(), total_bytes);
+    for file in files {
+        let sent = progress.clone();
+        let done = progress.clone();
+        let stream = futures::stream::once(tokio::fs::File::open(file.path))
+            .map_ok(ReaderStream::new)
+            .try_flatten()
+            .inspect_ok(move |chunk| sent.add_bytes(chunk.len() as u64))
+            .chain(futures::stream::once(async move {
+                done.add_file();
+                Ok(Default::default())
+            }));
+        let part =
+            multipart::Part::stream_with_length(reqwest::Body::wrap_stream(stream), file.len)
+                .file_name(file.name);
+        if let Some(mtime) = file.mtime {
+            form = form.text("mtime", mtime.to_string());
+        }
+        form = form.part("file", part);
+    }
+
+    let url = client.base_url().join("/api/v0/bucket/add_dir")?;
+    let request = client.http_client().post(url).multipart(form).send();
+    let response = if progress.total_files > 0 {
+        progress.report(request).await?
+    } else {
+        request.await?
+    };
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await?;
+        return Err(ApiError::HttpStatus(status, body));
+    }
+    Ok(response.json().await?)
+}
@@ -0,0 +1,94 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::log::{LogRequest, LogResponse};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Log {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Maximum number of versions to show (defaults to all)
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketLogError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Log {
+    type Error = BucketLogError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketLogError::NoBucketIdentifier);
+        };
+
+        let request = LogRequest {
+            bucket_id,
+            limit: self.limit,
+        };
+        let response: LogResponse = client.call(request).await?;
+
+        if response.versions.is_empty() {
+            return Ok("No versions found".to_string());
+        }
+
+        // Newest first, one version per line:
+        //  <version> <time> <author> [message] [(current)]
+        let output = response
+            .versions
+            .iter()
+            .enumerate()
+            .map(|(i, version)| {
+                let time = version
+                    .timestamp
+                    .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts as i64).ok())
+                    .and_then(|time| time.format(&Rfc3339).ok())
+                    .unwrap_or_else(|| "unknown time".to_string());
+                let author = version
+                    .author
+                    .as_deref()
+                    .map(|author| author.chars().take(8).collect())
+                    .unwrap_or_else(|| "unknown".to_string());
+                let message = version
+                    .message
+                    .as_deref()
+                    .map(|message| format!("  {}", message.lines().next().unwrap_or_default()))
+                    .unwrap_or_default();
+                let current = if i == 0 { "  (current)" } else { "" };
+                format!(
+                    "{}  {}  {}{}{}",
+                    version.link.hash(),
+                    time,
+                    author,
+                    message,
+                    current
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(output)
+    }
+}
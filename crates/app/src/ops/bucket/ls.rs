@@ -1,4 +1,5 @@
 use clap::Args;
+use common::linked_data::Hash;
 use service::http_server::api::client::ApiError;
 use service::http_server::api::v0::bucket::ls::{LsRequest, LsResponse};
 use uuid::Uuid;
@@ -20,6 +21,11 @@ pub struct Ls {
     /// List recursively
     #[arg(long)]
     pub deep: Option<bool>,
+
+    /// Version of the bucket to list, as shown by `jax bucket log`
+    ///  (defaults to the current version)
+    #[arg(long)]
+    pub version: Option<Hash>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -52,6 +58,7 @@ impl crate::op::Op for Ls {
             bucket_id,
             path: self.path.clone(),
             deep: self.deep,
+            version: self.version,
         };
 
         // Call API
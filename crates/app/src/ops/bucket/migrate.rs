@@ -0,0 +1,100 @@
+use std::io::Write;
+
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::migrate::{
+    MigrateEvent, MigrateRequest, MigrateResponse,
+};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Migrate {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketMigrateError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+    #[error("Migrate failed: {0}")]
+    Failed(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Migrate {
+    type Error = BucketMigrateError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketMigrateError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = MigrateRequest {
+            bucket_id,
+            message: self.message.clone(),
+        };
+
+        // Call API, reporting progress on stderr as it streams in
+        let mut outcome: Option<Result<MigrateResponse, String>> = None;
+        let mut reported = false;
+        client
+            .call_lines(request, |event| match event {
+                MigrateEvent::Progress(progress) => {
+                    eprint!(
+                        "\rRewrote {} directories and {} files ({} bytes)",
+                        progress.dirs, progress.files, progress.bytes
+                    );
+                    let _ = std::io::stderr().flush();
+                    reported = true;
+                }
+                MigrateEvent::Done(response) => outcome = Some(Ok(response)),
+                MigrateEvent::Error(e) => outcome = Some(Err(e)),
+            })
+            .await?;
+        if reported {
+            eprintln!();
+        }
+
+        let response = match outcome {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => return Err(BucketMigrateError::Failed(e)),
+            None => {
+                return Err(BucketMigrateError::Failed(
+                    "the service stopped responding".to_string(),
+                ))
+            }
+        };
+
+        Ok(match response.new_bucket_link {
+            Some(link) => format!(
+                "Migrated bucket {} to format {} (link: {})",
+                response.bucket_id, response.format, link
+            ),
+            None => format!(
+                "Bucket {} is already in format {}",
+                response.bucket_id, response.format
+            ),
+        })
+    }
+}
@@ -2,10 +2,26 @@ use clap::{Args, Subcommand};
 
 pub mod add;
 pub mod cat;
+pub mod checkout;
 pub mod create;
+pub mod diff;
+pub mod export;
+pub mod fsck;
+pub mod get;
+pub mod import;
 pub mod list;
+pub(crate) mod local;
+pub mod log;
 pub mod ls;
+pub mod migrate;
+pub mod mv;
+pub mod push_dir;
+pub mod retention;
+pub mod revert;
+pub mod rm;
 pub mod share;
+pub mod unshare;
+pub mod watch;
 
 use crate::op::Op;
 use service::http_server::api::v0::bucket::{CreateRequest, ListRequest, ShareRequest};
@@ -16,7 +32,22 @@ crate::command_enum! {
     (Add, add::Add),
     (Ls, ls::Ls),
     (Cat, cat::Cat),
+    (Get, get::Get),
+    (PushDir, push_dir::PushDir),
+    (Checkout, checkout::Checkout),
+    (Watch, watch::Watch),
+    (Mv, mv::Mv),
+    (Rm, rm::Rm),
+    (Log, log::Log),
+    (Diff, diff::Diff),
+    (Revert, revert::Revert),
     (Share, ShareRequest),
+    (Unshare, unshare::Unshare),
+    (Migrate, migrate::Migrate),
+    (Retention, retention::Retention),
+    (Fsck, fsck::Fsck),
+    (Export, export::Export),
+    (Import, import::Import),
 }
 
 // Rename the generated Command to BucketCommand for clarity
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::add_dir::IgnoreGlobs;
+use service::http_server::api::v0::bucket::ls::PathInfo;
+
+use super::local::{list_bucket_dir, same_contents, upload_dir, walk_dir, BucketPath, LocalFile};
+
+#[derive(Args, Debug, Clone)]
+pub struct PushDir {
+    /// Local directory to mirror
+    pub local: PathBuf,
+
+    /// Directory of a bucket to mirror it to, as `<bucket>:<path>`, where
+    ///  the bucket is a name or an ID
+    pub target: BucketPath,
+
+    /// Glob of files to leave out, matched against paths relative to the
+    ///  directory and each of their components (repeatable). Files in the
+    ///  bucket matching a glob are left alone.
+    #[arg(long = "ignore", value_name = "GLOB")]
+    pub ignore: Vec<String>,
+
+    /// Show what would change without changing the bucket
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketPushDirError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid ignore glob: {0}")]
+    InvalidGlob(#[from] glob::PatternError),
+    #[error("Failed to read directory: {0}")]
+    Walk(#[from] walkdir::Error),
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for PushDir {
+    type Error = BucketPushDirError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+        let bucket_id = self.target.bucket_id(&mut client).await?;
+
+        let dir = if self.local.is_absolute() {
+            self.local.clone()
+        } else {
+            env::current_dir()?.join(&self.local)
+        };
+        if !tokio::fs::metadata(&dir).await?.is_dir() {
+            return Err(BucketPushDirError::NotADirectory(dir.display().to_string()));
+        }
+
+        let globs = IgnoreGlobs::new(&self.ignore)?;
+        let remote = list_bucket_dir(&mut client, bucket_id, &self.target.path, None).await?;
+        let PushPlan {
+            added,
+            updated,
+            removed,
+            upload,
+        } = plan_push(&dir, &globs, &remote).await?;
+
+        if added.is_empty() && updated.is_empty() && removed.is_empty() {
+            return Ok(format!(
+                "{} is up to date with {}",
+                self.target,
+                dir.display()
+            ));
+        }
+
+        if self.dry_run {
+            let mut lines: Vec<String> = Vec::new();
+            lines.extend(
+                added
+                    .iter()
+                    .map(|name| format!("A {}", self.target.join(name))),
+            );
+            lines.extend(
+                updated
+                    .iter()
+                    .map(|name| format!("M {}", self.target.join(name))),
+            );
+            lines.extend(
+                removed
+                    .iter()
+                    .map(|name| format!("D {}", self.target.join(name))),
+            );
+            return Ok(lines.join("\n"));
+        }
+
+        let response = upload_dir(
+            &client,
+            bucket_id,
+            &self.target.path,
+            self.message.as_deref(),
+            upload,
+            &removed,
+            &[],
+        )
+        .await?;
+
+        Ok(format!(
+            "Pushed {} to {}: {} added, {} updated, {} removed (link: {})",
+            dir.display(),
+            self.target,
+            added.len(),
+            updated.len(),
+            removed.len(),
+            response.link.hash()
+        ))
+    }
+}
+
+/// What mirroring a local directory to a directory of a bucket changes
+pub struct PushPlan {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Files to upload, whether added or updated
+    pub upload: Vec<LocalFile>,
+}
+
+/// Compare what's under `dir` with a listing of the bucket directory to
+///  mirror it to, leaving out what's matched by `globs` on either side
+pub async fn plan_push(
+    dir: &Path,
+    globs: &IgnoreGlobs,
+    remote: &BTreeMap<String, PathInfo>,
+) -> Result<PushPlan, BucketPushDirError> {
+    let local = walk_dir(dir, globs)?;
+    let local_files: BTreeSet<String> = local.files.iter().map(|file| file.name.clone()).collect();
+
+    // Files that are new, or whose contents changed
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    let mut upload = Vec::new();
+    for file in local.files {
+        match remote.get(&file.name) {
+            Some(info) if !info.is_dir => {
+                if same_contents(&file.path, file.len, file.mtime, info).await? {
+                    continue;
+                }
+                updated.push(file.name.clone());
+            }
+            _ => added.push(file.name.clone()),
+        }
+        upload.push(file);
+    }
+
+    // What's in the bucket but not the directory, removing a directory as
+    //  a whole rather than everything in it. A file replacing a directory
+    //  replaces it when uploaded, but a directory replacing a file needs
+    //  the file removed first.
+    let mut removed: Vec<String> = Vec::new();
+    for (name, info) in remote {
+        let under_removed = removed
+            .iter()
+            .any(|dir| name.starts_with(&format!("{}/", dir)));
+        let present = local_files.contains(name) || (info.is_dir && local.dirs.contains(name));
+        if under_removed || present || globs.is_ignored(Path::new(name)) {
+            continue;
+        }
+        removed.push(name.clone());
+    }
+
+    Ok(PushPlan {
+        added,
+        updated,
+        removed,
+        upload,
+    })
+}
@@ -0,0 +1,85 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::retention::{RetentionRequest, RetentionResponse};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Retention {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Number of versions to keep, including the current one
+    #[arg(long)]
+    pub max_versions: Option<u32>,
+
+    /// Number of days of history to keep
+    #[arg(long)]
+    pub max_age_days: Option<u32>,
+
+    /// Remove the policy and keep all history from now on
+    #[arg(long, conflicts_with_all = ["max_versions", "max_age_days"])]
+    pub clear: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketRetentionError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Retention {
+    type Error = BucketRetentionError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketRetentionError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = RetentionRequest {
+            bucket_id,
+            max_versions: self.max_versions,
+            max_age_days: self.max_age_days,
+            clear: self.clear,
+        };
+
+        // Call API
+        let response: RetentionResponse = client.call(request).await?;
+
+        let limits: Vec<String> = response
+            .max_versions
+            .map(|max| format!("the last {} versions", max))
+            .into_iter()
+            .chain(
+                response
+                    .max_age_days
+                    .map(|days| format!("{} days of history", days)),
+            )
+            .collect();
+        Ok(if limits.is_empty() {
+            format!("Bucket {} keeps all of its history", response.bucket_id)
+        } else {
+            format!(
+                "Bucket {} keeps at most {}",
+                response.bucket_id,
+                limits.join(" and ")
+            )
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use clap::Args;
+use common::linked_data::Hash;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::revert::{RevertRequest, RevertResponse};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Revert {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Version to revert to (see `jax bucket log`)
+    #[arg(long)]
+    pub version: Hash,
+
+    /// Only revert this file or directory (defaults to the whole bucket)
+    #[arg(long)]
+    pub path: Option<String>,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketRevertError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Revert {
+    type Error = BucketRevertError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketRevertError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = RevertRequest {
+            bucket_id,
+            version: self.version,
+            path: self.path.clone(),
+            message: self.message.clone(),
+        };
+
+        // Call API
+        let response: RevertResponse = client.call(request).await?;
+
+        Ok(format!(
+            "Reverted {} to {} (link: {})",
+            response.path.as_deref().unwrap_or("bucket"),
+            response.version,
+            response.link.hash()
+        ))
+    }
+}
@@ -0,0 +1,66 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::rm::{RmRequest, RmResponse};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Rm {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Path of the file or directory in the bucket to remove
+    #[arg(long)]
+    pub path: String,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketRmError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Rm {
+    type Error = BucketRmError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketRmError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = RmRequest {
+            bucket_id,
+            path: self.path.clone(),
+            message: self.message.clone(),
+        };
+
+        // Call API
+        let response: RmResponse = client.call(request).await?;
+
+        Ok(format!(
+            "Removed {} (link: {})",
+            response.path,
+            response.link.hash()
+        ))
+    }
+}
@@ -0,0 +1,112 @@
+use std::io::Write;
+
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::bucket::unshare::{
+    UnshareEvent, UnshareRequest, UnshareResponse,
+};
+use uuid::Uuid;
+
+#[derive(Args, Debug, Clone)]
+pub struct Unshare {
+    /// Bucket ID (or use --name)
+    #[arg(long, group = "bucket_identifier")]
+    pub bucket_id: Option<Uuid>,
+
+    /// Bucket name (or use --bucket-id)
+    #[arg(long, group = "bucket_identifier")]
+    pub name: Option<String>,
+
+    /// Public key of the peer to revoke (hex-encoded)
+    #[arg(long)]
+    pub peer_public_key: String,
+
+    /// Also re-encrypt every file and directory under new keys, so the
+    /// peer can't decrypt files it read before either
+    #[arg(long)]
+    pub rekey: bool,
+
+    /// Message describing the change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketUnshareError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Either --bucket-id or --name must be provided")]
+    NoBucketIdentifier,
+    #[error("Unshare failed: {0}")]
+    Failed(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Unshare {
+    type Error = BucketUnshareError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Resolve bucket name to UUID if needed
+        let bucket_id = if let Some(id) = self.bucket_id {
+            id
+        } else if let Some(ref name) = self.name {
+            client.resolve_bucket_name(name).await?
+        } else {
+            return Err(BucketUnshareError::NoBucketIdentifier);
+        };
+
+        // Create API request
+        let request = UnshareRequest {
+            bucket_id,
+            peer_public_key: self.peer_public_key.clone(),
+            rekey: self.rekey,
+            message: self.message.clone(),
+        };
+
+        // Call API, reporting progress on stderr as it streams in
+        let mut outcome: Option<Result<UnshareResponse, String>> = None;
+        let mut reported = false;
+        client
+            .call_lines(request, |event| match event {
+                UnshareEvent::Progress(progress) => {
+                    eprint!(
+                        "\rRe-encrypted {} directories and {} files ({} bytes)",
+                        progress.dirs, progress.files, progress.bytes
+                    );
+                    let _ = std::io::stderr().flush();
+                    reported = true;
+                }
+                UnshareEvent::Done(response) => outcome = Some(Ok(response)),
+                UnshareEvent::Error(e) => outcome = Some(Err(e)),
+            })
+            .await?;
+        if reported {
+            eprintln!();
+        }
+
+        let response = match outcome {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => return Err(BucketUnshareError::Failed(e)),
+            None => {
+                return Err(BucketUnshareError::Failed(
+                    "the service stopped responding".to_string(),
+                ))
+            }
+        };
+
+        Ok(format!(
+            "Revoked {} from bucket {}{} (link: {})",
+            response.peer_public_key,
+            response.bucket_id,
+            if response.rekeyed {
+                " and re-encrypted it"
+            } else {
+                ""
+            },
+            response.new_bucket_link
+        ))
+    }
+}
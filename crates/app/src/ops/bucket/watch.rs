@@ -0,0 +1,620 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Args;
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::RecursiveMode;
+use notify_debouncer_full::{new_debouncer, DebouncedEvent};
+use service::http_server::api::client::{ApiClient, ApiError};
+use service::http_server::api::v0::bucket::add_dir::{AddDirResponse, IgnoreGlobs};
+use service::http_server::api::v0::bucket::ls::PathInfo;
+use uuid::Uuid;
+
+use super::local::{
+    list_bucket_dir, mtime_ms, relative_name, same_contents, upload_dir, walk_dir, BucketPath,
+    LocalFile,
+};
+use super::push_dir::{plan_push, BucketPushDirError};
+
+/// How long to wait before syncing again after a push failed
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Args, Debug, Clone)]
+pub struct Watch {
+    /// Local directory to watch
+    pub local: PathBuf,
+
+    /// Directory of a bucket to keep in sync with it, as `<bucket>:<path>`,
+    ///  where the bucket is a name or an ID
+    pub target: BucketPath,
+
+    /// Glob of files to leave out, matched against paths relative to the
+    ///  directory and each of their components (repeatable). Files in the
+    ///  bucket matching a glob are left alone.
+    #[arg(long = "ignore", value_name = "GLOB")]
+    pub ignore: Vec<String>,
+
+    /// How long changes have to settle before they're pushed, in
+    ///  milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub debounce_ms: u64,
+
+    /// Message describing each change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BucketWatchError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid ignore glob: {0}")]
+    InvalidGlob(#[from] glob::PatternError),
+    #[error("Failed to read directory: {0}")]
+    Walk(#[from] walkdir::Error),
+    #[error("{0}")]
+    PushDir(#[from] BucketPushDirError),
+    #[error("Failed to watch directory: {0}")]
+    Notify(#[from] notify::Error),
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Watch {
+    type Error = BucketWatchError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+        let bucket_id = self.target.bucket_id(&mut client).await?;
+
+        let dir = if self.local.is_absolute() {
+            self.local.clone()
+        } else {
+            env::current_dir()?.join(&self.local)
+        };
+        if !tokio::fs::metadata(&dir).await?.is_dir() {
+            return Err(BucketWatchError::NotADirectory(dir.display().to_string()));
+        }
+
+        let mut mirror = Mirror {
+            client,
+            bucket_id,
+            dir,
+            target: self.target.clone(),
+            globs: IgnoreGlobs::new(&self.ignore)?,
+            message: self.message.clone(),
+        };
+
+        // Watch before the first push, so nothing changed during it is missed
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(
+            Duration::from_millis(self.debounce_ms),
+            None,
+            move |result| {
+                let _ = sender.send(result);
+            },
+        )?;
+        debouncer.watch(&mirror.dir, RecursiveMode::Recursive)?;
+
+        let mut versions = 0;
+        if let Some(response) = mirror.push_all().await? {
+            tracing::info!("{}", mirror.describe(&response));
+            versions += 1;
+        }
+        tracing::info!(
+            "Watching {} for changes to push to {}",
+            mirror.dir.display(),
+            self.target
+        );
+
+        let mut resync = false;
+        loop {
+            let pushed = tokio::select! {
+                result = receiver.recv() => match result {
+                    Some(Ok(events)) if !resync => mirror.push_events(&events).await,
+                    Some(Ok(_)) => mirror.push_all().await,
+                    Some(Err(errors)) => {
+                        for error in errors {
+                            tracing::warn!("Watch error: {}", error);
+                        }
+                        mirror.push_all().await
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep(RETRY_INTERVAL), if resync => mirror.push_all().await,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            match pushed {
+                Ok(Some(response)) => {
+                    tracing::info!("{}", mirror.describe(&response));
+                    versions += 1;
+                    resync = false;
+                }
+                Ok(None) => resync = false,
+                // Events are lost by then, so compare everything next time
+                Err(e) => {
+                    tracing::warn!("Failed to push changes, will sync again: {}", e);
+                    resync = true;
+                }
+            }
+        }
+        debouncer.stop();
+
+        Ok(format!(
+            "Stopped watching {}, pushed {} versions to {}",
+            mirror.dir.display(),
+            versions,
+            self.target
+        ))
+    }
+}
+
+/// A local directory being kept in sync with a directory of a bucket
+struct Mirror {
+    client: ApiClient,
+    bucket_id: Uuid,
+    dir: PathBuf,
+    target: BucketPath,
+    globs: IgnoreGlobs,
+    message: Option<String>,
+}
+
+impl Mirror {
+    /// Mirror the whole directory, as `jax bucket push-dir` does
+    async fn push_all(&mut self) -> Result<Option<AddDirResponse>, BucketWatchError> {
+        let remote = self.list_remote().await?;
+        let plan = plan_push(&self.dir, &self.globs, &remote).await?;
+        if plan.upload.is_empty() && plan.removed.is_empty() {
+            return Ok(None);
+        }
+        self.push(plan.upload, &plan.removed, &[]).await.map(Some)
+    }
+
+    /// Push what changed at the paths of a batch of events, as a single
+    ///  version
+    ///
+    /// Renames within the directory are pushed as moves, unless something
+    ///  else changed under either end, in which case both are compared with
+    ///  the bucket like any other path.
+    async fn push_events(
+        &mut self,
+        events: &[DebouncedEvent],
+    ) -> Result<Option<AddDirResponse>, BucketWatchError> {
+        let Some(Changes {
+            mut touched,
+            renames,
+        }) = partition(events, |path| relative_to(&self.dir, &self.globs, path))
+        else {
+            return self.push_all().await;
+        };
+        if touched.is_empty() && renames.is_empty() {
+            return Ok(None);
+        }
+
+        let remote = self.list_remote().await?;
+
+        let mut moves: Vec<(String, String)> = Vec::new();
+        for (from, to) in renames {
+            if self.can_move(&remote, &from, &to).await? {
+                moves.push((from, to));
+            } else {
+                touched.insert(from);
+                touched.insert(to);
+            }
+        }
+        let moves = settle_moves(moves, &mut touched);
+
+        // By name, as a directory and files under it can both be touched
+        let mut upload = BTreeMap::new();
+        let mut removed = BTreeSet::new();
+        for name in &touched {
+            let path = self.dir.join(name);
+            let metadata = match tokio::fs::symlink_metadata(&path).await {
+                Ok(metadata) => metadata,
+                // Gone, or under a file that replaced its directory
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+                    ) =>
+                {
+                    if remote.contains_key(name) {
+                        removed.insert(name.clone());
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if !metadata.is_file() && !metadata.is_dir() {
+                continue;
+            }
+
+            // A directory replacing a file needs the file removed first
+            for parent in parents(name) {
+                if remote.get(parent).is_some_and(|info| !info.is_dir) {
+                    removed.insert(parent.to_string());
+                }
+            }
+
+            if metadata.is_file() {
+                let file = LocalFile {
+                    path,
+                    name: name.clone(),
+                    len: metadata.len(),
+                    mtime: mtime_ms(&metadata),
+                };
+                if !upload.contains_key(&file.name) && !self.is_unchanged(&remote, &file).await? {
+                    upload.insert(file.name.clone(), file);
+                }
+                continue;
+            }
+
+            if remote.get(name).is_some_and(|info| !info.is_dir) {
+                removed.insert(name.clone());
+            }
+            // Globs are matched against paths relative to the watched
+            //  directory, not this one
+            let tree = walk_dir(&path, &IgnoreGlobs::default())?;
+            let mut files = BTreeSet::new();
+            let mut dirs = BTreeSet::new();
+            for mut file in tree.files {
+                file.name = format!("{}/{}", name, file.name);
+                if self.globs.is_ignored(Path::new(&file.name)) {
+                    continue;
+                }
+                files.insert(file.name.clone());
+                if !upload.contains_key(&file.name) && !self.is_unchanged(&remote, &file).await? {
+                    upload.insert(file.name.clone(), file);
+                }
+            }
+            for dir in tree.dirs {
+                dirs.insert(format!("{}/{}", name, dir));
+            }
+            let prefix = format!("{}/", name);
+            for (under, info) in remote.range(prefix.clone()..) {
+                if !under.starts_with(&prefix) {
+                    break;
+                }
+                let present = files.contains(under) || (info.is_dir && dirs.contains(under));
+                if !present && !self.globs.is_ignored(Path::new(under)) {
+                    removed.insert(under.clone());
+                }
+            }
+        }
+
+        // Remove directories as a whole rather than everything in them
+        let mut topmost: Vec<String> = Vec::new();
+        for name in removed {
+            if !topmost.iter().any(|dir| overlaps(dir, &name)) {
+                topmost.push(name);
+            }
+        }
+
+        if upload.is_empty() && topmost.is_empty() && moves.is_empty() {
+            return Ok(None);
+        }
+        self.push(upload.into_values().collect(), &topmost, &moves)
+            .await
+            .map(Some)
+    }
+
+    /// Whether a rename can be pushed as a move: what was renamed is in the
+    ///  bucket and gone locally, and it's now somewhere the bucket has
+    ///  nothing, as the same kind of thing
+    async fn can_move(
+        &self,
+        remote: &BTreeMap<String, PathInfo>,
+        from: &str,
+        to: &str,
+    ) -> Result<bool, BucketWatchError> {
+        let Some(info) = remote.get(from) else {
+            return Ok(false);
+        };
+        if overlaps(from, to) || remote.contains_key(to) || self.dir.join(from).exists() {
+            return Ok(false);
+        }
+        if parents(to).any(|parent| remote.get(parent).is_some_and(|info| !info.is_dir)) {
+            return Ok(false);
+        }
+        let metadata = match tokio::fs::symlink_metadata(self.dir.join(to)).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        if info.is_dir {
+            Ok(metadata.is_dir())
+        } else {
+            Ok(metadata.is_file() && info.size.is_none_or(|size| size == metadata.len()))
+        }
+    }
+
+    /// Whether a local file is already in the bucket as it is
+    async fn is_unchanged(
+        &self,
+        remote: &BTreeMap<String, PathInfo>,
+        file: &LocalFile,
+    ) -> std::io::Result<bool> {
+        match remote.get(&file.name) {
+            Some(info) if !info.is_dir => {
+                same_contents(&file.path, file.len, file.mtime, info).await
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_remote(&mut self) -> Result<BTreeMap<String, PathInfo>, ApiError> {
+        list_bucket_dir(&mut self.client, self.bucket_id, &self.target.path, None).await
+    }
+
+    async fn push(
+        &self,
+        upload: Vec<LocalFile>,
+        removed: &[String],
+        moves: &[(String, String)],
+    ) -> Result<AddDirResponse, BucketWatchError> {
+        Ok(upload_dir(
+            &self.client,
+            self.bucket_id,
+            &self.target.path,
+            self.message.as_deref(),
+            upload,
+            removed,
+            moves,
+        )
+        .await?)
+    }
+
+    fn describe(&self, response: &AddDirResponse) -> String {
+        format!(
+            "Pushed to {}: {} changed, {} removed, {} moved (link: {})",
+            self.target,
+            response.added.len(),
+            response.removed.len(),
+            response.moved.len(),
+            response.link.hash()
+        )
+    }
+}
+
+/// What a batch of events changed, by path relative to the watched directory
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    /// Paths to compare with the bucket
+    touched: BTreeSet<String>,
+    /// Renames that may be pushed as moves, from and to
+    renames: Vec<(String, String)>,
+}
+
+/// Sort a batch of events into the paths they touched and the renames among
+///  them, with `relative` naming each path, or skipping it
+/// Returns None if the directory has to be compared as a whole.
+fn partition(
+    events: &[DebouncedEvent],
+    relative: impl Fn(&Path) -> Option<String>,
+) -> Option<Changes> {
+    let mut changes = Changes::default();
+    for event in events {
+        if event.need_rescan() {
+            return None;
+        }
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        let names: Vec<String> = event
+            .paths
+            .iter()
+            .filter_map(|path| relative(path))
+            .collect();
+        match (event.kind, names.as_slice()) {
+            (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to])
+                if event.paths.len() == 2 =>
+            {
+                changes.renames.push((from.clone(), to.clone()))
+            }
+            _ => changes.touched.extend(names),
+        }
+    }
+    Some(changes)
+}
+
+/// Keep the moves nothing else changed under either end of, as only those
+///  are safe to push as moves
+/// Both ends of every other move are added to `touched`, which can in turn
+///  overlap more moves.
+fn settle_moves(
+    mut moves: Vec<(String, String)>,
+    touched: &mut BTreeSet<String>,
+) -> Vec<(String, String)> {
+    loop {
+        let (overlapping, kept): (Vec<_>, Vec<_>) = moves.into_iter().partition(|(from, to)| {
+            touched
+                .iter()
+                .any(|name| overlaps(name, from) || overlaps(name, to))
+        });
+        moves = kept;
+        if overlapping.is_empty() {
+            return moves;
+        }
+        for (from, to) in overlapping {
+            touched.insert(from);
+            touched.insert(to);
+        }
+    }
+}
+
+/// Path relative to the watched directory `dir`, unless it's the directory
+///  itself, outside it, or ignored
+fn relative_to(dir: &Path, globs: &IgnoreGlobs, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?;
+    if relative.as_os_str().is_empty() || globs.is_ignored(relative) {
+        return None;
+    }
+    Some(relative_name(relative))
+}
+
+/// Whether one relative path is the same as, or under, the other
+fn overlaps(a: &str, b: &str) -> bool {
+    let under = |path: &str, dir: &str| {
+        path.len() > dir.len() && path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/'
+    };
+    a == b || under(a, b) || under(b, a)
+}
+
+/// The directories a relative path is under, outermost first
+fn parents(name: &str) -> impl Iterator<Item = &str> {
+    name.match_indices('/').map(move |(i, _)| &name[..i])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use notify::event::{CreateKind, RemoveKind};
+    use notify::Event;
+
+    use super::*;
+
+    fn event(kind: EventKind, paths: &[&str]) -> DebouncedEvent {
+        let event = paths.iter().fold(Event::new(kind), |event, path| {
+            event.add_path(PathBuf::from("/watched").join(path))
+        });
+        DebouncedEvent::new(event, Instant::now())
+    }
+
+    fn rename(from: &str, to: &str) -> DebouncedEvent {
+        event(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &[from, to],
+        )
+    }
+
+    fn changes(events: &[DebouncedEvent]) -> Option<Changes> {
+        let globs = IgnoreGlobs::new(&["*.tmp"]).unwrap();
+        partition(events, |path| {
+            relative_to(Path::new("/watched"), &globs, path)
+        })
+    }
+
+    fn names(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_overlaps() {
+        assert!(overlaps("docs", "docs"));
+        assert!(overlaps("docs", "docs/a.txt"));
+        assert!(overlaps("docs/sub/a.txt", "docs"));
+        assert!(!overlaps("docs", "docs2"));
+        assert!(!overlaps("docs2/a.txt", "docs"));
+        assert!(!overlaps("a.txt", "b.txt"));
+    }
+
+    #[test]
+    fn test_parents() {
+        assert_eq!(parents("a/b/c.txt").collect::<Vec<_>>(), vec!["a", "a/b"]);
+        assert_eq!(parents("c.txt").count(), 0);
+    }
+
+    #[test]
+    fn test_rename_within_dir() {
+        let events = [rename("docs/draft.txt", "docs/final.txt")];
+        let Changes { touched, renames } = changes(&events).unwrap();
+        assert!(touched.is_empty());
+        assert_eq!(renames, pairs(&[("docs/draft.txt", "docs/final.txt")]));
+
+        let mut touched = touched;
+        let moves = settle_moves(renames, &mut touched);
+        assert_eq!(moves, pairs(&[("docs/draft.txt", "docs/final.txt")]));
+        assert!(touched.is_empty());
+    }
+
+    #[test]
+    fn test_move_across_dirs() {
+        let events = [
+            rename("inbox/report.pdf", "archive/2024/report.pdf"),
+            event(EventKind::Create(CreateKind::File), &["notes.txt"]),
+        ];
+        let Changes {
+            mut touched,
+            renames,
+        } = changes(&events).unwrap();
+        assert_eq!(touched, names(&["notes.txt"]));
+
+        let moves = settle_moves(renames, &mut touched);
+        assert_eq!(
+            moves,
+            pairs(&[("inbox/report.pdf", "archive/2024/report.pdf")])
+        );
+        assert_eq!(touched, names(&["notes.txt"]));
+    }
+
+    #[test]
+    fn test_overlapping_changes_drop_moves() {
+        // A file created in the directory moved to, and a directory moved
+        //  into the one moved out of, overlap both moves
+        let events = [
+            rename("photos", "albums/photos"),
+            event(
+                EventKind::Create(CreateKind::File),
+                &["albums/photos/new.jpg"],
+            ),
+            rename("scratch", "photos"),
+            rename("keep.txt", "kept.txt"),
+            event(EventKind::Remove(RemoveKind::File), &["old.txt"]),
+        ];
+        let Changes {
+            mut touched,
+            renames,
+        } = changes(&events).unwrap();
+
+        let moves = settle_moves(renames, &mut touched);
+        assert_eq!(moves, pairs(&[("keep.txt", "kept.txt")]));
+        assert_eq!(
+            touched,
+            names(&[
+                "albums/photos",
+                "albums/photos/new.jpg",
+                "old.txt",
+                "photos",
+                "scratch"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_partition_skips_and_rescans() {
+        // Access, the directory itself and ignored paths change nothing
+        let events = [
+            event(
+                EventKind::Access(notify::event::AccessKind::Any),
+                &["a.txt"],
+            ),
+            event(EventKind::Create(CreateKind::File), &[""]),
+            event(EventKind::Create(CreateKind::File), &["build.tmp"]),
+        ];
+        assert_eq!(changes(&events), Some(Changes::default()));
+
+        // A rename from an ignored name is only a change where it ended up
+        let events = [rename("upload.tmp", "upload.bin")];
+        let Changes { touched, renames } = changes(&events).unwrap();
+        assert_eq!(touched, names(&["upload.bin"]));
+        assert!(renames.is_empty());
+
+        let mut rescan = event(EventKind::Other, &[]);
+        rescan.attrs.set_flag(notify::event::Flag::Rescan);
+        assert_eq!(changes(&[rescan]), None);
+    }
+}
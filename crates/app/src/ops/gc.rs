@@ -0,0 +1,61 @@
+use clap::Args;
+use service::http_server::api::client::ApiError;
+use service::http_server::api::v0::admin::gc::{GcRequest, GcResponse};
+
+#[derive(Args, Debug, Clone)]
+pub struct Gc {
+    /// Report what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Number of previous versions of each bucket to keep (defaults to 100)
+    #[arg(long)]
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GcError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Gc {
+    type Error = GcError;
+    type Output = String;
+
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        let mut client = ctx.client.clone();
+
+        // Create API request
+        let request = GcRequest {
+            dry_run: self.dry_run,
+            depth: self.depth,
+        };
+
+        // Call API
+        let response: GcResponse = client.call(request).await?;
+
+        let mut output = format!(
+            "Kept {} blobs from the last {} versions of {} buckets",
+            response.retained, response.depth, response.buckets
+        );
+        if !response.pruned.is_empty() {
+            output.push_str(&format!(
+                "\nPruned the pins of {} buckets",
+                response.pruned.len()
+            ));
+        }
+        output.push_str(&format!(
+            "\n{} {} blobs ({} bytes)",
+            if response.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            },
+            response.removed.blobs,
+            response.removed.bytes
+        ));
+        Ok(output)
+    }
+}
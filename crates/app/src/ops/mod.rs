@@ -1,9 +1,13 @@
 pub mod bucket;
+pub mod gc;
 pub mod init;
+pub mod mount;
 pub mod service;
 pub mod version;
 
 pub use bucket::Bucket;
+pub use gc::Gc;
 pub use init::Init;
+pub use mount::Mount;
 pub use service::Service;
 pub use version::Version;
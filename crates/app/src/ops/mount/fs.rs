@@ -0,0 +1,1400 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::linked_data::Hash;
+use fuser::{FileAttr, FileType, FUSE_ROOT_ID as ROOT};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use service::http_server::api::client::{ApiClient, ApiError};
+use service::http_server::api::v0::bucket::add_dir::AddDirResponse;
+use service::http_server::api::v0::bucket::ls::PathInfo;
+use uuid::Uuid;
+
+use crate::ops::bucket::local::{upload_dir, LocalFile};
+
+/// Files in the bucket are read at least this much at a time, so reading
+///  one through doesn't ask the node for every page
+const READ_BLOCK: u64 = 1024 * 1024;
+/// Block size reported for every file
+const BLOCK_SIZE: u32 = 4096;
+
+/**
+ * Bucket filesystem
+ * =================
+ * A bucket served over FUSE, through the node's API. The tree is listed
+ *  once when mounted, and kept in memory from then on:
+ * - files in the bucket are read from the version they were listed at,
+ *   in blocks, with ranged `cat` requests
+ * - files written to are staged in a local directory, copying their
+ *   contents down first unless they're truncated
+ * - creating, removing and renaming only changes the tree in memory
+ *
+ * Saving compares the tree with the bucket as of the last save, and
+ *  sends what changed to `add_dir` as a single version: staged files
+ *  are uploaded, renamed files and directories are moved, and what's
+ *  gone is removed. Files still open for writing are left for the next
+ *  save, unless it's for their own `fsync`. Directories with nothing in
+ *  them are only kept in memory, since the bucket has no empty
+ *  directories of its own.
+ */
+pub struct BucketFs {
+    client: ApiClient,
+    bucket_id: Uuid,
+    runtime: tokio::runtime::Handle,
+    message: Option<String>,
+    uid: u32,
+    gid: u32,
+    tree: Mutex<Tree>,
+    // held for the whole of a save, so saves don't overlap
+    saving: Mutex<()>,
+    saves: AtomicUsize,
+}
+
+/// Which files still open for writing a save includes
+#[derive(Debug, Clone, Copy)]
+pub enum Include {
+    Closed,
+    Also(u64),
+    All,
+}
+
+impl Include {
+    fn includes(self, ino: u64) -> bool {
+        match self {
+            Include::Closed => false,
+            Include::Also(also) => also == ino,
+            Include::All => true,
+        }
+    }
+}
+
+struct Tree {
+    nodes: HashMap<u64, Node>,
+    next_ino: u64,
+    handles: HashMap<u64, Handle>,
+    next_fh: u64,
+    // every path in the bucket as of the last save, and whether it's a
+    //  directory
+    base: BTreeMap<String, bool>,
+    staging: PathBuf,
+    next_staged: u64,
+    // whether anything changed since the last save
+    dirty: bool,
+}
+
+/// A file or directory in the tree
+struct Node {
+    // the directory it's in, or 0 once it's been removed
+    parent: u64,
+    name: String,
+    // times the kernel looked it up and hasn't forgotten it yet
+    lookups: u64,
+    mtime: SystemTime,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Dir {
+        children: BTreeMap<String, u64>,
+        // where it was in the bucket as of the last save
+        origin: Option<String>,
+    },
+    File(Contents),
+}
+
+enum Contents {
+    /// In the bucket, at `origin` in `version`
+    Remote {
+        origin: String,
+        version: Hash,
+        // unknown for files added before sizes were recorded
+        size: Option<u64>,
+    },
+    /// Written locally, and not saved yet
+    Staged(Staged),
+}
+
+struct Staged {
+    file: File,
+    path: PathBuf,
+    size: u64,
+    // handles open for writing
+    writers: usize,
+    // bumped on every write, to tell if it changed while being saved
+    generation: u64,
+}
+
+enum Handle {
+    File {
+        ino: u64,
+        write: bool,
+        // the last block read from the bucket, and where it starts
+        block: Option<(u64, Vec<u8>)>,
+    },
+    Dir {
+        entries: Vec<(u64, OsString, FileType)>,
+    },
+}
+
+/// What a save sends, and what it's sent from
+#[derive(Default)]
+struct Snapshot {
+    removed: Vec<String>,
+    moves: Vec<(String, String)>,
+    uploads: Vec<Upload>,
+    // every file and directory saved, with its path
+    placed: Vec<(u64, String)>,
+    // whether a file was left out because it's still being written
+    skipped: bool,
+}
+
+struct Upload {
+    ino: u64,
+    generation: u64,
+    file: LocalFile,
+}
+
+/// Why a file couldn't be read from the bucket
+#[derive(Debug, thiserror::Error)]
+enum ReadError {
+    #[error("{0}")]
+    Api(#[from] ApiError),
+    #[error("HTTP error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Snapshot {
+    fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.moves.is_empty() && self.uploads.is_empty()
+    }
+}
+
+impl BucketFs {
+    /// Serve `listing`, every path in the bucket at `version`, staging
+    ///  writes in `staging`, as whoever owns it
+    pub fn new(
+        client: ApiClient,
+        bucket_id: Uuid,
+        version: Hash,
+        listing: BTreeMap<String, PathInfo>,
+        staging: PathBuf,
+        message: Option<String>,
+    ) -> std::io::Result<Self> {
+        let owner = std::fs::metadata(&staging)?;
+        let mounted_at = SystemTime::now();
+        let mut tree = Tree {
+            nodes: HashMap::new(),
+            next_ino: ROOT + 1,
+            handles: HashMap::new(),
+            next_fh: 1,
+            base: BTreeMap::new(),
+            staging,
+            next_staged: 0,
+            dirty: false,
+        };
+        tree.nodes.insert(
+            ROOT,
+            Node {
+                parent: ROOT,
+                name: String::new(),
+                lookups: 1,
+                mtime: mounted_at,
+                kind: NodeKind::Dir {
+                    children: BTreeMap::new(),
+                    origin: Some(String::new()),
+                },
+            },
+        );
+
+        // Listings are sorted, so directories come before what's in them
+        let mut dirs = HashMap::from([(String::new(), ROOT)]);
+        for (path, info) in listing {
+            let (parent_path, name) = match path.rsplit_once('/') {
+                Some((parent, name)) => (parent, name),
+                None => ("", path.as_str()),
+            };
+            let Some(&parent) = dirs.get(parent_path) else {
+                continue;
+            };
+            let mtime = info
+                .mtime
+                .map(|mtime| UNIX_EPOCH + Duration::from_millis(mtime))
+                .unwrap_or(mounted_at);
+            let kind = if info.is_dir {
+                NodeKind::Dir {
+                    children: BTreeMap::new(),
+                    origin: Some(path.clone()),
+                }
+            } else {
+                NodeKind::File(Contents::Remote {
+                    origin: path.clone(),
+                    version,
+                    size: info.size,
+                })
+            };
+            let ino = tree.insert(parent, name.to_string(), kind, mtime, 0);
+            if info.is_dir {
+                dirs.insert(path.clone(), ino);
+            }
+            tree.base.insert(path, info.is_dir);
+        }
+
+        Ok(Self {
+            client,
+            bucket_id,
+            runtime: tokio::runtime::Handle::current(),
+            message,
+            uid: owner.uid(),
+            gid: owner.gid(),
+            tree: Mutex::new(tree),
+            saving: Mutex::new(()),
+            saves: AtomicUsize::new(0),
+        })
+    }
+
+    fn tree(&self) -> MutexGuard<'_, Tree> {
+        self.tree.lock().expect("tree lock poisoned")
+    }
+
+    pub fn lookup(&self, parent: u64, name: &OsStr) -> Result<FileAttr, i32> {
+        let ino = {
+            let mut tree = self.tree();
+            let ino = tree.child(parent, name_str(name)?)?;
+            tree.node_mut(ino)?.lookups += 1;
+            ino
+        };
+        self.attr(ino)
+    }
+
+    pub fn forget(&self, ino: u64, nlookup: u64) {
+        let mut tree = self.tree();
+        if let Ok(node) = tree.node_mut(ino) {
+            node.lookups = node.lookups.saturating_sub(nlookup);
+            tree.maybe_drop(ino);
+        }
+    }
+
+    pub fn attr(&self, ino: u64) -> Result<FileAttr, i32> {
+        let (origin, version) = {
+            let tree = self.tree();
+            let node = tree.node(ino)?;
+            match &node.kind {
+                NodeKind::File(Contents::Remote {
+                    origin,
+                    version,
+                    size: None,
+                }) => (origin.clone(), *version),
+                _ => return Ok(self.node_attr(ino, node)),
+            }
+        };
+
+        let size = self.remote_size(&origin, version)?;
+        let mut tree = self.tree();
+        let node = tree.node_mut(ino)?;
+        if let NodeKind::File(Contents::Remote {
+            origin: current,
+            version: current_version,
+            size: known @ None,
+        }) = &mut node.kind
+        {
+            if *current == origin && *current_version == version {
+                *known = Some(size);
+            }
+        }
+        Ok(self.node_attr(ino, node))
+    }
+
+    fn node_attr(&self, ino: u64, node: &Node) -> FileAttr {
+        let (kind, size) = match &node.kind {
+            NodeKind::Dir { .. } => (FileType::Directory, 0),
+            NodeKind::File(Contents::Staged(staged)) => (FileType::RegularFile, staged.size),
+            NodeKind::File(Contents::Remote { size, .. }) => {
+                (FileType::RegularFile, size.unwrap_or(0))
+            }
+        };
+        let (perm, nlink) = match kind {
+            FileType::Directory => (0o755, 2),
+            _ => (0o644, 1),
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: node.mtime,
+            mtime: node.mtime,
+            ctime: node.mtime,
+            crtime: node.mtime,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    pub fn setattr(
+        &self,
+        ino: u64,
+        size: Option<u64>,
+        mtime: Option<SystemTime>,
+    ) -> Result<FileAttr, i32> {
+        if let Some(size) = size {
+            self.truncate(ino, size)?;
+        }
+        if let Some(mtime) = mtime {
+            let mut tree = self.tree();
+            let node = tree.node_mut(ino)?;
+            node.mtime = mtime;
+            // Only recorded for files that are uploaded again
+            if matches!(node.kind, NodeKind::File(Contents::Staged(_))) {
+                tree.dirty = true;
+            }
+        }
+        self.attr(ino)
+    }
+
+    fn truncate(&self, ino: u64, size: u64) -> Result<(), i32> {
+        self.stage(ino, size == 0)?;
+        let mut tree = self.tree();
+        let node = tree.node_mut(ino)?;
+        let NodeKind::File(Contents::Staged(staged)) = &mut node.kind else {
+            return Err(libc::EIO);
+        };
+        staged.file.set_len(size).map_err(io_errno)?;
+        staged.size = size;
+        staged.generation += 1;
+        node.mtime = SystemTime::now();
+        tree.dirty = true;
+        Ok(())
+    }
+
+    /// Make a file writable, by copying it down from the bucket unless
+    ///  it's about to be emptied anyway
+    fn stage(&self, ino: u64, empty: bool) -> Result<(), i32> {
+        let (origin, version) = {
+            let mut tree = self.tree();
+            let (origin, version) = match &tree.node(ino)?.kind {
+                NodeKind::Dir { .. } => return Err(libc::EISDIR),
+                NodeKind::File(Contents::Staged(_)) => return Ok(()),
+                NodeKind::File(Contents::Remote {
+                    origin, version, ..
+                }) => (origin.clone(), *version),
+            };
+            if empty {
+                let staged = tree.new_staged().map_err(io_errno)?;
+                tree.node_mut(ino)?.kind = NodeKind::File(Contents::Staged(staged));
+                tree.dirty = true;
+                return Ok(());
+            }
+            (origin, version)
+        };
+
+        let mut staged = self.tree().new_staged().map_err(io_errno)?;
+        if let Err(e) = self.download(&origin, version, &mut staged) {
+            let _ = std::fs::remove_file(&staged.path);
+            return Err(e);
+        }
+        let mut tree = self.tree();
+        let node = tree.node_mut(ino)?;
+        match &node.kind {
+            NodeKind::File(Contents::Remote {
+                origin: current,
+                version: current_version,
+                ..
+            }) if *current == origin && *current_version == version => {
+                node.kind = NodeKind::File(Contents::Staged(staged));
+                tree.dirty = true;
+            }
+            // Staged by another request in the meantime
+            _ => {
+                let _ = std::fs::remove_file(&staged.path);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mkdir(&self, parent: u64, name: &OsStr) -> Result<FileAttr, i32> {
+        let ino = {
+            let mut tree = self.tree();
+            let name = name_str(name)?;
+            tree.check_free(parent, name)?;
+            let kind = NodeKind::Dir {
+                children: BTreeMap::new(),
+                origin: None,
+            };
+            let ino = tree.insert(parent, name.to_string(), kind, SystemTime::now(), 1);
+            tree.dirty = true;
+            ino
+        };
+        self.attr(ino)
+    }
+
+    /// Create an empty file, and open it if `flags` are given
+    pub fn create(
+        &self,
+        parent: u64,
+        name: &OsStr,
+        flags: Option<i32>,
+    ) -> Result<(FileAttr, u64), i32> {
+        let (ino, fh) = {
+            let mut tree = self.tree();
+            let name = name_str(name)?;
+            tree.check_free(parent, name)?;
+            let mut staged = tree.new_staged().map_err(io_errno)?;
+            let write = flags.is_some_and(is_write);
+            if write {
+                staged.writers += 1;
+            }
+            let kind = NodeKind::File(Contents::Staged(staged));
+            let ino = tree.insert(parent, name.to_string(), kind, SystemTime::now(), 1);
+            let fh = match flags {
+                Some(_) => tree.open_handle(Handle::File {
+                    ino,
+                    write,
+                    block: None,
+                }),
+                None => 0,
+            };
+            tree.dirty = true;
+            (ino, fh)
+        };
+        Ok((self.attr(ino)?, fh))
+    }
+
+    pub fn remove(&self, parent: u64, name: &OsStr, dir: bool) -> Result<(), i32> {
+        let mut tree = self.tree();
+        let ino = tree.child(parent, name_str(name)?)?;
+        match (&tree.node(ino)?.kind, dir) {
+            (NodeKind::Dir { children, .. }, true) if !children.is_empty() => {
+                return Err(libc::ENOTEMPTY)
+            }
+            (NodeKind::Dir { .. }, false) => return Err(libc::EISDIR),
+            (NodeKind::File(_), true) => return Err(libc::ENOTDIR),
+            _ => {}
+        }
+        tree.detach(ino);
+        tree.dirty = true;
+        Ok(())
+    }
+
+    pub fn rename(
+        &self,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+    ) -> Result<(), i32> {
+        if flags & libc::RENAME_EXCHANGE != 0 {
+            return Err(libc::EINVAL);
+        }
+        let (name, newname) = (name_str(name)?, name_str(newname)?);
+        let mut tree = self.tree();
+        let ino = tree.child(parent, name)?;
+        tree.children(newparent)?;
+
+        // A directory can't go under itself
+        let mut up = newparent;
+        while up != ROOT {
+            if up == ino {
+                return Err(libc::EINVAL);
+            }
+            up = tree.node(up)?.parent;
+        }
+
+        match tree.child(newparent, newname) {
+            Ok(existing) if existing == ino => return Ok(()),
+            Ok(_) if flags & libc::RENAME_NOREPLACE != 0 => return Err(libc::EEXIST),
+            Ok(existing) => {
+                let is_dir = matches!(tree.node(ino)?.kind, NodeKind::Dir { .. });
+                match (&tree.node(existing)?.kind, is_dir) {
+                    (NodeKind::Dir { children, .. }, true) if !children.is_empty() => {
+                        return Err(libc::ENOTEMPTY)
+                    }
+                    (NodeKind::Dir { .. }, false) => return Err(libc::EISDIR),
+                    (NodeKind::File(_), true) => return Err(libc::ENOTDIR),
+                    _ => {}
+                }
+                tree.detach(existing);
+            }
+            Err(libc::ENOENT) => {}
+            Err(e) => return Err(e),
+        }
+
+        tree.children(parent)?.remove(name);
+        tree.children(newparent)?.insert(newname.to_string(), ino);
+        let node = tree.node_mut(ino)?;
+        node.parent = newparent;
+        node.name = newname.to_string();
+        tree.dirty = true;
+        Ok(())
+    }
+
+    pub fn open(&self, ino: u64, flags: i32) -> Result<u64, i32> {
+        let write = is_write(flags);
+        loop {
+            if write {
+                if flags & libc::O_TRUNC != 0 {
+                    self.truncate(ino, 0)?;
+                } else {
+                    self.stage(ino, false)?;
+                }
+            }
+            let mut tree = self.tree();
+            match &mut tree.node_mut(ino)?.kind {
+                NodeKind::Dir { .. } => return Err(libc::EISDIR),
+                NodeKind::File(Contents::Staged(staged)) if write => staged.writers += 1,
+                // Saved since it was staged, so stage it again
+                NodeKind::File(Contents::Remote { .. }) if write => continue,
+                NodeKind::File(_) => {}
+            }
+            return Ok(tree.open_handle(Handle::File {
+                ino,
+                write,
+                block: None,
+            }));
+        }
+    }
+
+    pub fn read(&self, fh: u64, offset: u64, size: u32) -> Result<Vec<u8>, i32> {
+        let ino = match self.tree().handles.get(&fh) {
+            Some(Handle::File { ino, .. }) => *ino,
+            _ => return Err(libc::EBADF),
+        };
+        let file_size = self.attr(ino)?.size;
+        let end = file_size.min(offset.saturating_add(u64::from(size)));
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let (origin, version) = {
+            let tree = self.tree();
+            match &tree.node(ino)?.kind {
+                NodeKind::Dir { .. } => return Err(libc::EISDIR),
+                NodeKind::File(Contents::Staged(staged)) => {
+                    let mut data = vec![0; (end - offset) as usize];
+                    staged
+                        .file
+                        .read_exact_at(&mut data, offset)
+                        .map_err(io_errno)?;
+                    return Ok(data);
+                }
+                NodeKind::File(Contents::Remote {
+                    origin, version, ..
+                }) => {
+                    if let Some(Handle::File {
+                        block: Some((start, block)),
+                        ..
+                    }) = tree.handles.get(&fh)
+                    {
+                        if *start <= offset && end <= start + block.len() as u64 {
+                            let from = (offset - start) as usize;
+                            return Ok(block[from..from + (end - offset) as usize].to_vec());
+                        }
+                    }
+                    (origin.clone(), *version)
+                }
+            }
+        };
+
+        let block_end = file_size.min(end.max(offset + READ_BLOCK));
+        let block = self.fetch(&origin, version, offset, block_end)?;
+        if (block.len() as u64) < end - offset {
+            return Err(libc::EIO);
+        }
+        let data = block[..(end - offset) as usize].to_vec();
+        if let Some(Handle::File { block: cached, .. }) = self.tree().handles.get_mut(&fh) {
+            *cached = Some((offset, block));
+        }
+        Ok(data)
+    }
+
+    pub fn write(&self, fh: u64, offset: u64, data: &[u8]) -> Result<u32, i32> {
+        let mut tree = self.tree();
+        let ino = match tree.handles.get(&fh) {
+            Some(Handle::File {
+                ino, write: true, ..
+            }) => *ino,
+            _ => return Err(libc::EBADF),
+        };
+        let node = tree.node_mut(ino)?;
+        let NodeKind::File(Contents::Staged(staged)) = &mut node.kind else {
+            return Err(libc::EIO);
+        };
+        staged.file.write_all_at(data, offset).map_err(io_errno)?;
+        staged.size = staged.size.max(offset + data.len() as u64);
+        staged.generation += 1;
+        node.mtime = SystemTime::now();
+        tree.dirty = true;
+        Ok(data.len() as u32)
+    }
+
+    pub fn release(&self, fh: u64) {
+        let mut tree = self.tree();
+        let Some(handle) = tree.handles.remove(&fh) else {
+            return;
+        };
+        if let Handle::File { ino, write, .. } = handle {
+            if let Ok(Node {
+                kind: NodeKind::File(Contents::Staged(staged)),
+                ..
+            }) = tree.node_mut(ino)
+            {
+                if write {
+                    staged.writers -= 1;
+                }
+            }
+            tree.maybe_drop(ino);
+        }
+    }
+
+    pub fn fsync(&self, fh: u64) -> Result<(), i32> {
+        let ino = match self.tree().handles.get(&fh) {
+            Some(Handle::File { ino, .. }) => *ino,
+            _ => return Err(libc::EBADF),
+        };
+        match self.save(Include::Also(ino)) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Failed to save changes: {}", e);
+                Err(libc::EIO)
+            }
+        }
+    }
+
+    pub fn opendir(&self, ino: u64) -> Result<u64, i32> {
+        let mut tree = self.tree();
+        let node = tree.node(ino)?;
+        let NodeKind::Dir { children, .. } = &node.kind else {
+            return Err(libc::ENOTDIR);
+        };
+        let mut entries = vec![
+            (ino, OsString::from("."), FileType::Directory),
+            (node.parent, OsString::from(".."), FileType::Directory),
+        ];
+        for (name, &child) in children {
+            let kind = match tree.node(child)?.kind {
+                NodeKind::Dir { .. } => FileType::Directory,
+                NodeKind::File(_) => FileType::RegularFile,
+            };
+            entries.push((child, OsString::from(name), kind));
+        }
+        Ok(tree.open_handle(Handle::Dir { entries }))
+    }
+
+    /// List a directory opened with `opendir` from `offset`, passing each
+    ///  entry to `add` with the offset of the one after it, until it returns
+    ///  true because the reply is full
+    pub fn readdir(
+        &self,
+        fh: u64,
+        offset: u64,
+        mut add: impl FnMut(u64, u64, FileType, &OsStr) -> bool,
+    ) -> Result<(), i32> {
+        let tree = self.tree();
+        let Some(Handle::Dir { entries }) = tree.handles.get(&fh) else {
+            return Err(libc::EBADF);
+        };
+        for (i, (ino, name, kind)) in entries.iter().enumerate().skip(offset as usize) {
+            if add(*ino, i as u64 + 1, *kind, name) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save what changed since the last save as a new version of the
+    ///  bucket, returning whether there was anything to save
+    pub fn save(&self, include: Include) -> Result<bool, ApiError> {
+        let _saving = self.saving.lock().expect("save lock poisoned");
+        let snapshot = {
+            let mut tree = self.tree();
+            if !tree.dirty {
+                return Ok(false);
+            }
+            let snapshot = tree.snapshot(include);
+            tree.dirty = snapshot.skipped;
+            snapshot
+        };
+        if snapshot.is_empty() {
+            return Ok(false);
+        }
+
+        let files = snapshot
+            .uploads
+            .iter()
+            .map(|upload| LocalFile {
+                path: upload.file.path.clone(),
+                name: upload.file.name.clone(),
+                len: upload.file.len,
+                mtime: upload.file.mtime,
+            })
+            .collect();
+        let response = self.runtime.block_on(upload_dir(
+            &self.client,
+            self.bucket_id,
+            "/",
+            self.message.as_deref(),
+            files,
+            &snapshot.removed,
+            &snapshot.moves,
+        ));
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.tree().dirty = true;
+                return Err(e);
+            }
+        };
+        self.tree().saved(&snapshot, *response.link.hash());
+        self.saves.fetch_add(1, Ordering::Relaxed);
+        tracing::info!("{}", describe(&response));
+        Ok(true)
+    }
+
+    /// Versions of the bucket saved so far
+    pub fn saves(&self) -> usize {
+        self.saves.load(Ordering::Relaxed)
+    }
+
+    /// Read `start..end` of a file in the bucket
+    fn fetch(&self, origin: &str, version: Hash, start: u64, end: u64) -> Result<Vec<u8>, i32> {
+        let result: Result<Vec<u8>, ReadError> = self.runtime.block_on(async {
+            let response = self
+                .cat(origin, version)?
+                .header(RANGE, format!("bytes={}-{}", start, end - 1))
+                .send()
+                .await?;
+            match response.status() {
+                StatusCode::PARTIAL_CONTENT => Ok(response.bytes().await?.to_vec()),
+                // The whole file, if the range wasn't honoured
+                StatusCode::OK => {
+                    let body = response.bytes().await?;
+                    let start = (start as usize).min(body.len());
+                    let end = (end as usize).min(body.len());
+                    Ok(body[start..end].to_vec())
+                }
+                StatusCode::RANGE_NOT_SATISFIABLE => Ok(Vec::new()),
+                status => Err(ApiError::HttpStatus(status, response.text().await?).into()),
+            }
+        });
+        result.map_err(|e| {
+            tracing::warn!("Failed to read /{}: {}", origin, e);
+            libc::EIO
+        })
+    }
+
+    /// Size of a file in the bucket that didn't have its size recorded
+    fn remote_size(&self, origin: &str, version: Hash) -> Result<u64, i32> {
+        let result: Result<Option<u64>, ReadError> = self.runtime.block_on(async {
+            let response = self
+                .cat(origin, version)?
+                .header(RANGE, "bytes=0-0")
+                .send()
+                .await?;
+            let status = response.status();
+            if !status.is_success() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+                return Err(ApiError::HttpStatus(status, response.text().await?).into());
+            }
+            // `bytes 0-0/<size>`, or `bytes */<size>` for an empty file
+            Ok(response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .and_then(|range| range.rsplit_once('/'))
+                .and_then(|(_, size)| size.parse().ok())
+                .or(response.content_length()))
+        });
+        match result {
+            Ok(Some(size)) => Ok(size),
+            Ok(None) => Err(libc::EIO),
+            Err(e) => {
+                tracing::warn!("Failed to read /{}: {}", origin, e);
+                Err(libc::EIO)
+            }
+        }
+    }
+
+    /// Copy a file in the bucket into a staged file
+    fn download(&self, origin: &str, version: Hash, staged: &mut Staged) -> Result<(), i32> {
+        let result: Result<u64, ReadError> = self.runtime.block_on(async {
+            let mut response = self.cat(origin, version)?.send().await?;
+            if !response.status().is_success() {
+                let status = response.status();
+                return Err(ApiError::HttpStatus(status, response.text().await?).into());
+            }
+            let mut size = 0;
+            while let Some(chunk) = response.chunk().await? {
+                staged.file.write_all(&chunk)?;
+                size += chunk.len() as u64;
+            }
+            Ok(size)
+        });
+        match result {
+            Ok(size) => {
+                staged.size = size;
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read /{}: {}", origin, e);
+                Err(libc::EIO)
+            }
+        }
+    }
+
+    fn cat(&self, origin: &str, version: Hash) -> Result<reqwest::RequestBuilder, ApiError> {
+        let url = self.client.base_url().join("/api/v0/bucket/cat")?;
+        let query = [
+            ("bucket_id", self.bucket_id.to_string()),
+            ("path", format!("/{}", origin)),
+            ("download", "true".to_string()),
+            ("version", version.to_string()),
+        ];
+        Ok(self.client.http_client().get(url).query(&query))
+    }
+}
+
+impl Tree {
+    fn node(&self, ino: u64) -> Result<&Node, i32> {
+        self.nodes.get(&ino).ok_or(libc::ENOENT)
+    }
+
+    fn node_mut(&mut self, ino: u64) -> Result<&mut Node, i32> {
+        self.nodes.get_mut(&ino).ok_or(libc::ENOENT)
+    }
+
+    /// What's in a directory that's still in the tree
+    fn children(&mut self, ino: u64) -> Result<&mut BTreeMap<String, u64>, i32> {
+        let node = self.node_mut(ino)?;
+        if node.parent == 0 {
+            return Err(libc::ENOENT);
+        }
+        match &mut node.kind {
+            NodeKind::Dir { children, .. } => Ok(children),
+            NodeKind::File(_) => Err(libc::ENOTDIR),
+        }
+    }
+
+    fn child(&self, parent: u64, name: &str) -> Result<u64, i32> {
+        match &self.node(parent)?.kind {
+            NodeKind::Dir { children, .. } => children.get(name).copied().ok_or(libc::ENOENT),
+            NodeKind::File(_) => Err(libc::ENOTDIR),
+        }
+    }
+
+    /// Check a new file or directory can go at `name` in `parent`
+    fn check_free(&mut self, parent: u64, name: &str) -> Result<(), i32> {
+        if self.children(parent)?.contains_key(name) {
+            return Err(libc::EEXIST);
+        }
+        Ok(())
+    }
+
+    fn insert(
+        &mut self,
+        parent: u64,
+        name: String,
+        kind: NodeKind,
+        mtime: SystemTime,
+        lookups: u64,
+    ) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        if let Some(Node {
+            kind: NodeKind::Dir { children, .. },
+            ..
+        }) = self.nodes.get_mut(&parent)
+        {
+            children.insert(name.clone(), ino);
+        }
+        self.nodes.insert(
+            ino,
+            Node {
+                parent,
+                name,
+                lookups,
+                mtime,
+                kind,
+            },
+        );
+        ino
+    }
+
+    fn open_handle(&mut self, handle: Handle) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.handles.insert(fh, handle);
+        fh
+    }
+
+    fn new_staged(&mut self) -> std::io::Result<Staged> {
+        let path = self.staging.join(self.next_staged.to_string());
+        self.next_staged += 1;
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Staged {
+            file,
+            path,
+            size: 0,
+            writers: 0,
+            generation: 0,
+        })
+    }
+
+    /// Take a node out of the tree, keeping it for as long as the kernel
+    ///  still refers to it
+    fn detach(&mut self, ino: u64) {
+        let Some(node) = self.nodes.get_mut(&ino) else {
+            return;
+        };
+        let (parent, name) = (node.parent, node.name.clone());
+        node.parent = 0;
+        if let Ok(children) = self.children(parent) {
+            children.remove(&name);
+        }
+        self.maybe_drop(ino);
+    }
+
+    /// Forget a node that's out of the tree once nothing refers to it
+    fn maybe_drop(&mut self, ino: u64) {
+        let Some(node) = self.nodes.get(&ino) else {
+            return;
+        };
+        if ino == ROOT || node.parent != 0 || node.lookups > 0 {
+            return;
+        }
+        let open = self
+            .handles
+            .values()
+            .any(|handle| matches!(handle, Handle::File { ino: open, .. } if *open == ino));
+        if open {
+            return;
+        }
+        if let Some(Node {
+            kind: NodeKind::File(Contents::Staged(staged)),
+            ..
+        }) = self.nodes.remove(&ino)
+        {
+            let _ = std::fs::remove_file(&staged.path);
+        }
+    }
+
+    /// Names directly under `dir` in the bucket, as of the last save
+    fn base_children<'a>(&'a self, dir: &str) -> impl Iterator<Item = &'a str> + 'a {
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir)
+        };
+        let skip = prefix.len();
+        self.base
+            .range(prefix.clone()..)
+            .take_while(move |(path, _)| path.starts_with(&prefix))
+            .filter_map(move |(path, _)| {
+                let name = &path[skip..];
+                (!name.contains('/')).then_some(name)
+            })
+    }
+
+    /// What changed since the last save
+    fn snapshot(&self, include: Include) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        self.diff_dir(ROOT, "", Some(""), include, &mut snapshot);
+        snapshot
+    }
+
+    /// Compare a directory at `path` with `content`, the directory of the
+    ///  bucket that's there once the changes found so far are made
+    fn diff_dir(
+        &self,
+        ino: u64,
+        path: &str,
+        content: Option<&str>,
+        include: Include,
+        snapshot: &mut Snapshot,
+    ) {
+        let Some(Node {
+            kind: NodeKind::Dir { children, .. },
+            ..
+        }) = self.nodes.get(&ino)
+        else {
+            return;
+        };
+        for (name, &child) in children {
+            let child_path = join(path, name);
+            // What the bucket has at the same place
+            let there = content
+                .map(|content| join(content, name))
+                .filter(|there| self.base.contains_key(there));
+            let node = &self.nodes[&child];
+            match &node.kind {
+                NodeKind::Dir { origin, .. } => {
+                    let content = match origin {
+                        Some(origin) if there.as_ref() == Some(origin) => Some(origin.as_str()),
+                        Some(origin) => {
+                            snapshot.moves.push((origin.clone(), child_path.clone()));
+                            Some(origin.as_str())
+                        }
+                        None => {
+                            if there.is_some() {
+                                snapshot.removed.push(child_path.clone());
+                            }
+                            None
+                        }
+                    };
+                    snapshot.placed.push((child, child_path.clone()));
+                    self.diff_dir(child, &child_path, content, include, snapshot);
+                }
+                NodeKind::File(Contents::Remote { origin, .. }) => {
+                    if there.as_ref() != Some(origin) {
+                        snapshot.moves.push((origin.clone(), child_path.clone()));
+                    }
+                    snapshot.placed.push((child, child_path));
+                }
+                NodeKind::File(Contents::Staged(staged)) => {
+                    if staged.writers > 0 && !include.includes(child) {
+                        snapshot.skipped = true;
+                        continue;
+                    }
+                    snapshot.uploads.push(Upload {
+                        ino: child,
+                        generation: staged.generation,
+                        file: LocalFile {
+                            path: staged.path.clone(),
+                            name: child_path.clone(),
+                            len: staged.size,
+                            mtime: node
+                                .mtime
+                                .duration_since(UNIX_EPOCH)
+                                .ok()
+                                .and_then(|mtime| u64::try_from(mtime.as_millis()).ok()),
+                        },
+                    });
+                    snapshot.placed.push((child, child_path));
+                }
+            }
+        }
+
+        // What the bucket had here that's gone
+        if let Some(content) = content {
+            for name in self.base_children(content) {
+                if !children.contains_key(name) {
+                    snapshot.removed.push(join(path, name));
+                }
+            }
+        }
+    }
+
+    /// Catch up with a saved snapshot, which is now `version` of the bucket
+    fn saved(&mut self, snapshot: &Snapshot, version: Hash) {
+        let uploads: Vec<String> = snapshot
+            .uploads
+            .iter()
+            .map(|upload| upload.file.name.clone())
+            .collect();
+        self.base = apply_changes(&self.base, &snapshot.removed, &snapshot.moves, &uploads);
+        let generations: HashMap<u64, u64> = snapshot
+            .uploads
+            .iter()
+            .map(|upload| (upload.ino, upload.generation))
+            .collect();
+
+        for (ino, path) in &snapshot.placed {
+            let in_base = self.base.get(path).copied();
+            let Some(node) = self.nodes.get_mut(ino) else {
+                continue;
+            };
+            match &mut node.kind {
+                NodeKind::Dir { origin, .. } => {
+                    *origin = (in_base == Some(true)).then(|| path.clone());
+                }
+                NodeKind::File(Contents::Remote {
+                    origin,
+                    version: from,
+                    ..
+                }) => {
+                    if origin != path {
+                        *origin = path.clone();
+                        *from = version;
+                    }
+                }
+                // Read from the bucket from now on, unless it's changed or
+                //  is being written to
+                NodeKind::File(Contents::Staged(staged)) => {
+                    if staged.writers == 0 && generations.get(ino) == Some(&staged.generation) {
+                        let _ = std::fs::remove_file(&staged.path);
+                        node.kind = NodeKind::File(Contents::Remote {
+                            origin: path.clone(),
+                            version,
+                            size: Some(staged.size),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Paths in the bucket after a save, as `add_dir` applies it: removals and
+///  the sources of moves go first, then the destinations of moves and
+///  uploads, with changes to a directory made before those under it
+fn apply_changes(
+    base: &BTreeMap<String, bool>,
+    removed: &[String],
+    moves: &[(String, String)],
+    uploads: &[String],
+) -> BTreeMap<String, bool> {
+    // Paths set to a subtree, relative to where it goes, or removed
+    let mut changes: BTreeMap<String, Option<Vec<(String, bool)>>> = BTreeMap::new();
+    for path in removed {
+        changes.insert(path.clone(), None);
+    }
+    for (from, _) in moves {
+        changes.entry(from.clone()).or_insert(None);
+    }
+    for (from, to) in moves {
+        changes.insert(to.clone(), Some(subtree(base, from)));
+    }
+    for path in uploads {
+        changes.insert(path.clone(), Some(vec![(String::new(), false)]));
+    }
+
+    let mut next = base.clone();
+    for (path, change) in changes {
+        let prefix = format!("{}/", path);
+        next.retain(|existing, _| *existing != path && !existing.starts_with(&prefix));
+        if let Some(entries) = change {
+            for (i, _) in path.match_indices('/') {
+                next.insert(path[..i].to_string(), true);
+            }
+            for (relative, is_dir) in entries {
+                next.insert(join(&path, &relative), is_dir);
+            }
+        }
+    }
+    next
+}
+
+/// A path and everything under it, relative to it
+fn subtree(base: &BTreeMap<String, bool>, path: &str) -> Vec<(String, bool)> {
+    let prefix = format!("{}/", path);
+    base.iter()
+        .filter_map(|(existing, &is_dir)| {
+            if existing == path {
+                Some((String::new(), is_dir))
+            } else {
+                existing
+                    .strip_prefix(&prefix)
+                    .map(|relative| (relative.to_string(), is_dir))
+            }
+        })
+        .collect()
+}
+
+fn join(dir: &str, name: &str) -> String {
+    match (dir.is_empty(), name.is_empty()) {
+        (true, _) => name.to_string(),
+        (_, true) => dir.to_string(),
+        _ => format!("{}/{}", dir, name),
+    }
+}
+
+fn name_str(name: &OsStr) -> Result<&str, i32> {
+    name.to_str().ok_or(libc::EINVAL)
+}
+
+fn is_write(flags: i32) -> bool {
+    flags & libc::O_ACCMODE != libc::O_RDONLY
+}
+
+fn io_errno(e: std::io::Error) -> i32 {
+    e.raw_os_error().unwrap_or(libc::EIO)
+}
+
+fn describe(response: &AddDirResponse) -> String {
+    format!(
+        "Saved {} changed, {} removed, {} moved (link: {})",
+        response.added.len(),
+        response.removed.len(),
+        response.moved.len(),
+        response.link.hash()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use common::linked_data::Link;
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::*;
+
+    /// A filesystem over `paths`, with directories ending in `/`, that
+    ///  never reaches a node
+    fn setup(paths: &[&str]) -> (BucketFs, TempDir) {
+        let listing = paths
+            .iter()
+            .map(|path| {
+                let is_dir = path.ends_with('/');
+                let path = path.trim_end_matches('/').to_string();
+                let info = PathInfo {
+                    path: format!("/{}", path),
+                    name: path.rsplit('/').next().unwrap().to_string(),
+                    link: Link::default(),
+                    is_dir,
+                    mime_type: String::new(),
+                    size: (!is_dir).then_some(3),
+                    mtime: None,
+                    hash: None,
+                };
+                (path, info)
+            })
+            .collect();
+        let staging = TempDir::new().unwrap();
+        let client = ApiClient::new(&Url::parse("http://localhost:1").unwrap()).unwrap();
+        let fs = BucketFs::new(
+            client,
+            Uuid::new_v4(),
+            Hash::new(b"version"),
+            listing,
+            staging.path().to_path_buf(),
+            None,
+        )
+        .unwrap();
+        (fs, staging)
+    }
+
+    fn lookup(fs: &BucketFs, parent: u64, name: &str) -> Result<FileAttr, i32> {
+        fs.lookup(parent, OsStr::new(name))
+    }
+
+    #[tokio::test]
+    async fn test_listing_maps_paths_to_inodes() {
+        let (fs, _staging) = setup(&["docs/", "docs/a.txt", "b.txt"]);
+
+        assert_eq!(fs.attr(ROOT).unwrap().kind, FileType::Directory);
+        let docs = lookup(&fs, ROOT, "docs").unwrap();
+        assert_eq!(docs.kind, FileType::Directory);
+        let a = lookup(&fs, docs.ino, "a.txt").unwrap();
+        assert_eq!(a.kind, FileType::RegularFile);
+        assert_eq!(a.size, 3);
+        // Looked up again, it's the same inode
+        assert_eq!(lookup(&fs, docs.ino, "a.txt").unwrap().ino, a.ino);
+        assert_ne!(lookup(&fs, ROOT, "b.txt").unwrap().ino, a.ino);
+
+        assert_eq!(lookup(&fs, ROOT, "a.txt").unwrap_err(), libc::ENOENT);
+        assert_eq!(lookup(&fs, a.ino, "x").unwrap_err(), libc::ENOTDIR);
+        assert!(fs.tree().snapshot(Include::All).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_renames_and_removals_are_saved_by_path() {
+        let (fs, _staging) = setup(&["docs/", "docs/a.txt", "b.txt", "c.txt"]);
+        let docs = lookup(&fs, ROOT, "docs").unwrap().ino;
+        let a = lookup(&fs, docs, "a.txt").unwrap().ino;
+
+        fs.rename(ROOT, OsStr::new("docs"), ROOT, OsStr::new("papers"), 0)
+            .unwrap();
+        fs.remove(ROOT, OsStr::new("b.txt"), false).unwrap();
+        // A directory can't go under itself, and a file isn't a directory
+        assert_eq!(
+            fs.rename(ROOT, OsStr::new("papers"), docs, OsStr::new("x"), 0),
+            Err(libc::EINVAL)
+        );
+        assert_eq!(
+            fs.remove(ROOT, OsStr::new("c.txt"), true),
+            Err(libc::ENOTDIR)
+        );
+        assert_eq!(
+            fs.rename(
+                ROOT,
+                OsStr::new("c.txt"),
+                docs,
+                OsStr::new("a.txt"),
+                libc::RENAME_NOREPLACE
+            ),
+            Err(libc::EEXIST)
+        );
+
+        // The file keeps its inode under the directory's new name
+        assert_eq!(lookup(&fs, ROOT, "docs").unwrap_err(), libc::ENOENT);
+        assert_eq!(lookup(&fs, ROOT, "papers").unwrap().ino, docs);
+        assert_eq!(lookup(&fs, docs, "a.txt").unwrap().ino, a);
+
+        let mut tree = fs.tree();
+        let snapshot = tree.snapshot(Include::All);
+        assert_eq!(
+            snapshot.moves,
+            vec![("docs".to_string(), "papers".to_string())]
+        );
+        // The directory is gone from where it was too, which `add_dir`
+        //  applies along with the move
+        assert_eq!(snapshot.removed, vec!["b.txt", "docs"]);
+
+        // Once saved, the bucket is where the tree is
+        tree.saved(&snapshot, Hash::new(b"saved"));
+        assert_eq!(
+            tree.base.keys().collect::<Vec<_>>(),
+            vec!["c.txt", "papers", "papers/a.txt"]
+        );
+        assert!(tree.snapshot(Include::All).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_files_being_written_wait_for_release() {
+        let (fs, _staging) = setup(&[]);
+        let dir = fs.mkdir(ROOT, OsStr::new("new")).unwrap().ino;
+        let (file, fh) = fs
+            .create(dir, OsStr::new("c.txt"), Some(libc::O_WRONLY))
+            .unwrap();
+        assert_eq!(fs.write(fh, 0, b"abc").unwrap(), 3);
+        assert_eq!(fs.attr(file.ino).unwrap().size, 3);
+
+        let snapshot = fs.tree().snapshot(Include::Closed);
+        assert!(snapshot.skipped);
+        assert!(snapshot.uploads.is_empty());
+        let snapshot = fs.tree().snapshot(Include::Also(file.ino));
+        assert_eq!(snapshot.uploads[0].file.name, "new/c.txt");
+
+        fs.release(fh);
+        let snapshot = fs.tree().snapshot(Include::Closed);
+        assert!(!snapshot.skipped);
+        assert_eq!(snapshot.uploads.len(), 1);
+        assert_eq!(snapshot.uploads[0].ino, file.ino);
+    }
+
+    #[tokio::test]
+    async fn test_removed_inodes_live_until_forgotten() {
+        let (fs, staging) = setup(&[]);
+        let (file, fh) = fs
+            .create(ROOT, OsStr::new("gone.txt"), Some(libc::O_RDWR))
+            .unwrap();
+        fs.write(fh, 0, b"abc").unwrap();
+        fs.remove(ROOT, OsStr::new("gone.txt"), false).unwrap();
+
+        // Still open, and still looked up once by the kernel
+        assert_eq!(lookup(&fs, ROOT, "gone.txt").unwrap_err(), libc::ENOENT);
+        assert_eq!(fs.read(fh, 0, 10).unwrap(), b"abc");
+        fs.release(fh);
+        assert_eq!(fs.attr(file.ino).unwrap().size, 3);
+
+        fs.forget(file.ino, 1);
+        assert_eq!(fs.attr(file.ino).unwrap_err(), libc::ENOENT);
+        assert_eq!(std::fs::read_dir(staging.path()).unwrap().count(), 0);
+    }
+}
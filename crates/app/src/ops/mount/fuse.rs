@@ -0,0 +1,470 @@
+//! Serving a [`BucketFs`] to the kernel through `fuser`
+//!
+//! `fuser` reads requests one at a time, so each one is answered on a
+//! blocking thread of its own, and a slow read from the bucket doesn't hold
+//! up the rest.
+
+use std::env;
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use fuser::{
+    Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request, Session, TimeOrNow,
+};
+use service::http_server::api::v0::bucket::log::LogRequest;
+use uuid::Uuid;
+
+use super::fs::{BucketFs, Include};
+use super::{Mount, MountError};
+use crate::ops::bucket::local::list_bucket_dir;
+
+/// How long the kernel may cache names and attributes
+const TTL: Duration = Duration::from_secs(1);
+
+/// Mount the bucket and serve it until it's unmounted, saving changes as
+///  it goes
+pub async fn run(mount: &Mount, ctx: &crate::op::OpContext) -> Result<String, MountError> {
+    let mut client = ctx.client.clone();
+    let bucket_id = match Uuid::parse_str(&mount.bucket) {
+        Ok(id) => id,
+        Err(_) => client.resolve_bucket_name(&mount.bucket).await?,
+    };
+
+    let mountpoint = if mount.mountpoint.is_absolute() {
+        mount.mountpoint.clone()
+    } else {
+        env::current_dir()?.join(&mount.mountpoint)
+    };
+    if !tokio::fs::metadata(&mountpoint).await?.is_dir() {
+        return Err(MountError::NotADirectory(mountpoint.display().to_string()));
+    }
+
+    // Serve the version that was listed, whatever else happens to the
+    //  bucket while it's mounted
+    let request = LogRequest {
+        bucket_id,
+        limit: Some(1),
+    };
+    let log = client.call(request).await?;
+    let version = *log
+        .versions
+        .first()
+        .ok_or_else(|| MountError::NotFound(mount.bucket.clone()))?
+        .link
+        .hash();
+    let listing = list_bucket_dir(&mut client, bucket_id, "/", Some(version)).await?;
+
+    let staging = env::temp_dir().join(format!("jax-mount-{}", Uuid::new_v4()));
+    tokio::fs::create_dir_all(&staging).await?;
+    let fs = Arc::new(BucketFs::new(
+        client,
+        bucket_id,
+        version,
+        listing,
+        staging.clone(),
+        mount.message.clone(),
+    )?);
+
+    let mut options = vec![
+        MountOption::FSName("jax".to_string()),
+        MountOption::Subtype("jax".to_string()),
+        MountOption::DefaultPermissions,
+        MountOption::NoSuid,
+        MountOption::NoDev,
+    ];
+    if mount.read_only {
+        options.push(MountOption::RO);
+    }
+    if mount.allow_other {
+        options.push(MountOption::AllowOther);
+    }
+    let session = {
+        let (fs, mountpoint) = (fs.clone(), mountpoint.clone());
+        tokio::task::spawn_blocking(move || Session::new(Fuse::new(fs), &mountpoint, &options))
+            .await
+            .expect("mount task panicked")
+    };
+    let mut session = match session {
+        Ok(session) => session,
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return Err(MountError::Mount(e));
+        }
+    };
+    let mut unmounter = session.unmount_callable();
+    tracing::info!(
+        "Mounted {} at {} on {}",
+        mount.bucket,
+        version,
+        mountpoint.display()
+    );
+
+    // The session unmounts when it's dropped, if it hasn't been already
+    let serving = tokio::task::spawn_blocking(move || session.run());
+    tokio::pin!(serving);
+
+    let mut saves = tokio::time::interval(Duration::from_secs(mount.save_interval.max(1)));
+    saves.tick().await;
+    let mut unmounting = false;
+    let served = loop {
+        tokio::select! {
+            served = &mut serving => break served.expect("serving task panicked"),
+            _ = saves.tick(), if !mount.read_only => {
+                let fs = fs.clone();
+                let saved = tokio::task::spawn_blocking(move || fs.save(Include::Closed))
+                    .await
+                    .expect("save task panicked");
+                if let Err(e) = saved {
+                    tracing::warn!("Failed to save changes, will try again: {}", e);
+                }
+            }
+            // Files still open keep the mount around until they're closed
+            _ = tokio::signal::ctrl_c(), if !unmounting => {
+                tracing::info!("Unmounting {}", mountpoint.display());
+                unmounter.unmount()?;
+                unmounting = true;
+            }
+        }
+    };
+
+    if !mount.read_only {
+        let fs = fs.clone();
+        let saved = tokio::task::spawn_blocking(move || fs.save(Include::All))
+            .await
+            .expect("save task panicked");
+        if let Err(e) = saved {
+            return Err(MountError::Save(staging.display().to_string(), e));
+        }
+    }
+    tokio::fs::remove_dir_all(&staging).await?;
+    served?;
+
+    Ok(format!(
+        "Unmounted {} from {}, saved {} versions",
+        mount.bucket,
+        mountpoint.display(),
+        fs.saves()
+    ))
+}
+
+/// The filesystem as `fuser` calls it
+struct Fuse {
+    fs: Arc<BucketFs>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl Fuse {
+    fn new(fs: Arc<BucketFs>) -> Self {
+        Self {
+            fs,
+            runtime: tokio::runtime::Handle::current(),
+        }
+    }
+
+    /// Answer a request on a thread of its own
+    fn spawn(&self, answer: impl FnOnce(&BucketFs) + Send + 'static) {
+        let fs = self.fs.clone();
+        self.runtime.spawn_blocking(move || answer(&fs));
+    }
+}
+
+impl Filesystem for Fuse {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.lookup(parent, &name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        self.fs.forget(ino, nlookup);
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        self.spawn(move |fs| match fs.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let mtime = mtime.map(|mtime| match mtime {
+            TimeOrNow::SpecificTime(time) => time,
+            TimeOrNow::Now => SystemTime::now(),
+        });
+        self.spawn(move |fs| match fs.setattr(ino, size, mtime) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn mknod(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        _umask: u32,
+        _rdev: u32,
+        reply: ReplyEntry,
+    ) {
+        // Only regular files can be kept in a bucket
+        if mode & libc::S_IFMT != libc::S_IFREG {
+            reply.error(libc::EPERM);
+            return;
+        }
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.create(parent, &name, None) {
+            Ok((attr, _)) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.mkdir(parent, &name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.remove(parent, &name, false) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.remove(parent, &name, true) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request<'_>,
+        _parent: u64,
+        _link_name: &OsStr,
+        _target: &std::path::Path,
+        reply: ReplyEntry,
+    ) {
+        reply.error(libc::EPERM);
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let (name, newname) = (name.to_os_string(), newname.to_os_string());
+        self.spawn(
+            move |fs| match fs.rename(parent, &name, newparent, &newname, flags) {
+                Ok(()) => reply.ok(),
+                Err(errno) => reply.error(errno),
+            },
+        );
+    }
+
+    fn link(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _newparent: u64,
+        _newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        reply.error(libc::EPERM);
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.spawn(move |fs| match fs.open(ino, flags) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Ok(offset) = u64::try_from(offset) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        self.spawn(move |fs| match fs.read(fh, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let Ok(offset) = u64::try_from(offset) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let data = data.to_vec();
+        self.spawn(move |fs| match fs.write(fh, offset, &data) {
+            Ok(written) => reply.written(written),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn flush(&mut self, _req: &Request<'_>, _ino: u64, _fh: u64, _owner: u64, reply: ReplyEmpty) {
+        reply.ok();
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.fs.release(fh);
+        reply.ok();
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.spawn(move |fs| match fs.fsync(fh) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        });
+    }
+
+    fn opendir(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        match self.fs.opendir(ino) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let listed = self
+            .fs
+            .readdir(fh, offset.max(0) as u64, |ino, next, kind, name| {
+                reply.add(ino, next as i64, kind, name)
+            });
+        match listed {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn releasedir(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        reply: ReplyEmpty,
+    ) {
+        self.fs.release(fh);
+        reply.ok();
+    }
+
+    fn fsyncdir(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        _fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        reply.ok();
+    }
+
+    /// There are no real limits to report
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+        let plenty = u64::from(u32::MAX);
+        reply.statfs(plenty, plenty, plenty, plenty, plenty, 4096, 255, 4096);
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let name = name.to_os_string();
+        self.spawn(move |fs| match fs.create(parent, &name, Some(flags)) {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, 0),
+            Err(errno) => reply.error(errno),
+        });
+    }
+}
@@ -0,0 +1,78 @@
+//! Buckets mounted as local filesystems, over FUSE
+//!
+//! Mounting is only built on Linux, with the `fuse` feature; elsewhere the
+//! command reports that it isn't available.
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+mod fs;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+mod fuse;
+
+use std::path::PathBuf;
+
+use clap::Args;
+use service::http_server::api::client::ApiError;
+
+#[derive(Args, Debug, Clone)]
+pub struct Mount {
+    /// Bucket to mount, by name or ID
+    pub bucket: String,
+
+    /// Directory to mount it on
+    pub mountpoint: PathBuf,
+
+    /// Mount it read-only
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// How often changes are saved as a new version of the bucket, in
+    ///  seconds. They're also saved on `fsync` and when unmounting.
+    #[arg(long, default_value_t = 30)]
+    pub save_interval: u64,
+
+    /// Let other users access the mount (unless run as root, this needs
+    ///  `user_allow_other` in /etc/fuse.conf)
+    #[arg(long)]
+    pub allow_other: bool,
+
+    /// Message describing each change, shown in the bucket's history
+    #[arg(long)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(not(all(feature = "fuse", target_os = "linux")), allow(dead_code))]
+pub enum MountError {
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to mount: {0}")]
+    Mount(std::io::Error),
+    #[error("Bucket {0} not found")]
+    NotFound(String),
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+    #[error("Failed to save changes, what was written is kept in {0}: {1}")]
+    Save(String, ApiError),
+    #[error(
+        "Mounting isn't supported by this build of jax, which needs Linux and the `fuse` feature"
+    )]
+    Unsupported,
+}
+
+#[async_trait::async_trait]
+impl crate::op::Op for Mount {
+    type Error = MountError;
+    type Output = String;
+
+    #[cfg(all(feature = "fuse", target_os = "linux"))]
+    async fn execute(&self, ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        fuse::run(self, ctx).await
+    }
+
+    #[cfg(not(all(feature = "fuse", target_os = "linux")))]
+    async fn execute(&self, _ctx: &crate::op::OpContext) -> Result<Self::Output, Self::Error> {
+        Err(MountError::Unsupported)
+    }
+}
@@ -50,6 +50,12 @@ impl crate::op::Op for Service {
             node_blobs_store_path: Some(state.blobs_path),
             html_listen_addr: state.config.html_listen_addr.parse().ok(),
             api_listen_addr: state.config.api_listen_addr.parse().ok(),
+            s3_listen_addr: state
+                .config
+                .s3_listen_addr
+                .as_deref()
+                .and_then(|addr| addr.parse().ok()),
+            s3_access_keys: state.config.s3_access_keys.clone(),
             sqlite_path: Some(state.db_path),
             log_level: tracing::Level::DEBUG,
             ui_read_only: self.ui_read_only,
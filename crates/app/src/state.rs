@@ -18,6 +18,12 @@ pub struct AppConfig {
     /// Listen port for the peer (P2P) node (optional, defaults to ephemeral)
     #[serde(default)]
     pub peer_port: Option<u16>,
+    /// Listen address for the S3-compatible API (optional, off if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3_listen_addr: Option<String>,
+    /// Access keys S3 clients sign their requests with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub s3_access_keys: Vec<service::S3AccessKey>,
 }
 
 impl Default for AppConfig {
@@ -26,6 +32,8 @@ impl Default for AppConfig {
             html_listen_addr: "0.0.0.0:8080".to_string(),
             api_listen_addr: "0.0.0.0:3000".to_string(),
             peer_port: None,
+            s3_listen_addr: None,
+            s3_access_keys: Vec::new(),
         }
     }
 }
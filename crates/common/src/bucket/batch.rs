@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::Secret;
+use crate::linked_data::Hash;
+use crate::peer::BlobsStore;
+
+use super::mount::{Mount, MountError};
+use super::node::{Node, NodeLink};
+
+/**
+ * Batch
+ * =====
+ * Many links set or removed in a single pass over the tree, as done
+ *  by [`Mount::apply_staged`]. Setting one link at a time rewrites
+ *  every directory above it, so adding a thousand files to a
+ *  directory would write that directory a thousand times. Instead
+ *  the changes are grouped by the directory they go in:
+ * - each directory on the way to a change is loaded once, or created
+ *    if it doesn't exist yet
+ * - every link going in it is removed, then set, then the
+ *    directories under it are visited
+ * - it is written once, under a new secret, after its children
+ * A link replaces whatever was at its path, as with [`Mount::add`].
+ *  Removing a path that doesn't exist is an error, as with
+ *  [`Mount::rm`].
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+pub(crate) struct Batch<'a> {
+    blobs: &'a BlobsStore,
+    // hashes of the directory nodes written while setting links
+    created: Vec<Hash>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(blobs: &'a BlobsStore) -> Self {
+        Self {
+            blobs,
+            created: Vec::new(),
+        }
+    }
+
+    /// Hashes of the directory nodes written while setting links, to be pinned
+    pub fn created(self) -> Vec<Hash> {
+        self.created
+    }
+
+    /// Apply every change in `links`, keyed by path relative to `node`,
+    ///  returning the node with them applied
+    ///
+    /// `None` removes the link at a path. `path` is where `node` is in
+    ///  the bucket, and is only used in errors.
+    pub async fn node(
+        &mut self,
+        node: &Node,
+        path: &Path,
+        links: BTreeMap<PathBuf, Option<NodeLink>>,
+    ) -> Result<Node, MountError> {
+        // changes that go straight in this node, and those that go in
+        //  the directories under it, by the name of that directory
+        let mut here = BTreeMap::new();
+        let mut below: BTreeMap<String, BTreeMap<PathBuf, Option<NodeLink>>> = BTreeMap::new();
+        for (link_path, link) in links {
+            let mut parts = link_path.iter();
+            let Some(name) = parts.next() else {
+                continue;
+            };
+            let name = name.to_string_lossy().to_string();
+            let rest: PathBuf = parts.collect();
+            if rest.as_os_str().is_empty() {
+                here.insert(name, link);
+            } else {
+                below.entry(name).or_default().insert(rest, link);
+            }
+        }
+
+        let mut updated = node.clone();
+        for (name, link) in here {
+            match link {
+                Some(link) => {
+                    updated.insert(name, link);
+                }
+                None => {
+                    if updated.del(&name).is_none() {
+                        return Err(MountError::PathNotFound(path.join(name)));
+                    }
+                }
+            }
+        }
+        for (name, links) in below {
+            let dir_path = path.join(&name);
+            let dir = match updated.get_link(&name) {
+                Some(link @ NodeLink::Dir(..)) => {
+                    Mount::_get_node_from_blobs(link, self.blobs).await?
+                }
+                Some(NodeLink::Data(..)) => return Err(MountError::PathNotNode(dir_path)),
+                // Nothing to remove from a directory that isn't there
+                None if links.values().all(Option::is_none) => {
+                    return Err(MountError::PathNotFound(dir_path))
+                }
+                None => Node::default(),
+            };
+            let dir = Box::pin(self.node(&dir, &dir_path, links)).await?;
+            let secret = Secret::generate();
+            let link = Mount::_put_node_in_blobs(&dir, &secret, self.blobs).await?;
+            self.created.push(*link.hash());
+            updated.insert(name, NodeLink::new_dir(link, secret));
+        }
+        Ok(updated)
+    }
+}
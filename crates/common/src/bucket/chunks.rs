@@ -0,0 +1,314 @@
+use std::ops::Range;
+
+use fastcdc::v2020::AsyncStreamCDC;
+use futures::{stream, StreamExt, TryStreamExt};
+use iroh_blobs::BlobFormat;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::crypto::{PlaintextStream, Secret, SecretError, STREAM_HEADER_SIZE};
+use crate::linked_data::{BlockEncoded, DagCborCodec, Hash, Link, LD_RAW_CODEC};
+use crate::peer::BlobsStore;
+
+use super::mount::MountError;
+
+/// Smallest chunk the chunker will cut, except for the end of a file
+pub const CHUNK_MIN_SIZE: u32 = 256 * 1024;
+/// Chunk size the chunker aims for on average
+pub const CHUNK_AVG_SIZE: u32 = 1024 * 1024;
+/// Largest chunk the chunker will cut
+pub const CHUNK_MAX_SIZE: u32 = 4 * 1024 * 1024;
+
+/**
+ * Chunked Data
+ * ============
+ * File contents are split into chunks with content-defined
+ *  boundaries (FastCDC), so an edit to a file only changes the
+ *  chunks around it and the rest of the file keeps its hashes.
+ * How a file is stored depends on how many chunks it produces:
+ *  - a single chunk (or an empty file) is stored as one encrypted
+ *     Raw blob, the same as files written before chunking existed
+ *  - anything larger is stored as a HashSeq:
+ *     [ index, chunk_0, chunk_1, ... ]
+ *     where `index` is an encrypted DAG-CBOR `ChunkIndex` holding
+ *     the plaintext size of every chunk, so ranges can be mapped to
+ *     chunks without touching the chunks themselves
+ * Every blob is encrypted with the file's secret using
+ *  [`Secret::encrypt_deterministic`], and a file keeps its secret
+ *  when it's replaced, so unchanged chunks encrypt to the same blobs
+ *  across versions of a file and are stored (and synced) only once.
+ *  Blobs that are already stored aren't written again. Secrets only
+ *  change when a bucket is re-keyed, see [`super::Mount::revoke`].
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChunkIndex {
+    // plaintext size of each chunk, in HashSeq order
+    sizes: Vec<u64>,
+}
+
+impl BlockEncoded<DagCborCodec> for ChunkIndex {}
+
+/// The result of storing a file's contents in the blob store
+pub(crate) struct StoredData {
+    /// Link to the data, either a Raw blob or a HashSeq of chunks
+    pub link: Link,
+    /// Hashes of every blob the data is made of, which all need pinning
+    pub hashes: Vec<Hash>,
+    /// Size of the plaintext
+    pub size: u64,
+    /// BLAKE3 hash of the plaintext
+    pub plaintext_hash: Hash,
+}
+
+/// A chunk of a file's data as it's stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chunk {
+    /// Hash of the encrypted blob
+    pub hash: Hash,
+    /// Size of the plaintext
+    pub size: u64,
+}
+
+/// Chunk, encrypt and store the contents of `data`
+///
+/// Only one chunk is held in memory at a time.
+pub(crate) async fn put_data<R>(
+    data: R,
+    secret: &Secret,
+    blobs: &BlobsStore,
+) -> Result<StoredData, MountError>
+where
+    R: AsyncRead + Unpin,
+{
+    let (chunks, plaintext_hash) = put_chunks(data, secret, None, blobs).await?;
+    link_chunks(chunks, plaintext_hash, secret, blobs).await
+}
+
+/// Chunk, encrypt and store the contents of `data`, without linking the
+///  chunks together, returning them along with the BLAKE3 hash of the
+///  plaintext
+///
+/// With `hold`, each chunk is held under that name as soon as it's stored
+///  (see [`BlobsStore::hold`]).
+pub(crate) async fn put_chunks<R>(
+    data: R,
+    secret: &Secret,
+    hold: Option<&str>,
+    blobs: &BlobsStore,
+) -> Result<(Vec<Chunk>, Hash), MountError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut chunker = AsyncStreamCDC::new(data, CHUNK_MIN_SIZE, CHUNK_AVG_SIZE, CHUNK_MAX_SIZE);
+    let mut stream = Box::pin(chunker.as_stream());
+
+    let mut chunks = Vec::new();
+    let mut hasher = blake3::Hasher::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(anyhow::Error::from)?;
+        hasher.update(&chunk.data);
+        let encrypted = secret.encrypt_deterministic(&chunk.data)?;
+        let hash = put_new(encrypted, blobs).await?;
+        if let Some(name) = hold {
+            blobs.hold(name, hash).await?;
+        }
+        chunks.push(Chunk {
+            hash,
+            size: chunk.length as u64,
+        });
+    }
+
+    Ok((chunks, Hash::from_bytes(*hasher.finalize().as_bytes())))
+}
+
+/// Link chunks stored with [`put_chunks`], in order, into the data of a
+///  single file
+pub(crate) async fn link_chunks(
+    mut chunks: Vec<Chunk>,
+    plaintext_hash: Hash,
+    secret: &Secret,
+    blobs: &BlobsStore,
+) -> Result<StoredData, MountError> {
+    let size = chunks.iter().map(|chunk| chunk.size).sum();
+
+    if chunks.len() <= 1 {
+        let hash = match chunks.pop() {
+            Some(chunk) => chunk.hash,
+            None => put_new(secret.encrypt_deterministic(&[])?, blobs).await?,
+        };
+        return Ok(StoredData {
+            link: Link::new(LD_RAW_CODEC, hash, BlobFormat::Raw),
+            hashes: vec![hash],
+            size,
+            plaintext_hash,
+        });
+    }
+
+    let sizes = chunks.iter().map(|chunk| chunk.size).collect();
+    let mut hashes: Vec<Hash> = chunks.iter().map(|chunk| chunk.hash).collect();
+    let index = ChunkIndex { sizes }.encode()?;
+    let index_hash = put_new(secret.encrypt_deterministic(&index)?, blobs).await?;
+    let seq_hash = blobs
+        .create_hash_list(std::iter::once(index_hash).chain(hashes.iter().copied()))
+        .await?;
+
+    hashes.push(index_hash);
+    hashes.push(seq_hash);
+    Ok(StoredData {
+        link: Link::new(LD_RAW_CODEC, seq_hash, BlobFormat::HashSeq),
+        hashes,
+        size,
+        plaintext_hash,
+    })
+}
+
+/// Store a blob unless it's stored already, returning its hash
+async fn put_new(data: Vec<u8>, blobs: &BlobsStore) -> Result<Hash, MountError> {
+    let hash = Hash::new(&data);
+    if !blobs.stat(&hash).await? {
+        blobs.put(data).await?;
+    }
+    Ok(hash)
+}
+
+/// Decrypt and decode the chunk index of chunked data, returning the
+///  plaintext size of each chunk
+pub(crate) fn decode_index(ciphertext: &[u8], secret: &Secret) -> Result<Vec<u64>, MountError> {
+    Ok(ChunkIndex::decode(&secret.decrypt(ciphertext)?)?.sizes)
+}
+
+/// Where each encrypted blob of a file's data sits within its plaintext
+#[derive(Debug, Clone)]
+pub(crate) struct DataLayout {
+    chunks: Vec<(Hash, Range<u64>)>,
+}
+
+impl DataLayout {
+    /// Load the layout of the data at `link`
+    ///
+    /// This reads the chunk index of chunked data, or just the header
+    ///  of a single blob, never the file contents themselves.
+    pub async fn load(
+        link: &Link,
+        secret: &Secret,
+        blobs: &BlobsStore,
+    ) -> Result<Self, MountError> {
+        let hash = *link.hash();
+        if *link.format() == BlobFormat::Raw {
+            let size = Self::plaintext_len(hash, blobs).await?;
+            return Ok(Self {
+                chunks: vec![(hash, 0..size)],
+            });
+        }
+
+        let mut seq = blobs.read_hash_list(hash).await?.into_iter();
+        let index_hash = seq
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("chunked data {} has no index", hash))?;
+        let index = ChunkIndex::decode(&secret.decrypt(&blobs.get(&index_hash).await?)?)?;
+        if index.sizes.len() != seq.len() {
+            return Err(anyhow::anyhow!(
+                "chunk index of {} lists {} chunks, but there are {}",
+                hash,
+                index.sizes.len(),
+                seq.len()
+            )
+            .into());
+        }
+
+        let mut offset = 0;
+        let chunks = seq
+            .zip(index.sizes)
+            .map(|(chunk_hash, size)| {
+                let range = offset..offset + size;
+                offset += size;
+                (chunk_hash, range)
+            })
+            .collect();
+        Ok(Self { chunks })
+    }
+
+    /// Size of the plaintext in bytes
+    pub fn size(&self) -> u64 {
+        self.chunks.last().map(|(_, range)| range.end).unwrap_or(0)
+    }
+
+    /// Read and decrypt the whole plaintext into memory
+    pub async fn read_all(
+        &self,
+        secret: &Secret,
+        blobs: &BlobsStore,
+    ) -> Result<Vec<u8>, MountError> {
+        let mut data = Vec::with_capacity(self.size() as usize);
+        for (hash, _) in &self.chunks {
+            data.extend(secret.decrypt(&blobs.get(hash).await?)?);
+        }
+        Ok(data)
+    }
+
+    /// Open a stream over `range` of the plaintext
+    ///
+    /// Only the chunks overlapping the range are read. The first of them is
+    ///  opened up front so that missing or undecryptable data is reported here,
+    ///  the rest are opened as the stream reaches them.
+    pub async fn open(
+        self,
+        secret: &Secret,
+        range: Range<u64>,
+        blobs: &BlobsStore,
+    ) -> Result<PlaintextStream, MountError> {
+        let mut parts = self.chunks.into_iter().filter_map(move |(hash, chunk)| {
+            let start = range.start.max(chunk.start);
+            let end = range.end.min(chunk.end);
+            (start < end).then(|| (hash, start - chunk.start..end - chunk.start))
+        });
+
+        let first = match parts.next() {
+            Some((hash, range)) => Self::open_chunk(hash, range, secret, blobs).await?,
+            None => return Ok(Box::pin(stream::empty())),
+        };
+
+        let secret = secret.clone();
+        let blobs = blobs.clone();
+        let rest = stream::iter(parts)
+            .then(move |(hash, range)| {
+                let secret = secret.clone();
+                let blobs = blobs.clone();
+                async move {
+                    Self::open_chunk(hash, range, &secret, &blobs)
+                        .await
+                        .map_err(std::io::Error::other)
+                }
+            })
+            .try_flatten();
+        Ok(Box::pin(first.chain(rest)))
+    }
+
+    async fn open_chunk(
+        hash: Hash,
+        range: Range<u64>,
+        secret: &Secret,
+        blobs: &BlobsStore,
+    ) -> Result<PlaintextStream, MountError> {
+        let ciphertext_len = blobs.size(&hash).await?;
+        let reader = blobs.get_reader(hash).await?;
+        Ok(secret.decrypt_range(reader, ciphertext_len, range).await?)
+    }
+
+    async fn plaintext_len(hash: Hash, blobs: &BlobsStore) -> Result<u64, MountError> {
+        let ciphertext_len = blobs.size(&hash).await?;
+
+        // The start of the blob is enough to tell the size of the plaintext
+        let mut prefix = Vec::with_capacity(STREAM_HEADER_SIZE);
+        blobs
+            .get_reader(hash)
+            .await?
+            .take(STREAM_HEADER_SIZE as u64)
+            .read_to_end(&mut prefix)
+            .await
+            .map_err(SecretError::Io)?;
+        Ok(Secret::plaintext_len(&prefix, ciphertext_len)?)
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::linked_data::Link;
+use crate::peer::BlobsStore;
+
+use super::mount::{Mount, MountError};
+use super::node::{Node, NodeLink};
+
+/// A path that was moved between two versions without its contents changing
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Moved {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/**
+ * Diff
+ * ====
+ * The changes between two versions of a bucket, as returned by
+ *  [`Mount::diff`]. Paths are relative to the root of the bucket.
+ * - `added` / `removed` list files (and empty directories) that only
+ *    exist in one of the versions
+ * - `modified` lists files that exist in both versions with different
+ *    contents or metadata
+ * - `moved` lists files and directories whose link is unchanged but
+ *    which live at a different path. A directory that was moved as a
+ *    whole is reported once, not once per file inside of it
+ * Since nodes are content-addressed, subtrees whose links are equal in
+ *  both versions are never loaded.
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub moved: Vec<Moved>,
+}
+
+impl Diff {
+    /// Whether the two versions have the same contents
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.moved.is_empty()
+    }
+
+    /// Compare two entry nodes
+    pub(crate) async fn between(
+        from: &Node,
+        to: &Node,
+        blobs: &BlobsStore,
+    ) -> Result<Self, MountError> {
+        let mut changes = Changes::default();
+        changes.compare(from, to, Path::new(""), blobs).await?;
+
+        // Index everything that was added, at any depth, so that a
+        //  removed subtree can be found again wherever it was moved to
+        let added = expand(changes.added, blobs).await?;
+        let mut added_by_link: HashMap<&Link, Vec<&PathBuf>> = HashMap::new();
+        for (path, link) in &added {
+            added_by_link.entry(link.link()).or_default().push(path);
+        }
+
+        // Match removed paths top down, so a moved directory is claimed
+        //  as a whole before any of the files inside of it
+        let mut moved = Vec::new();
+        let mut claimed: Vec<PathBuf> = Vec::new();
+        let mut removed = BTreeSet::new();
+        let mut pending: VecDeque<(PathBuf, NodeLink)> = changes.removed.into_iter().collect();
+        while let Some((path, link)) = pending.pop_front() {
+            let target = added_by_link.get(link.link()).and_then(|paths| {
+                paths.iter().find(|to| {
+                    !claimed
+                        .iter()
+                        .any(|c| to.starts_with(c) || c.starts_with(to))
+                })
+            });
+            if let Some(to) = target {
+                claimed.push((*to).clone());
+                moved.push(Moved {
+                    from: path,
+                    to: (*to).clone(),
+                });
+                continue;
+            }
+            let children = children(&link, blobs).await?;
+            for (name, child) in &children {
+                pending.push_back((path.join(name), child.clone()));
+            }
+            if children.is_empty() {
+                removed.insert(path);
+            }
+        }
+        moved.sort();
+
+        // Directories only show up as added when they are empty. Paths
+        //  within a directory sort right after it, so a directory with
+        //  contents is followed by one of them
+        let leaves = added
+            .iter()
+            .filter(|(path, _)| !claimed.iter().any(|c| path.starts_with(c)))
+            .filter(|(path, link)| {
+                !link.is_dir()
+                    || !added
+                        .range::<PathBuf, _>((Bound::Excluded(*path), Bound::Unbounded))
+                        .next()
+                        .is_some_and(|(next, _)| next.starts_with(path))
+            })
+            .map(|(path, _)| path.clone());
+
+        Ok(Self {
+            added: leaves.collect(),
+            removed: removed.into_iter().collect(),
+            modified: changes.modified.into_iter().collect(),
+            moved,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Changes {
+    added: BTreeMap<PathBuf, NodeLink>,
+    removed: BTreeMap<PathBuf, NodeLink>,
+    modified: BTreeSet<PathBuf>,
+}
+
+impl Changes {
+    async fn compare(
+        &mut self,
+        from: &Node,
+        to: &Node,
+        path: &Path,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let from_links = from.get_links();
+        let to_links = to.get_links();
+
+        for (name, from_link) in from_links {
+            let item_path = path.join(name);
+            match (from_link, to_links.get(name)) {
+                (_, None) => {
+                    self.removed.insert(item_path, from_link.clone());
+                }
+                (_, Some(to_link)) if from_link == to_link => {}
+                (NodeLink::Dir(..), Some(to_link @ NodeLink::Dir(..))) => {
+                    let from_node = Mount::_get_node_from_blobs(from_link, blobs).await?;
+                    let to_node = Mount::_get_node_from_blobs(to_link, blobs).await?;
+                    Box::pin(self.compare(&from_node, &to_node, &item_path, blobs)).await?;
+                }
+                (NodeLink::Data(..), Some(NodeLink::Data(..))) => {
+                    self.modified.insert(item_path);
+                }
+                // a file replaced by a directory, or the other way around
+                (_, Some(to_link)) => {
+                    self.removed.insert(item_path.clone(), from_link.clone());
+                    self.added.insert(item_path, to_link.clone());
+                }
+            }
+        }
+
+        for (name, to_link) in to_links {
+            if !from_links.contains_key(name) {
+                self.added.insert(path.join(name), to_link.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The entries of a directory, or nothing for a file
+async fn children(
+    link: &NodeLink,
+    blobs: &BlobsStore,
+) -> Result<BTreeMap<String, NodeLink>, MountError> {
+    if !link.is_dir() {
+        return Ok(BTreeMap::new());
+    }
+    let node = Mount::_get_node_from_blobs(link, blobs).await?;
+    Ok(node.get_links().clone())
+}
+
+/// Collect every path within the given subtrees, directories included
+async fn expand(
+    items: BTreeMap<PathBuf, NodeLink>,
+    blobs: &BlobsStore,
+) -> Result<BTreeMap<PathBuf, NodeLink>, MountError> {
+    let mut expanded = BTreeMap::new();
+    let mut pending: Vec<(PathBuf, NodeLink)> = items.into_iter().collect();
+    while let Some((path, link)) = pending.pop() {
+        for (name, child) in children(&link, blobs).await? {
+            pending.push((path.join(name), child));
+        }
+        expanded.insert(path, link);
+    }
+    Ok(expanded)
+}
@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+
+use iroh_blobs::BlobFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::Secret;
+use crate::linked_data::{BlockEncoded, Hash, Link};
+use crate::peer::BlobsStore;
+
+use super::chunks::decode_index;
+use super::manifest::Manifest;
+use super::mount::MountError;
+use super::node::{Node, NodeLink};
+
+/// The part of a bucket a blob holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckBlob {
+    Manifest,
+    Pins,
+    Dir,
+    /// A file's data, or the HashSeq of a chunked file
+    File,
+    /// The chunk index of a chunked file
+    ChunkIndex,
+    Chunk,
+}
+
+/// What is wrong with a blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckIssue {
+    /// The blob isn't in the blob store
+    Missing,
+    /// The blob's contents don't match its hash, or can't be read
+    Corrupt,
+    /// The blob doesn't decrypt under the secret it's linked with
+    Undecryptable(String),
+    /// The blob decrypts, but isn't what it's linked as
+    Undecodable(String),
+}
+
+impl FsckIssue {
+    /// Whether fetching the blob again could fix it
+    pub fn is_refetchable(&self) -> bool {
+        matches!(self, FsckIssue::Missing | FsckIssue::Corrupt)
+    }
+}
+
+/// A blob that failed a check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsckProblem {
+    pub hash: Hash,
+    pub blob: FsckBlob,
+    /// Path of the file or directory the blob belongs to, missing for
+    ///  the manifest and pin set
+    pub path: Option<PathBuf>,
+    pub issue: FsckIssue,
+}
+
+/// What [`Mount::fsck`](super::Mount::fsck) checked, and what it found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsckReport {
+    /// Directories checked, including the root
+    pub dirs: u64,
+    /// Files checked
+    pub files: u64,
+    /// Blobs checked
+    pub blobs: u64,
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    /// Whether every blob passed its checks
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/**
+ * Fsck
+ * ====
+ * Checks every blob a version of a bucket is made of: the manifest,
+ *  the pin set, and every node and file reachable from the entry.
+ * Each blob must be present, hash to its link, and decrypt and decode
+ *  as what links to it. File data is read in full, a blob at a time.
+ * A blob that fails a check is recorded and the walk moves on, so one
+ *  run finds every problem it can. Whatever a broken directory links
+ *  to can't be reached, and is found once the directory is repaired.
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+pub(crate) struct Fsck<'a> {
+    blobs: &'a BlobsStore,
+    report: FsckReport,
+}
+
+impl<'a> Fsck<'a> {
+    pub fn new(blobs: &'a BlobsStore) -> Self {
+        Self {
+            blobs,
+            report: FsckReport::default(),
+        }
+    }
+
+    pub fn report(self) -> FsckReport {
+        self.report
+    }
+
+    /// Check the manifest at `link` and its pin set, returning the
+    ///  manifest if it can be read
+    pub async fn manifest(&mut self, link: &Link) -> Result<Option<Manifest>, MountError> {
+        let Some(data) = self.blob(link.hash(), FsckBlob::Manifest, None).await? else {
+            return Ok(None);
+        };
+        let manifest = match Manifest::decode(&data) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                self.problem(
+                    link.hash(),
+                    FsckBlob::Manifest,
+                    None,
+                    FsckIssue::Undecodable(e.to_string()),
+                );
+                return Ok(None);
+            }
+        };
+
+        let pins = manifest.pins().hash();
+        if let Some(data) = self.blob(pins, FsckBlob::Pins, None).await? {
+            if data.len() % 32 != 0 {
+                self.problem(
+                    pins,
+                    FsckBlob::Pins,
+                    None,
+                    FsckIssue::Undecodable("length is not a multiple of 32".to_string()),
+                );
+            }
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Check the directory at `node_link`, and everything under it
+    pub async fn dir(&mut self, node_link: &NodeLink, path: &Path) -> Result<(), MountError> {
+        let link = node_link.link();
+        let Some(data) = self.blob(link.hash(), FsckBlob::Dir, Some(path)).await? else {
+            return Ok(());
+        };
+        let Some(data) = self.decrypt(&data, node_link.secret(), link.hash(), FsckBlob::Dir, path)
+        else {
+            return Ok(());
+        };
+        let node = match Node::decode(&data) {
+            Ok(node) => node,
+            Err(e) => {
+                self.problem(
+                    link.hash(),
+                    FsckBlob::Dir,
+                    Some(path),
+                    FsckIssue::Undecodable(e.to_string()),
+                );
+                return Ok(());
+            }
+        };
+        self.report.dirs += 1;
+
+        for (name, child) in node.get_links() {
+            let child_path = path.join(name);
+            match child {
+                NodeLink::Dir(..) => Box::pin(self.dir(child, &child_path)).await?,
+                NodeLink::Data(data_link, secret, _) => {
+                    self.file(data_link, secret, &child_path).await?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn file(&mut self, link: &Link, secret: &Secret, path: &Path) -> Result<(), MountError> {
+        self.report.files += 1;
+        let Some(data) = self.blob(link.hash(), FsckBlob::File, Some(path)).await? else {
+            return Ok(());
+        };
+        if *link.format() == BlobFormat::Raw {
+            self.decrypt(&data, secret, link.hash(), FsckBlob::File, path);
+            return Ok(());
+        }
+
+        // Chunked data is a HashSeq of its index and chunks
+        if data.len() % 32 != 0 || data.is_empty() {
+            self.problem(
+                link.hash(),
+                FsckBlob::File,
+                Some(path),
+                FsckIssue::Undecodable("not a list of chunks".to_string()),
+            );
+            return Ok(());
+        }
+        let mut seq = data.chunks_exact(32).map(|bytes| {
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(bytes);
+            Hash::from_bytes(hash)
+        });
+        let index_hash = seq.next().expect("checked not empty");
+        let chunks: Vec<Hash> = seq.collect();
+        if let Some(index) = self
+            .blob(&index_hash, FsckBlob::ChunkIndex, Some(path))
+            .await?
+        {
+            match decode_index(&index, secret) {
+                Ok(sizes) if sizes.len() == chunks.len() => {}
+                Ok(sizes) => self.problem(
+                    &index_hash,
+                    FsckBlob::ChunkIndex,
+                    Some(path),
+                    FsckIssue::Undecodable(format!(
+                        "lists {} chunks, but there are {}",
+                        sizes.len(),
+                        chunks.len()
+                    )),
+                ),
+                Err(e) => self.problem(
+                    &index_hash,
+                    FsckBlob::ChunkIndex,
+                    Some(path),
+                    FsckIssue::Undecryptable(e.to_string()),
+                ),
+            }
+        }
+        for chunk in chunks {
+            if let Some(data) = self.blob(&chunk, FsckBlob::Chunk, Some(path)).await? {
+                self.decrypt(&data, secret, &chunk, FsckBlob::Chunk, path);
+            }
+        }
+        Ok(())
+    }
+
+    // Read a blob, checking that it is present and matches its hash
+    async fn blob(
+        &mut self,
+        hash: &Hash,
+        blob: FsckBlob,
+        path: Option<&Path>,
+    ) -> Result<Option<Vec<u8>>, MountError> {
+        self.report.blobs += 1;
+        if !self.blobs.stat(hash).await? {
+            self.problem(hash, blob, path, FsckIssue::Missing);
+            return Ok(None);
+        }
+        match self.blobs.get(hash).await {
+            Ok(data) if Hash::new(&data) == *hash => Ok(Some(data.to_vec())),
+            _ => {
+                self.problem(hash, blob, path, FsckIssue::Corrupt);
+                Ok(None)
+            }
+        }
+    }
+
+    fn decrypt(
+        &mut self,
+        data: &[u8],
+        secret: &Secret,
+        hash: &Hash,
+        blob: FsckBlob,
+        path: &Path,
+    ) -> Option<Vec<u8>> {
+        match secret.decrypt(data) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                self.problem(
+                    hash,
+                    blob,
+                    Some(path),
+                    FsckIssue::Undecryptable(e.to_string()),
+                );
+                None
+            }
+        }
+    }
+
+    fn problem(&mut self, hash: &Hash, blob: FsckBlob, path: Option<&Path>, issue: FsckIssue) {
+        self.report.problems.push(FsckProblem {
+            hash: *hash,
+            blob,
+            path: path.map(Path::to_path_buf),
+            issue,
+        });
+    }
+}
@@ -1,10 +1,11 @@
 use std::collections::BTreeMap;
 
+use ipld_core::codec::Codec;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::crypto::{PublicKey, Secret, Share, ShareError};
-use crate::linked_data::{BlockEncoded, DagCborCodec, Link};
+use crate::crypto::{PublicKey, Secret, SecretKey, Share, ShareError, Signature};
+use crate::linked_data::{BlockEncoded, CodecError, DagCborCodec, Link};
 use crate::version::Version;
 
 use super::principal::{Principal, PrincipalRole};
@@ -16,10 +17,10 @@ pub struct BucketShare {
 }
 
 impl BucketShare {
-    pub fn new(share: Share, public_key: PublicKey) -> Self {
+    pub fn new(share: Share, public_key: PublicKey, role: PrincipalRole) -> Self {
         Self {
             principal: Principal {
-                role: PrincipalRole::Owner,
+                role,
                 identity: public_key,
             },
             share,
@@ -37,6 +38,32 @@ impl BucketShare {
 
 pub type Shares = BTreeMap<String, BucketShare>;
 
+/// Newest format of manifests, and the bucket contents they link to,
+///  that this version of the software reads and writes
+///
+/// - 0: unversioned and unsigned, as written before signatures existed
+/// - 1: signed by the author of the version
+/// - 2: also, every node and file under the entry is encrypted in the
+///   segmented format, so none of them need reading whole
+///
+/// Manifests of a newer format are refused when decoded. Older ones
+///  decode as they are, and are brought up to date by
+///  [`Mount::migrate`](super::Mount::migrate).
+pub const MANIFEST_FORMAT: u32 = 2;
+
+/// Oldest format of signed manifests
+const SIGNED_FORMAT: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error("manifest is not signed")]
+    Unsigned,
+    #[error("invalid manifest signature by {0}")]
+    InvalidSignature(String),
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
+}
+
 /**
 * BucketData
 * ==========
@@ -47,7 +74,12 @@ pub type Shares = BTreeMap<String, BucketShare>;
 *   - shares (access control and encryption keys for principals)
 *   - pins (optional pin set)
 *   - previous version link
-*   - version info
+*   - on merges, the other version that was merged in
+*   - when the version was created, and an optional message
+*      describing the change
+*   - who created the version, and their signature over
+*      the rest of the manifest
+*   - the format of the encoding, and version info
 */
 #[allow(clippy::doc_overindented_list_items)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -70,11 +102,53 @@ pub struct Manifest {
     pins: Link,
     // and a point to the previous version of the bucket
     previous: Option<Link>,
+    // on a merge of two concurrent versions, the one that
+    //  isn't `previous`. Left out of the encoding otherwise,
+    //  so manifests that aren't merges are unchanged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    merged: Option<Link>,
+    // unix timestamp (seconds) of when this version was created
+    //  missing on manifests written before timestamps existed
+    #[serde(default)]
+    timestamp: Option<u64>,
+    // a message from the author describing the change, left
+    //  out of the encoding when there is none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    // the encoding of the manifest, see MANIFEST_FORMAT.
+    //  missing on manifests written before it was versioned
+    #[serde(default)]
+    format: u32,
+    // the peer that created this version, and its signature
+    //  over the manifest with the signature left out
+    #[serde(default)]
+    author: Option<PublicKey>,
+    #[serde(default)]
+    signature: Option<Signature>,
     // specify the software version as a sanity check
     version: Version,
 }
 
-impl BlockEncoded<DagCborCodec> for Manifest {}
+// Just enough of a manifest to tell which format the rest is in
+#[derive(Serialize, Deserialize)]
+struct FormatProbe {
+    #[serde(default)]
+    format: u32,
+}
+
+impl BlockEncoded<DagCborCodec> for Manifest {
+    fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        let probe: FormatProbe =
+            DagCborCodec::decode_from_slice(data).map_err(|_| CodecError::DecodeError)?;
+        match probe.format {
+            // Older formats only added fields, which default when missing
+            0..=MANIFEST_FORMAT => {
+                DagCborCodec::decode_from_slice(data).map_err(|_| CodecError::DecodeError)
+            }
+            format => Err(CodecError::UnsupportedFormat(format, MANIFEST_FORMAT)),
+        }
+    }
+}
 
 impl Manifest {
     /// Create a new bucket with a name, owner, and share, and entry node link
@@ -102,6 +176,12 @@ impl Manifest {
             entry,
             pins,
             previous: None,
+            merged: None,
+            timestamp: Some(unix_timestamp()),
+            message: None,
+            format: MANIFEST_FORMAT,
+            author: None,
+            signature: None,
             version: Version::default(),
         }
     }
@@ -110,13 +190,40 @@ impl Manifest {
         self.shares.get(&public_key.to_hex())
     }
 
-    pub fn add_share(&mut self, public_key: PublicKey, secret: Secret) -> Result<(), ShareError> {
+    /// Share `secret` with `public_key`, replacing any share it already has
+    pub fn add_share(
+        &mut self,
+        public_key: PublicKey,
+        role: PrincipalRole,
+        secret: Secret,
+    ) -> Result<(), ShareError> {
         let share = Share::new(&secret, &public_key)?;
-        let bucket_share = BucketShare::new(share, public_key);
+        let bucket_share = BucketShare::new(share, public_key, role);
         self.shares.insert(public_key.to_hex(), bucket_share);
         Ok(())
     }
 
+    pub fn remove_share(&mut self, public_key: &PublicKey) -> Option<BucketShare> {
+        self.shares.remove(&public_key.to_hex())
+    }
+
+    /// The role `public_key` has on the bucket, if it is a principal
+    pub fn role(&self, public_key: &PublicKey) -> Option<PrincipalRole> {
+        self.get_share(public_key)
+            .map(|share| share.principal().role)
+    }
+
+    /// The role of every principal, keyed by hex public key
+    ///
+    /// Unlike [`Manifest::shares`] this doesn't change between versions
+    ///  unless principals were added, removed or given another role.
+    pub fn roles(&self) -> BTreeMap<String, PrincipalRole> {
+        self.shares
+            .iter()
+            .map(|(key, share)| (key.clone(), share.principal().role))
+            .collect()
+    }
+
     pub fn unset_shares(&mut self) {
         self.shares.clear();
     }
@@ -153,13 +260,96 @@ impl Manifest {
         self.pins = pins_link;
     }
 
-    pub fn set_previous(&mut self, previous: Link) {
-        self.previous = Some(previous);
+    pub fn set_previous(&mut self, previous: Option<Link>) {
+        self.previous = previous;
     }
 
     pub fn previous(&self) -> &Option<Link> {
         &self.previous
     }
+
+    pub fn set_merged(&mut self, merged: Option<Link>) {
+        self.merged = merged;
+    }
+
+    /// The other version merged in, if this version is a merge
+    pub fn merged(&self) -> &Option<Link> {
+        &self.merged
+    }
+
+    /// Links to the versions this one was built on: `previous`, then
+    ///  `merged` for merges
+    pub fn parents(&self) -> impl Iterator<Item = &Link> {
+        self.previous.iter().chain(self.merged.iter())
+    }
+
+    /// Unix timestamp (seconds) of when this version was created, if known
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Stamp the manifest with the current time
+    pub fn set_timestamp_now(&mut self) {
+        self.timestamp = Some(unix_timestamp());
+    }
+
+    /// The author's description of the change, if they gave one
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    pub fn format(&self) -> u32 {
+        self.format
+    }
+
+    pub(crate) fn set_format(&mut self, format: u32) {
+        self.format = format;
+    }
+
+    /// The peer that created this version, if the manifest is signed
+    pub fn author(&self) -> Option<&PublicKey> {
+        self.author.as_ref()
+    }
+
+    /// Sign the manifest as its author
+    ///
+    /// This should be the last change made to the manifest, since any
+    ///  later change invalidates the signature.
+    pub fn sign(&mut self, secret_key: &SecretKey) -> Result<(), ManifestError> {
+        self.format = self.format.max(SIGNED_FORMAT);
+        self.author = Some(secret_key.public());
+        self.signature = Some(secret_key.sign(&self.signed_bytes()?));
+        Ok(())
+    }
+
+    /// Check the manifest's signature, returning its author
+    pub fn verify(&self) -> Result<&PublicKey, ManifestError> {
+        let (Some(author), Some(signature)) = (&self.author, &self.signature) else {
+            return Err(ManifestError::Unsigned);
+        };
+        author
+            .verify(&self.signed_bytes()?, signature)
+            .map_err(|_| ManifestError::InvalidSignature(author.to_hex()))?;
+        Ok(author)
+    }
+
+    /// The encoding of the manifest without its signature
+    fn signed_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        unsigned.encode()
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -199,4 +389,176 @@ mod tests {
 
         assert_eq!(principal, decoded);
     }
+
+    #[test]
+    fn test_decode_manifest_without_timestamp() {
+        // The manifest layout from before versions were timestamped
+        #[derive(Serialize)]
+        struct LegacyManifest {
+            id: Uuid,
+            name: String,
+            shares: Shares,
+            entry: Link,
+            pins: Link,
+            previous: Option<Link>,
+            version: Version,
+        }
+
+        let owner = crate::crypto::SecretKey::generate().public();
+        let share = Share::new(&Secret::generate(), &owner).unwrap();
+        let manifest = Manifest::new(
+            Uuid::new_v4(),
+            "legacy".to_string(),
+            owner,
+            share,
+            Link::default(),
+            Link::default(),
+        );
+        assert!(manifest.timestamp().is_some());
+
+        let legacy = LegacyManifest {
+            id: manifest.id,
+            name: manifest.name.clone(),
+            shares: manifest.shares.clone(),
+            entry: manifest.entry.clone(),
+            pins: manifest.pins.clone(),
+            previous: None,
+            version: manifest.version.clone(),
+        };
+        let encoded = serde_ipld_dagcbor::to_vec(&legacy).unwrap();
+        let decoded = Manifest::decode(&encoded).unwrap();
+
+        assert_eq!(decoded.timestamp(), None);
+        assert_eq!(decoded.merged(), &None);
+        assert_eq!(decoded.message(), None);
+        assert_eq!(decoded.format(), 0);
+        assert!(matches!(decoded.verify(), Err(ManifestError::Unsigned)));
+        assert_eq!(decoded.name(), "legacy");
+        assert_eq!(decoded.shares(), manifest.shares());
+    }
+
+    #[test]
+    fn test_sign_manifest() {
+        let author = crate::crypto::SecretKey::generate();
+        let share = Share::new(&Secret::generate(), &author.public()).unwrap();
+        let mut manifest = Manifest::new(
+            Uuid::new_v4(),
+            "signed".to_string(),
+            author.public(),
+            share,
+            Link::default(),
+            Link::default(),
+        );
+        assert!(matches!(manifest.verify(), Err(ManifestError::Unsigned)));
+
+        manifest.sign(&author).unwrap();
+        let decoded = Manifest::decode(&manifest.encode().unwrap()).unwrap();
+        assert_eq!(decoded.format(), MANIFEST_FORMAT);
+        assert_eq!(decoded.verify().unwrap(), &author.public());
+
+        // Any change after signing breaks the signature
+        let mut tampered = decoded.clone();
+        tampered.timestamp = Some(0);
+        assert!(matches!(
+            tampered.verify(),
+            Err(ManifestError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let author = crate::crypto::SecretKey::generate();
+        let share = Share::new(&Secret::generate(), &author.public()).unwrap();
+        let mut manifest = Manifest::new(
+            Uuid::new_v4(),
+            "messages".to_string(),
+            author.public(),
+            share,
+            Link::default(),
+            Link::default(),
+        );
+        manifest.set_message(Some("Add the quarterly report".to_string()));
+        manifest.sign(&author).unwrap();
+
+        let decoded = Manifest::decode(&manifest.encode().unwrap()).unwrap();
+        assert_eq!(decoded, manifest);
+        assert_eq!(decoded.message(), Some("Add the quarterly report"));
+        assert_eq!(decoded.verify().unwrap(), &author.public());
+
+        // The message is signed along with the rest of the version
+        let mut tampered = decoded.clone();
+        tampered.set_message(Some("Something else".to_string()));
+        assert!(matches!(
+            tampered.verify(),
+            Err(ManifestError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_newer_format_refused() {
+        let author = crate::crypto::SecretKey::generate();
+        let share = Share::new(&Secret::generate(), &author.public()).unwrap();
+        let mut manifest = Manifest::new(
+            Uuid::new_v4(),
+            "future".to_string(),
+            author.public(),
+            share,
+            Link::default(),
+            Link::default(),
+        );
+
+        // Older formats stay older when signed
+        manifest.set_format(0);
+        manifest.sign(&author).unwrap();
+        assert_eq!(manifest.format(), 1);
+
+        manifest.set_format(MANIFEST_FORMAT + 1);
+        assert!(matches!(
+            Manifest::decode(&manifest.encode().unwrap()),
+            Err(CodecError::UnsupportedFormat(found, supported))
+                if found == MANIFEST_FORMAT + 1 && supported == MANIFEST_FORMAT
+        ));
+    }
+
+    #[test]
+    fn test_share_roles() {
+        let owner = crate::crypto::SecretKey::generate().public();
+        let reader = crate::crypto::SecretKey::generate().public();
+        let share = Share::new(&Secret::generate(), &owner).unwrap();
+        let mut manifest = Manifest::new(
+            Uuid::new_v4(),
+            "roles".to_string(),
+            owner,
+            share,
+            Link::default(),
+            Link::default(),
+        );
+        assert_eq!(manifest.role(&owner), Some(PrincipalRole::Owner));
+        assert_eq!(manifest.role(&reader), None);
+
+        manifest
+            .add_share(reader, PrincipalRole::Reader, Secret::generate())
+            .unwrap();
+        let roles = manifest.roles();
+        assert_eq!(manifest.role(&reader), Some(PrincipalRole::Reader));
+        assert!(!PrincipalRole::Reader.can_write());
+        assert!(PrincipalRole::Writer.can_write());
+        assert!(!PrincipalRole::Writer.can_share());
+
+        // Re-sharing a new secret changes the shares but not the roles
+        let before = manifest.shares().clone();
+        manifest
+            .add_share(reader, PrincipalRole::Reader, Secret::generate())
+            .unwrap();
+        assert_ne!(manifest.shares(), &before);
+        assert_eq!(manifest.roles(), roles);
+
+        manifest.remove_share(&reader);
+        assert_eq!(manifest.role(&reader), None);
+        assert_eq!(
+            "Writer".parse::<PrincipalRole>().unwrap(),
+            PrincipalRole::Writer
+        );
+        assert!("editor".parse::<PrincipalRole>().is_err());
+    }
 }
@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+
+use crate::crypto::{PublicKey, Secret};
+use crate::linked_data::Hash;
+use crate::peer::BlobsStore;
+
+use super::manifest::Manifest;
+use super::mount::{Mount, MountError};
+use super::node::{Node, NodeLink};
+use super::principal::PrincipalRole;
+
+/**
+ * Merge
+ * =====
+ * A three-way merge of two versions of a directory that were both
+ *  built on a common `base`, as done by [`Mount::merge`]. For every
+ *  name in the directory:
+ * - if only one side changed it, that change is taken
+ * - if both sides changed a directory, the two are merged in turn
+ * - if one side removed it and the other changed it, the change is kept
+ * - otherwise both are kept, one at the original name and the other as
+ *    "name (conflict from <label>)"
+ * Which side keeps the original name only depends on the two labels,
+ *  so peers that merge the same two versions at the same time end up
+ *  with the same tree. Entries with equal links are never loaded.
+ * Principals are merged the same way by [`roles`], except that when
+ *  both sides changed a principal's role the one giving less access
+ *  is kept, so a concurrent revocation is never undone.
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+pub(crate) struct Merge<'a> {
+    ours: &'a str,
+    theirs: &'a str,
+    blobs: &'a BlobsStore,
+    // hashes of the directory nodes written while merging
+    created: Vec<Hash>,
+}
+
+impl<'a> Merge<'a> {
+    pub fn new(ours: &'a str, theirs: &'a str, blobs: &'a BlobsStore) -> Self {
+        Self {
+            ours,
+            theirs,
+            blobs,
+            created: Vec::new(),
+        }
+    }
+
+    /// Hashes of the directory nodes written while merging, to be pinned
+    pub fn created(self) -> Vec<Hash> {
+        self.created
+    }
+
+    /// Merge the changes in `theirs` into `ours`
+    ///
+    /// Returns `None` if `ours` already has everything `theirs` changed.
+    pub async fn nodes(
+        &mut self,
+        base: &Node,
+        ours: &Node,
+        theirs: &Node,
+    ) -> Result<Option<Node>, MountError> {
+        // names only left in base were removed on both sides
+        let names: BTreeSet<&String> = ours
+            .get_links()
+            .keys()
+            .chain(theirs.get_links().keys())
+            .collect();
+
+        let mut merged = ours.clone();
+        let mut changed = false;
+        for name in names {
+            let base_link = base.get_link(name);
+            let our_link = ours.get_link(name);
+            let their_link = theirs.get_link(name);
+
+            if our_link == their_link || base_link == their_link {
+                continue;
+            }
+
+            // only they changed it
+            if base_link == our_link {
+                match their_link {
+                    Some(link) => merged.insert(name.clone(), link.clone()),
+                    None => merged.del(name),
+                };
+                changed = true;
+                continue;
+            }
+
+            match (our_link, their_link) {
+                (Some(ours @ NodeLink::Dir(..)), Some(theirs @ NodeLink::Dir(..))) => {
+                    let base_node = match base_link {
+                        Some(base @ NodeLink::Dir(..)) => {
+                            Mount::_get_node_from_blobs(base, self.blobs).await?
+                        }
+                        _ => Node::default(),
+                    };
+                    let our_node = Mount::_get_node_from_blobs(ours, self.blobs).await?;
+                    let their_node = Mount::_get_node_from_blobs(theirs, self.blobs).await?;
+                    if let Some(node) =
+                        Box::pin(self.nodes(&base_node, &our_node, &their_node)).await?
+                    {
+                        let secret = Secret::generate();
+                        let link = Mount::_put_node_in_blobs(&node, &secret, self.blobs).await?;
+                        self.created.push(*link.hash());
+                        merged.insert(name.clone(), NodeLink::new_dir(link, secret));
+                        changed = true;
+                    }
+                }
+                // removed on one side and changed on the other, keep the change
+                (Some(_), None) => {}
+                (None, Some(theirs)) => {
+                    merged.insert(name.clone(), theirs.clone());
+                    changed = true;
+                }
+                (Some(ours), Some(theirs)) => {
+                    let copy = |label: &str| format!("{} (conflict from {})", name, label);
+                    if self.ours <= self.theirs {
+                        merged.insert(copy(self.theirs), theirs.clone());
+                    } else {
+                        merged.insert(name.clone(), theirs.clone());
+                        merged.insert(copy(self.ours), ours.clone());
+                    }
+                    changed = true;
+                }
+                (None, None) => unreachable!("equal links are skipped above"),
+            }
+        }
+
+        Ok(changed.then_some(merged))
+    }
+}
+
+/// The principals whose role `theirs` changed since `base` in a way that
+///  `ours` doesn't have yet, with the role they should end up with
+///
+/// `None` means the principal should be removed.
+pub(crate) fn roles(
+    base: &Manifest,
+    ours: &Manifest,
+    theirs: &Manifest,
+) -> Vec<(PublicKey, Option<PrincipalRole>)> {
+    let identities: BTreeSet<PublicKey> = ours
+        .shares()
+        .values()
+        .chain(theirs.shares().values())
+        .map(|share| share.principal().identity)
+        .collect();
+
+    identities
+        .into_iter()
+        .filter_map(|identity| {
+            let base_role = base.role(&identity);
+            let our_role = ours.role(&identity);
+            let their_role = theirs.role(&identity);
+            if our_role == their_role || base_role == their_role {
+                return None;
+            }
+            if base_role == our_role {
+                return Some((identity, their_role));
+            }
+            // both changed it, keep the least access
+            let role = our_role.min(their_role);
+            (role != our_role).then_some((identity, role))
+        })
+        .collect()
+}
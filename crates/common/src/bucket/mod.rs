@@ -5,8 +5,12 @@
 //! - **[`Manifest`]**: Bucket metadata including ID, name, shares, and content-addressed pointers
 //! - **[`Node`]**: DAG structure representing directories and files
 //! - **[`Mount`]**: In-memory representation of a bucket with CRUD operations
+//! - **[`Diff`]**: Changes between two versions of a bucket
+//! - **[`FsckReport`]**: Problems found checking every blob of a version of a bucket
+//! - **[`FileReader`]**: Streaming, range-capable reader over a file's decrypted contents
 //! - **[`Pins`]**: Set of content hashes that should be kept available
 //! - **[`Principal`]**: Access control entries (peer identity + role)
+//! - **[`RekeyProgress`]**: Progress of re-encrypting a bucket when revoking a share or migrating it
 //!
 //! # Architecture
 //!
@@ -35,6 +39,13 @@
 //! - Codec (DAG-CBOR for nodes, Raw for encrypted data)
 //! - Format (Raw blob or HashSeq)
 //!
+//! ## Chunked Files
+//!
+//! File contents are split into chunks with content-defined boundaries. Files
+//! that fit in a single chunk are stored as one Raw blob; larger files link
+//! their encrypted chunks through a HashSeq, so unchanged chunks are shared
+//! between versions of a file and peers can fetch parts of it.
+//!
 //! ## Encryption Model
 //!
 //! - Each node and file has its own encryption [`Secret`](crate::crypto::Secret)
@@ -42,14 +53,27 @@
 //! - The root node's secret is shared with authorized peers via [`Share`](crate::crypto::Share)
 //! - This provides fine-grained access control and efficient key rotation
 
+mod batch;
+mod chunks;
+mod diff;
+mod fsck;
 mod manifest;
 mod maybe_mime;
+mod merge;
 mod mount;
 mod node;
 mod pins;
 mod principal;
+mod reader;
+mod rekey;
 
-pub use manifest::Manifest;
-pub use mount::{Mount, MountError};
-pub use node::{Node, NodeError, NodeLink};
+pub use chunks::Chunk;
+pub use diff::{Diff, Moved};
+pub use fsck::{FsckBlob, FsckIssue, FsckProblem, FsckReport};
+pub use manifest::{BucketShare, Manifest, ManifestError, MANIFEST_FORMAT};
+pub use mount::{HistoryEntry, Mount, MountError};
+pub use node::{Data, Node, NodeError, NodeLink};
 pub use pins::Pins;
+pub use principal::{Principal, PrincipalRole};
+pub use reader::FileReader;
+pub use rekey::RekeyProgress;
@@ -1,18 +1,31 @@
-use std::collections::BTreeMap;
-use std::io::Read;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use futures::Stream;
+use iroh_blobs::BlobFormat;
 use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite};
 use uuid::Uuid;
 
 use crate::crypto::{PublicKey, Secret, SecretError, SecretKey, Share};
-use crate::linked_data::{BlockEncoded, CodecError, Link};
+use crate::linked_data::{
+    BlockEncoded, CarError, CarReader, CarWriter, CodecError, Hash, Link, LD_RAW_CODEC,
+};
 use crate::peer::{BlobsStore, BlobsStoreError};
 
-use super::manifest::Manifest;
-use super::node::{Node, NodeError, NodeLink};
+use super::batch::Batch;
+use super::chunks::{link_chunks, put_chunks, put_data, Chunk, DataLayout};
+use super::diff::Diff;
+use super::fsck::{Fsck, FsckReport};
+use super::manifest::{Manifest, ManifestError, MANIFEST_FORMAT};
+use super::merge::{self, Merge};
+use super::node::{Data, Node, NodeError, NodeLink};
 use super::pins::Pins;
+use super::principal::PrincipalRole;
+use super::reader::FileReader;
+use super::rekey::{Rekey, RekeyProgress};
 
 pub fn clean_path(path: &Path) -> PathBuf {
     if !path.is_absolute() {
@@ -34,6 +47,11 @@ pub struct MountInner {
     pub entry: Node,
     // the loaded pins
     pub pins: Pins,
+    // a concurrent version merged in since the last save,
+    //  recorded in the next manifest
+    pub merged: Option<Link>,
+    // the message to record in the next manifest
+    pub message: Option<String>,
 }
 
 impl MountInner {
@@ -51,8 +69,35 @@ impl MountInner {
     }
 }
 
+/// A loaded bucket, along with the blob store it lives in and the key of
+///  the peer working on it, which signs every version it saves
 #[derive(Clone)]
-pub struct Mount(Arc<Mutex<MountInner>>, BlobsStore);
+pub struct Mount(Arc<Mutex<MountInner>>, BlobsStore, SecretKey);
+
+/// A single version of a bucket, as yielded by [`Mount::history`]
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// Link to the version's manifest
+    pub link: Link,
+    pub manifest: Manifest,
+}
+
+impl HistoryEntry {
+    /// Unix timestamp (seconds) of when the version was created, if known
+    pub fn timestamp(&self) -> Option<u64> {
+        self.manifest.timestamp()
+    }
+
+    /// The peer that created the version, if the manifest is signed
+    pub fn author(&self) -> Option<&PublicKey> {
+        self.manifest.author()
+    }
+
+    /// The author's description of the change, if they gave one
+    pub fn message(&self) -> Option<&str> {
+        self.manifest.message()
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum MountError {
@@ -60,10 +105,14 @@ pub enum MountError {
     Default(#[from] anyhow::Error),
     #[error("link not found")]
     LinkNotFound(Link),
+    #[error("blob not found: {0}")]
+    BlobNotFound(Hash),
     #[error("path not found: {0}")]
     PathNotFound(PathBuf),
     #[error("path is not a node: {0}")]
     PathNotNode(PathBuf),
+    #[error("path already exists: {0}")]
+    PathAlreadyExists(PathBuf),
     #[error("blobs store error: {0}")]
     BlobsStore(#[from] BlobsStoreError),
     #[error("secret error: {0}")]
@@ -72,10 +121,20 @@ pub enum MountError {
     Node(#[from] NodeError),
     #[error("codec error: {0}")]
     Codec(#[from] CodecError),
+    #[error("car error: {0}")]
+    Car(#[from] CarError),
     #[error("share error: {0}")]
     Share(#[from] crate::crypto::ShareError),
+    #[error("manifest error: {0}")]
+    Manifest(#[from] ManifestError),
     #[error("peers share was not found. this should be impossible")]
     ShareNotFound,
+    #[error("only writers and owners may change this bucket")]
+    ReadOnly,
+    #[error("only owners may change who this bucket is shared with")]
+    NotOwner,
+    #[error("not a principal of this bucket: {0}")]
+    NotPrincipal(String),
 }
 
 impl Mount {
@@ -92,40 +151,74 @@ impl Mount {
         inner.link.clone()
     }
 
+    /// Set the message recorded with the next save, describing the change
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.0.lock().message = message;
+    }
+
     /// Save the current mount state to the blobs store
     #[allow(clippy::await_holding_lock)]
     pub async fn save(&self, blobs: &BlobsStore) -> Result<Link, MountError> {
         let mut inner = self.0.lock();
-        // Create a new secret for the updated root
-        let secret = Secret::generate();
+        // Peers reject versions authored by readers, don't bother making one
+        if !inner
+            .manifest
+            .role(&self.2.public())
+            .is_some_and(|role| role.can_write())
+        {
+            return Err(MountError::ReadOnly);
+        }
         // get the now previous link to the bucket
         let previous = inner.link.clone();
+        // put the previous link and its pinset into the pins, so peers
+        //  that sync the bucket can load old versions
+        inner.pins.insert(*previous.hash());
+        let previous_pins = *inner.manifest.pins().hash();
+        inner.pins.insert(previous_pins);
+        Self::_commit(&mut inner, Some(previous), &self.2, blobs).await
+    }
+
+    // Write the mount's state as a new version built on `previous`, and
+    //  make it the current one
+    async fn _commit(
+        inner: &mut MountInner,
+        previous: Option<Link>,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<Link, MountError> {
+        // Create a new secret for the updated root
+        let secret = Secret::generate();
         // Put the current root node into blobs with the new secret
         let entry = Self::_put_node_in_blobs(&inner.entry, &secret, blobs).await?;
-        // Serialize current pins to blobs
-        // put the new root link into the pins, as well as the previous link
+        // Serialize current pins to blobs, including the new root link
         inner.pins.insert(*entry.clone().hash());
-        inner.pins.insert(*previous.hash());
         let pins_link = Self::_put_pins_in_blobs(&inner.pins, blobs).await?;
         // Update the bucket's share with the new root link
-        // (add_share creates the Share internally)
+        // (add_share creates the Share internally), keeping each
+        //  principal's role
         let mut manifest = inner.manifest.clone();
         let _m = manifest.clone();
         let shares = _m.shares();
         manifest.unset_shares();
         for share in shares.values() {
-            let public_key = share.principal().identity;
-            manifest.add_share(public_key, secret.clone())?;
+            let principal = share.principal();
+            manifest.add_share(principal.identity, principal.role, secret.clone())?;
         }
         // Update the bucket's pins field
         manifest.set_pins(pins_link.clone());
         manifest.set_previous(previous);
+        manifest.set_merged(inner.merged.take());
+        manifest.set_message(inner.message.take());
         manifest.set_entry(entry.clone());
+        manifest.set_timestamp_now();
+        // Sign the manifest as the author of the new version
+        manifest.sign(secret_key)?;
         // Put the updated manifest into blobs to determine the new link
         let link = Self::_put_manifest_in_blobs(&manifest, blobs).await?;
 
         // update the internal state
         inner.manifest = manifest;
+        inner.link = link.clone();
 
         Ok(link)
     }
@@ -150,7 +243,7 @@ impl Mount {
         // Put the pins in blobs to get a pins link
         let pins_link = Self::_put_pins_in_blobs(&pins, blobs).await?;
         // construct the new manifest
-        let manifest = Manifest::new(
+        let mut manifest = Manifest::new(
             id,
             name.clone(),
             owner.public(),
@@ -158,6 +251,7 @@ impl Mount {
             entry_link.clone(),
             pins_link.clone(),
         );
+        manifest.sign(owner)?;
         let link = Self::_put_manifest_in_blobs(&manifest, blobs).await?;
 
         // return the new mount
@@ -167,8 +261,11 @@ impl Mount {
                 manifest,
                 entry,
                 pins,
+                merged: None,
+                message: None,
             })),
             blobs.clone(),
+            owner.clone(),
         ))
     }
 
@@ -191,7 +288,7 @@ impl Mount {
 
         let pins = Self::_get_pins_from_blobs(manifest.pins(), blobs).await?;
         let entry =
-            Self::_get_node_from_blobs(&NodeLink::Dir(manifest.entry().clone(), secret), blobs)
+            Self::_get_node_from_blobs(&NodeLink::new_dir(manifest.entry().clone(), secret), blobs)
                 .await?;
 
         Ok(Mount(
@@ -200,19 +297,429 @@ impl Mount {
                 manifest,
                 entry,
                 pins,
+                merged: None,
+                message: None,
             })),
             blobs.clone(),
+            secret_key.clone(),
         ))
     }
 
+    /// Links to every blob an archive of the version at `link` holds: its
+    ///  manifest and pin set, followed by everything pinned, in hash order
+    ///
+    /// Fails if a blob the version itself is made of is missing. Blobs
+    ///  pinned for earlier versions are left out if garbage collection
+    ///  already removed them.
+    pub async fn car_blocks(
+        link: &Link,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<Vec<Link>, MountError> {
+        let manifest = Self::_get_manifest_from_blobs(link, blobs).await?;
+        let required = Self::retained(link, 0, secret_key, blobs).await?;
+        let pinned = blobs.read_hash_list(*manifest.pins().hash()).await?;
+
+        let mut hashes: BTreeSet<Hash> = required.iter().chain(pinned.iter()).copied().collect();
+        hashes.remove(link.hash());
+        hashes.remove(manifest.pins().hash());
+        let mut links = vec![link.clone(), manifest.pins().clone()];
+        for hash in hashes {
+            if blobs.stat(&hash).await? {
+                links.push(Link::new(LD_RAW_CODEC, hash, BlobFormat::Raw));
+            } else if required.contains(&hash) {
+                return Err(MountError::BlobNotFound(hash));
+            }
+        }
+        Ok(links)
+    }
+
+    /// Write the blobs `blocks` link to as a CAR archive rooted at `link`,
+    ///  returning the writer
+    ///
+    /// Blobs are written as they're stored, so the archive is as private
+    ///  as the bucket: only its principals can read what's in it.
+    pub async fn export_car<W: AsyncWrite + Unpin>(
+        link: &Link,
+        blocks: &[Link],
+        blobs: &BlobsStore,
+        writer: W,
+    ) -> Result<W, MountError> {
+        let mut car = CarWriter::new(writer, link).await?;
+        for block in blocks {
+            let data = blobs.get(block.hash()).await?;
+            car.write(block, &data).await?;
+        }
+        Ok(car.finish().await?)
+    }
+
+    /// Add every blob in a CAR archive written by [`Mount::export_car`]
+    ///  to the blob store, and load the version at its root
+    pub async fn import_car<R: AsyncRead + Unpin>(
+        reader: R,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<Self, MountError> {
+        let mut car = CarReader::new(reader).await?;
+        let root = car.root().clone();
+        let mut found_root = false;
+        while let Some((link, data)) = car.next().await? {
+            found_root |= link.hash() == root.hash();
+            blobs.put(data).await?;
+        }
+        if !found_root {
+            return Err(MountError::BlobNotFound(*root.hash()));
+        }
+        Self::load(&root, secret_key, blobs).await
+    }
+
+    /// Check every blob the version of the bucket at `link` is made of
+    ///
+    /// Each blob must be in the blob store, hash to its link, and decrypt
+    ///  and decode as what links to it. Unlike [`Mount::load`], a broken
+    ///  bucket isn't an error: every problem found is listed in the report.
+    ///  Without a share in the version only its manifest and pin set can
+    ///  be checked.
+    pub async fn fsck(
+        link: &Link,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<FsckReport, MountError> {
+        let mut fsck = Fsck::new(blobs);
+        let Some(manifest) = fsck.manifest(link).await? else {
+            return Ok(fsck.report());
+        };
+        let Some(share) = manifest.get_share(&secret_key.public()) else {
+            return Ok(fsck.report());
+        };
+        let secret = share.share().recover(secret_key)?;
+        fsck.dir(
+            &NodeLink::new_dir(manifest.entry().clone(), secret),
+            Path::new("/"),
+        )
+        .await?;
+        Ok(fsck.report())
+    }
+
+    /// Walk the history of the bucket, starting at the version this mount
+    ///  was loaded from (or last saved as)
+    ///
+    /// Yields one [`HistoryEntry`] per version, newest first, following each
+    ///  manifest's `previous` link back to the version that created the bucket,
+    ///  or to the oldest version garbage collection left in the blob store.
+    ///  Any version can be opened with [`Mount::load`] on its link.
+    pub fn history(
+        &self,
+        blobs: &BlobsStore,
+    ) -> impl Stream<Item = Result<HistoryEntry, MountError>> + Send + 'static {
+        let blobs = blobs.clone();
+        let start = (Some(self.link()), HashSet::new());
+        futures::stream::try_unfold(start, move |(next, mut seen)| {
+            let blobs = blobs.clone();
+            async move {
+                let Some(link) = next else {
+                    return Ok(None);
+                };
+                if !seen.is_empty() && !blobs.stat(link.hash()).await? {
+                    return Ok(None);
+                }
+                if !seen.insert(link.clone()) {
+                    return Err(MountError::Default(anyhow::anyhow!(
+                        "cycle in bucket history at {}",
+                        link.hash()
+                    )));
+                }
+                let manifest = Self::_get_manifest_from_blobs(&link, &blobs).await?;
+                let previous = manifest.previous().clone();
+                Ok(Some((HistoryEntry { link, manifest }, (previous, seen))))
+            }
+        })
+    }
+
+    /// Every blob the version at `link`, and the `depth` versions before
+    ///  it, are made of
+    ///
+    /// This is each version's manifest and pin set, and every node and
+    ///  file reachable from its entry. Versions `secret_key` has no share
+    ///  in can't be walked, so everything in their pin set is included
+    ///  instead. Versions missing from the blob store end the walk.
+    pub async fn retained(
+        link: &Link,
+        depth: usize,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<Pins, MountError> {
+        let mut retained = Pins::new();
+        let mut next = Some(link.clone());
+        for _ in 0..=depth {
+            let Some(link) = next.take() else {
+                break;
+            };
+            if !retained.insert(*link.hash()) || !blobs.stat(link.hash()).await? {
+                break;
+            }
+            let manifest = Self::_get_manifest_from_blobs(&link, blobs).await?;
+            retained.insert(*manifest.pins().hash());
+            if let Some(merged) = manifest.merged() {
+                retained.insert(*merged.hash());
+            }
+            match manifest.get_share(&secret_key.public()) {
+                Some(share) => {
+                    let secret = share.share().recover(secret_key)?;
+                    let entry = NodeLink::new_dir(manifest.entry().clone(), secret);
+                    Self::_reachable(&entry, &mut retained, blobs).await?;
+                }
+                None => {
+                    let pins = Self::_get_pins_from_blobs(manifest.pins(), blobs).await?;
+                    retained.extend(pins.iter().copied());
+                }
+            }
+            next = manifest.previous().clone();
+        }
+        Ok(retained)
+    }
+
+    /// Drop everything from the pin set that neither the current version
+    ///  nor the `depth` versions before it need
+    ///
+    /// Saving the mount afterwards records the smaller pin set, so peers
+    ///  that sync the bucket only fetch the history that is retained.
+    ///  Since that version needs pruning again, the pin set is only
+    ///  changed when it holds more than one version beyond `depth`.
+    ///
+    /// Returns false, and leaves the mount as it is, if nothing needed
+    ///  dropping.
+    pub async fn prune_pins(
+        &mut self,
+        depth: usize,
+        blobs: &BlobsStore,
+    ) -> Result<bool, MountError> {
+        let link = self.link();
+        let slack = Self::retained(&link, depth + 1, &self.2, blobs).await?;
+        if self.0.lock().pins.iter().all(|hash| slack.contains(hash)) {
+            return Ok(false);
+        }
+        let retained = Self::retained(&link, depth, &self.2, blobs).await?;
+        self.0.lock().pins = retained;
+        Ok(true)
+    }
+
+    /// Drop the history before the current version and the `keep`
+    ///  versions preceding it, saving the result as a new version
+    ///
+    /// Versions link to the ones before them by hash, so the kept versions
+    ///  are rewritten, oldest first, with the oldest no longer building on
+    ///  anything. The rewrites keep their content, time and message, but
+    ///  are signed by us. The new version builds on the rewritten history
+    ///  and merges in the current one, so peers at the current version
+    ///  accept it as a successor. Its pin set only holds what the kept
+    ///  versions need, leaving what only dropped versions referenced to
+    ///  garbage collection.
+    ///
+    /// Only owners can squash a bucket, since the rewritten versions carry
+    ///  over the principal changes of the originals. Returns None, and
+    ///  leaves the mount as it is, if there is nothing before the kept
+    ///  versions to drop.
     #[allow(clippy::await_holding_lock)]
-    pub async fn share(&mut self, peer: PublicKey) -> Result<(), MountError> {
+    pub async fn squash(
+        &mut self,
+        keep: usize,
+        blobs: &BlobsStore,
+    ) -> Result<Option<Link>, MountError> {
+        if !self.role().is_some_and(|role| role.can_share()) {
+            return Err(MountError::NotOwner);
+        }
+        let head = self.link();
+
+        // Collect the kept versions, newest first. History that is already
+        //  missing from the blob store is dropped as well.
+        let mut kept = Vec::new();
+        let mut next = self.0.lock().manifest.previous().clone();
+        let mut dropping = false;
+        while let Some(link) = next.take() {
+            if kept.len() == keep || !blobs.stat(link.hash()).await? {
+                dropping = true;
+                break;
+            }
+            let manifest = Self::_get_manifest_from_blobs(&link, blobs).await?;
+            next = manifest.previous().clone();
+            kept.push((link, manifest));
+        }
+        if !dropping {
+            return Ok(None);
+        }
+
+        // Keep what the kept versions need, less the manifests and pin
+        //  sets being replaced, and the current root the new version
+        //  re-encrypts
+        let mut pins = Self::retained(&head, keep, &self.2, blobs).await?;
+        {
+            let inner = self.0.lock();
+            pins.remove(inner.manifest.pins().hash());
+            pins.remove(inner.manifest.entry().hash());
+        }
+        for (link, manifest) in &kept {
+            pins.remove(link.hash());
+            pins.remove(manifest.pins().hash());
+        }
+        let kept_pins = Self::_put_pins_in_blobs(&pins, blobs).await?;
+        pins.insert(*kept_pins.hash());
+
+        let mut previous = None;
+        for (_, mut manifest) in kept.into_iter().rev() {
+            manifest.set_previous(previous.take());
+            manifest.set_pins(kept_pins.clone());
+            manifest.sign(&self.2)?;
+            let link = Self::_put_manifest_in_blobs(&manifest, blobs).await?;
+            pins.insert(*link.hash());
+            previous = Some(link);
+        }
+
         let mut inner = self.0.lock();
-        inner.manifest.add_share(peer, Secret::default())?;
-        Ok(())
+        inner.pins = pins;
+        inner.merged = Some(head);
+        Self::_commit(&mut inner, previous, &self.2, blobs)
+            .await
+            .map(Some)
+    }
+
+    /// Compare two versions of a bucket
+    ///
+    /// Both versions are loaded with `secret_key`, which needs a share in
+    ///  each of them. Subtrees with the same link in both versions are
+    ///  skipped, so the cost scales with the size of the change rather
+    ///  than the size of the bucket.
+    pub async fn diff(
+        from: &Link,
+        to: &Link,
+        secret_key: &SecretKey,
+        blobs: &BlobsStore,
+    ) -> Result<Diff, MountError> {
+        let from = Self::load(from, secret_key, blobs).await?.inner();
+        let to = Self::load(to, secret_key, blobs).await?.inner();
+        Diff::between(from.entry(), to.entry(), blobs).await
     }
 
+    /// Share the bucket with `peer` as `role`, or change the role of
+    ///  an existing principal. Only owners may do this.
     #[allow(clippy::await_holding_lock)]
+    pub async fn share(&mut self, peer: PublicKey, role: PrincipalRole) -> Result<(), MountError> {
+        let mut inner = self.0.lock();
+        if !inner
+            .manifest
+            .role(&self.2.public())
+            .is_some_and(|role| role.can_share())
+        {
+            return Err(MountError::NotOwner);
+        }
+        inner.manifest.add_share(peer, role, Secret::default())?;
+        Ok(())
+    }
+
+    /// Remove `peer`'s share of the bucket. Only owners may do this, and
+    ///  not on themselves.
+    ///
+    /// Once saved, `peer` can't decrypt new versions of the bucket. Files
+    ///  keep their secrets though, even when they change, so a peer that
+    ///  read them before can still decrypt them if it gets hold of the
+    ///  blobs.
+    ///  With `rekey`, every directory and file is re-encrypted under new
+    ///  secrets as well, calling `on_progress` after each one. Earlier
+    ///  versions are not touched and stay readable with their old shares.
+    pub async fn revoke(
+        &mut self,
+        peer: &PublicKey,
+        rekey: bool,
+        blobs: &BlobsStore,
+        on_progress: impl FnMut(&RekeyProgress),
+    ) -> Result<(), MountError> {
+        let entry = {
+            let mut inner = self.0.lock();
+            if !inner
+                .manifest
+                .role(&self.2.public())
+                .is_some_and(|role| role.can_share())
+            {
+                return Err(MountError::NotOwner);
+            }
+            if *peer == self.2.public() {
+                return Err(MountError::Default(anyhow::anyhow!(
+                    "Cannot revoke your own share"
+                )));
+            }
+            if inner.manifest.remove_share(peer).is_none() {
+                return Err(MountError::NotPrincipal(peer.to_hex()));
+            }
+            inner.entry.clone()
+        };
+        if !rekey {
+            return Ok(());
+        }
+
+        let mut rekey = Rekey::new(blobs, on_progress);
+        let entry = rekey.node(&entry).await?;
+        let mut inner = self.0.lock();
+        inner.entry = entry;
+        inner.pins.extend(rekey.created());
+        // Everything is in the newest format after being rewritten
+        inner.manifest.set_format(MANIFEST_FORMAT);
+        Ok(())
+    }
+
+    /// Rewrite the bucket in the newest format, [`MANIFEST_FORMAT`]
+    ///
+    /// Every directory and file is re-encrypted under the secret it
+    ///  already has, calling `on_progress` as it goes, so blobs stored in
+    ///  older formats are replaced by ones in the newest. Saving the mount
+    ///  afterwards records the migration as a new version.
+    ///
+    /// Returns false, and leaves the mount as it is, if the bucket is
+    ///  already in the newest format.
+    pub async fn migrate(
+        &mut self,
+        blobs: &BlobsStore,
+        on_progress: impl FnMut(&RekeyProgress),
+    ) -> Result<bool, MountError> {
+        let entry = {
+            let inner = self.0.lock();
+            if !inner
+                .manifest
+                .role(&self.2.public())
+                .is_some_and(|role| role.can_write())
+            {
+                return Err(MountError::ReadOnly);
+            }
+            if inner.manifest.format() >= MANIFEST_FORMAT {
+                return Ok(false);
+            }
+            inner.entry.clone()
+        };
+
+        let mut rekey = Rekey::new(blobs, on_progress).keep_secrets();
+        let entry = rekey.node(&entry).await?;
+        let mut inner = self.0.lock();
+        inner.entry = entry;
+        inner.pins.extend(rekey.created());
+        inner.manifest.set_format(MANIFEST_FORMAT);
+        Ok(true)
+    }
+
+    /// The format of the bucket, see [`MANIFEST_FORMAT`]
+    pub fn format(&self) -> u32 {
+        self.0.lock().manifest.format()
+    }
+
+    /// The role we have on the bucket, if we are a principal of it
+    pub fn role(&self) -> Option<PrincipalRole> {
+        self.0.lock().manifest.role(&self.2.public())
+    }
+
+    /// Encrypt and store data at the given path
+    ///
+    /// The data is chunked, encrypted and written to the blob store as it is
+    ///  read, so the whole file is never held in memory. When replacing a file,
+    ///  its secret is kept so chunks that didn't change are deduplicated
+    ///  against the previous version, and the same content leaves it as it is.
     pub async fn add<R>(
         &mut self,
         path: &Path,
@@ -220,53 +727,140 @@ impl Mount {
         blobs: &BlobsStore,
     ) -> Result<(), MountError>
     where
-        R: Read + Send + Sync + 'static + Unpin,
+        R: AsyncRead + Send + Sync + 'static + Unpin,
     {
-        let secret = Secret::generate();
-
-        let encrypted_reader = secret.encrypt_reader(data)?;
+        let node_link = self.stage(path, data, blobs).await?;
+        self._set_entry_link_at_path(node_link, path, blobs).await
+    }
 
-        // TODO (amiller68): this is incredibly dumb
-        use bytes::Bytes;
-        use futures::stream;
-        let encrypted_bytes = {
-            let mut buf = Vec::new();
-            let mut reader = encrypted_reader;
-            reader.read_to_end(&mut buf).map_err(SecretError::Io)?;
-            buf
+    /// Encrypt and store data to go at the given path, without adding it
+    ///  to the tree yet
+    ///
+    /// Returns the link to pass to [`Mount::add_staged`] along with the
+    ///  links of every other file staged, so adding many files only
+    ///  rebuilds the tree once. The data is pinned right away.
+    pub async fn stage<R>(
+        &mut self,
+        path: &Path,
+        data: R,
+        blobs: &BlobsStore,
+    ) -> Result<NodeLink, MountError>
+    where
+        R: AsyncRead + Send + Sync + 'static + Unpin,
+    {
+        let existing = match self.get(path, blobs).await {
+            Ok(existing @ NodeLink::Data(..)) => Some(existing),
+            _ => None,
         };
+        let secret = match &existing {
+            Some(existing) => existing.secret().clone(),
+            None => Secret::generate(),
+        };
+        let stored = put_data(data, &secret, blobs).await?;
+
+        // The same content encrypts to the blobs already there, so none were
+        //  written and the file is left as it is
+        if let Some(existing) = existing {
+            if existing
+                .data()
+                .and_then(|data| data.plaintext_hash())
+                .is_some_and(|hash| hash == stored.plaintext_hash)
+            {
+                return Ok(existing);
+            }
+        }
 
-        let stream = Box::pin(stream::once(async move {
-            Ok::<_, std::io::Error>(Bytes::from(encrypted_bytes))
-        }));
-
-        let hash = blobs.put_stream(stream).await?;
+        // Track pins: data blobs, the nodes are pinned once they're added
+        self.0.lock().pins.extend(stored.hashes);
+        let mut data = Data::from_path(path);
+        data.set_size(stored.size);
+        data.set_plaintext_hash(stored.plaintext_hash);
+        Ok(NodeLink::Data(stored.link, secret, data))
+    }
 
-        let link = Link::new(
-            crate::linked_data::LD_RAW_CODEC,
-            hash,
-            iroh_blobs::BlobFormat::Raw,
-        );
+    /// Encrypt and store one part of a file that arrives in parts, such
+    ///  as an S3 multipart upload, returning its chunks and the BLAKE3 hash
+    ///  of the part
+    ///
+    /// The chunks are held under `hold` (see [`BlobsStore::hold`]) so they
+    ///  survive garbage collection however long the file takes to finish.
+    ///  Release them once the file is saved, or abandoned.
+    pub async fn put_part<R>(
+        data: R,
+        secret: &Secret,
+        hold: &str,
+        blobs: &BlobsStore,
+    ) -> Result<(Vec<Chunk>, Hash), MountError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        put_chunks(data, secret, Some(hold), blobs).await
+    }
 
-        let node_link = NodeLink::new_data_from_path(link.clone(), secret, path);
+    /// Join the chunks of parts stored with [`Mount::put_part`], in order,
+    ///  into a file to go at the given path, as [`Mount::stage`] does with
+    ///  data that arrives whole
+    ///
+    /// The chunks are read back to hash the file, which also checks that
+    ///  every one of them is still stored.
+    pub async fn stage_parts(
+        &mut self,
+        path: &Path,
+        secret: Secret,
+        chunks: Vec<Chunk>,
+        blobs: &BlobsStore,
+    ) -> Result<NodeLink, MountError> {
+        let mut hasher = blake3::Hasher::new();
+        for chunk in &chunks {
+            hasher.update(&secret.decrypt(&blobs.get(&chunk.hash).await?)?);
+        }
+        let plaintext_hash = Hash::from_bytes(*hasher.finalize().as_bytes());
+        let stored = link_chunks(chunks, plaintext_hash, &secret, blobs).await?;
+
+        self.0.lock().pins.extend(stored.hashes);
+        let mut data = Data::from_path(path);
+        data.set_size(stored.size);
+        data.set_plaintext_hash(stored.plaintext_hash);
+        Ok(NodeLink::Data(stored.link, secret, data))
+    }
 
-        let mut inner = self.0.lock();
-        let root_node = inner.entry.clone();
-        let (updated_link, node_hashes) =
-            Self::_set_node_link_at_path(root_node, node_link, path, blobs).await?;
+    /// Add links returned by [`Mount::stage`], keyed by their path, writing
+    ///  each directory above them once rather than once per link
+    pub async fn add_staged(
+        &mut self,
+        links: BTreeMap<PathBuf, NodeLink>,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let changes = links
+            .into_iter()
+            .map(|(path, link)| (path, Some(link)))
+            .collect();
+        self.apply_staged(changes, blobs).await
+    }
 
-        // Track pins: data blob + all created node hashes
-        inner.pins.insert(hash);
-        inner.pins.extend(node_hashes);
+    /// Add links returned by [`Mount::stage`] and remove paths together,
+    ///  keyed by their path, with `None` removing whatever is at the path
+    ///
+    /// As with [`Mount::add_staged`], each directory above the changes is
+    ///  written once. Removals under a path are applied before additions,
+    ///  so a file can replace a directory, or a directory a file.
+    pub async fn apply_staged(
+        &mut self,
+        changes: BTreeMap<PathBuf, Option<NodeLink>>,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let root_node = self.0.lock().entry.clone();
+        let links = changes
+            .into_iter()
+            .map(|(path, link)| (clean_path(&path), link))
+            .collect();
 
-        if let NodeLink::Dir(new_root_link, new_secret) = updated_link {
-            inner.entry = Self::_get_node_from_blobs(
-                &NodeLink::Dir(new_root_link.clone(), new_secret),
-                blobs,
-            )
-            .await?;
-        }
+        let mut batch = Batch::new(blobs);
+        let entry = batch.node(&root_node, Path::new("/"), links).await?;
 
+        let mut inner = self.0.lock();
+        inner.entry = entry;
+        inner.pins.extend(batch.created());
         Ok(())
     }
 
@@ -329,6 +923,185 @@ impl Mount {
         Ok(())
     }
 
+    /// Move a file or directory from one path to another
+    ///
+    /// The existing [`NodeLink`] is re-attached at the destination as-is,
+    ///  so the underlying data keeps its link, secret and metadata and
+    ///  nothing is re-encrypted or re-uploaded. Only the nodes along the
+    ///  source and destination paths are rebuilt.
+    ///
+    /// The destination is the full new path of the item (not a directory
+    ///  to move it into) and must not already exist.
+    pub async fn mv(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let from_path = clean_path(from);
+        let to_path = clean_path(to);
+
+        if from_path == Path::new("") || to_path == Path::new("") {
+            return Err(MountError::Default(anyhow::anyhow!("Cannot move root")));
+        }
+        if from_path == to_path {
+            return Ok(());
+        }
+        if to_path.starts_with(&from_path) {
+            return Err(MountError::Default(anyhow::anyhow!(
+                "Cannot move {} into itself",
+                from.display()
+            )));
+        }
+
+        let node_link = self.get(from, blobs).await?;
+
+        match self.get(to, blobs).await {
+            Ok(_) => return Err(MountError::PathAlreadyExists(to_path)),
+            Err(MountError::PathNotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        self.rm(from, blobs).await?;
+        self._set_entry_link_at_path(node_link, to, blobs).await
+    }
+
+    /// Create an empty directory at the given path, along with any missing
+    ///  parents
+    ///
+    /// Directories are otherwise only created to hold what's added under
+    ///  them. Nothing may already be at the path.
+    pub async fn mkdir(&mut self, path: &Path, blobs: &BlobsStore) -> Result<(), MountError> {
+        let dir_path = clean_path(path);
+        if dir_path == Path::new("") {
+            return Err(MountError::PathAlreadyExists(dir_path));
+        }
+        match self.get(path, blobs).await {
+            Ok(_) => return Err(MountError::PathAlreadyExists(dir_path)),
+            Err(MountError::PathNotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        let secret = Secret::generate();
+        let link = Self::_put_node_in_blobs(&Node::default(), &secret, blobs).await?;
+        self.0.lock().pins.insert(*link.hash());
+        self._set_entry_link_at_path(NodeLink::new_dir(link, secret), path, blobs)
+            .await
+    }
+
+    /// Merge a concurrent version of the bucket into this one
+    ///
+    /// `base` is the latest version that both this mount and `theirs` were
+    ///  built on. What `theirs` changed since `base` is applied on top of
+    ///  the current contents as described on [`Merge`], with `our_label` and
+    ///  `their_label` naming the two sides in conflict copies. Saving the
+    ///  mount afterwards records `theirs` as the version merged in, so peers
+    ///  on either side accept the result as a successor. Principals that
+    ///  `theirs` added, removed or gave another role are changed here too.
+    ///
+    /// Returns false, and leaves the mount as it is, if this version already
+    ///  has everything `theirs` changed.
+    pub async fn merge(
+        &mut self,
+        base: &Link,
+        theirs: &Link,
+        our_label: &str,
+        their_label: &str,
+        blobs: &BlobsStore,
+    ) -> Result<bool, MountError> {
+        let base = self._load_version(base, blobs).await?.inner();
+        let their_mount = self._load_version(theirs, blobs).await?.inner();
+        let ours = self.inner();
+
+        let mut merge = Merge::new(our_label, their_label, blobs);
+        let entry = merge
+            .nodes(base.entry(), ours.entry(), their_mount.entry())
+            .await?;
+        let roles = merge::roles(base.manifest(), ours.manifest(), their_mount.manifest());
+        if entry.is_none() && roles.is_empty() {
+            return Ok(false);
+        }
+
+        let mut inner = self.0.lock();
+        if let Some(entry) = entry {
+            inner.entry = entry;
+        }
+        for (identity, role) in roles {
+            match role {
+                Some(role) => inner
+                    .manifest
+                    .add_share(identity, role, Secret::default())?,
+                None => {
+                    inner.manifest.remove_share(&identity);
+                }
+            }
+        }
+        inner.pins.extend(their_mount.pins.iter().copied());
+        inner.pins.extend(merge.created());
+        inner.merged = Some(theirs.clone());
+        // Their side may bring in items stored in an older format
+        let format = inner.manifest.format().min(their_mount.manifest.format());
+        inner.manifest.set_format(format);
+        Ok(true)
+    }
+
+    /// Restore the contents of the bucket to those of an earlier version
+    ///
+    /// The entry node and pins of `version` replace the current ones, while
+    ///  the rest of the manifest (name, shares, ...) is kept as it is now.
+    ///  Saving the mount afterwards records the revert as a new version whose
+    ///  `previous` is the current one, so history stays linear and peers
+    ///  accept it like any other update.
+    pub async fn revert_to(
+        &mut self,
+        version: &Link,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let old = self._load_version(version, blobs).await?.inner();
+
+        let mut inner = self.0.lock();
+        inner.entry = old.entry;
+        inner.pins = old.pins;
+        let format = inner.manifest.format().min(old.manifest.format());
+        inner.manifest.set_format(format);
+        Ok(())
+    }
+
+    /// Restore a single file or directory to how it was at an earlier version
+    ///
+    /// The item's [`NodeLink`] is copied over from `version`, replacing
+    ///  whatever is at the path now, so nothing is re-encrypted or
+    ///  re-uploaded. If the path didn't exist at `version` it is removed.
+    ///  Reverting the root is the same as [`Mount::revert_to`].
+    pub async fn revert_path(
+        &mut self,
+        version: &Link,
+        path: &Path,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        if clean_path(path) == Path::new("") {
+            return self.revert_to(version, blobs).await;
+        }
+
+        let old = self._load_version(version, blobs).await?;
+        match old.get(path, blobs).await {
+            Ok(node_link) => {
+                // Everything under the restored item is pinned by the old
+                //  version, and may be stored in its older format
+                {
+                    let old = old.inner();
+                    let mut inner = self.0.lock();
+                    inner.pins.extend(old.pins.iter().copied());
+                    let format = inner.manifest.format().min(old.manifest.format());
+                    inner.manifest.set_format(format);
+                }
+                self._set_entry_link_at_path(node_link, path, blobs).await
+            }
+            Err(MountError::PathNotFound(_)) => self.rm(path, blobs).await,
+            Err(err) => Err(err),
+        }
+    }
+
     #[allow(clippy::await_holding_lock)]
     pub async fn ls(
         &self,
@@ -439,22 +1212,45 @@ impl Mount {
 
         match link {
             NodeLink::Data(link, secret, _) => {
-                let encrypted_data = blobs.get(link.hash()).await?;
-                let data = secret.decrypt(&encrypted_data)?;
-                Ok(data)
+                let layout = DataLayout::load(link, secret, blobs).await?;
+                layout.read_all(secret, blobs).await
             }
             NodeLink::Dir(_, _) => Err(MountError::PathNotNode(path.to_path_buf())),
         }
     }
 
+    /// Open a streaming reader over the decrypted contents of the file at `path`
+    ///
+    /// Only the chunks and encrypted segments overlapping `range` (the whole file
+    ///  if `None`) are read from the blob store, and only as the reader is consumed. The
+    ///  range is clamped to the size of the file, see [`FileReader::range`].
+    pub async fn cat_reader(
+        &self,
+        path: &Path,
+        range: Option<Range<u64>>,
+        blobs: &BlobsStore,
+    ) -> Result<FileReader, MountError> {
+        let (link, secret) = match self.get(path, blobs).await? {
+            NodeLink::Data(link, secret, _) => (link, secret),
+            NodeLink::Dir(_, _) => return Err(MountError::PathNotNode(clean_path(path))),
+        };
+        let layout = DataLayout::load(&link, &secret, blobs).await?;
+        let size = layout.size();
+
+        let range = range.unwrap_or(0..size);
+        let start = range.start.min(size);
+        let range = start..range.end.clamp(start, size);
+
+        let stream = layout.open(&secret, range.clone(), blobs).await?;
+        Ok(FileReader::new(stream, size, range))
+    }
+
     /// Get the NodeLink for a file at a given path
-    #[allow(clippy::await_holding_lock)]
     pub async fn get(&self, path: &Path, blobs: &BlobsStore) -> Result<NodeLink, MountError> {
         let path = clean_path(path);
 
-        let inner = self.0.lock();
-        let root_node = inner.entry.clone();
-        drop(inner);
+        // Not held past here, so the future can be sent between threads
+        let root_node = self.0.lock().entry.clone();
 
         let (parent_path, file_name) = if let Some(parent) = path.parent() {
             (
@@ -491,11 +1287,41 @@ impl Mount {
             let next_link = current_node
                 .get_link(&next)
                 .ok_or(MountError::PathNotFound(consumed_path.clone()))?;
+            if !next_link.is_dir() {
+                return Err(MountError::PathNotNode(consumed_path));
+            }
             current_node = Self::_get_node_from_blobs(next_link, blobs).await?
         }
         Ok(current_node)
     }
 
+    /// Set a node link at the given path within the entry node, rebuilding
+    ///  the parent nodes up to the root and pinning every node created
+    #[allow(clippy::await_holding_lock)]
+    async fn _set_entry_link_at_path(
+        &self,
+        node_link: NodeLink,
+        path: &Path,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let mut inner = self.0.lock();
+        let root_node = inner.entry.clone();
+        let (updated_link, node_hashes) =
+            Self::_set_node_link_at_path(root_node, node_link, path, blobs).await?;
+
+        inner.pins.extend(node_hashes);
+
+        if let NodeLink::Dir(new_root_link, new_secret) = updated_link {
+            inner.entry = Self::_get_node_from_blobs(
+                &NodeLink::Dir(new_root_link.clone(), new_secret),
+                blobs,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn _set_node_link_at_path(
         node: Node,
         node_link: NodeLink,
@@ -551,6 +1377,49 @@ impl Mount {
         Ok((node_link, created_hashes))
     }
 
+    /// Load another version of this bucket, refusing versions of other buckets
+    async fn _load_version(&self, version: &Link, blobs: &BlobsStore) -> Result<Mount, MountError> {
+        let old = Self::load(version, &self.2, blobs).await?;
+        let id = *self.0.lock().manifest.id();
+        if *old.inner().manifest().id() != id {
+            return Err(MountError::Default(anyhow::anyhow!(
+                "{} is not a version of bucket {}",
+                version.hash(),
+                id
+            )));
+        }
+        Ok(old)
+    }
+
+    // Add the blobs of `node_link`, and of everything under it, to `pins`.
+    //  Subtrees whose blob is already there are skipped, so walking many
+    //  versions only costs as much as what changed between them.
+    async fn _reachable(
+        node_link: &NodeLink,
+        pins: &mut Pins,
+        blobs: &BlobsStore,
+    ) -> Result<(), MountError> {
+        let link = node_link.link();
+        if !pins.insert(*link.hash()) {
+            return Ok(());
+        }
+        match node_link {
+            NodeLink::Dir(..) => {
+                let node = Self::_get_node_from_blobs(node_link, blobs).await?;
+                for child in node.get_links().values() {
+                    Box::pin(Self::_reachable(child, pins, blobs)).await?;
+                }
+            }
+            NodeLink::Data(..) => {
+                // Chunked data is a HashSeq of its index and chunks
+                if *link.format() == iroh_blobs::BlobFormat::HashSeq {
+                    pins.extend(blobs.read_hash_list(*link.hash()).await?);
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn _get_manifest_from_blobs(
         link: &Link,
         blobs: &BlobsStore,
@@ -636,7 +1505,7 @@ impl Mount {
         Ok(Pins::from_vec(hashes))
     }
 
-    async fn _get_node_from_blobs(
+    pub(crate) async fn _get_node_from_blobs(
         node_link: &NodeLink,
         blobs: &BlobsStore,
     ) -> Result<Node, MountError> {
@@ -687,7 +1556,7 @@ impl Mount {
     // TODO (amiller68): you should inline a Link
     //  into the node when we store encrypt it,
     //  so that we have an integrity check
-    async fn _put_node_in_blobs(
+    pub(crate) async fn _put_node_in_blobs(
         node: &Node,
         secret: &Secret,
         blobs: &BlobsStore,
@@ -732,6 +1601,8 @@ impl Mount {
 
 #[cfg(test)]
 mod test {
+    use super::super::diff::Moved;
+    use super::super::fsck::{FsckBlob, FsckIssue};
     use super::*;
     use std::io::Cursor;
     use tempfile::TempDir;
@@ -793,6 +1664,143 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_add_staged() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        mount
+            .add(
+                &PathBuf::from("/docs/old.txt"),
+                Cursor::new(b"old".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+
+        let mut links = BTreeMap::new();
+        for (path, data) in [
+            ("/docs/old.txt", "replaced"),
+            ("/docs/new.txt", "new"),
+            ("/docs/deep/a.txt", "a"),
+            ("/docs/deep/b.txt", "b"),
+            ("/top.txt", "top"),
+        ] {
+            let path = PathBuf::from(path);
+            let link = mount
+                .stage(&path, Cursor::new(data.as_bytes().to_vec()), &blobs)
+                .await
+                .unwrap();
+            links.insert(path, link);
+        }
+        // Nothing is in the tree until the links are added
+        assert!(mount.get(&PathBuf::from("/top.txt"), &blobs).await.is_err());
+        let pins_before = mount.inner().pins().len();
+        mount.add_staged(links, &blobs).await.unwrap();
+
+        let items = mount.ls_deep(&PathBuf::from("/"), &blobs).await.unwrap();
+        let paths: Vec<_> = items
+            .keys()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "docs",
+                "docs/deep",
+                "docs/deep/a.txt",
+                "docs/deep/b.txt",
+                "docs/new.txt",
+                "docs/old.txt",
+                "top.txt"
+            ]
+        );
+        let old = mount.cat(&PathBuf::from("/docs/old.txt"), &blobs).await;
+        assert_eq!(old.unwrap(), b"replaced");
+        let deep = mount.cat(&PathBuf::from("/docs/deep/b.txt"), &blobs).await;
+        assert_eq!(deep.unwrap(), b"b");
+
+        // Each of the two directories under the root was written once
+        assert_eq!(mount.inner().pins().len(), pins_before + 2);
+
+        // A file can't be added under another file
+        let path = PathBuf::from("/top.txt/nested.txt");
+        let link = mount
+            .stage(&path, Cursor::new(b"x".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let result = mount
+            .add_staged(BTreeMap::from([(path, link)]), &blobs)
+            .await;
+        assert!(matches!(result, Err(MountError::PathNotNode(_))));
+
+        // The batch survives a save and load
+        let link = mount.save(&blobs).await.unwrap();
+        let secret_key = mount.2.clone();
+        let loaded = Mount::load(&link, &secret_key, &blobs).await.unwrap();
+        let result = loaded.cat(&PathBuf::from("/docs/deep/a.txt"), &blobs).await;
+        assert_eq!(result.unwrap(), b"a");
+    }
+
+    #[tokio::test]
+    async fn test_apply_staged() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        for path in [
+            "/keep.txt",
+            "/gone.txt",
+            "/dir/a.txt",
+            "/dir/b.txt",
+            "/was_file",
+        ] {
+            mount
+                .add(&PathBuf::from(path), Cursor::new(b"x".to_vec()), &blobs)
+                .await
+                .unwrap();
+        }
+
+        // Staged files record the size and hash of their plaintext
+        let path = PathBuf::from("/was_file/now_dir.txt");
+        let link = mount
+            .stage(&path, Cursor::new(b"hello".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let data = link.data().unwrap();
+        assert_eq!(data.size(), Some(5));
+        assert_eq!(data.plaintext_hash(), Some(Hash::new(b"hello")));
+
+        // Removals come before additions, so a directory can replace a file
+        let changes = BTreeMap::from([
+            (PathBuf::from("/gone.txt"), None),
+            (PathBuf::from("/dir/a.txt"), None),
+            (PathBuf::from("/was_file"), None),
+            (path, Some(link)),
+        ]);
+        mount.apply_staged(changes, &blobs).await.unwrap();
+
+        let items = mount.ls_deep(&PathBuf::from("/"), &blobs).await.unwrap();
+        let paths: Vec<_> = items
+            .keys()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "dir",
+                "dir/b.txt",
+                "keep.txt",
+                "was_file",
+                "was_file/now_dir.txt"
+            ]
+        );
+
+        // Removing a path that isn't there fails, as with rm
+        for missing in ["/missing.txt", "/nowhere/missing.txt"] {
+            let changes = BTreeMap::from([(PathBuf::from(missing), None)]);
+            let result = mount.apply_staged(changes, &blobs).await;
+            assert!(matches!(result, Err(MountError::PathNotFound(_))));
+        }
+    }
+
     #[tokio::test]
     async fn test_ls() {
         let (mut mount, blobs, _, _temp) = setup_test_env().await;
@@ -1019,9 +2027,1224 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_save_load() {
-        let (mount, blobs, secret_key, _temp) = setup_test_env().await;
-        let link = mount.save(&blobs).await.unwrap();
-        let _mount = Mount::load(&link, &secret_key, &blobs).await.unwrap();
+    async fn test_mv_file() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        mount
+            .add(
+                &PathBuf::from("/notes.md"),
+                Cursor::new(b"notes".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let before = mount
+            .get(&PathBuf::from("/notes.md"), &blobs)
+            .await
+            .unwrap();
+
+        mount
+            .mv(
+                &PathBuf::from("/notes.md"),
+                &PathBuf::from("/archive/old-notes.txt"),
+                &blobs,
+            )
+            .await
+            .unwrap();
+
+        assert!(mount
+            .cat(&PathBuf::from("/notes.md"), &blobs)
+            .await
+            .is_err());
+
+        // Same data link, secret and metadata as before the move
+        let after = mount
+            .get(&PathBuf::from("/archive/old-notes.txt"), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(before, after);
+        assert_eq!(
+            after.data().unwrap().mime().map(|m| m.as_ref()),
+            Some("text/markdown")
+        );
+
+        let data = mount
+            .cat(&PathBuf::from("/archive/old-notes.txt"), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(data, b"notes");
+    }
+
+    #[tokio::test]
+    async fn test_mv_dir() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        mount
+            .add(
+                &PathBuf::from("/src/main.rs"),
+                Cursor::new(b"main".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        mount
+            .add(
+                &PathBuf::from("/src/util/mod.rs"),
+                Cursor::new(b"util".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+
+        mount
+            .mv(&PathBuf::from("/src"), &PathBuf::from("/lib/src"), &blobs)
+            .await
+            .unwrap();
+
+        let items = mount.ls(&PathBuf::from("/"), &blobs).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items.contains_key(&PathBuf::from("lib")));
+
+        let data = mount
+            .cat(&PathBuf::from("/lib/src/util/mod.rs"), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(data, b"util");
+    }
+
+    #[tokio::test]
+    async fn test_mkdir() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        mount
+            .mkdir(&PathBuf::from("/docs/drafts"), &blobs)
+            .await
+            .unwrap();
+
+        let items = mount.ls(&PathBuf::from("/docs"), &blobs).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[&PathBuf::from("docs/drafts")].is_dir());
+        let items = mount
+            .ls(&PathBuf::from("/docs/drafts"), &blobs)
+            .await
+            .unwrap();
+        assert!(items.is_empty());
+
+        // Files can go in it, and it can't be created again
+        mount
+            .add(
+                &PathBuf::from("/docs/drafts/a.txt"),
+                Cursor::new(b"a".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let result = mount.mkdir(&PathBuf::from("/docs/drafts"), &blobs).await;
+        assert!(matches!(result, Err(MountError::PathAlreadyExists(_))));
+
+        // Not over a file, or below one
+        let result = mount
+            .mkdir(&PathBuf::from("/docs/drafts/a.txt"), &blobs)
+            .await;
+        assert!(matches!(result, Err(MountError::PathAlreadyExists(_))));
+        let result = mount
+            .mkdir(&PathBuf::from("/docs/drafts/a.txt/b"), &blobs)
+            .await;
+        assert!(matches!(result, Err(MountError::PathNotNode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_mv_errors() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        mount
+            .add(&PathBuf::from("/a.txt"), Cursor::new(b"a".to_vec()), &blobs)
+            .await
+            .unwrap();
+        mount
+            .add(
+                &PathBuf::from("/dir/b.txt"),
+                Cursor::new(b"b".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+
+        // Source does not exist
+        let result = mount
+            .mv(
+                &PathBuf::from("/missing.txt"),
+                &PathBuf::from("/c.txt"),
+                &blobs,
+            )
+            .await;
+        assert!(matches!(result, Err(MountError::PathNotFound(_))));
+
+        // Destination already exists
+        let result = mount
+            .mv(
+                &PathBuf::from("/a.txt"),
+                &PathBuf::from("/dir/b.txt"),
+                &blobs,
+            )
+            .await;
+        assert!(matches!(result, Err(MountError::PathAlreadyExists(_))));
+
+        // Directory into itself
+        let result = mount
+            .mv(&PathBuf::from("/dir"), &PathBuf::from("/dir/sub"), &blobs)
+            .await;
+        assert!(result.is_err());
+
+        // Destination below a file
+        let result = mount
+            .mv(
+                &PathBuf::from("/dir/b.txt"),
+                &PathBuf::from("/a.txt/b.txt"),
+                &blobs,
+            )
+            .await;
+        assert!(matches!(result, Err(MountError::PathNotNode(_))));
+
+        // Nothing was changed by the failed moves
+        assert_eq!(
+            mount.cat(&PathBuf::from("/a.txt"), &blobs).await.unwrap(),
+            b"a"
+        );
+        assert_eq!(
+            mount
+                .cat(&PathBuf::from("/dir/b.txt"), &blobs)
+                .await
+                .unwrap(),
+            b"b"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cat_reader() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+
+        // Large enough to span several encrypted segments
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        mount
+            .add(
+                &PathBuf::from("/video.mp4"),
+                Cursor::new(data.clone()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+
+        let mut reader = mount
+            .cat_reader(&PathBuf::from("/video.mp4"), None, &blobs)
+            .await
+            .unwrap();
+        assert_eq!(reader.size(), data.len() as u64);
+        assert_eq!(reader.range(), 0..data.len() as u64);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, data);
+
+        let mut reader = mount
+            .cat_reader(&PathBuf::from("/video.mp4"), Some(100_000..200_123), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(reader.len(), 100_123);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, &data[100_000..200_123]);
+
+        // Ranges past the end are clamped
+        let reader = mount
+            .cat_reader(&PathBuf::from("/video.mp4"), Some(299_990..400_000), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(reader.range(), 299_990..300_000);
+
+        let result = mount.cat_reader(&PathBuf::from("/"), None, &blobs).await;
+        assert!(result.is_err());
+        let result = mount
+            .cat_reader(&PathBuf::from("/missing.mp4"), None, &blobs)
+            .await;
+        assert!(matches!(result, Err(MountError::PathNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_add_and_dedup() {
+        use std::collections::HashSet;
+        use tokio::io::AsyncReadExt;
+
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/disk.img");
+
+        // Pseudo-random content, so the chunker finds content-defined boundaries
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data: Vec<u8> = (0..6 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        mount
+            .add(&path, Cursor::new(data.clone()), &blobs)
+            .await
+            .unwrap();
+
+        let link = mount.get(&path, &blobs).await.unwrap().link().clone();
+        assert_eq!(*link.format(), iroh_blobs::BlobFormat::HashSeq);
+        let seq = blobs.read_hash_list(*link.hash()).await.unwrap();
+        assert!(seq.len() > 2);
+        let pins = mount.inner().pins().clone();
+        assert!(pins.contains(link.hash()));
+        assert!(seq.iter().all(|hash| pins.contains(hash)));
+
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), data);
+
+        // A range spanning chunk boundaries
+        let range = 1_000_000..4_500_000;
+        let mut reader = mount
+            .cat_reader(&path, Some(range.clone()), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(reader.size(), data.len() as u64);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, &data[1_000_000..4_500_000]);
+
+        // Adding the same data again leaves the file as it is
+        mount
+            .add(&path, Cursor::new(data.clone()), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(*mount.get(&path, &blobs).await.unwrap().link(), link);
+
+        // Editing one byte only replaces the chunks around it
+        let mut edited = data.clone();
+        edited[3 * 1024 * 1024] ^= 0xff;
+        mount
+            .add(&path, Cursor::new(edited.clone()), &blobs)
+            .await
+            .unwrap();
+
+        let new_link = mount.get(&path, &blobs).await.unwrap().link().clone();
+        assert_ne!(new_link, link);
+        let new_seq = blobs.read_hash_list(*new_link.hash()).await.unwrap();
+        // Skip the chunk index at the front of each sequence
+        let old_chunks: HashSet<_> = seq[1..].iter().collect();
+        let changed = new_seq[1..]
+            .iter()
+            .filter(|hash| !old_chunks.contains(hash))
+            .count();
+        assert!((1..=2).contains(&changed), "{} chunks changed", changed);
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), edited);
+
+        // Identical chunks within a file are stored once
+        let mut repeated = data.clone();
+        repeated.extend_from_within(..);
+        let repeated_path = PathBuf::from("/repeated.img");
+        mount
+            .add(&repeated_path, Cursor::new(repeated.clone()), &blobs)
+            .await
+            .unwrap();
+        let repeated_link = mount
+            .get(&repeated_path, &blobs)
+            .await
+            .unwrap()
+            .link()
+            .clone();
+        let repeated_seq = blobs.read_hash_list(*repeated_link.hash()).await.unwrap();
+        // Skip the chunk index at the front of the sequence
+        let unique: HashSet<_> = repeated_seq[1..].iter().collect();
+        assert!(unique.len() < repeated_seq.len() - 1);
+        assert_eq!(mount.cat(&repeated_path, &blobs).await.unwrap(), repeated);
+
+        // Small files are still stored as a single blob
+        mount
+            .add(
+                &PathBuf::from("/small.txt"),
+                Cursor::new(b"small".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let small = mount
+            .get(&PathBuf::from("/small.txt"), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(*small.link().format(), iroh_blobs::BlobFormat::Raw);
+    }
+
+    #[tokio::test]
+    async fn test_changed_file_keeps_secret() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/notes.txt");
+
+        mount
+            .add(&path, Cursor::new(b"first draft".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let first = mount.get(&path, &blobs).await.unwrap();
+
+        // The same content keeps the file's link
+        mount
+            .add(&path, Cursor::new(b"first draft".to_vec()), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(mount.get(&path, &blobs).await.unwrap(), first);
+
+        // Changed content is encrypted under the same secret
+        mount
+            .add(&path, Cursor::new(b"second draft".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let second = mount.get(&path, &blobs).await.unwrap();
+        assert_ne!(second.link(), first.link());
+        assert_eq!(second.secret(), first.secret());
+        assert_eq!(
+            first
+                .secret()
+                .decrypt(&blobs.get(second.link().hash()).await.unwrap())
+                .unwrap(),
+            b"second draft".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stage_parts() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/upload/video.bin");
+
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let data: Vec<u8> = (0..5 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        // Parts may be stored in any order, they're joined in the order given
+        let secret = Secret::generate();
+        let (second, _) = Mount::put_part(
+            Cursor::new(data[3_000_000..].to_vec()),
+            &secret,
+            "upload",
+            &blobs,
+        )
+        .await
+        .unwrap();
+        let (first, first_hash) = Mount::put_part(
+            Cursor::new(data[..3_000_000].to_vec()),
+            &secret,
+            "upload",
+            &blobs,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            first_hash,
+            Hash::from_bytes(*blake3::hash(&data[..3_000_000]).as_bytes())
+        );
+
+        // Held parts survive garbage collection until they're released
+        blobs
+            .collect_garbage(&HashSet::new(), std::time::Duration::ZERO, false)
+            .await
+            .unwrap();
+        for chunk in first.iter().chain(&second) {
+            assert!(blobs.stat(&chunk.hash).await.unwrap());
+        }
+
+        let chunks = first.into_iter().chain(second).collect();
+        let link = mount
+            .stage_parts(&path, secret, chunks, &blobs)
+            .await
+            .unwrap();
+        let stored = link.data().unwrap();
+        assert_eq!(stored.size(), Some(data.len() as u64));
+        assert_eq!(
+            stored.plaintext_hash(),
+            Some(Hash::from_bytes(*blake3::hash(&data).as_bytes()))
+        );
+        assert!(mount.inner().pins().contains(link.link().hash()));
+
+        mount
+            .add_staged(BTreeMap::from([(path.clone(), link)]), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), data);
+
+        // A part too small to be chunked is stored as a single blob
+        let secret = Secret::generate();
+        let (chunks, _) = Mount::put_part(Cursor::new(b"tiny".to_vec()), &secret, "tiny", &blobs)
+            .await
+            .unwrap();
+        let link = mount
+            .stage_parts(&PathBuf::from("/tiny.txt"), secret, chunks, &blobs)
+            .await
+            .unwrap();
+        assert_eq!(*link.link().format(), iroh_blobs::BlobFormat::Raw);
+    }
+
+    #[tokio::test]
+    async fn test_save_load() {
+        let (mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let link = mount.save(&blobs).await.unwrap();
+        let mount = Mount::load(&link, &secret_key, &blobs).await.unwrap();
+
+        // Every version is signed by the peer that saved it
+        let manifest = mount.inner().manifest().clone();
+        assert_eq!(manifest.verify().unwrap(), &secret_key.public());
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        use futures::TryStreamExt;
+
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/notes.txt");
+        let first = mount.link();
+
+        mount
+            .add(&path, Cursor::new(b"yesterday".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let second = mount.save(&blobs).await.unwrap();
+
+        mount
+            .add(&path, Cursor::new(b"today".to_vec()), &blobs)
+            .await
+            .unwrap();
+        mount.set_message(Some("Update notes".to_string()));
+        let third = mount.save(&blobs).await.unwrap();
+        assert_eq!(mount.link(), third);
+
+        let history: Vec<HistoryEntry> = mount.history(&blobs).try_collect().await.unwrap();
+        let links: Vec<Link> = history.iter().map(|entry| entry.link.clone()).collect();
+        assert_eq!(links, vec![third.clone(), second.clone(), first.clone()]);
+        assert!(history.iter().all(|entry| entry.timestamp().is_some()));
+        assert!(history
+            .iter()
+            .all(|entry| entry.author() == Some(&secret_key.public())));
+        // Messages only apply to the save they were set for
+        assert_eq!(history[0].message(), Some("Update notes"));
+        assert_eq!(history[1].message(), None);
+        assert_eq!(history[0].manifest.previous(), &Some(second.clone()));
+        assert_eq!(history[2].manifest.previous(), &None);
+
+        // Older versions can be loaded and read as they were
+        let old = Mount::load(&second, &secret_key, &blobs).await.unwrap();
+        assert_eq!(old.cat(&path, &blobs).await.unwrap(), b"yesterday");
+        let oldest = Mount::load(&first, &secret_key, &blobs).await.unwrap();
+        assert!(matches!(
+            oldest.cat(&path, &blobs).await,
+            Err(MountError::PathNotFound(_))
+        ));
+        let latest = Mount::load(&third, &secret_key, &blobs).await.unwrap();
+        assert_eq!(latest.cat(&path, &blobs).await.unwrap(), b"today");
+
+        // History of a historical mount starts at that version
+        let older: Vec<HistoryEntry> = old.history(&blobs).try_collect().await.unwrap();
+        assert_eq!(older.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        for (path, data) in [
+            ("/keep.txt", "same"),
+            ("/edit.txt", "before"),
+            ("/gone.txt", "bye"),
+            ("/old-name.txt", "renamed"),
+            ("/docs/a.txt", "a"),
+            ("/docs/b.txt", "b"),
+            ("/src/lib.rs", "lib"),
+        ] {
+            mount
+                .add(
+                    &PathBuf::from(path),
+                    Cursor::new(data.as_bytes().to_vec()),
+                    &blobs,
+                )
+                .await
+                .unwrap();
+        }
+        let from = mount.save(&blobs).await.unwrap();
+
+        mount
+            .add(
+                &PathBuf::from("/edit.txt"),
+                Cursor::new(b"after".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        mount
+            .add(
+                &PathBuf::from("/src/main.rs"),
+                Cursor::new(b"main".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        mount.rm(&PathBuf::from("/gone.txt"), &blobs).await.unwrap();
+        mount
+            .mv(
+                &PathBuf::from("/old-name.txt"),
+                &PathBuf::from("/new-name.txt"),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        mount
+            .mv(
+                &PathBuf::from("/docs"),
+                &PathBuf::from("/archive/docs"),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let to = mount.save(&blobs).await.unwrap();
+
+        let diff = Mount::diff(&from, &to, &secret_key, &blobs).await.unwrap();
+        assert_eq!(diff.added, vec![PathBuf::from("src/main.rs")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("gone.txt")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("edit.txt")]);
+        assert_eq!(
+            diff.moved,
+            vec![
+                Moved {
+                    from: PathBuf::from("docs"),
+                    to: PathBuf::from("archive/docs"),
+                },
+                Moved {
+                    from: PathBuf::from("old-name.txt"),
+                    to: PathBuf::from("new-name.txt"),
+                },
+            ]
+        );
+
+        // Going the other way undoes every change
+        let back = Mount::diff(&to, &from, &secret_key, &blobs).await.unwrap();
+        assert_eq!(back.added, diff.removed);
+        assert_eq!(back.removed, diff.added);
+        assert_eq!(back.modified, diff.modified);
+        assert_eq!(back.moved.len(), 2);
+
+        assert!(Mount::diff(&to, &to, &secret_key, &blobs)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revert() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        for (path, data) in [
+            ("/docs/a.txt", "a"),
+            ("/docs/b.txt", "b"),
+            ("/notes.txt", "v1"),
+        ] {
+            mount
+                .add(
+                    &PathBuf::from(path),
+                    Cursor::new(data.as_bytes().to_vec()),
+                    &blobs,
+                )
+                .await
+                .unwrap();
+        }
+        let good = mount.save(&blobs).await.unwrap();
+
+        mount.rm(&PathBuf::from("/docs"), &blobs).await.unwrap();
+        mount
+            .add(
+                &PathBuf::from("/notes.txt"),
+                Cursor::new(b"v2".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        mount
+            .add(
+                &PathBuf::from("/new.txt"),
+                Cursor::new(b"new".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let bad = mount.save(&blobs).await.unwrap();
+
+        // Restoring one path leaves the rest of the bucket alone
+        mount
+            .revert_path(&good, &PathBuf::from("/docs"), &blobs)
+            .await
+            .unwrap();
+        assert_eq!(
+            mount
+                .cat(&PathBuf::from("/docs/a.txt"), &blobs)
+                .await
+                .unwrap(),
+            b"a"
+        );
+        assert_eq!(
+            mount
+                .cat(&PathBuf::from("/notes.txt"), &blobs)
+                .await
+                .unwrap(),
+            b"v2"
+        );
+
+        // A path that didn't exist back then is removed
+        mount
+            .revert_path(&good, &PathBuf::from("/new.txt"), &blobs)
+            .await
+            .unwrap();
+        assert!(mount.get(&PathBuf::from("/new.txt"), &blobs).await.is_err());
+
+        // Reverting everything is recorded on top of the current version
+        mount.revert_to(&good, &blobs).await.unwrap();
+        let reverted = mount.save(&blobs).await.unwrap();
+        assert_ne!(reverted, good);
+        assert_eq!(mount.inner().manifest().previous(), &Some(bad.clone()));
+        assert!(Mount::diff(&good, &reverted, &secret_key, &blobs)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let reloaded = Mount::load(&reverted, &secret_key, &blobs).await.unwrap();
+        assert_eq!(
+            reloaded
+                .cat(&PathBuf::from("/notes.txt"), &blobs)
+                .await
+                .unwrap(),
+            b"v1"
+        );
+
+        // Versions of other buckets are refused
+        let other = Mount::init(Uuid::new_v4(), "other".to_string(), &secret_key, &blobs)
+            .await
+            .unwrap();
+        assert!(mount.revert_to(&other.link(), &blobs).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        for (path, data) in [
+            ("/shared.txt", "base"),
+            ("/docs/a.txt", "a"),
+            ("/edited-and-removed.txt", "base"),
+        ] {
+            mount
+                .add(
+                    &PathBuf::from(path),
+                    Cursor::new(data.as_bytes().to_vec()),
+                    &blobs,
+                )
+                .await
+                .unwrap();
+        }
+        let base = mount.save(&blobs).await.unwrap();
+
+        // Two peers commit on top of the same version
+        let ours = Mount::load(&base, &secret_key, &blobs).await.unwrap();
+        let mut theirs = Mount::load(&base, &secret_key, &blobs).await.unwrap();
+        for (mut mount, path, data) in [
+            (ours.clone(), "/shared.txt", "ours"),
+            (ours.clone(), "/docs/ours.txt", "ours"),
+            (ours.clone(), "/edited-and-removed.txt", "edited"),
+            (theirs.clone(), "/shared.txt", "theirs"),
+            (theirs.clone(), "/docs/theirs.txt", "theirs"),
+            (theirs.clone(), "/theirs.txt", "theirs"),
+        ] {
+            mount
+                .add(
+                    &PathBuf::from(path),
+                    Cursor::new(data.as_bytes().to_vec()),
+                    &blobs,
+                )
+                .await
+                .unwrap();
+        }
+        theirs
+            .rm(&PathBuf::from("/edited-and-removed.txt"), &blobs)
+            .await
+            .unwrap();
+        let our_head = ours.save(&blobs).await.unwrap();
+        let their_head = theirs.save(&blobs).await.unwrap();
+
+        let mut merged = ours.clone();
+        assert!(merged
+            .merge(&base, &their_head, "a", "b", &blobs)
+            .await
+            .unwrap());
+        let merge_link = merged.save(&blobs).await.unwrap();
+        let manifest = merged.inner().manifest().clone();
+        assert_eq!(manifest.previous(), &Some(our_head.clone()));
+        assert_eq!(manifest.merged(), &Some(their_head.clone()));
+
+        let cat = |mount: Mount, path: &'static str| {
+            let blobs = blobs.clone();
+            async move {
+                String::from_utf8(mount.cat(&PathBuf::from(path), &blobs).await.unwrap()).unwrap()
+            }
+        };
+        assert_eq!(cat(merged.clone(), "/docs/ours.txt").await, "ours");
+        assert_eq!(cat(merged.clone(), "/docs/theirs.txt").await, "theirs");
+        assert_eq!(cat(merged.clone(), "/theirs.txt").await, "theirs");
+        assert_eq!(
+            cat(merged.clone(), "/edited-and-removed.txt").await,
+            "edited"
+        );
+        assert_eq!(cat(merged.clone(), "/shared.txt").await, "ours");
+        assert_eq!(
+            cat(merged.clone(), "/shared.txt (conflict from b)").await,
+            "theirs"
+        );
+
+        // Merging the other way around gives the same tree
+        let mut other = theirs.clone();
+        assert!(other
+            .merge(&base, &our_head, "b", "a", &blobs)
+            .await
+            .unwrap());
+        let other_link = other.save(&blobs).await.unwrap();
+        assert!(Mount::diff(&merge_link, &other_link, &secret_key, &blobs)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // ...so merging those two merges has nothing left to do
+        assert!(!merged
+            .merge(&our_head, &other_link, "a", "b", &blobs)
+            .await
+            .unwrap());
+
+        // The merge marker only applies to the save that follows the merge
+        merged.save(&blobs).await.unwrap();
+        assert_eq!(merged.inner().manifest().merged(), &None);
+    }
+
+    #[tokio::test]
+    async fn test_roles() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+        let reader_key = SecretKey::generate();
+        let writer_key = SecretKey::generate();
+        mount
+            .share(reader_key.public(), PrincipalRole::Reader)
+            .await
+            .unwrap();
+        mount
+            .share(writer_key.public(), PrincipalRole::Writer)
+            .await
+            .unwrap();
+        let base = mount.save(&blobs).await.unwrap();
+        assert_eq!(mount.role(), Some(PrincipalRole::Owner));
+
+        // Readers can decrypt the bucket, but not change it
+        let mut reader = Mount::load(&base, &reader_key, &blobs).await.unwrap();
+        assert_eq!(reader.role(), Some(PrincipalRole::Reader));
+        assert!(reader.ls(&PathBuf::from("/"), &blobs).await.is_ok());
+        reader
+            .add(
+                &PathBuf::from("/reader.txt"),
+                Cursor::new(b"reader".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            reader.save(&blobs).await,
+            Err(MountError::ReadOnly)
+        ));
+
+        // Writers can commit, but not change the shares
+        let mut writer = Mount::load(&base, &writer_key, &blobs).await.unwrap();
+        assert!(matches!(
+            writer
+                .share(SecretKey::generate().public(), PrincipalRole::Owner)
+                .await,
+            Err(MountError::NotOwner)
+        ));
+        writer
+            .add(
+                &PathBuf::from("/writer.txt"),
+                Cursor::new(b"writer".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let writer_head = writer.save(&blobs).await.unwrap();
+        assert_eq!(
+            writer.inner().manifest().roles(),
+            mount.inner().manifest().roles()
+        );
+
+        // Meanwhile the owner demotes the writer, which a merge on either side keeps
+        mount
+            .share(writer_key.public(), PrincipalRole::Reader)
+            .await
+            .unwrap();
+        let owner_head = mount.save(&blobs).await.unwrap();
+        assert!(writer
+            .merge(&base, &owner_head, "w", "o", &blobs)
+            .await
+            .unwrap());
+        assert_eq!(writer.role(), Some(PrincipalRole::Reader));
+        assert!(mount
+            .merge(&base, &writer_head, "o", "w", &blobs)
+            .await
+            .unwrap());
+        assert_eq!(
+            mount.inner().manifest().role(&writer_key.public()),
+            Some(PrincipalRole::Reader)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let (mut mount, blobs, _, _temp) = setup_test_env().await;
+        let peer_key = SecretKey::generate();
+        for (path, data) in [
+            ("/a.txt", "a"),
+            ("/docs/b.txt", "b"),
+            ("/docs/deep/c.txt", "c"),
+        ] {
+            mount
+                .add(
+                    &PathBuf::from(path),
+                    Cursor::new(data.as_bytes().to_vec()),
+                    &blobs,
+                )
+                .await
+                .unwrap();
+        }
+        mount
+            .share(peer_key.public(), PrincipalRole::Writer)
+            .await
+            .unwrap();
+        let shared = mount.save(&blobs).await.unwrap();
+        let before = mount.ls_deep(&PathBuf::from("/"), &blobs).await.unwrap();
+
+        // Without rekeying only the share goes away
+        let mut plain = Mount::load(&shared, &mount.2, &blobs).await.unwrap();
+        plain
+            .revoke(&peer_key.public(), false, &blobs, |_| {})
+            .await
+            .unwrap();
+        let plain_link = plain.save(&blobs).await.unwrap();
+        assert!(matches!(
+            Mount::load(&plain_link, &peer_key, &blobs).await,
+            Err(MountError::ShareNotFound)
+        ));
+        assert_eq!(
+            plain.ls_deep(&PathBuf::from("/"), &blobs).await.unwrap(),
+            before
+        );
+
+        // Rekeying replaces every secret, but not the contents
+        let mut updates = Vec::new();
+        mount
+            .revoke(&peer_key.public(), true, &blobs, |progress| {
+                updates.push(progress.clone())
+            })
+            .await
+            .unwrap();
+        let last = updates.last().unwrap();
+        assert_eq!((last.dirs, last.files, last.bytes), (2, 3, 3));
+        let rekeyed = mount.save(&blobs).await.unwrap();
+        assert!(Mount::load(&rekeyed, &peer_key, &blobs).await.is_err());
+
+        let after = mount.ls_deep(&PathBuf::from("/"), &blobs).await.unwrap();
+        assert_eq!(
+            after.keys().collect::<Vec<_>>(),
+            before.keys().collect::<Vec<_>>()
+        );
+        for (path, link) in &after {
+            assert_ne!(link.secret(), before[path].secret());
+        }
+        for (path, data) in [
+            ("/a.txt", "a"),
+            ("/docs/b.txt", "b"),
+            ("/docs/deep/c.txt", "c"),
+        ] {
+            let content = mount.cat(&PathBuf::from(path), &blobs).await.unwrap();
+            assert_eq!(content, data.as_bytes());
+        }
+
+        // Revoking twice, or revoking ourselves, fails
+        assert!(matches!(
+            mount
+                .revoke(&peer_key.public(), false, &blobs, |_| {})
+                .await,
+            Err(MountError::NotPrincipal(_))
+        ));
+        let owner = mount.2.public();
+        assert!(mount.revoke(&owner, false, &blobs, |_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        use chacha20poly1305::aead::{Aead, KeyInit};
+        use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        assert_eq!(mount.format(), MANIFEST_FORMAT);
+        assert!(!mount.migrate(&blobs, |_| {}).await.unwrap());
+
+        // A file stored in the single-shot layout, as before segmented
+        //  encryption, in a bucket of the format before it was required
+        let secret = Secret::generate();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(secret.bytes()));
+        let nonce = [7u8; 12];
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), b"old data".as_slice())
+            .unwrap();
+        let hash = blobs
+            .put([nonce.as_slice(), ciphertext.as_slice()].concat())
+            .await
+            .unwrap();
+        let legacy = Link::new(
+            crate::linked_data::LD_RAW_CODEC,
+            hash,
+            iroh_blobs::BlobFormat::Raw,
+        );
+        let path = PathBuf::from("/docs/old.txt");
+        mount
+            ._set_entry_link_at_path(NodeLink::new_data(legacy.clone(), secret), &path, &blobs)
+            .await
+            .unwrap();
+        mount.0.lock().manifest.set_format(1);
+        let old = mount.save(&blobs).await.unwrap();
+
+        let mut mount = Mount::load(&old, &secret_key, &blobs).await.unwrap();
+        assert_eq!(mount.format(), 1);
+        let mut updates = Vec::new();
+        assert!(mount
+            .migrate(&blobs, |progress| updates.push(progress.clone()))
+            .await
+            .unwrap());
+        let last = updates.last().unwrap();
+        assert_eq!((last.dirs, last.files, last.bytes), (1, 1, 8));
+        let migrated = mount.save(&blobs).await.unwrap();
+
+        let mut mount = Mount::load(&migrated, &secret_key, &blobs).await.unwrap();
+        assert_eq!(mount.format(), MANIFEST_FORMAT);
+        let node_link = mount.get(&path, &blobs).await.unwrap();
+        assert_ne!(node_link.link(), &legacy);
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), b"old data");
+
+        // Reverting to the old version brings its format back with it
+        mount.revert_path(&old, &path, &blobs).await.unwrap();
+        assert_eq!(mount.format(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_prune_pins() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/notes.txt");
+
+        let mut files = Vec::new();
+        for text in ["first", "second", "third"] {
+            mount
+                .add(&path, Cursor::new(text.as_bytes().to_vec()), &blobs)
+                .await
+                .unwrap();
+            mount.save(&blobs).await.unwrap();
+            files.push(*mount.get(&path, &blobs).await.unwrap().link().hash());
+        }
+        assert!(files.iter().all(|hash| mount.inner().pins().contains(hash)));
+
+        // Only the latest version's file is needed with no history kept
+        let link = mount.link();
+        let retained = Mount::retained(&link, 0, &secret_key, &blobs)
+            .await
+            .unwrap();
+        assert!(retained.contains(link.hash()));
+        assert!(retained.contains(&files[2]));
+        assert!(!retained.contains(&files[1]));
+        let retained = Mount::retained(&link, 1, &secret_key, &blobs)
+            .await
+            .unwrap();
+        assert!(retained.contains(&files[1]));
+        assert!(!retained.contains(&files[0]));
+
+        assert!(mount.prune_pins(1, &blobs).await.unwrap());
+        assert!(mount.inner().pins().contains(&files[1]));
+        assert!(!mount.inner().pins().contains(&files[0]));
+        mount.save(&blobs).await.unwrap();
+
+        // The version the prune saved doesn't need pruning again straight away
+        let mut mount = Mount::load(&mount.link(), &secret_key, &blobs)
+            .await
+            .unwrap();
+        assert!(!mount.inner().pins().contains(&files[0]));
+        assert!(!mount.prune_pins(1, &blobs).await.unwrap());
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), b"third");
+    }
+
+    #[tokio::test]
+    async fn test_squash() {
+        use futures::TryStreamExt;
+
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/notes.txt");
+
+        let mut files = Vec::new();
+        let mut links = Vec::new();
+        for text in ["first", "second", "third"] {
+            mount
+                .add(&path, Cursor::new(text.as_bytes().to_vec()), &blobs)
+                .await
+                .unwrap();
+            mount.set_message(Some(format!("Write {}", text)));
+            links.push(mount.save(&blobs).await.unwrap());
+            files.push(*mount.get(&path, &blobs).await.unwrap().link().hash());
+        }
+        let second = Mount::load(&links[1], &secret_key, &blobs).await.unwrap();
+        let second = second.inner().manifest().clone();
+
+        mount.set_message(Some("Keep one version".to_string()));
+        let squashed = mount.squash(1, &blobs).await.unwrap().unwrap();
+        assert_eq!(mount.link(), squashed);
+
+        // The new version sits on a rewrite of the version it kept, and
+        //  merges in the version it replaced
+        let history: Vec<HistoryEntry> = mount.history(&blobs).try_collect().await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].message(), Some("Keep one version"));
+        assert_eq!(history[0].manifest.merged(), &Some(links[2].clone()));
+        let kept = &history[1].manifest;
+        assert_ne!(history[1].link, links[1]);
+        assert_eq!(kept.previous(), &None);
+        assert_eq!(kept.entry(), second.entry());
+        assert_eq!(kept.timestamp(), second.timestamp());
+        assert_eq!(kept.message(), Some("Write second"));
+        let old = Mount::load(&history[1].link, &secret_key, &blobs)
+            .await
+            .unwrap();
+        assert_eq!(old.cat(&path, &blobs).await.unwrap(), b"second");
+
+        // Only the dropped version's file is unpinned
+        let inner = mount.inner();
+        assert!(!inner.pins().contains(&files[0]));
+        assert!(inner.pins().contains(&files[1]));
+        assert!(inner.pins().contains(&files[2]));
+        assert_eq!(mount.cat(&path, &blobs).await.unwrap(), b"third");
+
+        // Nothing is left to squash or prune
+        assert!(!mount.prune_pins(1, &blobs).await.unwrap());
+        assert_eq!(mount.squash(1, &blobs).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_fsck() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let file = PathBuf::from("/docs/a.txt");
+        mount
+            .add(&file, Cursor::new(b"alpha".to_vec()), &blobs)
+            .await
+            .unwrap();
+        mount
+            .add(
+                &PathBuf::from("/b.txt"),
+                Cursor::new(b"beta".to_vec()),
+                &blobs,
+            )
+            .await
+            .unwrap();
+        let link = mount.save(&blobs).await.unwrap();
+
+        let report = Mount::fsck(&link, &secret_key, &blobs).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.dirs, 2);
+        assert_eq!(report.files, 2);
+
+        // A missing file is reported at its path
+        let hash = *mount.get(&file, &blobs).await.unwrap().link().hash();
+        blobs.evict(&HashSet::from([hash])).await.unwrap();
+        let report = Mount::fsck(&link, &secret_key, &blobs).await.unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].hash, hash);
+        assert_eq!(report.problems[0].blob, FsckBlob::File);
+        assert_eq!(report.problems[0].path, Some(file.clone()));
+        assert_eq!(report.problems[0].issue, FsckIssue::Missing);
+        assert!(report.problems[0].issue.is_refetchable());
+
+        // As is a directory that doesn't decrypt, without stopping the walk
+        let garbage = blobs.put(b"not a directory".to_vec()).await.unwrap();
+        mount.0.lock().entry.insert(
+            "bad".to_string(),
+            NodeLink::new_dir(
+                Link::new(
+                    crate::linked_data::LD_RAW_CODEC,
+                    garbage,
+                    iroh_blobs::BlobFormat::Raw,
+                ),
+                Secret::generate(),
+            ),
+        );
+        let link = mount.save(&blobs).await.unwrap();
+        let report = Mount::fsck(&link, &secret_key, &blobs).await.unwrap();
+        assert_eq!(report.problems.len(), 2);
+        let bad = report
+            .problems
+            .iter()
+            .find(|problem| problem.hash == garbage)
+            .unwrap();
+        assert_eq!(bad.path, Some(PathBuf::from("/bad")));
+        assert!(matches!(bad.issue, FsckIssue::Undecryptable(_)));
+        assert!(!bad.issue.is_refetchable());
+    }
+
+    #[tokio::test]
+    async fn test_car_roundtrip() {
+        let (mut mount, blobs, secret_key, _temp) = setup_test_env().await;
+        let path = PathBuf::from("/docs/notes.txt");
+        mount
+            .add(&path, Cursor::new(b"first".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let first = mount.save(&blobs).await.unwrap();
+        mount
+            .add(&path, Cursor::new(b"second".to_vec()), &blobs)
+            .await
+            .unwrap();
+        let link = mount.save(&blobs).await.unwrap();
+
+        let blocks = Mount::car_blocks(&link, &secret_key, &blobs).await.unwrap();
+        assert_eq!(blocks[0], link);
+        let archive = Mount::export_car(&link, &blocks, &blobs, Vec::new())
+            .await
+            .unwrap();
+
+        // Importing into another store brings the whole pinned history
+        let temp = TempDir::new().unwrap();
+        let other = BlobsStore::load(temp.path()).await.unwrap();
+        let imported = Mount::import_car(archive.as_slice(), &secret_key, &other)
+            .await
+            .unwrap();
+        assert_eq!(imported.link(), link);
+        assert_eq!(imported.cat(&path, &other).await.unwrap(), b"second");
+        let previous = Mount::load(&first, &secret_key, &other).await.unwrap();
+        assert_eq!(previous.cat(&path, &other).await.unwrap(), b"first");
+
+        // The archive is only readable by the bucket's principals
+        let temp = TempDir::new().unwrap();
+        let other = BlobsStore::load(temp.path()).await.unwrap();
+        let result = Mount::import_car(archive.as_slice(), &SecretKey::generate(), &other).await;
+        assert!(matches!(result, Err(MountError::ShareNotFound)));
+
+        // Blobs the version needs can't be left out
+        let hash = *mount.get(&path, &blobs).await.unwrap().link().hash();
+        blobs.evict(&HashSet::from([hash])).await.unwrap();
+        let result = Mount::car_blocks(&link, &secret_key, &blobs).await;
+        assert!(matches!(result, Err(MountError::BlobNotFound(missing)) if missing == hash));
     }
 }
@@ -7,7 +7,7 @@ use mime::Mime;
 use serde::{Deserialize, Serialize};
 
 use crate::crypto::Secret;
-use crate::linked_data::{BlockEncoded, DagCborCodec, Link, LinkedData};
+use crate::linked_data::{BlockEncoded, DagCborCodec, Hash, Link, LinkedData};
 
 use super::maybe_mime::MaybeMime;
 
@@ -38,6 +38,11 @@ pub struct Data {
     metadata: Option<BTreeMap<String, LinkedData>>,
 }
 
+// Metadata keys describing the file a Data Link was added from
+const SIZE_KEY: &str = "size";
+const MTIME_KEY: &str = "mtime";
+const HASH_KEY: &str = "blake3";
+
 impl Default for Data {
     fn default() -> Self {
         Self::new()
@@ -167,6 +172,52 @@ impl Data {
     pub fn metadata(&self) -> Option<&BTreeMap<String, LinkedData>> {
         self.metadata.as_ref()
     }
+
+    fn metadata_int(&self, key: &str) -> Option<u64> {
+        match self.metadata.as_ref()?.get(key)? {
+            LinkedData::Integer(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Size of the plaintext in bytes, missing on files added before it
+    ///  was recorded
+    pub fn size(&self) -> Option<u64> {
+        self.metadata_int(SIZE_KEY)
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        self.set_metadata(SIZE_KEY.to_string(), LinkedData::Integer(size.into()));
+    }
+
+    /// Modification time of the file the data was added from, as a unix
+    ///  timestamp in milliseconds, if the uploader gave one
+    pub fn mtime(&self) -> Option<u64> {
+        self.metadata_int(MTIME_KEY)
+    }
+
+    pub fn set_mtime(&mut self, mtime: u64) {
+        self.set_metadata(MTIME_KEY.to_string(), LinkedData::Integer(mtime.into()));
+    }
+
+    /// BLAKE3 hash of the plaintext, missing on files added before it
+    ///  was recorded
+    pub fn plaintext_hash(&self) -> Option<Hash> {
+        match self.metadata.as_ref()?.get(HASH_KEY)? {
+            LinkedData::Bytes(bytes) => {
+                let bytes: [u8; 32] = bytes.as_slice().try_into().ok()?;
+                Some(Hash::from_bytes(bytes))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_plaintext_hash(&mut self, hash: Hash) {
+        self.set_metadata(
+            HASH_KEY.to_string(),
+            LinkedData::Bytes(hash.as_bytes().to_vec()),
+        );
+    }
 }
 
 // Lastly, we have a node, which is either a data link,
@@ -216,6 +267,14 @@ impl NodeLink {
         }
     }
 
+    /// Get mutable data info if this is a Data link
+    pub fn data_mut(&mut self) -> Option<&mut Data> {
+        match self {
+            NodeLink::Data(_, _, data) => Some(data),
+            NodeLink::Dir(_, _) => None,
+        }
+    }
+
     /// Check if this is a directory link
     pub fn is_dir(&self) -> bool {
         matches!(self, NodeLink::Dir(_, _))
@@ -353,4 +412,33 @@ mod test {
         assert!(!node_link.is_data());
         assert!(node_link.data().is_none());
     }
+
+    #[test]
+    fn test_data_file_metadata() {
+        let mut data = Data::from_path(Path::new("/photos/beach.jpg"));
+        assert_eq!(data.size(), None);
+        assert_eq!(data.mtime(), None);
+        assert_eq!(data.plaintext_hash(), None);
+
+        let hash = Hash::new(b"beach");
+        data.set_size(5);
+        data.set_mtime(1_700_000_000_000);
+        data.set_plaintext_hash(hash);
+        assert_eq!(data.size(), Some(5));
+        assert_eq!(data.mtime(), Some(1_700_000_000_000));
+        assert_eq!(data.plaintext_hash(), Some(hash));
+        assert_eq!(data.mime().map(|m| m.as_ref()), Some("image/jpeg"));
+
+        // The metadata survives encoding along with the rest of the link
+        let mut node = Node::new();
+        node.insert(
+            "beach.jpg".to_string(),
+            NodeLink::Data(Link::default(), Secret::default(), data.clone()),
+        );
+        let decoded = Node::decode(&node.encode().unwrap()).unwrap();
+        assert_eq!(
+            decoded.get_link("beach.jpg").and_then(|link| link.data()),
+            Some(&data)
+        );
+    }
 }
@@ -32,6 +32,11 @@ impl Pins {
         self.0.insert(hash)
     }
 
+    /// Remove a single hash from the pin set
+    pub fn remove(&mut self, hash: &Hash) -> bool {
+        self.0.remove(hash)
+    }
+
     /// Extend the pin set with an iterator of hashes
     pub fn extend<I>(&mut self, hashes: I)
     where
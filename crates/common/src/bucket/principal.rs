@@ -1,6 +1,9 @@
 #![allow(clippy::doc_lazy_continuation)]
 #![allow(clippy::doc_overindented_list_items)]
 
+use std::fmt;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
 use crate::crypto::PublicKey;
@@ -18,24 +21,70 @@ use crate::crypto::PublicKey;
  *  have permissions on the bucket.
  * Principals:
  *  - descrive a public key
- *  - which has a role on the bucket (for now we just support 'owner')
+ *  - which has a role on the bucket:
+ *    - readers may decrypt the bucket
+ *    - writers may also commit changes to it
+ *    - owners may also change who the bucket is shared with
  *  - and have a share into the bucket's encryption key
  * To be clear:
- *  - there is no cryptographic validation of the role *other* than
- *     whether or not clients are willing to accept updates given
- *     the prior state of a bucket. It is the responsibility of clients
- *     to check that bucket updates respect principal roles at a given
- *     update
+ *  - every manifest is signed by its author, and peers only accept
+ *     updates signed by a writer or owner of the version they build
+ *     on, and changes to the shares signed by an owner. Beyond that,
+ *     there is no cryptographic validation of the role. It is the
+ *     responsibility of clients to check that bucket updates respect
+ *     principal roles at a given update
  *  - shares may be assumed to point to the entry of a bucket for
  *     each principal. It is the responsibility of the updater to
  *     share to all principals st they may read the bucket
  */
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+// NOTE: variants are ordered from least to most access,
+//  so comparing roles compares what they allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PrincipalRole {
+    Reader,
+    Writer,
     Owner,
 }
 
+impl PrincipalRole {
+    /// Whether versions authored by this role are accepted
+    pub fn can_write(&self) -> bool {
+        *self >= PrincipalRole::Writer
+    }
+
+    /// Whether this role may add, remove or change principals
+    pub fn can_share(&self) -> bool {
+        *self == PrincipalRole::Owner
+    }
+}
+
+impl fmt::Display for PrincipalRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrincipalRole::Reader => write!(f, "reader"),
+            PrincipalRole::Writer => write!(f, "writer"),
+            PrincipalRole::Owner => write!(f, "owner"),
+        }
+    }
+}
+
+impl FromStr for PrincipalRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reader" => Ok(PrincipalRole::Reader),
+            "writer" => Ok(PrincipalRole::Writer),
+            "owner" => Ok(PrincipalRole::Owner),
+            _ => Err(anyhow::anyhow!(
+                "unknown role '{}', expected reader, writer or owner",
+                s
+            )),
+        }
+    }
+}
+
 // NOTE (amiller68): we omit the key from the Principal struct
 //  since we use it to index into the Principals map
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -0,0 +1,70 @@
+use std::ops::Range;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio_util::io::StreamReader;
+
+use crate::crypto::PlaintextStream;
+
+/// A streaming reader over the decrypted contents of a file in a bucket
+///
+/// Created by [`Mount::cat_reader`](super::Mount::cat_reader). Encrypted segments
+///  are fetched from the blob store and authenticated as the reader is consumed,
+///  so only a single segment of the file is held in memory at a time.
+pub struct FileReader {
+    inner: StreamReader<PlaintextStream, Bytes>,
+    size: u64,
+    range: Range<u64>,
+}
+
+impl FileReader {
+    pub(crate) fn new(stream: PlaintextStream, size: u64, range: Range<u64>) -> Self {
+        Self {
+            inner: StreamReader::new(stream),
+            size,
+            range,
+        }
+    }
+
+    /// Size of the whole file in bytes, regardless of the range being read
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The byte range of the file this reader yields, after clamping
+    ///  the requested range to the size of the file
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    /// Number of bytes this reader yields
+    pub fn len(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// Whether this reader yields no bytes
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+}
+
+impl std::fmt::Debug for FileReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileReader")
+            .field("size", &self.size)
+            .field("range", &self.range)
+            .finish()
+    }
+}
+
+impl AsyncRead for FileReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
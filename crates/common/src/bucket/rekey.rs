@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::Secret;
+use crate::linked_data::Hash;
+use crate::peer::BlobsStore;
+
+use super::chunks::{put_data, DataLayout};
+use super::mount::{Mount, MountError};
+use super::node::{Node, NodeLink};
+use super::reader::FileReader;
+
+/// How far along re-encrypting a bucket is, as reported by [`Mount::revoke`]
+///  and [`Mount::migrate`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RekeyProgress {
+    /// Directories re-encrypted so far
+    pub dirs: u64,
+    /// Files re-encrypted so far
+    pub files: u64,
+    /// Plaintext bytes of file data re-encrypted so far
+    pub bytes: u64,
+}
+
+/**
+ * Rekey
+ * =====
+ * Re-encrypts every node and file reachable from a directory under
+ *  fresh secrets, as done by [`Mount::revoke`]. Saving the mount
+ *  only rotates the root secret, so a principal that was removed
+ *  could otherwise still decrypt anything that didn't change with
+ *  the secrets it read from earlier versions.
+ * File contents are streamed from their old blobs into new ones a
+ *  chunk at a time, so large files are never held in memory.
+ * Old blobs are left alone: earlier versions still link to them.
+ * With [`Rekey::keep_secrets`] everything is re-encrypted under the
+ *  secret it already has instead, which rewrites blobs stored in
+ *  older formats in the newest one, as done by [`Mount::migrate`].
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+pub(crate) struct Rekey<'a, F> {
+    blobs: &'a BlobsStore,
+    on_progress: F,
+    progress: RekeyProgress,
+    // hashes of every blob written while rekeying
+    created: Vec<Hash>,
+    // whether to reuse each item's secret rather than generate one
+    keep_secrets: bool,
+}
+
+impl<'a, F> Rekey<'a, F>
+where
+    F: FnMut(&RekeyProgress),
+{
+    pub fn new(blobs: &'a BlobsStore, on_progress: F) -> Self {
+        Self {
+            blobs,
+            on_progress,
+            progress: RekeyProgress::default(),
+            created: Vec::new(),
+            keep_secrets: false,
+        }
+    }
+
+    /// Re-encrypt everything under the secrets it already has
+    pub fn keep_secrets(mut self) -> Self {
+        self.keep_secrets = true;
+        self
+    }
+
+    /// Hashes of every blob written while rekeying, to be pinned
+    pub fn created(self) -> Vec<Hash> {
+        self.created
+    }
+
+    /// Re-encrypt everything `node` links to, returning the node
+    ///  with its links replaced
+    pub async fn node(&mut self, node: &Node) -> Result<Node, MountError> {
+        let mut rekeyed = node.clone();
+        for (name, link) in node.get_links() {
+            let secret = if self.keep_secrets {
+                link.secret().clone()
+            } else {
+                Secret::generate()
+            };
+            let link = match link {
+                NodeLink::Dir(..) => {
+                    let child = Mount::_get_node_from_blobs(link, self.blobs).await?;
+                    let child = Box::pin(self.node(&child)).await?;
+                    let child_link = Mount::_put_node_in_blobs(&child, &secret, self.blobs).await?;
+                    self.created.push(*child_link.hash());
+                    self.progress.dirs += 1;
+                    NodeLink::new_dir(child_link, secret)
+                }
+                NodeLink::Data(data_link, old_secret, data) => {
+                    let layout = DataLayout::load(data_link, old_secret, self.blobs).await?;
+                    let size = layout.size();
+                    let stream = layout.open(old_secret, 0..size, self.blobs).await?;
+                    let reader = FileReader::new(stream, size, 0..size);
+                    let stored = put_data(reader, &secret, self.blobs).await?;
+                    self.created.extend(stored.hashes);
+                    self.progress.files += 1;
+                    self.progress.bytes += size;
+                    NodeLink::Data(stored.link, secret, data.clone())
+                }
+            };
+            rekeyed.insert(name.clone(), link);
+            (self.on_progress)(&self.progress);
+        }
+        Ok(rekeyed)
+    }
+}
@@ -2,8 +2,9 @@
 //!
 //! This module provides the cryptographic foundation for JaxBucket's security model:
 //!
-//! - **Identity & Authentication**: Ed25519 keypairs for peer identity
-//! - **Encryption**: AES-256-GCM for content encryption with per-item secrets
+//! - **Identity & Authentication**: Ed25519 keypairs for peer identity, and signatures
+//!   over the manifests each peer commits
+//! - **Encryption**: ChaCha20-Poly1305 (segmented STREAM format) for content encryption with per-item secrets
 //! - **Key Sharing**: ECDH-based key sharing using X25519 curve conversion
 //!
 //! # Security Model
@@ -35,7 +36,9 @@
 mod keys;
 mod secret;
 mod share;
+mod signature;
 
 pub use keys::{PublicKey, SecretKey};
-pub use secret::{Secret, SecretError};
+pub use secret::{PlaintextStream, Secret, SecretError, STREAM_HEADER_SIZE};
 pub use share::{Share, ShareError};
+pub use signature::{Signature, SIGNATURE_SIZE};
@@ -5,24 +5,68 @@
 //! - **Content-addressed storage**: Encrypted data can be hashed deterministically
 //! - **Per-item encryption**: Compromising one key doesn't affect other items
 //! - **Efficient key rotation**: Can re-encrypt specific items without touching others
+//!
+//! # Format
+//!
+//! Ciphertext uses a segmented format based on the STREAM construction:
+//!
+//! ```text
+//! header:   magic "JAXS" (4) || version (1) || segment size (4, BE) || nonce prefix (7)
+//! segments: sealed(segment 0) || sealed(segment 1) || ... || sealed(segment n)
+//! ```
+//!
+//! Plaintext is split into [`SEGMENT_SIZE`] segments which are sealed on their own,
+//! with a nonce of `nonce prefix || counter (4, BE) || last flag (1)` and the header
+//! as associated data. Only the last segment may be short, and it is the only one
+//! sealed with the last flag set, so dropping, reordering or truncating segments
+//! fails authentication. Because every segment can be located from its index,
+//! data can be encrypted as it is read ([`Secret::encrypt_stream`]), decrypted as
+//! it is read ([`Secret::decrypt_stream`]) and decrypted at random offsets
+//! ([`Secret::decrypt_range`]).
+//!
+//! Blobs written before the segmented format used a single-shot layout,
+//! `nonce (12 bytes) || ciphertext || tag (16 bytes)`. Every decryption method
+//! detects these by the missing header and still reads them.
 
-use std::io::Read;
-use std::ops::Deref;
+use std::io::{Read, SeekFrom};
+use std::ops::{Deref, Range};
+use std::pin::Pin;
 
+use bytes::Bytes;
+use chacha20poly1305::aead::stream::{NewStream, StreamBE32, StreamPrimitive};
 use chacha20poly1305::Key;
 use chacha20poly1305::{
     aead::{Aead, KeyInit},
     ChaCha20Poly1305, Nonce,
 };
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 
 /// Size of ChaCha20-Poly1305 nonce in bytes
 pub const NONCE_SIZE: usize = 12;
 /// Size of ChaCha20-Poly1305 key in bytes (256 bits)
 pub const SECRET_SIZE: usize = 32;
-/// Default chunk size for streaming operations
-#[allow(dead_code)]
-pub const CHUNK_SIZE: usize = 4096;
+/// Size of the Poly1305 authentication tag in bytes
+pub const TAG_SIZE: usize = 16;
+/// Plaintext size of a single segment in the segmented format
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Magic bytes at the start of a segmented ciphertext
+pub const STREAM_MAGIC: [u8; 4] = *b"JAXS";
+/// Current version of the segmented format
+pub const STREAM_VERSION: u8 = 1;
+/// Size of the random nonce prefix used by the STREAM construction
+///  (the remaining 5 nonce bytes hold the segment counter and last-segment flag)
+pub const STREAM_NONCE_PREFIX_SIZE: usize = 7;
+/// Largest segment size accepted when parsing a header, so a corrupt or
+///  hostile header can't make readers allocate unbounded buffers
+const MAX_SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+/// Size of the segmented format header:
+///  `magic (4) || version (1) || segment size (4, big-endian) || nonce prefix (7)`
+pub const STREAM_HEADER_SIZE: usize = STREAM_MAGIC.len() + 1 + 4 + STREAM_NONCE_PREFIX_SIZE;
+/// Key derivation context for the nonces used by [`Secret::encrypt_deterministic`]
+const DETERMINISTIC_NONCE_CONTEXT: &str = "jax-buckets 2025 deterministic nonce prefix v1";
 
 /// Errors that can occur during encryption/decryption
 #[derive(Debug, thiserror::Error)]
@@ -33,10 +77,272 @@ pub enum SecretError {
     Io(#[from] std::io::Error),
 }
 
+/// Header of a segmented ciphertext
+///
+/// The serialized header is also used as associated data for every segment,
+///  so it cannot be altered without failing authentication.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StreamHeader {
+    segment_size: u32,
+    nonce_prefix: [u8; STREAM_NONCE_PREFIX_SIZE],
+}
+
+impl StreamHeader {
+    fn generate(segment_size: usize) -> Result<Self, SecretError> {
+        let segment_size = u32::try_from(segment_size)
+            .ok()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| anyhow::anyhow!("invalid segment size: {}", segment_size))?;
+        let mut nonce_prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        getrandom::getrandom(&mut nonce_prefix)
+            .map_err(|e| anyhow::anyhow!("failed to generate nonce: {}", e))?;
+        Ok(Self {
+            segment_size,
+            nonce_prefix,
+        })
+    }
+
+    /// Parse a header from the start of `data`, returning `None` if
+    ///  the data does not look like a segmented ciphertext
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < STREAM_HEADER_SIZE
+            || data[..STREAM_MAGIC.len()] != STREAM_MAGIC
+            || data[STREAM_MAGIC.len()] != STREAM_VERSION
+        {
+            return None;
+        }
+        let size_offset = STREAM_MAGIC.len() + 1;
+        let segment_size = u32::from_be_bytes(
+            data[size_offset..size_offset + 4]
+                .try_into()
+                .expect("slice has length 4"),
+        );
+        if segment_size == 0 || segment_size as usize > MAX_SEGMENT_SIZE {
+            return None;
+        }
+        let mut nonce_prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&data[size_offset + 4..STREAM_HEADER_SIZE]);
+        Some(Self {
+            segment_size,
+            nonce_prefix,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; STREAM_HEADER_SIZE] {
+        let mut out = [0u8; STREAM_HEADER_SIZE];
+        let size_offset = STREAM_MAGIC.len() + 1;
+        out[..STREAM_MAGIC.len()].copy_from_slice(&STREAM_MAGIC);
+        out[STREAM_MAGIC.len()] = STREAM_VERSION;
+        out[size_offset..size_offset + 4].copy_from_slice(&self.segment_size.to_be_bytes());
+        out[size_offset + 4..].copy_from_slice(&self.nonce_prefix);
+        out
+    }
+
+    fn segment_size(&self) -> usize {
+        self.segment_size as usize
+    }
+
+    fn sealed_segment_size(&self) -> usize {
+        self.segment_size() + TAG_SIZE
+    }
+
+    /// Number of segments and plaintext size of a segmented ciphertext
+    ///  of `ciphertext_len` bytes (header included)
+    fn layout(&self, ciphertext_len: u64) -> Result<(u64, u64), SecretError> {
+        let body_len = ciphertext_len
+            .checked_sub(STREAM_HEADER_SIZE as u64)
+            .filter(|len| *len >= TAG_SIZE as u64)
+            .ok_or_else(|| anyhow::anyhow!("data too short for segment"))?;
+        let sealed = self.sealed_segment_size() as u64;
+
+        // There is always at least one segment; only the last one may be short
+        let segment_count = body_len.div_ceil(sealed).max(1);
+        if body_len - (segment_count - 1) * sealed < TAG_SIZE as u64 {
+            return Err(anyhow::anyhow!("data too short for segment").into());
+        }
+        Ok((segment_count, body_len - segment_count * TAG_SIZE as u64))
+    }
+}
+
+/// Read from `reader` until `buffer` holds `target` bytes or the reader is exhausted.
+///  Returns whether the reader is exhausted.
+async fn read_lookahead<R>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    target: usize,
+) -> std::io::Result<bool>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut filled = buffer.len();
+    buffer.resize(target.max(filled), 0);
+    let mut eof = false;
+    while filled < target {
+        let n = reader.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            eof = true;
+            break;
+        }
+        filled += n;
+    }
+    buffer.truncate(filled);
+    Ok(eof)
+}
+
+/// State carried between the segments of [`Secret::encrypt_stream`]
+struct EncryptStreamState<R> {
+    reader: R,
+    cipher: StreamBE32<ChaCha20Poly1305>,
+    header: StreamHeader,
+    position: u32,
+    buffer: Vec<u8>,
+    header_sent: bool,
+    finished: bool,
+}
+
+impl<R> EncryptStreamState<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Produce the next piece of ciphertext, or `None` once the last segment was emitted
+    async fn next_chunk(&mut self) -> std::io::Result<Option<Bytes>> {
+        if !self.header_sent {
+            self.header_sent = true;
+            return Ok(Some(Bytes::copy_from_slice(&self.header.to_bytes())));
+        }
+        if self.finished {
+            return Ok(None);
+        }
+
+        // We always look one byte past the segment so we know whether
+        //  the segment we are about to seal is the last one
+        let target = self.header.segment_size() + 1;
+        let eof = read_lookahead(&mut self.reader, &mut self.buffer, target).await?;
+        let last = eof && self.buffer.len() <= self.header.segment_size();
+        let mut segment = if last {
+            self.finished = true;
+            std::mem::take(&mut self.buffer)
+        } else {
+            let rest = self.buffer.split_off(self.header.segment_size());
+            std::mem::replace(&mut self.buffer, rest)
+        };
+
+        let header = self.header.to_bytes();
+        self.cipher
+            .encrypt_in_place(self.position, last, &header, &mut segment)
+            .map_err(|_| std::io::Error::other("encrypt error"))?;
+        if !last {
+            self.position = self
+                .position
+                .checked_add(1)
+                .ok_or_else(|| std::io::Error::other("too many segments"))?;
+        }
+
+        Ok(Some(Bytes::from(segment)))
+    }
+}
+
+/// State carried between the segments of [`Secret::decrypt_stream`] and [`Secret::decrypt_range`]
+struct DecryptStreamState<R> {
+    reader: R,
+    cipher: StreamBE32<ChaCha20Poly1305>,
+    header: StreamHeader,
+    position: u32,
+    buffer: Vec<u8>,
+    /// Plaintext bytes to drop from the start of the next opened segment
+    skip: usize,
+    /// Plaintext bytes left to yield, if the output is bounded
+    remaining: Option<u64>,
+    finished: bool,
+}
+
+impl<R> DecryptStreamState<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Read the next sealed segment and whether it is the last one
+    async fn next_sealed(&mut self) -> std::io::Result<Option<(Vec<u8>, bool)>> {
+        if self.finished || self.remaining == Some(0) {
+            return Ok(None);
+        }
+
+        let sealed_size = self.header.sealed_segment_size();
+        let eof = read_lookahead(&mut self.reader, &mut self.buffer, sealed_size + 1).await?;
+        if self.buffer.len() < TAG_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "data too short for segment",
+            ));
+        }
+
+        let last = eof && self.buffer.len() <= sealed_size;
+        let sealed = if last {
+            std::mem::take(&mut self.buffer)
+        } else {
+            let rest = self.buffer.split_off(sealed_size);
+            std::mem::replace(&mut self.buffer, rest)
+        };
+        Ok(Some((sealed, last)))
+    }
+
+    /// Open a sealed segment, returning the plaintext that falls within the requested output
+    fn open(&mut self, mut segment: Vec<u8>, last: bool) -> std::io::Result<Bytes> {
+        let header = self.header.to_bytes();
+        self.cipher
+            .decrypt_in_place(self.position, last, &header, &mut segment)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "decrypt error"))?;
+        self.finished = last;
+        self.position = self
+            .position
+            .checked_add(1)
+            .ok_or_else(|| std::io::Error::other("too many segments"))?;
+
+        let mut plaintext = Bytes::from(segment);
+        let _ = plaintext.split_to(std::mem::take(&mut self.skip).min(plaintext.len()));
+        if let Some(remaining) = self.remaining.as_mut() {
+            plaintext.truncate((*remaining).min(plaintext.len() as u64) as usize);
+            *remaining -= plaintext.len() as u64;
+        }
+        Ok(plaintext)
+    }
+
+    /// Produce the next piece of plaintext, or `None` once the output is complete
+    async fn next_chunk(&mut self) -> std::io::Result<Option<Bytes>> {
+        match self.next_sealed().await? {
+            Some((sealed, last)) => self.open(sealed, last).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Turn the state into a stream, yielding `first` ahead of the remaining segments
+    fn into_stream(self, first: Bytes) -> PlaintextStream
+    where
+        R: Send + 'static,
+    {
+        let rest = futures::stream::try_unfold(self, |mut state| async move {
+            Ok(state.next_chunk().await?.map(|chunk| (chunk, state)))
+        });
+        Box::pin(futures::stream::once(async move { Ok(first) }).chain(rest))
+    }
+}
+
+/// Slice `range` out of `data`, clamping it to the length of `data`
+fn slice_range(mut data: Vec<u8>, range: Range<u64>) -> Vec<u8> {
+    let len = data.len() as u64;
+    let start = range.start.min(len) as usize;
+    let end = range.end.clamp(start as u64, len) as usize;
+    data.truncate(end);
+    data.drain(..start);
+    data
+}
+
+/// A stream of decrypted plaintext
+pub type PlaintextStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
 /// A 256-bit symmetric encryption key for content encryption
 ///
 /// Each `Secret` is used to encrypt a single item (node or data blob) using ChaCha20-Poly1305 AEAD.
-/// The encrypted format is: `nonce (12 bytes) || ciphertext (variable) || tag (16 bytes)`.
+/// The encrypted format is described in the [module docs](self).
 ///
 /// # Examples
 ///
@@ -108,36 +414,65 @@ impl Secret {
 
     /// Encrypt data using ChaCha20-Poly1305 AEAD
     ///
-    /// The output format is: `nonce (12 bytes) || ciphertext || auth_tag (16 bytes)`.
-    /// A random nonce is generated for each encryption operation.
+    /// The output uses the segmented format (see the module docs), the same as
+    ///  [`Secret::encrypt_stream`], so it can later be decrypted in pieces.
+    /// A random nonce prefix is generated for each encryption operation.
     ///
     /// # Errors
     ///
     /// Returns an error if encryption fails (should be rare, only on system RNG failure).
     pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
-        let key = Key::from_slice(self.bytes());
-        let cipher = ChaCha20Poly1305::new(key);
+        let header = StreamHeader::generate(SEGMENT_SIZE)?;
+        self.seal(header, data)
+    }
 
-        // Generate random nonce
-        let mut nonce_bytes = [0u8; NONCE_SIZE];
-        getrandom::getrandom(&mut nonce_bytes)
-            .map_err(|e| anyhow::anyhow!("failed to generate nonce: {}", e))?;
-        let nonce = Nonce::from_slice(&nonce_bytes);
+    /// Encrypt data such that the same secret and plaintext always produce
+    ///  the same ciphertext
+    ///
+    /// The nonce prefix is derived from a keyed hash of the plaintext instead of
+    ///  being random, which lets identical content under one secret deduplicate
+    ///  in the blob store. The only thing this reveals is whether two ciphertexts
+    ///  under the same secret hold the same plaintext. The output is in the
+    ///  regular segmented format and decrypts with [`Secret::decrypt`].
+    pub fn encrypt_deterministic(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
+        let nonce_key = blake3::derive_key(DETERMINISTIC_NONCE_CONTEXT, &self.0);
+        let digest = blake3::keyed_hash(&nonce_key, data);
+        let mut nonce_prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&digest.as_bytes()[..STREAM_NONCE_PREFIX_SIZE]);
+        let header = StreamHeader {
+            segment_size: SEGMENT_SIZE as u32,
+            nonce_prefix,
+        };
+        self.seal(header, data)
+    }
 
-        let ciphertext = cipher
-            .encrypt(nonce, data.as_ref())
-            .map_err(|_| anyhow::anyhow!("encrypt error"))?;
+    fn seal(&self, header: StreamHeader, data: &[u8]) -> Result<Vec<u8>, SecretError> {
+        let cipher = self.stream_cipher(&header);
+        let aad = header.to_bytes();
 
-        let mut out = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
-        out.extend_from_slice(nonce.as_ref());
-        out.extend_from_slice(ciphertext.as_ref());
+        let segment_count = data.len().div_ceil(SEGMENT_SIZE).max(1);
+        let mut out =
+            Vec::with_capacity(STREAM_HEADER_SIZE + data.len() + segment_count * TAG_SIZE);
+        out.extend_from_slice(&aad);
+        for index in 0..segment_count {
+            let start = index * SEGMENT_SIZE;
+            let end = (start + SEGMENT_SIZE).min(data.len());
+            let position =
+                u32::try_from(index).map_err(|_| anyhow::anyhow!("too many segments"))?;
+            let mut segment = data[start..end].to_vec();
+            cipher
+                .encrypt_in_place(position, index + 1 == segment_count, &aad, &mut segment)
+                .map_err(|_| anyhow::anyhow!("encrypt error"))?;
+            out.extend_from_slice(&segment);
+        }
 
         Ok(out)
     }
 
     /// Decrypt data using ChaCha20-Poly1305 AEAD
     ///
-    /// Expects input in the format: `nonce (12 bytes) || ciphertext || auth_tag (16 bytes)`.
+    /// Accepts both the segmented format and the legacy single-shot format:
+    ///  `nonce (12 bytes) || ciphertext || auth_tag (16 bytes)`.
     ///
     /// # Errors
     ///
@@ -145,6 +480,17 @@ impl Secret {
     /// - Data is too short to contain a nonce
     /// - Authentication tag verification fails (data was tampered with or wrong key)
     pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
+        if let Some(header) = StreamHeader::parse(data) {
+            // A single-shot blob whose random nonce happens to start with the
+            //  stream header won't authenticate as segmented data, so fall back
+            return self
+                .decrypt_segmented(&header, data)
+                .or_else(|err| self.decrypt_single_shot(data).map_err(|_| err));
+        }
+        self.decrypt_single_shot(data)
+    }
+
+    fn decrypt_single_shot(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
         if data.len() < NONCE_SIZE {
             return Err(anyhow::anyhow!("data too short for nonce").into());
         }
@@ -159,10 +505,228 @@ impl Secret {
         Ok(decrypted.to_vec())
     }
 
+    /// Decrypt a complete segmented ciphertext (header included)
+    fn decrypt_segmented(
+        &self,
+        header: &StreamHeader,
+        data: &[u8],
+    ) -> Result<Vec<u8>, SecretError> {
+        let (segment_count, plaintext_len) = header.layout(data.len() as u64)?;
+        let cipher = self.stream_cipher(header);
+        let aad = header.to_bytes();
+
+        let mut out = Vec::with_capacity(plaintext_len as usize);
+        let sealed_segments = data[STREAM_HEADER_SIZE..].chunks(header.sealed_segment_size());
+        for (index, sealed) in sealed_segments.enumerate() {
+            let position =
+                u32::try_from(index).map_err(|_| anyhow::anyhow!("too many segments"))?;
+            let last = index as u64 + 1 == segment_count;
+            let mut segment = sealed.to_vec();
+            cipher
+                .decrypt_in_place(position, last, &aad, &mut segment)
+                .map_err(|_| anyhow::anyhow!("decrypt error"))?;
+            out.extend_from_slice(&segment);
+        }
+
+        Ok(out)
+    }
+
+    /// Size of the plaintext held by a ciphertext of `ciphertext_len` bytes
+    ///
+    /// `prefix` is the start of the ciphertext (at least [`STREAM_HEADER_SIZE`] bytes
+    ///  if available), which is enough to tell the two formats apart.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ciphertext is too short to be valid.
+    pub fn plaintext_len(prefix: &[u8], ciphertext_len: u64) -> Result<u64, SecretError> {
+        match StreamHeader::parse(prefix) {
+            Some(header) => Ok(header.layout(ciphertext_len)?.1),
+            None => ciphertext_len
+                .checked_sub((NONCE_SIZE + TAG_SIZE) as u64)
+                .ok_or_else(|| anyhow::anyhow!("data too short for nonce").into()),
+        }
+    }
+
+    /// Encrypt a reader into a stream of ciphertext using the segmented format
+    ///
+    /// Plaintext is read and sealed one [`SEGMENT_SIZE`] segment at a time, so memory
+    ///  use does not depend on the size of the input. The first item of the stream
+    ///  is the format header; every following item is one sealed segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the nonce prefix cannot be generated. Read errors from
+    ///  `reader` are surfaced as items of the returned stream.
+    pub fn encrypt_stream<R>(
+        &self,
+        reader: R,
+    ) -> Result<
+        impl Stream<Item = std::io::Result<Bytes>> + Send + Sync + Unpin + 'static,
+        SecretError,
+    >
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let header = StreamHeader::generate(SEGMENT_SIZE)?;
+        let state = EncryptStreamState {
+            reader,
+            cipher: self.stream_cipher(&header),
+            header,
+            position: 0,
+            buffer: Vec::new(),
+            header_sent: false,
+            finished: false,
+        };
+
+        Ok(Box::pin(futures::stream::try_unfold(
+            state,
+            |mut state| async move { Ok(state.next_chunk().await?.map(|chunk| (chunk, state))) },
+        )))
+    }
+
+    /// Decrypt a reader of ciphertext into a stream of plaintext
+    ///
+    /// Segmented ciphertext is opened one segment at a time, so memory use does not
+    ///  depend on the size of the input. Legacy single-shot ciphertext can only be
+    ///  authenticated as a whole, so it is buffered and yielded in one piece.
+    ///
+    /// The first segment is opened before returning, so a wrong key or a corrupt
+    ///  header is reported here rather than from the stream. Later failures
+    ///  (tampering, truncation) are surfaced as items of the returned stream.
+    pub async fn decrypt_stream<R>(&self, mut reader: R) -> Result<PlaintextStream, SecretError>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let mut prefix = Vec::with_capacity(STREAM_HEADER_SIZE);
+        read_lookahead(&mut reader, &mut prefix, STREAM_HEADER_SIZE).await?;
+
+        let Some(header) = StreamHeader::parse(&prefix) else {
+            reader.read_to_end(&mut prefix).await?;
+            return Ok(Self::single_chunk_stream(
+                self.decrypt_single_shot(&prefix)?,
+            ));
+        };
+
+        let mut state = DecryptStreamState {
+            reader,
+            cipher: self.stream_cipher(&header),
+            header,
+            position: 0,
+            buffer: Vec::new(),
+            skip: 0,
+            remaining: None,
+            finished: false,
+        };
+        let (sealed, last) = state
+            .next_sealed()
+            .await?
+            .expect("unbounded output always has a first segment");
+
+        match state.open(sealed.clone(), last) {
+            Ok(first) => Ok(state.into_stream(first)),
+            Err(err) => {
+                // Possibly a single-shot blob whose nonce looks like a header
+                let mut data = prefix;
+                data.extend_from_slice(&sealed);
+                data.append(&mut state.buffer);
+                state.reader.read_to_end(&mut data).await?;
+                let plaintext = self.decrypt_single_shot(&data).map_err(|_| err)?;
+                Ok(Self::single_chunk_stream(plaintext))
+            }
+        }
+    }
+
+    /// Decrypt a byte range of the plaintext from a seekable reader of ciphertext
+    ///
+    /// For segmented ciphertext only the segments overlapping `range` are read and
+    ///  authenticated, so reading the tail of a large blob does not touch the rest
+    ///  of it. `ciphertext_len` is the total size of the ciphertext. The range is
+    ///  clamped to the size of the plaintext, and may therefore yield nothing.
+    ///
+    /// Legacy single-shot ciphertext is read and authenticated as a whole
+    ///  before the range is sliced out of it.
+    pub async fn decrypt_range<R>(
+        &self,
+        mut reader: R,
+        ciphertext_len: u64,
+        range: Range<u64>,
+    ) -> Result<PlaintextStream, SecretError>
+    where
+        R: AsyncRead + AsyncSeek + Send + Unpin + 'static,
+    {
+        let mut prefix = Vec::with_capacity(STREAM_HEADER_SIZE);
+        reader.seek(SeekFrom::Start(0)).await?;
+        read_lookahead(&mut reader, &mut prefix, STREAM_HEADER_SIZE).await?;
+
+        let Some(header) = StreamHeader::parse(&prefix) else {
+            reader.read_to_end(&mut prefix).await?;
+            let plaintext = self.decrypt_single_shot(&prefix)?;
+            return Ok(Self::single_chunk_stream(slice_range(plaintext, range)));
+        };
+
+        let (_, plaintext_len) = header.layout(ciphertext_len)?;
+        let start = range.start.min(plaintext_len);
+        let end = range.end.clamp(start, plaintext_len);
+        if start == end {
+            return Ok(Box::pin(futures::stream::empty()));
+        }
+
+        let segment_size = header.segment_size() as u64;
+        let first_segment = start / segment_size;
+        let position =
+            u32::try_from(first_segment).map_err(|_| anyhow::anyhow!("too many segments"))?;
+        reader
+            .seek(SeekFrom::Start(
+                STREAM_HEADER_SIZE as u64 + first_segment * header.sealed_segment_size() as u64,
+            ))
+            .await?;
+
+        let mut state = DecryptStreamState {
+            reader,
+            cipher: self.stream_cipher(&header),
+            header,
+            position,
+            buffer: Vec::new(),
+            skip: (start % segment_size) as usize,
+            remaining: Some(end - start),
+            finished: false,
+        };
+        let (sealed, last) = state
+            .next_sealed()
+            .await?
+            .expect("non-empty range always has a first segment");
+
+        match state.open(sealed, last) {
+            Ok(first) => Ok(state.into_stream(first)),
+            Err(err) => {
+                // Possibly a single-shot blob whose nonce looks like a header
+                let mut data = Vec::new();
+                state.reader.seek(SeekFrom::Start(0)).await?;
+                state.reader.read_to_end(&mut data).await?;
+                let plaintext = self.decrypt_single_shot(&data).map_err(|_| err)?;
+                Ok(Self::single_chunk_stream(slice_range(plaintext, range)))
+            }
+        }
+    }
+
+    fn single_chunk_stream(plaintext: Vec<u8>) -> PlaintextStream {
+        Box::pin(futures::stream::once(
+            async move { Ok(Bytes::from(plaintext)) },
+        ))
+    }
+
+    /// Build the STREAM cipher for a segmented ciphertext with the given header
+    fn stream_cipher(&self, header: &StreamHeader) -> StreamBE32<ChaCha20Poly1305> {
+        let key = Key::from_slice(self.bytes());
+        let cipher = ChaCha20Poly1305::new(key);
+        StreamBE32::from_aead(cipher, header.nonce_prefix.as_ref().into())
+    }
+
     /// Create an encrypted reader from a plaintext reader
     ///
     /// This buffers all data in memory, encrypts it, and returns a reader over the encrypted data.
-    /// Future optimization: implement true streaming encryption.
+    /// Use [`Secret::encrypt_stream`] to encrypt without buffering.
     pub fn encrypt_reader<R>(&self, reader: R) -> Result<impl Read, SecretError>
     where
         R: Read,
@@ -178,7 +742,7 @@ impl Secret {
     /// Create a decrypted reader from an encrypted reader
     ///
     /// This buffers all encrypted data in memory, decrypts it, and returns a reader over the plaintext.
-    /// Future optimization: implement true streaming decryption.
+    /// Use [`Secret::decrypt_stream`] or [`Secret::decrypt_range`] to decrypt without buffering.
     pub fn decrypt_reader<R>(&self, reader: R) -> Result<impl Read, SecretError>
     where
         R: Read,
@@ -233,6 +797,253 @@ mod test {
         assert_eq!(data.to_vec(), decrypted_data);
     }
 
+    #[test]
+    fn test_encrypt_deterministic() {
+        let secret = Secret::generate();
+        let data = vec![7u8; 2 * SEGMENT_SIZE + 3];
+
+        let first = secret.encrypt_deterministic(&data).unwrap();
+        let second = secret.encrypt_deterministic(&data).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(secret.decrypt(&first).unwrap(), data);
+
+        // Different plaintext or a different secret yields a different nonce
+        let mut other = data.clone();
+        other[0] ^= 1;
+        let changed = secret.encrypt_deterministic(&other).unwrap();
+        assert_ne!(first[..STREAM_HEADER_SIZE], changed[..STREAM_HEADER_SIZE]);
+        let rekeyed = Secret::generate().encrypt_deterministic(&data).unwrap();
+        assert_ne!(first[..STREAM_HEADER_SIZE], rekeyed[..STREAM_HEADER_SIZE]);
+    }
+
+    async fn encrypt_stream_to_vec(secret: &Secret, data: Vec<u8>) -> Vec<u8> {
+        use futures::TryStreamExt;
+
+        let chunks: Vec<Bytes> = secret
+            .encrypt_stream(Cursor::new(data))
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        chunks.concat()
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_stream_decrypt() {
+        let secret = Secret::generate();
+
+        // Cover empty input, partial segments and exact segment boundaries
+        for len in [
+            0,
+            1,
+            SEGMENT_SIZE - 1,
+            SEGMENT_SIZE,
+            SEGMENT_SIZE + 1,
+            3 * SEGMENT_SIZE,
+            3 * SEGMENT_SIZE + 7,
+        ] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let encrypted = encrypt_stream_to_vec(&secret, data.clone()).await;
+
+            let segments = len.div_ceil(SEGMENT_SIZE).max(1);
+            assert_eq!(
+                encrypted.len(),
+                STREAM_HEADER_SIZE + len + segments * TAG_SIZE
+            );
+            assert_eq!(&encrypted[..STREAM_MAGIC.len()], &STREAM_MAGIC);
+
+            let decrypted = secret.decrypt(&encrypted).unwrap();
+            assert_eq!(data, decrypted, "roundtrip failed for length {}", len);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_stream_tampering() {
+        let secret = Secret::generate();
+        let data: Vec<u8> = (0..2 * SEGMENT_SIZE + 100).map(|i| i as u8).collect();
+        let encrypted = encrypt_stream_to_vec(&secret, data).await;
+
+        // Dropping the last segment must not yield a valid (truncated) plaintext
+        let truncated = &encrypted[..STREAM_HEADER_SIZE + 2 * (SEGMENT_SIZE + TAG_SIZE)];
+        assert!(secret.decrypt(truncated).is_err());
+
+        // Flipping a ciphertext bit fails authentication
+        let mut corrupted = encrypted.clone();
+        corrupted[STREAM_HEADER_SIZE + 10] ^= 1;
+        assert!(secret.decrypt(&corrupted).is_err());
+
+        // The header is authenticated as well
+        let mut corrupted = encrypted.clone();
+        corrupted[STREAM_HEADER_SIZE - 1] ^= 1;
+        assert!(secret.decrypt(&corrupted).is_err());
+
+        // Wrong key
+        assert!(Secret::generate().decrypt(&encrypted).is_err());
+    }
+
+    async fn collect_plaintext(stream: PlaintextStream) -> std::io::Result<Vec<u8>> {
+        use futures::TryStreamExt;
+
+        let chunks: Vec<Bytes> = stream.try_collect().await?;
+        Ok(chunks.concat())
+    }
+
+    /// Encrypt with the single-shot layout used before the segmented format
+    fn legacy_encrypt(secret: &Secret, data: &[u8]) -> Vec<u8> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(secret.bytes()));
+        let nonce_bytes = [7u8; NONCE_SIZE];
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), data)
+            .unwrap();
+        [nonce_bytes.as_slice(), ciphertext.as_slice()].concat()
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_stream() {
+        let secret = Secret::generate();
+
+        for len in [0, 1, SEGMENT_SIZE, SEGMENT_SIZE + 1, 3 * SEGMENT_SIZE + 7] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let encrypted = secret.encrypt(&data).unwrap();
+            assert_eq!(
+                Secret::plaintext_len(&encrypted, encrypted.len() as u64).unwrap(),
+                len as u64
+            );
+
+            let stream = secret.decrypt_stream(Cursor::new(encrypted)).await.unwrap();
+            let decrypted = collect_plaintext(stream).await.unwrap();
+            assert_eq!(data, decrypted, "roundtrip failed for length {}", len);
+        }
+
+        // Truncation is only noticed once the stream reaches the end
+        let data = vec![1u8; 2 * SEGMENT_SIZE + 100];
+        let encrypted = secret.encrypt(&data).unwrap();
+        let truncated = encrypted[..encrypted.len() - 100].to_vec();
+        let stream = secret.decrypt_stream(Cursor::new(truncated)).await.unwrap();
+        assert!(collect_plaintext(stream).await.is_err());
+
+        // A wrong key is reported up front
+        assert!(Secret::generate()
+            .decrypt_stream(Cursor::new(encrypted))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_range() {
+        let secret = Secret::generate();
+        let len = 3 * SEGMENT_SIZE + 7;
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let encrypted = secret.encrypt(&data).unwrap();
+        let ciphertext_len = encrypted.len() as u64;
+        let len = len as u64;
+        let segment = SEGMENT_SIZE as u64;
+
+        for range in [
+            0..10,
+            segment - 5..segment + 5,
+            segment..2 * segment,
+            2 * segment + 1..len,
+            0..len,
+            len - 1..len + 100,
+            len + 5..len + 10,
+            10..10,
+        ] {
+            let stream = secret
+                .decrypt_range(
+                    Cursor::new(encrypted.clone()),
+                    ciphertext_len,
+                    range.clone(),
+                )
+                .await
+                .unwrap();
+            let decrypted = collect_plaintext(stream).await.unwrap();
+            let start = range.start.min(len) as usize;
+            let end = range.end.clamp(start as u64, len) as usize;
+            assert_eq!(&data[start..end], decrypted.as_slice(), "range {:?}", range);
+        }
+
+        // Only the segments overlapping the range are read, so damage
+        //  elsewhere in the blob doesn't affect the result
+        let mut corrupted = encrypted.clone();
+        corrupted[STREAM_HEADER_SIZE + 1] ^= 1;
+        let stream = secret
+            .decrypt_range(
+                Cursor::new(corrupted.clone()),
+                ciphertext_len,
+                2 * segment..len,
+            )
+            .await
+            .unwrap();
+        let decrypted = collect_plaintext(stream).await.unwrap();
+        assert_eq!(&data[2 * SEGMENT_SIZE..], decrypted.as_slice());
+
+        let result = secret
+            .decrypt_range(Cursor::new(corrupted), ciphertext_len, 0..10)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_single_shot_compat() {
+        let secret = Secret::generate();
+        let data = b"written before the segmented format existed".to_vec();
+        let encrypted = legacy_encrypt(&secret, &data);
+
+        assert_eq!(secret.decrypt(&encrypted).unwrap(), data);
+        assert_eq!(
+            Secret::plaintext_len(&encrypted, encrypted.len() as u64).unwrap(),
+            data.len() as u64
+        );
+
+        let stream = secret
+            .decrypt_stream(Cursor::new(encrypted.clone()))
+            .await
+            .unwrap();
+        assert_eq!(collect_plaintext(stream).await.unwrap(), data);
+
+        let stream = secret
+            .decrypt_range(
+                Cursor::new(encrypted.clone()),
+                encrypted.len() as u64,
+                8..14,
+            )
+            .await
+            .unwrap();
+        assert_eq!(collect_plaintext(stream).await.unwrap(), &data[8..14]);
+
+        // Tampered legacy blobs are still rejected
+        let mut corrupted = encrypted;
+        corrupted[NONCE_SIZE] ^= 1;
+        assert!(secret.decrypt(&corrupted).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_nonce_colliding_with_header() {
+        // A legacy blob whose random nonce starts with the stream magic
+        //  and version must still be read as a legacy blob
+        let secret = Secret::generate();
+        let data = vec![42u8; 100];
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(secret.bytes()));
+        let mut nonce_bytes = [0u8; NONCE_SIZE];
+        nonce_bytes[..STREAM_MAGIC.len()].copy_from_slice(&STREAM_MAGIC);
+        nonce_bytes[STREAM_MAGIC.len()] = STREAM_VERSION;
+        nonce_bytes[STREAM_MAGIC.len() + 3] = 1;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), data.as_slice())
+            .unwrap();
+        let encrypted = [nonce_bytes.as_slice(), ciphertext.as_slice()].concat();
+        assert!(StreamHeader::parse(&encrypted).is_some());
+
+        assert_eq!(secret.decrypt(&encrypted).unwrap(), data);
+
+        let stream = secret
+            .decrypt_stream(Cursor::new(encrypted.clone()))
+            .await
+            .unwrap();
+        assert_eq!(collect_plaintext(stream).await.unwrap(), data);
+    }
+
     #[test]
     fn test_secret_size_validation() {
         let too_short = [1u8; 16];
@@ -0,0 +1,144 @@
+//! Ed25519 signatures over bucket data
+//!
+//! Peers sign the manifests they commit with the same Ed25519 keypair that
+//! identifies them on the network, so other peers can check who made a
+//! version of a bucket before accepting it.
+
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use super::keys::{KeyError, PublicKey, SecretKey};
+
+/// Size of an Ed25519 signature in bytes
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// An Ed25519 signature made with a [`SecretKey`]
+///
+/// # Examples
+///
+/// ```ignore
+/// let secret_key = SecretKey::generate();
+/// let signature = secret_key.sign(b"message");
+/// secret_key.public().verify(b"message", &signature)?;
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Signature(pub(crate) [u8; SIGNATURE_SIZE]);
+
+impl SecretKey {
+    /// Sign a message with this key
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        let signing_key = SigningKey::from_bytes(&self.to_bytes());
+        Signature(signing_key.sign(message).to_bytes())
+    }
+}
+
+impl PublicKey {
+    /// Check that `signature` was made over `message` by the matching secret key
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), KeyError> {
+        let verifying_key = VerifyingKey::from_bytes(&self.to_bytes())
+            .map_err(|_| anyhow::anyhow!("public key is not a valid ed25519 key"))?;
+        let signature = ed25519_dalek::Signature::from_bytes(&signature.0);
+        verifying_key
+            .verify(message, &signature)
+            .map_err(|_| anyhow::anyhow!("signature verification failed"))?;
+        Ok(())
+    }
+}
+
+impl Serialize for Signature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{Error, Visitor};
+        use std::fmt;
+
+        struct SignatureVisitor;
+
+        impl<'de> Visitor<'de> for SignatureVisitor {
+            type Value = Signature;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte array or sequence of SIGNATURE_SIZE")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                let array: [u8; SIGNATURE_SIZE] = v.try_into().map_err(|_| {
+                    E::invalid_length(
+                        v.len(),
+                        &format!("expected {} bytes", SIGNATURE_SIZE).as_str(),
+                    )
+                })?;
+                Ok(Signature(array))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                self.visit_bytes(&bytes)
+            }
+        }
+
+        // Try bytes first (for CBOR/bincode), fallback to seq (for JSON)
+        deserializer.deserialize_byte_buf(SignatureVisitor)
+    }
+}
+
+impl From<[u8; SIGNATURE_SIZE]> for Signature {
+    fn from(bytes: [u8; SIGNATURE_SIZE]) -> Self {
+        Signature(bytes)
+    }
+}
+
+impl From<Signature> for [u8; SIGNATURE_SIZE] {
+    fn from(signature: Signature) -> Self {
+        signature.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let secret_key = SecretKey::generate();
+        let signature = secret_key.sign(b"hello");
+
+        assert!(secret_key.public().verify(b"hello", &signature).is_ok());
+        assert!(secret_key.public().verify(b"hullo", &signature).is_err());
+        assert!(SecretKey::generate()
+            .public()
+            .verify(b"hello", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn test_signature_serialize() {
+        use ipld_core::codec::Codec;
+        use serde_ipld_dagcbor::codec::DagCborCodec;
+
+        let signature = SecretKey::generate().sign(b"hello");
+        let encoded = DagCborCodec::encode_to_vec(&signature).unwrap();
+        let decoded: Signature = DagCborCodec::decode_from_slice(&encoded).unwrap();
+
+        assert_eq!(signature, decoded);
+    }
+}
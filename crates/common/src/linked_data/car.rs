@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::ipld::{Cid, CidError, BLAKE3_HASH_CODE, LD_CBOR_CODEC, LD_RAW_CODEC};
+use super::{BlockEncoded, DagCborCodec, Hash, Link};
+
+/// Version of the CAR format read and written
+pub const CAR_VERSION: u64 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CarError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid car header")]
+    InvalidHeader,
+    #[error("unsupported car version {0}, only version {CAR_VERSION} can be read")]
+    UnsupportedVersion(u64),
+    #[error("expected a single root, found {0}")]
+    InvalidRoots(usize),
+    #[error("invalid cid: {0}")]
+    Cid(#[from] CidError),
+    #[error("unsupported cid {0}, only blake3 raw and dag-cbor blocks can be read")]
+    UnsupportedCid(Cid),
+    #[error("block does not match its cid {0}")]
+    HashMismatch(Cid),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CarHeader {
+    roots: Vec<Cid>,
+    version: u64,
+}
+
+impl BlockEncoded<DagCborCodec> for CarHeader {}
+
+/**
+ * CAR Archives
+ * ============
+ * A CARv1 archive is a header naming its root, followed by a
+ *  section per block. Every part is prefixed with its length as
+ *  an unsigned LEB128 varint:
+ *  - the header is DAG-CBOR: `{ roots: [cid], version: 1 }`
+ *  - a section is a block's CID (binary), followed by its bytes
+ * Links convert to CIDs and back without loss, other than the
+ *  blob format, which readers have to know from context.
+ *  Only archives with a single root are supported.
+ */
+#[allow(clippy::doc_overindented_list_items)]
+#[allow(clippy::doc_lazy_continuation)]
+pub struct CarWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> CarWriter<W> {
+    /// Start an archive rooted at `root`, writing its header
+    pub async fn new(mut writer: W, root: &Link) -> Result<Self, CarError> {
+        let header = CarHeader {
+            roots: vec![root.clone().into()],
+            version: CAR_VERSION,
+        };
+        let header = header.encode().map_err(|_| CarError::InvalidHeader)?;
+        write_varint(&mut writer, header.len() as u64).await?;
+        writer.write_all(&header).await?;
+        Ok(Self { writer })
+    }
+
+    /// Write the block `data` links to
+    pub async fn write(&mut self, link: &Link, data: &[u8]) -> Result<(), CarError> {
+        let cid: Cid = link.clone().into();
+        let cid = cid.to_bytes();
+        write_varint(&mut self.writer, (cid.len() + data.len()) as u64).await?;
+        self.writer.write_all(&cid).await?;
+        self.writer.write_all(data).await?;
+        Ok(())
+    }
+
+    /// Flush the archive, returning the writer
+    pub async fn finish(mut self) -> Result<W, CarError> {
+        self.writer.flush().await?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the blocks of a CAR archive in order, checking each matches
+///  its CID
+pub struct CarReader<R> {
+    reader: R,
+    root: Link,
+}
+
+impl<R: AsyncRead + Unpin> CarReader<R> {
+    /// Open an archive, reading its header
+    pub async fn new(mut reader: R) -> Result<Self, CarError> {
+        let len = read_varint(&mut reader)
+            .await?
+            .ok_or(CarError::InvalidHeader)?;
+        let header = read_exact(&mut reader, len).await?;
+        let header = CarHeader::decode(&header).map_err(|_| CarError::InvalidHeader)?;
+        if header.version != CAR_VERSION {
+            return Err(CarError::UnsupportedVersion(header.version));
+        }
+        let [root] = header.roots.as_slice() else {
+            return Err(CarError::InvalidRoots(header.roots.len()));
+        };
+        let root = to_link(*root)?;
+        Ok(Self { reader, root })
+    }
+
+    pub fn root(&self) -> &Link {
+        &self.root
+    }
+
+    /// Read the next block, or None at the end of the archive
+    pub async fn next(&mut self) -> Result<Option<(Link, Vec<u8>)>, CarError> {
+        let Some(len) = read_varint(&mut self.reader).await? else {
+            return Ok(None);
+        };
+        let section = read_exact(&mut self.reader, len).await?;
+        let mut bytes = section.as_slice();
+        let cid = Cid::read_bytes(&mut bytes)?;
+        let link = to_link(cid)?;
+        if Hash::new(bytes) != *link.hash() {
+            return Err(CarError::HashMismatch(cid));
+        }
+        Ok(Some((link, bytes.to_vec())))
+    }
+}
+
+// `Link: From<Cid>` panics on CIDs we don't produce, so check first
+fn to_link(cid: Cid) -> Result<Link, CarError> {
+    let supported = cid.hash().code() == BLAKE3_HASH_CODE
+        && cid.hash().size() == 32
+        && (cid.codec() == LD_RAW_CODEC || cid.codec() == LD_CBOR_CODEC);
+    if !supported {
+        return Err(CarError::UnsupportedCid(cid));
+    }
+    Ok(cid.into())
+}
+
+async fn write_varint<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut value: u64,
+) -> std::io::Result<()> {
+    let mut buf = Vec::with_capacity(10);
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            break;
+        }
+        buf.push(byte | 0x80);
+    }
+    writer.write_all(&buf).await
+}
+
+// Returns None at a clean end of the archive, before the first byte
+async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<u64>, CarError> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte).await? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(CarError::InvalidHeader)
+}
+
+// Read `len` bytes, without trusting `len` enough to allocate it up front
+async fn read_exact<R: AsyncRead + Unpin>(reader: &mut R, len: u64) -> Result<Vec<u8>, CarError> {
+    let mut data = Vec::new();
+    (&mut *reader).take(len).read_to_end(&mut data).await?;
+    if (data.len() as u64) < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_blobs::BlobFormat;
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let blocks: Vec<Vec<u8>> = vec![b"root".to_vec(), Vec::new(), vec![7u8; 300]];
+        let links: Vec<Link> = blocks
+            .iter()
+            .map(|data| Link::new(LD_RAW_CODEC, Hash::new(data), BlobFormat::Raw))
+            .collect();
+
+        let mut car = CarWriter::new(Vec::new(), &links[0]).await.unwrap();
+        for (link, data) in links.iter().zip(&blocks) {
+            car.write(link, data).await.unwrap();
+        }
+        let archive = car.finish().await.unwrap();
+
+        let mut car = CarReader::new(archive.as_slice()).await.unwrap();
+        assert_eq!(car.root(), &links[0]);
+        for (link, data) in links.iter().zip(&blocks) {
+            let (read_link, read_data) = car.next().await.unwrap().unwrap();
+            assert_eq!(&read_link, link);
+            assert_eq!(&read_data, data);
+        }
+        assert!(car.next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_corrupt_block() {
+        let link = Link::new(LD_RAW_CODEC, Hash::new(b"data"), BlobFormat::Raw);
+        let mut car = CarWriter::new(Vec::new(), &link).await.unwrap();
+        car.write(&link, b"atad").await.unwrap();
+        let archive = car.finish().await.unwrap();
+
+        let mut car = CarReader::new(archive.as_slice()).await.unwrap();
+        assert!(matches!(car.next().await, Err(CarError::HashMismatch(_))));
+
+        // A truncated archive is an error, not an early end
+        let mut car = CarReader::new(&archive[..archive.len() - 1]).await.unwrap();
+        assert!(matches!(car.next().await, Err(CarError::Io(_))));
+    }
+}
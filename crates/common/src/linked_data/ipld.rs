@@ -15,6 +15,8 @@ pub enum CodecError {
     EncodeError,
     #[error("decoding error")]
     DecodeError,
+    #[error("unsupported format {0}, only formats up to {1} can be read")]
+    UnsupportedFormat(u32, u32),
 }
 
 // TODO (amiller68): this seems silly, but saves
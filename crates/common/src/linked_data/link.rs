@@ -28,7 +28,8 @@ pub struct Link(u64, Hash, BlobFormat);
  * For our purposes, we keep the following assumptions:
  *  - hashes are always blake3, and usually generated by iroh-blobs
  *  - blob formats may be Raw or HashSeq. Most of our links will be Raw,
- *     we only use HashSeq for declaring a Bucket's pinset
+ *     we only use HashSeq for declaring a Bucket's pinset and for
+ *     linking the chunks of large files
  *  - links may either be RAW or DAG-CBOR encoded depending on whether they
  *   point to:
  *    - public raw data (RAW)
@@ -1,6 +1,8 @@
+mod car;
 mod ipld;
 mod link;
 
+pub use car::{CarError, CarReader, CarWriter, CAR_VERSION};
 pub use ipld::{multibase, BlockEncoded, Cid, CidError, CodecError, LinkedData, LD_RAW_CODEC};
 pub use iroh_blobs::Hash;
 pub use link::Link;
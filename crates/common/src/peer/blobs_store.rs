@@ -1,21 +1,57 @@
+use std::collections::{HashMap, HashSet};
 use std::future::IntoFuture;
 use std::ops::Deref;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use anyhow::anyhow;
 use bytes::Bytes;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use iroh::{Endpoint, NodeId};
 use iroh_blobs::{
     api::{
         blobs::{BlobReader as Reader, BlobStatus, Blobs},
         downloader::{Downloader, Shuffled},
-        ExportBaoError, RequestError,
+        ExportBaoError, RequestError, Tag,
     },
-    store::fs::FsStore,
-    BlobsProtocol, Hash,
+    store::fs::{
+        options::{GcConfig, Options, ProtectCb, ProtectOutcome},
+        FsStore,
+    },
+    BlobsProtocol, Hash, HashAndFormat,
 };
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+/// How often the store checks for a garbage collection request
+const GC_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prefix of the tags the store creates for every blob added to it,
+///  followed by the time the tag was created
+const AUTO_TAG_PREFIX: &str = "auto-";
+
+/// Prefix of the tags that hold blobs until they're released,
+///  followed by the name they're held under and the hash
+const HOLD_TAG_PREFIX: &str = "hold-";
+
+/// How many blobs a garbage collection run found unreferenced, or removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcStats {
+    /// Number of blobs
+    pub blobs: u64,
+    /// Their total size in bytes
+    pub bytes: u64,
+}
+
+/// A request for the store to run garbage collection, keeping `live`.
+///  `done` is dropped once the run is over.
+#[derive(Debug)]
+struct GcRequest {
+    live: HashSet<Hash>,
+    done: oneshot::Sender<()>,
+}
 
 // TODO (amiller68): maybe at some point it would make sense
 //  to implement some sort of `BlockStore` trait over BlobStore
@@ -27,6 +63,7 @@ use iroh_blobs::{
 #[derive(Clone, Debug)]
 pub struct BlobsStore {
     pub inner: Arc<BlobsProtocol>,
+    gc: mpsc::Sender<GcRequest>,
 }
 
 impl Deref for BlobsStore {
@@ -43,9 +80,23 @@ pub enum BlobsStoreError {
     #[error("blob store i/o error: {0}")]
     Io(#[from] std::io::Error),
     #[error("export bao error: {0}")]
-    ExportBao(#[from] ExportBaoError),
+    ExportBao(Box<ExportBaoError>),
     #[error("request error: {0}")]
-    Request(#[from] RequestError),
+    Request(Box<RequestError>),
+}
+
+// NOTE: the iroh-blobs errors are large, so we box them to keep
+//  `Result<_, BlobsStoreError>` (and everything wrapping it) small
+impl From<ExportBaoError> for BlobsStoreError {
+    fn from(err: ExportBaoError) -> Self {
+        BlobsStoreError::ExportBao(Box::new(err))
+    }
+}
+
+impl From<RequestError> for BlobsStoreError {
+    fn from(err: RequestError) -> Self {
+        BlobsStoreError::Request(Box::new(err))
+    }
 }
 
 impl BlobsStore {
@@ -60,11 +111,47 @@ impl BlobsStore {
     ///     the endpoint.
     #[allow(clippy::doc_overindented_list_items)]
     pub async fn load(path: &Path) -> Result<Self, BlobsStoreError> {
-        let store = FsStore::load(path).await?;
+        let (gc, requests) = mpsc::channel(1);
+        let options = Options {
+            gc: Some(GcConfig {
+                interval: GC_POLL_INTERVAL,
+                add_protected: Some(Self::gc_protect_callback(requests)),
+            }),
+            ..Options::new(path)
+        };
+        let store = FsStore::load_with_opts(path.join("blobs.db"), options).await?;
         // let blobs = Blobs::builder(store).build(&endpoint);
         let blobs = BlobsProtocol::new(&store, None);
         Ok(Self {
             inner: Arc::new(blobs),
+            gc,
+        })
+    }
+
+    // NOTE: blobs can only be deleted by the store's own garbage
+    //  collection, which runs on an interval and asks this callback
+    //  which blobs to keep before each run. Runs are held here until
+    //  `collect_garbage` requests one, so the store never deletes
+    //  anything on its own.
+    fn gc_protect_callback(requests: mpsc::Receiver<GcRequest>) -> ProtectCb {
+        let requests = Arc::new(tokio::sync::Mutex::new(requests));
+        let running: Arc<Mutex<Option<oneshot::Sender<()>>>> = Arc::new(Mutex::new(None));
+        Arc::new(move |live: &mut HashSet<Hash>| {
+            let requests = requests.clone();
+            let running = running.clone();
+            Box::pin(async move {
+                // We are only called again once the previous run is over
+                running.lock().take();
+                let request = requests.lock().await.recv().await;
+                match request {
+                    Some(request) => {
+                        live.extend(request.live);
+                        *running.lock() = Some(request.done);
+                        ProtectOutcome::Continue
+                    }
+                    None => ProtectOutcome::Abort,
+                }
+            })
         })
     }
 
@@ -118,6 +205,38 @@ impl BlobsStore {
         Ok(matches!(stat, BlobStatus::Complete { .. }))
     }
 
+    /// Keep a blob from garbage collection until everything held
+    ///  under `name` is released
+    pub async fn hold(&self, name: &str, hash: Hash) -> Result<(), BlobsStoreError> {
+        let tag = format!("{HOLD_TAG_PREFIX}{name}/{hash}");
+        self.inner.store().tags().set(tag, hash).await?;
+        Ok(())
+    }
+
+    /// Release the blobs held under `name`, or under any name nested
+    ///  below it
+    pub async fn release(&self, name: &str) -> Result<(), BlobsStoreError> {
+        let prefix = format!("{HOLD_TAG_PREFIX}{name}/");
+        self.inner.store().tags().delete_prefix(prefix).await?;
+        Ok(())
+    }
+
+    /// Get the size of a blob, which must be complete in the store
+    pub async fn size(&self, hash: &Hash) -> Result<u64, BlobsStoreError> {
+        let stat = self
+            .blobs()
+            .status(*hash)
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        match stat {
+            BlobStatus::Complete { size } => Ok(size),
+            _ => Err(BlobsStoreError::Default(anyhow!(
+                "blob {} is not complete",
+                hash
+            ))),
+        }
+    }
+
     /// Download a single hash from peers
     ///
     /// This checks if the hash exists locally first, then downloads if needed.
@@ -146,6 +265,15 @@ impl BlobsStore {
             peer_ids
         );
 
+        // Protect the blob from garbage collection while it downloads
+        let temp_tag = self
+            .inner
+            .store()
+            .tags()
+            .temp_tag(HashAndFormat::raw(hash))
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+
         // Create downloader - needs the Store from BlobsProtocol
         let downloader = Downloader::new(self.inner.store(), endpoint);
 
@@ -190,6 +318,15 @@ impl BlobsStore {
             }
         }
 
+        // Tag the blob like the ones we add ourselves, so it is kept
+        //  until garbage collection finds nothing references it
+        self.inner
+            .store()
+            .tags()
+            .create(HashAndFormat::raw(hash))
+            .await?;
+        drop(temp_tag);
+
         Ok(())
     }
 
@@ -284,6 +421,56 @@ impl BlobsStore {
         Ok(())
     }
 
+    /// Remove `hashes` from the store, whatever references them, so that
+    ///  they can be downloaded again
+    ///
+    /// Everything else in the store is kept.
+    pub async fn evict(&self, hashes: &HashSet<Hash>) -> Result<(), BlobsStoreError> {
+        let tags = self.inner.store().tags();
+        let mut evicted = Vec::new();
+        let mut stream = tags
+            .list()
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        while let Some(tag) = stream.next().await {
+            let tag = tag.map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+            if hashes.contains(&tag.hash) {
+                evicted.push(tag.name);
+            }
+        }
+        for name in evicted {
+            tags.delete(name).await?;
+        }
+
+        let mut live = HashSet::new();
+        let mut stream = self
+            .blobs()
+            .list()
+            .stream()
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        while let Some(hash) = stream.next().await {
+            let hash = hash.map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+            if !hashes.contains(&hash) {
+                live.insert(hash);
+            }
+        }
+        self.run_gc(live).await
+    }
+
+    // Run the store's garbage collection once, keeping `live`, and wait
+    //  for it to finish
+    async fn run_gc(&self, live: HashSet<Hash>) -> Result<(), BlobsStoreError> {
+        let (done, finished) = oneshot::channel();
+        self.gc
+            .send(GcRequest { live, done })
+            .await
+            .map_err(|_| anyhow!("blob store garbage collection is not running"))?;
+        // The sender is dropped when the run ends, however it ends
+        let _ = finished.await;
+        Ok(())
+    }
+
     /// Create a simple blob containing a sequence of hashes
     /// Each hash is 32 bytes, stored consecutively
     /// Returns the hash of the blob containing all the hashes
@@ -302,6 +489,101 @@ impl BlobsStore {
         Ok(hash)
     }
 
+    /// Remove every blob that isn't in `live`
+    ///
+    /// Blobs added or downloaded within `grace` are kept regardless, since
+    ///  they may belong to a change that isn't saved yet. With `dry_run`
+    ///  nothing is removed, and the returned stats describe what would be.
+    ///  Otherwise they describe what was.
+    pub async fn collect_garbage(
+        &self,
+        live: &HashSet<Hash>,
+        grace: Duration,
+        dry_run: bool,
+    ) -> Result<GcStats, BlobsStoreError> {
+        // Every blob we add is tagged with the time it was added. Tags are
+        //  named so that those created before the cutoff sort before it.
+        let cutoff = chrono::DateTime::<chrono::Utc>::from(SystemTime::now() - grace);
+        let cutoff = format!(
+            "{}{}",
+            AUTO_TAG_PREFIX,
+            cutoff.format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        );
+        let tags = self.inner.store().tags();
+        let mut expired: HashMap<Hash, Vec<Tag>> = HashMap::new();
+        let mut kept = HashSet::new();
+        let mut stream = tags
+            .list()
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        while let Some(tag) = stream.next().await {
+            let tag = tag.map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+            if live.contains(&tag.hash) {
+                continue;
+            }
+            let name = tag.name.as_ref();
+            if name.starts_with(AUTO_TAG_PREFIX.as_bytes()) && name < cutoff.as_bytes() {
+                expired.entry(tag.hash).or_default().push(tag.name);
+            } else {
+                kept.insert(tag.hash);
+            }
+        }
+
+        // Anything else not in `live` goes, whatever tags it has expire
+        let mut garbage = HashMap::new();
+        let mut stream = self
+            .blobs()
+            .list()
+            .stream()
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        while let Some(hash) = stream.next().await {
+            let hash = hash.map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+            if live.contains(&hash) || kept.contains(&hash) {
+                continue;
+            }
+            garbage.insert(hash, self.stored_size(&hash).await?.unwrap_or(0));
+        }
+        if dry_run {
+            return Ok(GcStats {
+                blobs: garbage.len() as u64,
+                bytes: garbage.values().sum(),
+            });
+        }
+
+        for hash in garbage.keys() {
+            for name in expired.remove(hash).unwrap_or_default() {
+                tags.delete(name).await?;
+            }
+        }
+        self.run_gc(live.clone()).await?;
+
+        // Report what is actually gone
+        let mut stats = GcStats::default();
+        for (hash, size) in garbage {
+            if self.stored_size(&hash).await?.is_none() {
+                stats.blobs += 1;
+                stats.bytes += size;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Bytes stored locally for a blob, complete or not, or None if it
+    ///  isn't stored at all
+    async fn stored_size(&self, hash: &Hash) -> Result<Option<u64>, BlobsStoreError> {
+        let stat = self
+            .blobs()
+            .status(*hash)
+            .await
+            .map_err(|err| BlobsStoreError::Default(anyhow!(err)))?;
+        Ok(match stat {
+            BlobStatus::Complete { size } => Some(size),
+            BlobStatus::Partial { size } => Some(size.unwrap_or(0)),
+            BlobStatus::NotFound => None,
+        })
+    }
+
     /// Read all hashes from a hash list blob
     /// Returns a Vec of all hashes in the list
     pub async fn read_hash_list(&self, list_hash: Hash) -> Result<Vec<Hash>, BlobsStoreError> {
@@ -429,6 +711,87 @@ mod tests {
         assert_eq!(store.get(&hash3).await.unwrap().as_ref(), data3);
     }
 
+    #[tokio::test]
+    async fn test_collect_garbage() {
+        let (store, _temp) = setup_test_store().await;
+
+        let kept = store.put(b"kept".to_vec()).await.unwrap();
+        let garbage = store.put(b"garbage".to_vec()).await.unwrap();
+        let live = HashSet::from([kept]);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Recent blobs are kept within the grace period
+        let stats = store
+            .collect_garbage(&live, Duration::from_secs(3600), false)
+            .await
+            .unwrap();
+        assert_eq!(stats, GcStats::default());
+        assert!(store.stat(&garbage).await.unwrap());
+
+        // A dry run only reports what would go
+        let stats = store
+            .collect_garbage(&live, Duration::ZERO, true)
+            .await
+            .unwrap();
+        assert_eq!(stats, GcStats { blobs: 1, bytes: 7 });
+        assert!(store.stat(&garbage).await.unwrap());
+
+        let stats = store
+            .collect_garbage(&live, Duration::ZERO, false)
+            .await
+            .unwrap();
+        assert_eq!(stats, GcStats { blobs: 1, bytes: 7 });
+        assert!(!store.stat(&garbage).await.unwrap());
+        assert_eq!(store.get(&kept).await.unwrap().as_ref(), b"kept");
+    }
+
+    #[tokio::test]
+    async fn test_hold_and_release() {
+        let (store, _temp) = setup_test_store().await;
+
+        let held = store.put(b"held".to_vec()).await.unwrap();
+        let other = store.put(b"other".to_vec()).await.unwrap();
+        store.hold("uploads/a", held).await.unwrap();
+        store.hold("uploads/ab", other).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Held blobs outlive the grace period
+        let stats = store
+            .collect_garbage(&HashSet::new(), Duration::ZERO, false)
+            .await
+            .unwrap();
+        assert_eq!(stats, GcStats::default());
+
+        // Releasing one name leaves names that only share its prefix held
+        store.release("uploads/a").await.unwrap();
+        store
+            .collect_garbage(&HashSet::new(), Duration::ZERO, false)
+            .await
+            .unwrap();
+        assert!(!store.stat(&held).await.unwrap());
+        assert!(store.stat(&other).await.unwrap());
+
+        // Releasing a parent name releases everything below it
+        store.release("uploads").await.unwrap();
+        store
+            .collect_garbage(&HashSet::new(), Duration::ZERO, false)
+            .await
+            .unwrap();
+        assert!(!store.stat(&other).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_evict() {
+        let (store, _temp) = setup_test_store().await;
+
+        let evicted = store.put(b"evicted".to_vec()).await.unwrap();
+        let kept = store.put(b"kept".to_vec()).await.unwrap();
+
+        store.evict(&HashSet::from([evicted])).await.unwrap();
+        assert!(!store.stat(&evicted).await.unwrap());
+        assert_eq!(store.get(&kept).await.unwrap().as_ref(), b"kept");
+    }
+
     #[tokio::test]
     async fn test_get_nonexistent() {
         let (store, _temp) = setup_test_store().await;
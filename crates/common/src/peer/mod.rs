@@ -10,7 +10,7 @@ use crate::crypto::SecretKey;
 mod blobs_store;
 pub mod jax_protocol;
 
-pub use blobs_store::{BlobsStore, BlobsStoreError};
+pub use blobs_store::{BlobsStore, BlobsStoreError, GcStats};
 pub use jax_protocol::{
     announce_to_peer, fetch_bucket, ping_peer, BucketStateProvider, JaxProtocol, PingRequest,
     PingResponse, SyncStatus, JAX_ALPN,
@@ -0,0 +1,2 @@
+-- Rollback history retention policies
+DROP TABLE bucket_retention;
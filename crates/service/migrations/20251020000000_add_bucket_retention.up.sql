@@ -0,0 +1,12 @@
+-- History retention policies, at most one per bucket
+CREATE TABLE bucket_retention (
+    -- the bucket the policy applies to
+    bucket_id TEXT PRIMARY KEY REFERENCES buckets (id) ON DELETE CASCADE,
+    -- the number of versions to keep, including the current one
+    max_versions INTEGER,
+    -- the number of days of history to keep
+    max_age_days INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -4,6 +4,7 @@ use std::{
 };
 
 use common::prelude::SecretKey;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub struct Config {
@@ -25,6 +26,11 @@ pub struct Config {
     /// address for the API server to listen on.
     ///  if not set then 0.0.0.0:3000 will be used
     pub api_listen_addr: Option<SocketAddr>,
+    /// address for the S3-compatible API to listen on,
+    ///  if not set then it isn't served
+    pub s3_listen_addr: Option<SocketAddr>,
+    /// access keys the S3-compatible API accepts requests signed with
+    pub s3_access_keys: Vec<S3AccessKey>,
 
     // data store configuration
     /// a path to a sqlite database, if not set then an
@@ -49,6 +55,8 @@ impl Default for Config {
             node_blobs_store_path: None,
             html_listen_addr: Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8080)),
             api_listen_addr: Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 3000)),
+            s3_listen_addr: None,
+            s3_access_keys: Vec::new(),
             sqlite_path: None,
             log_level: tracing::Level::INFO,
             ui_read_only: false,
@@ -57,6 +65,22 @@ impl Default for Config {
     }
 }
 
+/// A key pair S3 clients sign their requests with
+#[derive(Clone, Serialize, Deserialize)]
+pub struct S3AccessKey {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+// Keep secrets out of logs
+impl std::fmt::Debug for S3AccessKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3AccessKey")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
 // TODO (amiller68): real error handling
 #[allow(dead_code)]
 #[derive(Debug, thiserror::Error)]
@@ -76,16 +76,22 @@ impl Bucket {
         Ok(bucket)
     }
 
+    /// Point the bucket at a new version, as long as it's still at the version
+    ///  it was read at
+    ///
+    /// Fails with [`BucketError::Conflict`] if the link changed since, so an update
+    ///  made in the meantime isn't lost.
     pub async fn update_link(&self, new_link: Link, db: &Database) -> Result<(), BucketError> {
         let dcid: DCid = new_link.into();
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
             UPDATE buckets
             SET link = $1, updated_at = CURRENT_TIMESTAMP
-            WHERE id = $2
+            WHERE id = $2 AND link = $3
             "#,
             dcid,
-            self.id
+            self.id,
+            self.link
         )
         .execute(&**db)
         .await
@@ -99,6 +105,9 @@ impl Bucket {
             }
             _ => BucketError::Database(e),
         })?;
+        if result.rows_affected() == 0 {
+            return Err(BucketError::Conflict(self.id));
+        }
 
         Ok(())
     }
@@ -126,24 +135,29 @@ impl Bucket {
         Ok(())
     }
 
-    /// Update link and mark as synced
+    /// Update link and mark as synced, as long as the bucket is still at the
+    ///  version it was read at, as with [`Bucket::update_link`]
     pub async fn update_link_and_sync(
         &self,
         new_link: Link,
         db: &Database,
     ) -> Result<(), BucketError> {
         let dcid: DCid = new_link.into();
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
             UPDATE buckets
             SET link = $1, updated_at = CURRENT_TIMESTAMP, sync_status = 'synced', last_sync_attempt = CURRENT_TIMESTAMP, sync_error = NULL
-            WHERE id = $2
+            WHERE id = $2 AND link = $3
             "#,
             dcid,
-            self.id
+            self.id,
+            self.link
         )
         .execute(&**db)
         .await?;
+        if result.rows_affected() == 0 {
+            return Err(BucketError::Conflict(self.id));
+        }
 
         Ok(())
     }
@@ -204,6 +218,44 @@ impl Bucket {
 
         Ok(buckets)
     }
+
+    /// Every bucket, however many there are
+    pub async fn list_all(db: &Database) -> Result<Vec<Bucket>, BucketError> {
+        let buckets = sqlx::query_as!(
+            Bucket,
+            r#"
+            SELECT id as "id!: Uuid", name as "name!", link as "link!: DCid", created_at as "created_at!", updated_at as "updated_at!", sync_status as "sync_status!: SyncStatus", last_sync_attempt as "last_sync_attempt: OffsetDateTime", sync_error as "sync_error: String"
+            FROM buckets
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&**db)
+        .await?;
+
+        Ok(buckets)
+    }
+
+    /// Find a bucket by its ID, or failing that by its name, as buckets are
+    ///  named in WebDAV and S3 paths
+    pub async fn find(name_or_id: &str, db: &Database) -> Result<Option<Bucket>, BucketError> {
+        let id = Uuid::parse_str(name_or_id).ok();
+        let bucket = sqlx::query_as!(
+            Bucket,
+            r#"
+            SELECT id as "id!: Uuid", name as "name!", link as "link!: DCid", created_at as "created_at!", updated_at as "updated_at!", sync_status as "sync_status!: SyncStatus", last_sync_attempt as "last_sync_attempt: OffsetDateTime", sync_error as "sync_error: String"
+            FROM buckets
+            WHERE id = $1 OR name = $2
+            ORDER BY id = $1 DESC
+            LIMIT 1
+            "#,
+            id,
+            name_or_id
+        )
+        .fetch_optional(&**db)
+        .await?;
+
+        Ok(bucket)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -212,11 +264,14 @@ pub enum BucketError {
     Database(#[from] sqlx::Error),
     #[error("Bucket already exists: {0}")]
     AlreadyExists(String),
+    #[error("Bucket changed while it was being updated: {0}")]
+    Conflict(Uuid),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::linked_data::{Hash, LD_RAW_CODEC};
     use sqlx::SqlitePool;
 
     async fn setup_test_db() -> Database {
@@ -282,6 +337,7 @@ mod tests {
                 // Sometimes constraint violation comes through as generic DB error
                 assert!(e.to_string().contains("UNIQUE") || e.to_string().contains("constraint"));
             }
+            Err(e) => panic!("Expected a constraint violation but got {}", e),
             Ok(_) => panic!("Expected error but got Ok"),
         }
     }
@@ -336,4 +392,60 @@ mod tests {
 
         assert_eq!(buckets.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_update_link_conflict() {
+        let db = setup_test_db().await;
+
+        let id = Uuid::new_v4();
+        let bucket = Bucket::create(id, "test-bucket".to_string(), Link::default(), &db)
+            .await
+            .unwrap();
+        let format = *Link::default().format();
+        let first = Link::new(LD_RAW_CODEC, Hash::new(b"first"), format);
+        let second = Link::new(LD_RAW_CODEC, Hash::new(b"second"), format);
+
+        bucket.update_link(first.clone(), &db).await.unwrap();
+        // The bucket was read before the first update, which isn't overwritten
+        assert!(matches!(
+            bucket.update_link(second.clone(), &db).await,
+            Err(BucketError::Conflict(conflict)) if conflict == id
+        ));
+        assert!(matches!(
+            bucket.update_link_and_sync(second.clone(), &db).await,
+            Err(BucketError::Conflict(_))
+        ));
+        let bucket = Bucket::get_by_id(&id, &db).await.unwrap().unwrap();
+        assert_eq!(Link::from(bucket.link), first);
+
+        bucket.update_link(second.clone(), &db).await.unwrap();
+        let bucket = Bucket::get_by_id(&id, &db).await.unwrap().unwrap();
+        assert_eq!(Link::from(bucket.link), second);
+    }
+
+    #[tokio::test]
+    async fn test_find() {
+        let db = setup_test_db().await;
+
+        let id = Uuid::new_v4();
+        Bucket::create(id, "photos".to_string(), Link::default(), &db)
+            .await
+            .unwrap();
+        // A bucket named after another's ID doesn't hide that bucket
+        let other = Uuid::new_v4();
+        Bucket::create(other, id.to_string(), Link::default(), &db)
+            .await
+            .unwrap();
+
+        let found = Bucket::find("photos", &db).await.unwrap().unwrap();
+        assert_eq!(found.id, id);
+        let found = Bucket::find(&other.to_string(), &db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, other);
+        let found = Bucket::find(&id.to_string(), &db).await.unwrap().unwrap();
+        assert_eq!(found.id, id);
+        assert!(Bucket::find("missing", &db).await.unwrap().is_none());
+    }
 }
@@ -1,3 +1,5 @@
 pub mod bucket;
+pub mod retention;
 
 pub use bucket::{Bucket, SyncStatus};
+pub use retention::RetentionPolicy;
@@ -0,0 +1,168 @@
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::Database;
+
+/// How much of a bucket's history to keep
+///
+/// Versions are dropped once either limit is passed. The current version
+///  is always kept.
+#[derive(FromRow, Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub bucket_id: Uuid,
+    /// Number of versions to keep, including the current one
+    pub max_versions: Option<i64>,
+    /// Number of days of history to keep
+    pub max_age_days: Option<i64>,
+}
+
+impl RetentionPolicy {
+    /// Set the policy of a bucket, replacing any it had
+    pub async fn set(
+        bucket_id: Uuid,
+        max_versions: Option<i64>,
+        max_age_days: Option<i64>,
+        db: &Database,
+    ) -> Result<RetentionPolicy, RetentionPolicyError> {
+        let policy = sqlx::query_as!(
+            RetentionPolicy,
+            r#"
+            INSERT INTO bucket_retention (bucket_id, max_versions, max_age_days)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (bucket_id) DO UPDATE
+            SET max_versions = excluded.max_versions, max_age_days = excluded.max_age_days, updated_at = CURRENT_TIMESTAMP
+            RETURNING bucket_id as "bucket_id!: Uuid", max_versions, max_age_days
+            "#,
+            bucket_id,
+            max_versions,
+            max_age_days
+        )
+        .fetch_one(&**db)
+        .await?;
+
+        Ok(policy)
+    }
+
+    /// Remove the policy of a bucket, returning whether it had one
+    pub async fn clear(bucket_id: &Uuid, db: &Database) -> Result<bool, RetentionPolicyError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM bucket_retention
+            WHERE bucket_id = $1
+            "#,
+            bucket_id
+        )
+        .execute(&**db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_by_bucket_id(
+        bucket_id: &Uuid,
+        db: &Database,
+    ) -> Result<Option<RetentionPolicy>, RetentionPolicyError> {
+        let policy = sqlx::query_as!(
+            RetentionPolicy,
+            r#"
+            SELECT bucket_id as "bucket_id!: Uuid", max_versions, max_age_days
+            FROM bucket_retention
+            WHERE bucket_id = $1
+            "#,
+            bucket_id
+        )
+        .fetch_optional(&**db)
+        .await?;
+
+        Ok(policy)
+    }
+
+    /// Every bucket's policy
+    pub async fn list(db: &Database) -> Result<Vec<RetentionPolicy>, RetentionPolicyError> {
+        let policies = sqlx::query_as!(
+            RetentionPolicy,
+            r#"
+            SELECT bucket_id as "bucket_id!: Uuid", max_versions, max_age_days
+            FROM bucket_retention
+            "#
+        )
+        .fetch_all(&**db)
+        .await?;
+
+        Ok(policies)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RetentionPolicyError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> Database {
+        // Create in-memory database
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create in-memory database");
+
+        // Run migrations
+        sqlx::query(
+            r#"
+            CREATE TABLE bucket_retention (
+                bucket_id TEXT PRIMARY KEY,
+                max_versions INTEGER,
+                max_age_days INTEGER,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .expect("Failed to create table");
+
+        Database::new(pool)
+    }
+
+    #[tokio::test]
+    async fn test_set_policy() {
+        let db = setup_test_db().await;
+
+        let id = Uuid::new_v4();
+        let policy = RetentionPolicy::set(id, Some(100), None, &db)
+            .await
+            .unwrap();
+        assert_eq!(policy.bucket_id, id);
+        assert_eq!(policy.max_versions, Some(100));
+        assert_eq!(policy.max_age_days, None);
+
+        // Setting it again replaces it
+        let policy = RetentionPolicy::set(id, None, Some(30), &db).await.unwrap();
+        assert_eq!(
+            RetentionPolicy::get_by_bucket_id(&id, &db).await.unwrap(),
+            Some(policy.clone())
+        );
+        assert_eq!(RetentionPolicy::list(&db).await.unwrap(), vec![policy]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_policy() {
+        let db = setup_test_db().await;
+
+        let id = Uuid::new_v4();
+        RetentionPolicy::set(id, Some(10), Some(7), &db)
+            .await
+            .unwrap();
+        assert!(RetentionPolicy::clear(&id, &db).await.unwrap());
+        assert!(!RetentionPolicy::clear(&id, &db).await.unwrap());
+        assert_eq!(
+            RetentionPolicy::get_by_bucket_id(&id, &db).await.unwrap(),
+            None
+        );
+    }
+}
@@ -38,6 +38,34 @@ impl ApiClient {
         }
     }
 
+    /// Call an endpoint that responds with newline-delimited JSON, passing
+    /// each line to `on_line` as soon as it arrives
+    pub async fn call_lines<T: ApiRequest>(
+        &mut self,
+        request: T,
+        mut on_line: impl FnMut(T::Response),
+    ) -> Result<(), ApiError> {
+        let request_builder = request.build_request(&self.remote, &self.client);
+        let mut response = request_builder.send().await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::HttpStatus(
+                response.status(),
+                response.text().await?,
+            ));
+        }
+
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                on_line(serde_json::from_slice(&line)?);
+            }
+        }
+        Ok(())
+    }
+
     /// Resolve a bucket name to a UUID
     /// Returns the first bucket with an exact name match
     pub async fn resolve_bucket_name(&mut self, name: &str) -> Result<Uuid, ApiError> {
@@ -8,4 +8,6 @@ pub enum ApiError {
     UrlParse(#[from] url::ParseError),
     #[error("HTTP status {0}: {1}")]
     HttpStatus(StatusCode, String),
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
 }
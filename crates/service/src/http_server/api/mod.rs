@@ -1,5 +1,8 @@
 use axum::Router;
-use http::header::{ACCEPT, CONTENT_TYPE, ORIGIN};
+use http::header::{
+    ACCEPT, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    ORIGIN, RANGE,
+};
 use http::Method;
 use tower_http::cors::{Any, CorsLayer};
 
@@ -11,7 +14,8 @@ use crate::ServiceState;
 pub fn router(state: ServiceState) -> Router<ServiceState> {
     let cors_layer = CorsLayer::new()
         .allow_methods(vec![Method::GET, Method::POST])
-        .allow_headers(vec![ACCEPT, CONTENT_TYPE, ORIGIN])
+        .allow_headers(vec![ACCEPT, CONTENT_TYPE, ORIGIN, RANGE, IF_NONE_MATCH])
+        .expose_headers(vec![ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, ETAG])
         .allow_origin(Any)
         .allow_credentials(false);
 
@@ -0,0 +1,116 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::peer::GcStats;
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::{GcReport, MountOpsError};
+use crate::ServiceState;
+
+/// How many previous versions of each bucket are kept by default
+pub const DEFAULT_HISTORY_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct GcRequest {
+    /// Report what would be removed without removing anything
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Number of previous versions of each bucket to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcResponse {
+    pub dry_run: bool,
+    /// Number of previous versions of each bucket that were kept
+    pub depth: usize,
+    /// Number of buckets whose history was walked
+    pub buckets: usize,
+    /// Buckets whose pin set was pruned, saving a new version of each
+    pub pruned: Vec<Uuid>,
+    /// Number of blobs still referenced by a bucket
+    pub retained: usize,
+    /// What was, or with a dry run would be, removed from the blob store
+    pub removed: GcStats,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<GcRequest>,
+) -> Result<impl IntoResponse, GcError> {
+    let depth = req.depth.unwrap_or(DEFAULT_HISTORY_DEPTH);
+
+    tracing::info!(
+        "Collecting garbage, keeping {} previous versions of each bucket{}",
+        depth,
+        if req.dry_run { " (dry run)" } else { "" }
+    );
+
+    // Run mount operations in blocking task
+    let report = tokio::task::spawn_blocking(move || -> Result<GcReport, MountOpsError> {
+        tokio::runtime::Handle::current().block_on(async {
+            crate::mount_ops::collect_garbage(Some(depth), req.dry_run, &state).await
+        })
+    })
+    .await
+    .map_err(|e| GcError::MountOps(format!("Task join error: {}", e)))??;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(GcResponse {
+            dry_run: req.dry_run,
+            depth,
+            buckets: report.buckets,
+            pruned: report.pruned,
+            retained: report.retained,
+            removed: report.removed,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GcError {
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for GcError {
+    fn from(err: MountOpsError) -> Self {
+        GcError::MountOps(err.to_string())
+    }
+}
+
+impl IntoResponse for GcError {
+    fn into_response(self) -> Response {
+        match self {
+            GcError::MountOps(msg) => {
+                tracing::error!("Garbage collection failed: {}", msg);
+                (
+                    http::StatusCode::INTERNAL_SERVER_ERROR,
+                    "Unexpected error".to_string(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for GcRequest {
+    type Response = GcResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/admin/gc").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
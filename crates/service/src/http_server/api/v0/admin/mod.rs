@@ -0,0 +1,15 @@
+use axum::routing::post;
+use axum::Router;
+
+use crate::ServiceState;
+
+pub mod gc;
+
+// Re-export for convenience
+pub use gc::{GcRequest, GcResponse};
+
+pub fn router(state: ServiceState) -> Router<ServiceState> {
+    Router::new()
+        .route("/gc", post(gc::handler))
+        .with_state(state)
+}
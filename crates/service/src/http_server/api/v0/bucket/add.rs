@@ -1,15 +1,22 @@
+use axum::body::Bytes;
+use axum::extract::multipart::Field;
 use axum::extract::{Multipart, State};
 use axum::response::{IntoResponse, Response};
+use futures::channel::mpsc;
+use futures::SinkExt;
 use serde::{Deserialize, Serialize};
-use std::io::Cursor;
 use std::path::PathBuf;
+use tokio_util::io::StreamReader;
 use uuid::Uuid;
 
-use common::prelude::Link;
+use common::prelude::{Link, MountError};
 
 use crate::mount_ops::{add_data_to_bucket, MountOpsError};
 use crate::ServiceState;
 
+/// Number of uploaded chunks buffered between the request and the mount
+const UPLOAD_CHANNEL_SIZE: usize = 16;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "clap", derive(clap::Args))]
 pub struct AddRequest {
@@ -20,6 +27,11 @@ pub struct AddRequest {
     /// Path in bucket where file should be mounted
     #[cfg_attr(feature = "clap", arg(long))]
     pub mount_path: String,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,9 +48,11 @@ pub async fn handler(
 ) -> Result<impl IntoResponse, AddError> {
     let mut bucket_id: Option<Uuid> = None;
     let mut mount_path: Option<String> = None;
-    let mut file_data: Option<Vec<u8>> = None;
+    let mut message: Option<String> = None;
+    let mut added: Option<(String, Link, String)> = None;
 
-    // Parse multipart form data
+    // Parse multipart form data. The file is streamed straight into the
+    //  bucket, so the other fields must come before it.
     while let Some(field) = multipart
         .next_field()
         .await
@@ -65,25 +79,53 @@ pub async fn handler(
                         .map_err(|e| AddError::MultipartError(e.to_string()))?,
                 );
             }
-            "file" => {
-                file_data = Some(
+            "message" => {
+                message = Some(
                     field
-                        .bytes()
+                        .text()
                         .await
-                        .map_err(|e| AddError::MultipartError(e.to_string()))?
-                        .to_vec(),
+                        .map_err(|e| AddError::MultipartError(e.to_string()))?,
                 );
             }
+            "file" => {
+                let bucket_id = bucket_id.ok_or_else(|| {
+                    AddError::InvalidRequest("bucket_id is required before file".into())
+                })?;
+                let mount_path = mount_path.take().ok_or_else(|| {
+                    AddError::InvalidRequest("mount_path is required before file".into())
+                })?;
+                added =
+                    Some(stream_file(field, bucket_id, mount_path, message.take(), &state).await?);
+                break;
+            }
             _ => {}
         }
     }
 
-    let bucket_id =
-        bucket_id.ok_or_else(|| AddError::InvalidRequest("bucket_id is required".into()))?;
-    let mount_path =
-        mount_path.ok_or_else(|| AddError::InvalidRequest("mount_path is required".into()))?;
-    let file_data = file_data.ok_or_else(|| AddError::InvalidRequest("file is required".into()))?;
+    let (mount_path, new_bucket_link, mime_type) =
+        added.ok_or_else(|| AddError::InvalidRequest("file is required".into()))?;
 
+    Ok((
+        http::StatusCode::OK,
+        axum::Json(AddResponse {
+            mount_path,
+            link: new_bucket_link,
+            mime_type,
+        }),
+    )
+        .into_response())
+}
+
+/// Stream a multipart file field into the bucket at `mount_path`
+///
+/// Returns the mount path, the new bucket link and the detected MIME type
+async fn stream_file(
+    mut field: Field<'_>,
+    bucket_id: Uuid,
+    mount_path: String,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<(String, Link, String), AddError> {
     // Validate mount path
     let mount_path_buf = PathBuf::from(&mount_path);
     if !mount_path_buf.is_absolute() {
@@ -102,36 +144,48 @@ pub async fn handler(
         mime_type
     );
 
-    // Detect MIME type from file extension
-    let mime_type = mime_guess::from_path(&mount_path_buf)
-        .first_or_octet_stream()
-        .to_string();
-    // Clone for blocking task
-    let mount_path_clone = mount_path_buf.clone();
-    let state_clone = state.clone();
+    // The field borrows the request, so it is forwarded to the
+    //  mount through a bounded channel as it arrives
+    let (mut sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(UPLOAD_CHANNEL_SIZE);
+    let reader = StreamReader::new(receiver);
 
     // Run file operations in blocking task
-    let new_bucket_link = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
-        // Create a cursor from the file data
-        let reader = Cursor::new(file_data);
+    let state_clone = state.clone();
+    let add_task = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
         tokio::runtime::Handle::current().block_on(async {
             let bucket_link =
-                add_data_to_bucket(bucket_id, mount_path_clone, reader, &state_clone).await?;
+                add_data_to_bucket(bucket_id, mount_path_buf, reader, message, &state_clone)
+                    .await?;
             Ok(bucket_link)
         })
-    })
-    .await
-    .map_err(|e| AddError::Default(anyhow::anyhow!("Task join error: {}", e)))??;
+    });
+
+    let mut multipart_error = None;
+    loop {
+        let chunk = match field.chunk().await {
+            Ok(Some(chunk)) => Ok(chunk),
+            Ok(None) => break,
+            Err(e) => {
+                // Fail the add rather than letting it store a truncated file
+                multipart_error = Some(e.to_string());
+                Err(std::io::Error::other(e.to_string()))
+            }
+        };
+        // A closed channel means the add already failed; its error is returned below
+        if sender.send(chunk).await.is_err() || multipart_error.is_some() {
+            break;
+        }
+    }
+    drop(sender);
 
-    Ok((
-        http::StatusCode::OK,
-        axum::Json(AddResponse {
-            mount_path,
-            link: new_bucket_link,
-            mime_type,
-        }),
-    )
-        .into_response())
+    let result = add_task
+        .await
+        .map_err(|e| AddError::Default(anyhow::anyhow!("Task join error: {}", e)))?;
+    if let Some(msg) = multipart_error {
+        return Err(AddError::MultipartError(msg));
+    }
+
+    Ok((mount_path, result?, mime_type))
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -167,6 +221,9 @@ impl IntoResponse for AddError {
                 format!("Bad request: {}", msg),
             )
                 .into_response(),
+            AddError::MountOps(MountOpsError::Mount(e @ MountError::ReadOnly)) => {
+                (http::StatusCode::FORBIDDEN, e.to_string()).into_response()
+            }
             AddError::Database(_) | AddError::Default(_) | AddError::MountOps(_) => (
                 http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Unexpected error".to_string(),
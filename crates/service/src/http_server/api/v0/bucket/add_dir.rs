@@ -0,0 +1,400 @@
+use axum::body::Bytes;
+use axum::extract::multipart::Field;
+use axum::extract::{Multipart, State};
+use axum::response::{IntoResponse, Response};
+use futures::channel::mpsc;
+use futures::SinkExt;
+use glob::{MatchOptions, Pattern, PatternError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use common::prelude::{Link, MountError};
+
+use crate::mount_ops::{add_files_to_bucket, MountOpsError, UploadFile, UploadInfo};
+use crate::ServiceState;
+
+/// Number of uploaded chunks buffered between the request and the mount
+const UPLOAD_CHANNEL_SIZE: usize = 16;
+
+type FileReader = StreamReader<mpsc::Receiver<std::io::Result<Bytes>>, Bytes>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddDirResponse {
+    /// Directory in the bucket the files were added under
+    pub mount_path: String,
+    pub link: Link,
+    /// Paths of the files added
+    pub added: Vec<String>,
+    /// Paths removed
+    #[serde(default)]
+    pub removed: Vec<String>,
+    /// Paths moved, from and to
+    #[serde(default)]
+    pub moved: Vec<(String, String)>,
+    /// Relative paths of the files left out by an ignore glob
+    pub ignored: Vec<String>,
+}
+
+/// Globs naming files to leave out of a directory upload
+///
+/// A glob matches a file if it matches the file's whole path relative to
+///  the directory, or any one of its components, so `*.log` and `.git`
+///  apply at any depth, while `build/*.o` only applies at the top.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreGlobs(Vec<Pattern>);
+
+impl IgnoreGlobs {
+    pub fn new<S: AsRef<str>>(globs: &[S]) -> Result<Self, PatternError> {
+        let patterns = globs
+            .iter()
+            .map(|glob| Pattern::new(glob.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self(patterns))
+    }
+
+    /// Whether a relative path, to a file or a directory, is ignored
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.0.iter().any(|pattern| {
+            pattern.matches_path_with(path, options)
+                || path
+                    .iter()
+                    .any(|part| pattern.matches_with(&part.to_string_lossy(), options))
+        })
+    }
+}
+
+/// Add a tree of files to a bucket as a single version
+///
+/// Takes the multipart fields `bucket_id`, `mount_path` (the directory to
+///  add under, defaults to the root), and optionally `message`, any
+///  number of `ignore` globs, `remove` paths and `move_from` and
+///  `move_to` pairs, followed by a `file` part per file, each with its
+///  path relative to `mount_path` as its file name, and optionally
+///  preceded by an `mtime` field (unix milliseconds) to record with it.
+///  Paths to remove or move are relative to `mount_path` too, and are
+///  changed in the same version. Every file is stored as it arrives, and
+///  the tree is rebuilt once at the end.
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AddDirError> {
+    let mut fields = DirFields::default();
+    let mut mtime: Option<u64> = None;
+    let mut upload: Option<Upload> = None;
+
+    // Files are stored as they arrive, so the other fields must come first
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AddDirError::MultipartError(e.to_string()))?
+    {
+        let field_name = field.name().unwrap_or("").to_string();
+
+        match field_name.as_str() {
+            "bucket_id" if upload.is_none() => {
+                let text = field_text(field).await?;
+                fields.bucket_id = Some(
+                    Uuid::parse_str(&text)
+                        .map_err(|_| AddDirError::InvalidRequest("Invalid bucket_id".into()))?,
+                );
+            }
+            "mount_path" if upload.is_none() => fields.mount_path = field_text(field).await?,
+            "message" if upload.is_none() => fields.message = Some(field_text(field).await?),
+            "ignore" if upload.is_none() => fields.ignore.push(field_text(field).await?),
+            "remove" if upload.is_none() => fields.remove.push(field_text(field).await?),
+            "move_from" if upload.is_none() => fields.move_from = Some(field_text(field).await?),
+            "move_to" if upload.is_none() => {
+                let from = fields.move_from.take().ok_or_else(|| {
+                    AddDirError::InvalidRequest("move_to must follow a move_from".into())
+                })?;
+                fields.moves.push((from, field_text(field).await?));
+            }
+            "mtime" => {
+                let text = field_text(field).await?;
+                mtime = Some(
+                    text.parse()
+                        .map_err(|_| AddDirError::InvalidRequest("Invalid mtime".into()))?,
+                );
+            }
+            "file" => {
+                if upload.is_none() {
+                    upload = Some(Upload::start(std::mem::take(&mut fields), &state)?);
+                }
+                let upload = upload.as_mut().expect("upload was just started");
+                if !upload.send(field, mtime.take()).await? {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A request may only remove or move paths
+    let upload = match upload {
+        Some(upload) => upload,
+        None if !fields.remove.is_empty() || !fields.moves.is_empty() => {
+            Upload::start(fields, &state)?
+        }
+        None => return Err(AddDirError::InvalidRequest("No files given".into())),
+    };
+    let response = upload.finish().await?;
+
+    tracing::info!(
+        "Added {} files to {}, removing {}, moving {} and ignoring {}",
+        response.added.len(),
+        response.mount_path,
+        response.removed.len(),
+        response.moved.len(),
+        response.ignored.len()
+    );
+    Ok((http::StatusCode::OK, axum::Json(response)).into_response())
+}
+
+async fn field_text(field: Field<'_>) -> Result<String, AddDirError> {
+    field
+        .text()
+        .await
+        .map_err(|e| AddDirError::MultipartError(e.to_string()))
+}
+
+/// The fields of a directory upload that come before its files
+struct DirFields {
+    bucket_id: Option<Uuid>,
+    mount_path: String,
+    message: Option<String>,
+    ignore: Vec<String>,
+    remove: Vec<String>,
+    moves: Vec<(String, String)>,
+    // a `move_from` waiting for its `move_to`
+    move_from: Option<String>,
+}
+
+impl Default for DirFields {
+    fn default() -> Self {
+        Self {
+            bucket_id: None,
+            mount_path: "/".to_string(),
+            message: None,
+            ignore: Vec::new(),
+            remove: Vec::new(),
+            moves: Vec::new(),
+            move_from: None,
+        }
+    }
+}
+
+/// A directory upload in progress, feeding files one at a time to the
+///  blocking task that stores them
+struct Upload {
+    mount_path: String,
+    globs: IgnoreGlobs,
+    ignored: Vec<String>,
+    files: Option<flume::Sender<UploadFile<FileReader>>>,
+    task: tokio::task::JoinHandle<Result<UploadInfo, MountOpsError>>,
+}
+
+impl Upload {
+    fn start(fields: DirFields, state: &ServiceState) -> Result<Self, AddDirError> {
+        let bucket_id = fields.bucket_id.ok_or_else(|| {
+            AddDirError::InvalidRequest("bucket_id is required before files".into())
+        })?;
+        let globs = IgnoreGlobs::new(&fields.ignore)
+            .map_err(|e| AddDirError::InvalidRequest(format!("Invalid glob: {}", e)))?;
+        let mount_path = fields.mount_path;
+        let mount_path_buf = PathBuf::from(&mount_path);
+        if !mount_path_buf.is_absolute() {
+            return Err(AddDirError::InvalidPath(
+                "Mount path must be absolute".into(),
+            ));
+        }
+
+        // One file is forwarded at a time, so the channel never holds more
+        let (files, receiver) = flume::bounded(1);
+        let state = state.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(add_files_to_bucket(
+                bucket_id,
+                mount_path_buf,
+                receiver,
+                fields.remove,
+                fields.moves,
+                fields.message,
+                &state,
+            ))
+        });
+
+        Ok(Self {
+            mount_path,
+            globs,
+            ignored: Vec::new(),
+            files: Some(files),
+            task,
+        })
+    }
+
+    /// Stream a file part to the mount, unless it's ignored
+    ///
+    /// Returns false once the mount has stopped taking files, after an
+    ///  error that `finish` returns.
+    async fn send(
+        &mut self,
+        mut field: Field<'_>,
+        mtime: Option<u64>,
+    ) -> Result<bool, AddDirError> {
+        let name = field
+            .file_name()
+            .ok_or_else(|| AddDirError::InvalidRequest("Every file needs a file name".into()))?
+            .to_string();
+        if self.globs.is_ignored(Path::new(&name)) {
+            self.ignored.push(name);
+            return Ok(true);
+        }
+
+        // The field borrows the request, so it is forwarded to the
+        //  mount through a bounded channel as it arrives
+        let (mut sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(UPLOAD_CHANNEL_SIZE);
+        let files = self
+            .files
+            .as_ref()
+            .expect("files are sent before finishing");
+        let file = UploadFile {
+            name,
+            mtime,
+            reader: StreamReader::new(receiver),
+        };
+        if files.send_async(file).await.is_err() {
+            return Ok(false);
+        }
+
+        loop {
+            let chunk = match field.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => return Ok(true),
+                Err(e) => {
+                    // Fail the upload rather than storing a truncated file
+                    let _ = sender.send(Err(std::io::Error::other(e.to_string()))).await;
+                    self.files = None;
+                    let _ = (&mut self.task).await;
+                    return Err(AddDirError::MultipartError(e.to_string()));
+                }
+            };
+            // A closed channel means storing failed; its error is returned by `finish`
+            if sender.send(Ok(chunk)).await.is_err() {
+                return Ok(false);
+            }
+        }
+    }
+
+    async fn finish(mut self) -> Result<AddDirResponse, AddDirError> {
+        self.files = None;
+        let info = self
+            .task
+            .await
+            .map_err(|e| AddDirError::MountOps(format!("Task join error: {}", e)))??;
+        Ok(AddDirResponse {
+            mount_path: self.mount_path,
+            link: info.link,
+            added: info.added,
+            removed: info.removed,
+            moved: info.moved,
+            ignored: self.ignored,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AddDirError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Multipart error: {0}")]
+    MultipartError(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for AddDirError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => AddDirError::BucketNotFound(id),
+            MountOpsError::InvalidPath(msg) => AddDirError::InvalidPath(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                AddDirError::PathNotFound(path.display().to_string())
+            }
+            // A file where the bucket has a directory, or under a file
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                AddDirError::InvalidPath(format!("{} conflicts with the bucket", path.display()))
+            }
+            MountOpsError::Mount(e @ MountError::ReadOnly) => AddDirError::Forbidden(e.to_string()),
+            e => AddDirError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for AddDirError {
+    fn into_response(self) -> Response {
+        match self {
+            AddDirError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            AddDirError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            AddDirError::InvalidPath(msg)
+            | AddDirError::InvalidRequest(msg)
+            | AddDirError::MultipartError(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            AddDirError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            AddDirError::MountOps(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_globs() {
+        let globs = IgnoreGlobs::new(&["*.log", ".git", "build/*.o"]).unwrap();
+        let ignored = |path: &str| globs.is_ignored(Path::new(path));
+
+        assert!(ignored("debug.log"));
+        assert!(ignored("logs/today.log"));
+        assert!(ignored(".git"));
+        assert!(ignored(".git/HEAD"));
+        assert!(ignored("build/main.o"));
+
+        assert!(!ignored("src/main.rs"));
+        assert!(!ignored("debug.log.txt"));
+        assert!(!ignored("src/build/main.o"));
+        assert!(!ignored("build/sub/main.o"));
+
+        assert!(IgnoreGlobs::new(&["[unclosed"]).is_err());
+    }
+}
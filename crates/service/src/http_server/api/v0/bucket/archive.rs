@@ -0,0 +1,342 @@
+use axum::body::{Body, Bytes};
+use axum::extract::multipart::Field;
+use axum::extract::{Multipart, Query, State};
+use axum::response::{IntoResponse, Response};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio_util::io::{ReaderStream, StreamReader};
+use uuid::Uuid;
+
+use common::linked_data::Hash;
+use common::prelude::{Link, MountError};
+
+use crate::mount_ops::{add_archive_to_bucket, MountOpsError};
+use crate::ServiceState;
+
+pub use crate::mount_ops::ArchiveFormat;
+
+/// Number of uploaded chunks buffered between the request and the mount
+const UPLOAD_CHANNEL_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveQuery {
+    /// Bucket ID to read from
+    pub bucket_id: Uuid,
+    /// Path in bucket to archive, a directory or a single file
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Version of the bucket to read from (defaults to the current version)
+    #[serde(default)]
+    pub version: Option<Hash>,
+    /// Format of the archive (defaults to zip)
+    #[serde(default = "default_format")]
+    pub format: ArchiveFormat,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+fn default_format() -> ArchiveFormat {
+    ArchiveFormat::Zip
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveResponse {
+    /// Directory in the bucket the archive was unpacked into
+    pub mount_path: String,
+    pub link: Link,
+    /// Paths of the files added
+    pub added: Vec<String>,
+    /// Names of archive entries that weren't files or directories
+    pub skipped: Vec<String>,
+}
+
+/// Stream the decrypted contents of a directory, or a single file, as a
+///  tar or zip archive
+///
+/// Files are decrypted as the archive is sent, so this works on
+///  directories of any size. Entries are named relative to the path.
+#[axum::debug_handler]
+pub async fn download_handler(
+    State(state): State<ServiceState>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, ArchiveError> {
+    let archive = crate::mount_ops::get_bucket_archive(
+        query.bucket_id,
+        query.path,
+        query.version,
+        query.format,
+        &state,
+    )
+    .await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(query.format.content_type()),
+    );
+    let file_name = archive.name.replace(['"', '/', '\\'], "");
+    if let Ok(value) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}.{}\"",
+        file_name,
+        query.format.extension()
+    )) {
+        headers.insert(CONTENT_DISPOSITION, value);
+    }
+
+    // An archive that fails part way aborts the response, rather than
+    //  ending it early with what looks like a whole archive
+    let done = archive.done;
+    let trailer = futures::stream::once(async move {
+        match done.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(e)),
+            Err(_) => Some(Err(std::io::Error::other("archive stopped"))),
+        }
+    })
+    .filter_map(futures::future::ready);
+    let body = Body::from_stream(ReaderStream::new(archive.reader).chain(trailer));
+    Ok((http::StatusCode::OK, headers, body).into_response())
+}
+
+/// Unpack an uploaded tar or zip archive into a directory of a bucket,
+///  saving every file in it as a single version
+///
+/// Takes the multipart fields `bucket_id`, `mount_path` (the directory),
+///  and optionally `message` and `format`, followed by the archive as
+///  `file`. Without `format` it is guessed from the archive's file name.
+#[axum::debug_handler]
+pub async fn upload_handler(
+    State(state): State<ServiceState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ArchiveError> {
+    let mut bucket_id: Option<Uuid> = None;
+    let mut mount_path: Option<String> = None;
+    let mut message: Option<String> = None;
+    let mut format: Option<ArchiveFormat> = None;
+    let mut response: Option<ArchiveResponse> = None;
+
+    // The archive is unpacked as it arrives, so the other fields must
+    //  come before it
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ArchiveError::MultipartError(e.to_string()))?
+    {
+        let field_name = field.name().unwrap_or("").to_string();
+
+        match field_name.as_str() {
+            "bucket_id" => {
+                let text = field_text(field).await?;
+                bucket_id = Some(
+                    Uuid::parse_str(&text)
+                        .map_err(|_| ArchiveError::InvalidRequest("Invalid bucket_id".into()))?,
+                );
+            }
+            "mount_path" => mount_path = Some(field_text(field).await?),
+            "message" => message = Some(field_text(field).await?),
+            "format" => {
+                let text = field_text(field).await?;
+                format = Some(text.parse().map_err(ArchiveError::InvalidRequest)?);
+            }
+            "file" => {
+                let bucket_id = bucket_id.ok_or_else(|| {
+                    ArchiveError::InvalidRequest("bucket_id is required before file".into())
+                })?;
+                let mount_path = mount_path.take().unwrap_or_else(default_path);
+                let format = format
+                    .or_else(|| field.file_name().and_then(ArchiveFormat::from_file_name))
+                    .ok_or_else(|| {
+                        ArchiveError::InvalidRequest(
+                            "format is required unless the file is named .tar or .zip".into(),
+                        )
+                    })?;
+                response = Some(
+                    stream_archive(field, bucket_id, mount_path, format, message.take(), &state)
+                        .await?,
+                );
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let response =
+        response.ok_or_else(|| ArchiveError::InvalidRequest("file is required".into()))?;
+    Ok((http::StatusCode::OK, axum::Json(response)).into_response())
+}
+
+async fn field_text(field: Field<'_>) -> Result<String, ArchiveError> {
+    field
+        .text()
+        .await
+        .map_err(|e| ArchiveError::MultipartError(e.to_string()))
+}
+
+/// Stream a multipart archive field into the bucket under `mount_path`
+async fn stream_archive(
+    mut field: Field<'_>,
+    bucket_id: Uuid,
+    mount_path: String,
+    format: ArchiveFormat,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<ArchiveResponse, ArchiveError> {
+    let mount_path_buf = PathBuf::from(&mount_path);
+    if !mount_path_buf.is_absolute() {
+        return Err(ArchiveError::InvalidPath(
+            "Mount path must be absolute".into(),
+        ));
+    }
+
+    tracing::info!(
+        "Unpacking {} archive into bucket {} at {}",
+        format,
+        bucket_id,
+        mount_path
+    );
+
+    // The field borrows the request, so it is forwarded to the
+    //  mount through a bounded channel as it arrives
+    let (mut sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(UPLOAD_CHANNEL_SIZE);
+    let reader = StreamReader::new(receiver);
+
+    let state_clone = state.clone();
+    let add_task = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(add_archive_to_bucket(
+            bucket_id,
+            mount_path_buf,
+            format,
+            reader,
+            message,
+            &state_clone,
+        ))
+    });
+
+    let mut multipart_error = None;
+    loop {
+        let chunk = match field.chunk().await {
+            Ok(Some(chunk)) => Ok(chunk),
+            Ok(None) => break,
+            Err(e) => {
+                // Fail the upload rather than saving part of the archive
+                multipart_error = Some(e.to_string());
+                Err(std::io::Error::other(e.to_string()))
+            }
+        };
+        // A closed channel means the unpacking already failed; its error is returned below
+        if sender.send(chunk).await.is_err() || multipart_error.is_some() {
+            break;
+        }
+    }
+    drop(sender);
+
+    let result = add_task
+        .await
+        .map_err(|e| ArchiveError::MountOps(format!("Task join error: {}", e)))?;
+    if let Some(msg) = multipart_error {
+        return Err(ArchiveError::MultipartError(msg));
+    }
+    let upload = result?;
+
+    tracing::info!(
+        "Unpacked {} files into bucket {} at {}, skipping {}",
+        upload.added.len(),
+        bucket_id,
+        mount_path,
+        upload.skipped.len()
+    );
+
+    Ok(ArchiveResponse {
+        mount_path,
+        link: upload.link,
+        added: upload.added,
+        skipped: upload.skipped,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Version not found: {0}")]
+    VersionNotFound(Hash),
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Multipart error: {0}")]
+    MultipartError(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for ArchiveError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => ArchiveError::BucketNotFound(id),
+            MountOpsError::InvalidPath(msg) => ArchiveError::InvalidPath(msg),
+            MountOpsError::VersionNotFound(hash) => ArchiveError::VersionNotFound(hash),
+            MountOpsError::InvalidArchive(msg) => ArchiveError::InvalidArchive(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                ArchiveError::PathNotFound(path.display().to_string())
+            }
+            // A file in the archive where the bucket has a directory, or the other way round
+            MountOpsError::Mount(MountError::PathNotNode(path))
+            | MountOpsError::Mount(MountError::PathAlreadyExists(path)) => {
+                ArchiveError::InvalidPath(format!("{} conflicts with the bucket", path.display()))
+            }
+            MountOpsError::Mount(e @ MountError::ReadOnly) => {
+                ArchiveError::Forbidden(e.to_string())
+            }
+            e => ArchiveError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ArchiveError {
+    fn into_response(self) -> Response {
+        match self {
+            ArchiveError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            ArchiveError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            ArchiveError::VersionNotFound(hash) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Version not found: {}", hash),
+            )
+                .into_response(),
+            ArchiveError::InvalidPath(msg)
+            | ArchiveError::InvalidArchive(msg)
+            | ArchiveError::InvalidRequest(msg)
+            | ArchiveError::MultipartError(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            ArchiveError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            ArchiveError::MountOps(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
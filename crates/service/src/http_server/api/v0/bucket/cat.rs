@@ -1,13 +1,22 @@
-use axum::extract::{Json, State};
+use std::ops::{Bound, Range};
+
+use axum::body::Body;
+use axum::extract::{Json, Query, State};
 use axum::response::{IntoResponse, Response};
 use base64::Engine;
+use headers::{AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, IfNoneMatch};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
 use reqwest::{Client, RequestBuilder, Url};
 use serde::{Deserialize, Serialize};
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
+use common::linked_data::Hash;
 use common::prelude::MountError;
 
 use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
 use crate::ServiceState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +29,12 @@ pub struct CatRequest {
     /// Path in bucket to read
     #[cfg_attr(feature = "clap", arg(long))]
     pub path: String,
+
+    /// Version of the bucket to read from, as the hash of its manifest
+    ///  (defaults to the current version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub version: Option<Hash>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,14 +52,9 @@ pub async fn handler(
     Json(req): Json<CatRequest>,
 ) -> Result<impl IntoResponse, CatError> {
     // Use mount_ops to get file content
-    let file_content = crate::mount_ops::get_file_content(req.bucket_id, req.path.clone(), &state)
-        .await
-        .map_err(|e| match e {
-            crate::mount_ops::MountOpsError::BucketNotFound(id) => CatError::BucketNotFound(id),
-            crate::mount_ops::MountOpsError::InvalidPath(msg) => CatError::InvalidPath(msg),
-            crate::mount_ops::MountOpsError::Mount(me) => CatError::Mount(me),
-            e => CatError::MountOps(e.to_string()),
-        })?;
+    let file_content =
+        crate::mount_ops::get_file_content(req.bucket_id, req.path.clone(), req.version, &state)
+            .await?;
 
     // Encode as base64 for JSON transport
     let content = base64::engine::general_purpose::STANDARD.encode(&file_content.data);
@@ -62,18 +72,176 @@ pub async fn handler(
         .into_response())
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct CatQuery {
+    /// Bucket ID to read from
+    pub bucket_id: Uuid,
+    /// Path in bucket to read
+    pub path: String,
+    /// Version of the bucket to read from (defaults to the current version)
+    #[serde(default)]
+    pub version: Option<Hash>,
+    /// Serve the file as an attachment rather than inline
+    #[serde(default)]
+    pub download: bool,
+}
+
+/// Stream a file's content as the response body
+///
+/// Unlike the JSON handler this never holds the whole file in memory, and
+///  supports single `Range` requests and `If-None-Match` revalidation. The
+///  ETag is the link to the file's encrypted content.
+#[axum::debug_handler]
+pub async fn download_handler(
+    State(state): State<ServiceState>,
+    Query(query): Query<CatQuery>,
+    headers: HeaderMap,
+) -> Result<Response, CatError> {
+    let range_header = headers.typed_get::<headers::Range>();
+    let select_range = {
+        let range_header = range_header.clone();
+        move |size| match resolve_range(range_header.as_ref(), size) {
+            RangeRequest::Full => None,
+            RangeRequest::Partial(range) => Some(range),
+            // Nothing will be served, so don't read anything
+            RangeRequest::Unsatisfiable => Some(size..size),
+        }
+    };
+    let file = crate::mount_ops::get_file_reader(
+        query.bucket_id,
+        query.path.clone(),
+        query.version,
+        select_range,
+        &state,
+    )
+    .await?;
+
+    let etag: ETag = format!("\"{}\"", file.link.hash())
+        .parse()
+        .map_err(|_| CatError::MountOps("invalid etag".into()))?;
+    let size = file.reader.size();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.typed_insert(etag.clone());
+    response_headers.typed_insert(AcceptRanges::bytes());
+
+    if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
+        if !if_none_match.precondition_passes(&etag) {
+            return Ok((http::StatusCode::NOT_MODIFIED, response_headers).into_response());
+        }
+    }
+
+    let status = match resolve_range(range_header.as_ref(), size) {
+        RangeRequest::Full => http::StatusCode::OK,
+        RangeRequest::Partial(range) => {
+            let content_range = ContentRange::bytes(range, size)
+                .map_err(|_| CatError::MountOps("invalid content range".into()))?;
+            response_headers.typed_insert(content_range);
+            http::StatusCode::PARTIAL_CONTENT
+        }
+        RangeRequest::Unsatisfiable => {
+            response_headers.typed_insert(ContentRange::unsatisfied_bytes(size));
+            return Ok((http::StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+
+    response_headers.typed_insert(ContentLength(file.reader.len()));
+    if let Ok(content_type) = HeaderValue::from_str(&file.mime_type) {
+        response_headers.insert(CONTENT_TYPE, content_type);
+    }
+    let file_name = std::path::Path::new(&query.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    let disposition = if query.download {
+        "attachment"
+    } else {
+        "inline"
+    };
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("{}; filename=\"{}\"", disposition, file_name))
+    {
+        response_headers.insert(CONTENT_DISPOSITION, value);
+    }
+
+    let body = Body::from_stream(ReaderStream::new(file.reader));
+    Ok((status, response_headers, body).into_response())
+}
+
+/// How a `Range` header applies to a file of a given size
+#[derive(Debug, PartialEq)]
+pub(crate) enum RangeRequest {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+pub(crate) fn resolve_range(header: Option<&headers::Range>, size: u64) -> RangeRequest {
+    let Some(header) = header else {
+        return RangeRequest::Full;
+    };
+
+    let ranges: Vec<_> = header.satisfiable_ranges(size).collect();
+    let (start, end) = match ranges.as_slice() {
+        [] => return RangeRequest::Unsatisfiable,
+        [range] => *range,
+        // Multipart responses aren't supported, and ignoring the header is allowed
+        _ => return RangeRequest::Full,
+    };
+
+    let start = match start {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match end {
+        Bound::Included(end) => end.saturating_add(1),
+        Bound::Excluded(end) => end,
+        Bound::Unbounded => size,
+    }
+    .min(size);
+
+    if start >= end {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(start..end)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CatError {
     #[error("Bucket not found: {0}")]
     BucketNotFound(Uuid),
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Version not found: {0}")]
+    VersionNotFound(Hash),
     #[error("MountOps error: {0}")]
     MountOps(String),
     #[error("Mount error: {0}")]
     Mount(#[from] MountError),
 }
 
+impl From<MountOpsError> for CatError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => CatError::BucketNotFound(id),
+            MountOpsError::InvalidPath(msg) => CatError::InvalidPath(msg),
+            MountOpsError::VersionNotFound(hash) => CatError::VersionNotFound(hash),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                CatError::PathNotFound(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                CatError::InvalidPath(format!("{} is not a file", path.display()))
+            }
+            MountOpsError::Mount(me) => CatError::Mount(me),
+            e => CatError::MountOps(e.to_string()),
+        }
+    }
+}
+
 impl IntoResponse for CatError {
     fn into_response(self) -> Response {
         match self {
@@ -87,6 +255,16 @@ impl IntoResponse for CatError {
                 format!("Invalid path: {}", msg),
             )
                 .into_response(),
+            CatError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            CatError::VersionNotFound(hash) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Version not found: {}", hash),
+            )
+                .into_response(),
             CatError::MountOps(_) | CatError::Mount(_) => (
                 http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Unexpected error".to_string(),
@@ -105,3 +283,54 @@ impl ApiRequest for CatRequest {
         client.post(full_url).json(&self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: &'static str) -> headers::Range {
+        let mut map = HeaderMap::new();
+        map.insert(http::header::RANGE, HeaderValue::from_static(value));
+        map.typed_get::<headers::Range>().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(resolve_range(None, 100), RangeRequest::Full);
+        assert_eq!(
+            resolve_range(Some(&range("bytes=0-9")), 100),
+            RangeRequest::Partial(0..10)
+        );
+        assert_eq!(
+            resolve_range(Some(&range("bytes=90-")), 100),
+            RangeRequest::Partial(90..100)
+        );
+        // Suffix ranges count back from the end
+        assert_eq!(
+            resolve_range(Some(&range("bytes=-10")), 100),
+            RangeRequest::Partial(90..100)
+        );
+        // Ends past the size are clamped
+        assert_eq!(
+            resolve_range(Some(&range("bytes=50-1000")), 100),
+            RangeRequest::Partial(50..100)
+        );
+        assert_eq!(
+            resolve_range(Some(&range("bytes=100-")), 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            resolve_range(Some(&range("bytes=-200")), 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            resolve_range(Some(&range("bytes=0-0")), 0),
+            RangeRequest::Unsatisfiable
+        );
+        // Multiple ranges fall back to the whole file
+        assert_eq!(
+            resolve_range(Some(&range("bytes=0-1,5-6")), 100),
+            RangeRequest::Full
+        );
+    }
+}
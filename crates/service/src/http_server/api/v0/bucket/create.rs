@@ -50,9 +50,7 @@ pub async fn handler(
             crate::database::models::bucket::BucketError::AlreadyExists(name) => {
                 CreateError::AlreadyExists(name)
             }
-            crate::database::models::bucket::BucketError::Database(e) => {
-                CreateError::Database(e.to_string())
-            }
+            e => CreateError::Database(e.to_string()),
         })?;
 
     Ok((
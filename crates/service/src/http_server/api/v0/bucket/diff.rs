@@ -0,0 +1,130 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::linked_data::Hash;
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct DiffRequest {
+    /// Bucket ID to compare versions of
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Version to compare from, as the hash of its manifest
+    ///  (defaults to the version before `to`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub from: Option<Hash>,
+
+    /// Version to compare to, as the hash of its manifest
+    ///  (defaults to the current version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub to: Option<Hash>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffResponse {
+    /// Link to the version compared from
+    pub from: Link,
+    /// Link to the version compared to
+    pub to: Link,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub moved: Vec<MovedPath>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedPath {
+    pub from: String,
+    pub to: String,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<DiffRequest>,
+) -> Result<impl IntoResponse, DiffError> {
+    let diff = crate::mount_ops::get_bucket_diff(req.bucket_id, req.from, req.to, &state).await?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(DiffResponse {
+            from: diff.from,
+            to: diff.to,
+            added: diff.added,
+            removed: diff.removed,
+            modified: diff.modified,
+            moved: diff
+                .moved
+                .into_iter()
+                .map(|(from, to)| MovedPath { from, to })
+                .collect(),
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiffError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Version not found: {0}")]
+    VersionNotFound(Hash),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(#[from] MountError),
+}
+
+impl From<MountOpsError> for DiffError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => DiffError::BucketNotFound(id),
+            MountOpsError::VersionNotFound(hash) => DiffError::VersionNotFound(hash),
+            MountOpsError::Mount(me) => DiffError::Mount(me),
+            e => DiffError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for DiffError {
+    fn into_response(self) -> Response {
+        match self {
+            DiffError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            DiffError::VersionNotFound(hash) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Version not found: {}", hash),
+            )
+                .into_response(),
+            DiffError::MountOps(_) | DiffError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for DiffRequest {
+    type Response = DiffResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/diff").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
@@ -0,0 +1,100 @@
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use common::prelude::MountError;
+
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+/// Media type of CAR archives
+pub const CAR_CONTENT_TYPE: &str = "application/vnd.ipld.car";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportQuery {
+    /// Bucket ID to export
+    pub bucket_id: Uuid,
+}
+
+/// Stream the current version of a bucket, and its stored history, as a
+///  CARv1 archive rooted at the version's manifest
+///
+/// The blobs are sent as they're stored, encrypted, so the archive can be
+///  carried to another node and imported with `/api/v0/bucket/import`.
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ExportError> {
+    let archive = crate::mount_ops::export_bucket(query.bucket_id, &state).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(CAR_CONTENT_TYPE));
+    let file_name = archive.name.replace(['"', '/', '\\'], "");
+    if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{}.car\"", file_name))
+    {
+        headers.insert(CONTENT_DISPOSITION, value);
+    }
+
+    // An export that fails part way aborts the response, rather than
+    //  ending it early with what looks like a whole archive
+    let done = archive.done;
+    let trailer = futures::stream::once(async move {
+        match done.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(std::io::Error::other(e.to_string()))),
+            Err(_) => Some(Err(std::io::Error::other("export stopped"))),
+        }
+    })
+    .filter_map(futures::future::ready);
+    let body = Body::from_stream(ReaderStream::new(archive.reader).chain(trailer));
+    Ok((http::StatusCode::OK, headers, body).into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Incomplete bucket: {0}")]
+    Incomplete(MountError),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for ExportError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => ExportError::BucketNotFound(id),
+            MountOpsError::Mount(e @ MountError::BlobNotFound(_)) => ExportError::Incomplete(e),
+            e => ExportError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        match self {
+            ExportError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            ExportError::Incomplete(e) => (
+                http::StatusCode::CONFLICT,
+                format!("Bucket is missing data, run fsck to repair it: {}", e),
+            )
+                .into_response(),
+            ExportError::MountOps(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::bucket::{FsckProblem, FsckReport};
+use common::linked_data::Hash;
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct FsckRequest {
+    /// Bucket ID to check
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Fetch missing or corrupt blobs again from the bucket's peers
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub repair: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsckResponse {
+    pub bucket_id: Uuid,
+    /// Link to the version checked
+    pub link: Link,
+    /// What the check found, before any repair
+    pub report: FsckReport,
+    /// Blobs fetched again from peers and now intact
+    pub repaired: Vec<Hash>,
+    /// Problems left after repairing, or all of them without a repair
+    pub remaining: Vec<FsckProblem>,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<FsckRequest>,
+) -> Result<impl IntoResponse, FsckError> {
+    let fsck = crate::mount_ops::fsck_bucket(req.bucket_id, req.repair, &state).await?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(FsckResponse {
+            bucket_id: req.bucket_id,
+            link: fsck.link,
+            report: fsck.report,
+            repaired: fsck.repaired,
+            remaining: fsck.remaining,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FsckError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(#[from] MountError),
+}
+
+impl From<MountOpsError> for FsckError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => FsckError::BucketNotFound(id),
+            MountOpsError::Mount(me) => FsckError::Mount(me),
+            e => FsckError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for FsckError {
+    fn into_response(self) -> Response {
+        match self {
+            FsckError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            FsckError::MountOps(_) | FsckError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for FsckRequest {
+    type Response = FsckResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/fsck").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
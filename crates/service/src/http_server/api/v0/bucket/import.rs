@@ -0,0 +1,93 @@
+use axum::body::Body;
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use common::linked_data::CodecError;
+use common::prelude::{Link, MountError};
+
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub bucket_id: Uuid,
+    pub name: String,
+    /// Link to the version in the archive
+    pub link: Link,
+    /// Whether the bucket was created, rather than already known
+    pub created: bool,
+}
+
+/// Import a CAR archive written by `/api/v0/bucket/export`, sent as the
+///  request body
+///
+/// The archive is streamed into the blob store as it arrives.
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    body: Body,
+) -> Result<impl IntoResponse, ImportError> {
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let import = crate::mount_ops::import_bucket(reader, &state).await?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(ImportResponse {
+            bucket_id: import.bucket_id,
+            name: import.name,
+            link: import.link,
+            created: import.created,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(MountError),
+    #[error("Not shared with this node")]
+    Forbidden,
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for ImportError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::Mount(MountError::ShareNotFound) => ImportError::Forbidden,
+            MountOpsError::Mount(
+                e @ (MountError::Car(_)
+                | MountError::BlobNotFound(_)
+                | MountError::Codec(CodecError::UnsupportedFormat(..))),
+            ) => ImportError::InvalidArchive(e),
+            e => ImportError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ImportError {
+    fn into_response(self) -> Response {
+        match self {
+            ImportError::InvalidArchive(e) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Invalid archive: {}", e),
+            )
+                .into_response(),
+            ImportError::Forbidden => (
+                http::StatusCode::FORBIDDEN,
+                "The bucket in the archive isn't shared with this node".to_string(),
+            )
+                .into_response(),
+            ImportError::MountOps(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct LogRequest {
+    /// Bucket ID to show the history of
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Maximum number of versions to return (defaults to all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogResponse {
+    /// Versions of the bucket, newest first
+    pub versions: Vec<VersionEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionEntry {
+    /// Link to the version's manifest; its hash identifies the version
+    pub link: Link,
+    pub previous: Option<Link>,
+    /// Unix timestamp (seconds) of when the version was created, if known
+    pub timestamp: Option<u64>,
+    /// Public key (hex) of the node that created the version, if known
+    #[serde(default)]
+    pub author: Option<String>,
+    /// The author's description of the change
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<LogRequest>,
+) -> Result<impl IntoResponse, LogError> {
+    let versions = crate::mount_ops::get_bucket_history(req.bucket_id, req.limit, &state)
+        .await?
+        .into_iter()
+        .map(|version| VersionEntry {
+            link: version.link,
+            previous: version.previous,
+            timestamp: version.timestamp,
+            author: version.author.map(|author| author.to_hex()),
+            message: version.message,
+        })
+        .collect();
+
+    Ok((http::StatusCode::OK, Json(LogResponse { versions })).into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(#[from] MountError),
+}
+
+impl From<MountOpsError> for LogError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => LogError::BucketNotFound(id),
+            MountOpsError::Mount(me) => LogError::Mount(me),
+            e => LogError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for LogError {
+    fn into_response(self) -> Response {
+        match self {
+            LogError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            LogError::MountOps(_) | LogError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for LogRequest {
+    type Response = LogResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/log").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
@@ -4,6 +4,7 @@ use reqwest::{Client, RequestBuilder, Url};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use common::linked_data::Hash;
 use common::prelude::{Link, MountError};
 
 use crate::http_server::api::client::ApiRequest;
@@ -25,6 +26,12 @@ pub struct LsRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[cfg_attr(feature = "clap", arg(long))]
     pub deep: Option<bool>,
+
+    /// Version of the bucket to list, as the hash of its manifest
+    ///  (defaults to the current version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub version: Option<Hash>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +46,15 @@ pub struct PathInfo {
     pub link: Link,
     pub is_dir: bool,
     pub mime_type: String,
+    /// Size of a file's plaintext, if recorded when it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Modification time of a file in unix milliseconds, if its uploader gave one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<u64>,
+    /// BLAKE3 hash of a file's plaintext, if recorded when it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
 }
 
 #[axum::debug_handler]
@@ -49,13 +65,20 @@ pub async fn handler(
     let deep = req.deep.unwrap_or(false);
 
     // Use mount_ops to list bucket contents
-    let items = crate::mount_ops::list_bucket_contents(req.bucket_id, req.path, deep, &state)
-        .await
-        .map_err(|e| match e {
-            crate::mount_ops::MountOpsError::BucketNotFound(id) => LsError::BucketNotFound(id),
-            crate::mount_ops::MountOpsError::Mount(me) => LsError::Mount(me),
-            e => LsError::MountOps(e.to_string()),
-        })?;
+    let items =
+        crate::mount_ops::list_bucket_contents(req.bucket_id, req.path, deep, req.version, &state)
+            .await
+            .map_err(|e| match e {
+                crate::mount_ops::MountOpsError::BucketNotFound(id) => LsError::BucketNotFound(id),
+                crate::mount_ops::MountOpsError::VersionNotFound(hash) => {
+                    LsError::VersionNotFound(hash)
+                }
+                crate::mount_ops::MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                    LsError::PathNotFound(path.display().to_string())
+                }
+                crate::mount_ops::MountOpsError::Mount(me) => LsError::Mount(me),
+                e => LsError::MountOps(e.to_string()),
+            })?;
 
     // Convert to response format
     let path_infos = items
@@ -66,6 +89,9 @@ pub async fn handler(
             link: item.link,
             is_dir: item.is_dir,
             mime_type: item.mime_type,
+            size: item.size,
+            mtime: item.mtime,
+            hash: item.hash,
         })
         .collect();
 
@@ -76,6 +102,10 @@ pub async fn handler(
 pub enum LsError {
     #[error("Bucket not found: {0}")]
     BucketNotFound(Uuid),
+    #[error("Version not found: {0}")]
+    VersionNotFound(Hash),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
     #[error("MountOps error: {0}")]
     MountOps(String),
     #[error("Mount error: {0}")]
@@ -90,6 +120,16 @@ impl IntoResponse for LsError {
                 format!("Bucket not found: {}", id),
             )
                 .into_response(),
+            LsError::VersionNotFound(hash) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Version not found: {}", hash),
+            )
+                .into_response(),
+            LsError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
             LsError::MountOps(_) | LsError::Mount(_) => (
                 http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Unexpected error".to_string(),
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use http::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::bucket::{MountError, RekeyProgress, MANIFEST_FORMAT};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::{self, MountOpsError};
+use crate::ServiceState;
+
+/// How often progress is reported while rewriting a bucket
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct MigrateRequest {
+    /// Bucket ID to migrate
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrateResponse {
+    pub bucket_id: Uuid,
+    /// The format the bucket is now in
+    pub format: u32,
+    /// Link to the migrated version, missing if the bucket was already
+    ///  in the newest format
+    pub new_bucket_link: Option<String>,
+}
+
+/// A line of the newline-delimited JSON response to a migrate request
+///
+/// Any number of `progress` events are sent while the bucket is
+///  rewritten, followed by either `done` or `error`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrateEvent {
+    Progress(RekeyProgress),
+    Done(MigrateResponse),
+    Error(String),
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<MigrateRequest>,
+) -> Result<impl IntoResponse, MigrateError> {
+    // Check what we can before responding, so these failures get a
+    //  status code rather than an error event in the stream
+    match mount_ops::get_bucket_role(req.bucket_id, &state).await? {
+        Some(role) if role.can_write() => {}
+        _ => return Err(MigrateError::Forbidden(MountError::ReadOnly.to_string())),
+    }
+
+    // Run the migration in a blocking task, streaming its progress back
+    let (tx, rx) = flume::unbounded();
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            let progress_tx = tx.clone();
+            let mut last_report: Option<Instant> = None;
+            let on_progress = |progress: &RekeyProgress| {
+                if last_report.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+                    last_report = Some(Instant::now());
+                    let _ = progress_tx.send(MigrateEvent::Progress(progress.clone()));
+                }
+            };
+            let result =
+                mount_ops::migrate_bucket(req.bucket_id, on_progress, req.message, &state).await;
+
+            let event = match result {
+                Ok(new_bucket_link) => {
+                    tracing::info!("Bucket {} is in format {}", req.bucket_id, MANIFEST_FORMAT);
+                    MigrateEvent::Done(MigrateResponse {
+                        bucket_id: req.bucket_id,
+                        format: MANIFEST_FORMAT,
+                        new_bucket_link: new_bucket_link.map(|link| link.hash().to_string()),
+                    })
+                }
+                Err(e) => {
+                    tracing::error!("Failed to migrate bucket {}: {}", req.bucket_id, e);
+                    MigrateEvent::Error(e.to_string())
+                }
+            };
+            let _ = tx.send(event);
+        })
+    });
+
+    let lines = rx
+        .into_stream()
+        .map(|event| serde_json::to_string(&event).map(|line| line + "\n"));
+    Ok((
+        http::StatusCode::OK,
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrateError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("Bucket changed while it was being updated: {0}")]
+    Conflict(Uuid),
+    #[error("Mount error: {0}")]
+    Mount(String),
+}
+
+impl From<MountOpsError> for MigrateError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => MigrateError::BucketNotFound(id),
+            MountOpsError::Database(msg) => MigrateError::Database(msg),
+            MountOpsError::Conflict(id) => MigrateError::Conflict(id),
+            MountOpsError::Mount(e) => MigrateError::Mount(e.to_string()),
+            MountOpsError::ShareNotFound => MigrateError::Mount("Share not found".to_string()),
+            MountOpsError::CryptoError(msg) => MigrateError::Mount(msg),
+            MountOpsError::ShareError(msg) => MigrateError::Mount(msg),
+            MountOpsError::InvalidPath(msg) => MigrateError::Mount(msg),
+            MountOpsError::InvalidArchive(msg) => MigrateError::Mount(msg),
+            MountOpsError::VersionNotFound(hash) => {
+                MigrateError::Mount(format!("Version not found: {}", hash))
+            }
+        }
+    }
+}
+
+impl IntoResponse for MigrateError {
+    fn into_response(self) -> Response {
+        match self {
+            MigrateError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            MigrateError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            MigrateError::Conflict(id) => (
+                http::StatusCode::CONFLICT,
+                format!(
+                    "Bucket {} changed while it was being updated, try again",
+                    id
+                ),
+            )
+                .into_response(),
+            MigrateError::Database(_) | MigrateError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+// NOTE: the response is a stream of events, one per line, so use
+//  `ApiClient::call_lines` rather than `ApiClient::call`
+impl ApiRequest for MigrateRequest {
+    type Response = MigrateEvent;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/migrate").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
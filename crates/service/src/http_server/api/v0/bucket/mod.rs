@@ -1,30 +1,86 @@
-use axum::routing::post;
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
 use axum::Router;
 
 use crate::ServiceState;
 
 pub mod add;
+pub mod add_dir;
+pub mod archive;
 pub mod cat;
 pub mod create;
+pub mod diff;
+pub mod export;
+pub mod fsck;
+pub mod import;
 pub mod list;
+pub mod log;
 pub mod ls;
+pub mod migrate;
+pub mod mv;
+pub mod retention;
+pub mod revert;
+pub mod rm;
 pub mod share;
+pub mod unshare;
 
 // Re-export for convenience
 pub use add::{AddRequest, AddResponse};
+pub use add_dir::{AddDirResponse, IgnoreGlobs};
+pub use archive::{ArchiveFormat, ArchiveQuery, ArchiveResponse};
 pub use cat::{CatRequest, CatResponse};
 pub use create::{CreateRequest, CreateResponse};
+pub use diff::{DiffRequest, DiffResponse};
+pub use export::ExportQuery;
+pub use fsck::{FsckRequest, FsckResponse};
+pub use import::ImportResponse;
 pub use list::{ListRequest, ListResponse};
+pub use log::{LogRequest, LogResponse};
 pub use ls::{LsRequest, LsResponse};
+pub use migrate::{MigrateEvent, MigrateRequest, MigrateResponse};
+pub use mv::{MvRequest, MvResponse};
+pub use retention::{RetentionRequest, RetentionResponse};
+pub use revert::{RevertRequest, RevertResponse};
+pub use rm::{RmRequest, RmResponse};
 pub use share::{ShareRequest, ShareResponse};
+pub use unshare::{UnshareEvent, UnshareRequest, UnshareResponse};
 
 pub fn router(state: ServiceState) -> Router<ServiceState> {
     Router::new()
         .route("/", post(create::handler))
         .route("/list", post(list::handler))
-        .route("/add", post(add::handler))
+        // Uploads are streamed into the bucket, so they aren't bound by the API body limit
+        .route(
+            "/add",
+            post(add::handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/add_dir",
+            post(add_dir::handler).layer(DefaultBodyLimit::disable()),
+        )
+        // Archives are unpacked as they're uploaded, so they aren't bound by the API body limit
+        .route(
+            "/archive",
+            get(archive::download_handler)
+                .merge(post(archive::upload_handler).layer(DefaultBodyLimit::disable())),
+        )
         .route("/ls", post(ls::handler))
-        .route("/cat", post(cat::handler))
+        .route("/cat", post(cat::handler).merge(get(cat::download_handler)))
+        .route("/mv", post(mv::handler))
+        .route("/rm", post(rm::handler))
+        .route("/log", post(log::handler))
+        .route("/diff", post(diff::handler))
+        .route("/revert", post(revert::handler))
         .route("/share", post(share::handler))
+        .route("/unshare", post(unshare::handler))
+        .route("/migrate", post(migrate::handler))
+        .route("/retention", post(retention::handler))
+        .route("/fsck", post(fsck::handler))
+        .route("/export", get(export::handler))
+        // Archives are streamed into the blob store, so they aren't bound by the API body limit
+        .route(
+            "/import",
+            post(import::handler).layer(DefaultBodyLimit::disable()),
+        )
         .with_state(state)
 }
@@ -0,0 +1,158 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct MvRequest {
+    /// Bucket ID to move within
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Current path of the file or directory
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub from: String,
+
+    /// New path of the file or directory
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub to: String,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MvResponse {
+    pub from: String,
+    pub to: String,
+    pub link: Link,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<MvRequest>,
+) -> Result<impl IntoResponse, MvError> {
+    let from = PathBuf::from(&req.from);
+    let to = PathBuf::from(&req.to);
+
+    tracing::info!(
+        "Moving {} to {} in bucket {}",
+        req.from,
+        req.to,
+        req.bucket_id
+    );
+
+    // Run mount operations in blocking task
+    let new_bucket_link = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
+        tokio::runtime::Handle::current().block_on(async {
+            crate::mount_ops::move_path_in_bucket(req.bucket_id, from, to, req.message, &state)
+                .await
+        })
+    })
+    .await
+    .map_err(|e| MvError::MountOps(format!("Task join error: {}", e)))??;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(MvResponse {
+            from: req.from,
+            to: req.to,
+            link: new_bucket_link,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MvError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Path already exists: {0}")]
+    PathAlreadyExists(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(MountError),
+}
+
+impl From<MountOpsError> for MvError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => MvError::BucketNotFound(id),
+            MountOpsError::InvalidPath(msg) => MvError::InvalidPath(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                MvError::PathNotFound(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                MvError::InvalidPath(format!("{} is not a directory", path.display()))
+            }
+            MountOpsError::Mount(MountError::PathAlreadyExists(path)) => {
+                MvError::PathAlreadyExists(path.display().to_string())
+            }
+            MountOpsError::Mount(e @ MountError::ReadOnly) => MvError::Forbidden(e.to_string()),
+            MountOpsError::Mount(e) => MvError::Mount(e),
+            e => MvError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for MvError {
+    fn into_response(self) -> Response {
+        match self {
+            MvError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            MvError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            MvError::PathAlreadyExists(path) => (
+                http::StatusCode::CONFLICT,
+                format!("Path already exists: {}", path),
+            )
+                .into_response(),
+            MvError::InvalidPath(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            MvError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            MvError::MountOps(_) | MvError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for MvRequest {
+    type Response = MvResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/mv").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
@@ -0,0 +1,156 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::prelude::MountError;
+
+use crate::database::models::RetentionPolicy;
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::{self, MountOpsError};
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct RetentionRequest {
+    /// Bucket ID to get or set the retention policy of
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Number of versions to keep, including the current one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub max_versions: Option<u32>,
+
+    /// Number of days of history to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub max_age_days: Option<u32>,
+
+    /// Remove the policy and keep all history from now on
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub clear: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionResponse {
+    pub bucket_id: Uuid,
+    /// Number of versions kept, missing if not limited
+    pub max_versions: Option<u32>,
+    /// Number of days of history kept, missing if not limited
+    pub max_age_days: Option<u32>,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<RetentionRequest>,
+) -> Result<impl IntoResponse, RetentionError> {
+    if req.max_versions == Some(0) {
+        return Err(RetentionError::InvalidRequest(
+            "max_versions must keep at least the current version".into(),
+        ));
+    }
+
+    let policy = if req.clear || req.max_versions.is_some() || req.max_age_days.is_some() {
+        // Run mount operations in blocking task
+        let policy = tokio::task::spawn_blocking(
+            move || -> Result<Option<RetentionPolicy>, MountOpsError> {
+                tokio::runtime::Handle::current().block_on(async {
+                    mount_ops::set_bucket_retention(
+                        req.bucket_id,
+                        req.max_versions,
+                        req.max_age_days,
+                        &state,
+                    )
+                    .await
+                })
+            },
+        )
+        .await
+        .map_err(|e| RetentionError::MountOps(format!("Task join error: {}", e)))??;
+        tracing::info!(
+            "Set retention policy of bucket {} to {:?}",
+            req.bucket_id,
+            policy
+        );
+        policy
+    } else {
+        mount_ops::get_bucket_retention(req.bucket_id, &state).await?
+    };
+
+    Ok((
+        http::StatusCode::OK,
+        Json(RetentionResponse {
+            bucket_id: req.bucket_id,
+            max_versions: policy
+                .as_ref()
+                .and_then(|policy| policy.max_versions)
+                .map(|max| max as u32),
+            max_age_days: policy
+                .as_ref()
+                .and_then(|policy| policy.max_age_days)
+                .map(|days| days as u32),
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RetentionError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for RetentionError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => RetentionError::BucketNotFound(id),
+            MountOpsError::Mount(MountError::NotOwner) => {
+                RetentionError::Forbidden(MountError::NotOwner.to_string())
+            }
+            e => RetentionError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for RetentionError {
+    fn into_response(self) -> Response {
+        match self {
+            RetentionError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            RetentionError::InvalidRequest(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            RetentionError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            RetentionError::MountOps(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for RetentionRequest {
+    type Response = RetentionResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/retention").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
@@ -0,0 +1,159 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use common::linked_data::Hash;
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct RevertRequest {
+    /// Bucket ID to revert
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Version to revert to, as the hash of its manifest
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub version: Hash,
+
+    /// Only revert this file or directory (defaults to the whole bucket)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub path: Option<String>,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevertResponse {
+    /// Version that was reverted to
+    pub version: Hash,
+    pub path: Option<String>,
+    /// Link to the new version created by the revert
+    pub link: Link,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<RevertRequest>,
+) -> Result<impl IntoResponse, RevertError> {
+    let path = req.path.as_ref().map(PathBuf::from);
+
+    tracing::info!(
+        "Reverting {} in bucket {} to version {}",
+        req.path.as_deref().unwrap_or("/"),
+        req.bucket_id,
+        req.version
+    );
+
+    // Run mount operations in blocking task
+    let new_bucket_link = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
+        tokio::runtime::Handle::current().block_on(async {
+            crate::mount_ops::revert_bucket(req.bucket_id, req.version, path, req.message, &state)
+                .await
+        })
+    })
+    .await
+    .map_err(|e| RevertError::MountOps(format!("Task join error: {}", e)))??;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(RevertResponse {
+            version: req.version,
+            path: req.path,
+            link: new_bucket_link,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RevertError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Version not found: {0}")]
+    VersionNotFound(Hash),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(MountError),
+}
+
+impl From<MountOpsError> for RevertError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => RevertError::BucketNotFound(id),
+            MountOpsError::VersionNotFound(hash) => RevertError::VersionNotFound(hash),
+            MountOpsError::InvalidPath(msg) => RevertError::InvalidPath(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                RevertError::PathNotFound(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                RevertError::InvalidPath(format!("{} is not a directory", path.display()))
+            }
+            MountOpsError::Mount(e @ MountError::ReadOnly) => RevertError::Forbidden(e.to_string()),
+            MountOpsError::Mount(e) => RevertError::Mount(e),
+            e => RevertError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for RevertError {
+    fn into_response(self) -> Response {
+        match self {
+            RevertError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            RevertError::VersionNotFound(hash) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Version not found: {}", hash),
+            )
+                .into_response(),
+            RevertError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            RevertError::InvalidPath(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            RevertError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            RevertError::MountOps(_) | RevertError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for RevertRequest {
+    type Response = RevertResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/revert").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
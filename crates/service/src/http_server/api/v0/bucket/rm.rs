@@ -0,0 +1,153 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use common::prelude::{Link, MountError};
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct RmRequest {
+    /// Bucket ID to remove from
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Path of the file or directory to remove
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub path: String,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RmResponse {
+    pub path: String,
+    pub link: Link,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<RmRequest>,
+) -> Result<impl IntoResponse, RmError> {
+    let path = PathBuf::from(&req.path);
+
+    tracing::info!("Removing {} from bucket {}", req.path, req.bucket_id);
+
+    // Run mount operations in blocking task
+    let new_bucket_link = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
+        tokio::runtime::Handle::current().block_on(async {
+            crate::mount_ops::remove_path_from_bucket(req.bucket_id, path, req.message, &state)
+                .await
+        })
+    })
+    .await
+    .map_err(|e| RmError::MountOps(format!("Task join error: {}", e)))??;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(RmResponse {
+            path: req.path,
+            link: new_bucket_link,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RmError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+    #[error("Mount error: {0}")]
+    Mount(MountError),
+}
+
+impl From<MountOpsError> for RmError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => RmError::BucketNotFound(id),
+            MountOpsError::InvalidPath(msg) => RmError::InvalidPath(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                RmError::PathNotFound(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                RmError::InvalidPath(format!("{} is not a directory", path.display()))
+            }
+            MountOpsError::Mount(e @ MountError::ReadOnly) => RmError::Forbidden(e.to_string()),
+            MountOpsError::Mount(e) => RmError::Mount(e),
+            e => RmError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for RmError {
+    fn into_response(self) -> Response {
+        match self {
+            RmError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            RmError::PathNotFound(path) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Path not found: {}", path),
+            )
+                .into_response(),
+            RmError::InvalidPath(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Bad request: {}", msg),
+            )
+                .into_response(),
+            RmError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            RmError::MountOps(_) | RmError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+impl ApiRequest for RmRequest {
+    type Response = RmResponse;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/rm").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_is_forbidden() {
+        let response = RmError::from(MountOpsError::Mount(MountError::ReadOnly)).into_response();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let response = RmError::from(MountOpsError::Mount(MountError::PathNotFound(
+            PathBuf::from("/missing"),
+        )))
+        .into_response();
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    }
+}
@@ -5,6 +5,7 @@ use reqwest::{Client, RequestBuilder, Url};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use common::bucket::{MountError, PrincipalRole};
 use common::crypto::PublicKey;
 
 use crate::http_server::api::client::ApiRequest;
@@ -21,12 +22,27 @@ pub struct ShareRequest {
     /// Public key of the peer to share with (hex-encoded)
     #[cfg_attr(feature = "clap", arg(long))]
     pub peer_public_key: String,
+
+    /// Role to give the peer: reader, writer or owner
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = PrincipalRole::Owner))]
+    #[serde(default = "default_role")]
+    pub role: PrincipalRole,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+fn default_role() -> PrincipalRole {
+    PrincipalRole::Owner
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShareResponse {
     pub bucket_id: Uuid,
     pub peer_public_key: String,
+    pub role: PrincipalRole,
     pub new_bucket_link: String,
 }
 
@@ -43,8 +59,14 @@ pub async fn handler(
     let new_bucket_link = tokio::task::spawn_blocking(move || -> Result<Link, MountOpsError> {
         tokio::runtime::Handle::current().block_on(async {
             tracing::info!("Adding file to mount");
-            let bucket_link =
-                crate::mount_ops::share_bucket(req.bucket_id, peer_public_key, &state).await?;
+            let bucket_link = crate::mount_ops::share_bucket(
+                req.bucket_id,
+                peer_public_key,
+                req.role,
+                req.message,
+                &state,
+            )
+            .await?;
             Ok(bucket_link)
         })
     })
@@ -52,9 +74,10 @@ pub async fn handler(
     .map_err(|e| ShareError::Mount(format!("Task join error: {}", e)))??;
 
     tracing::info!(
-        "Bucket {} shared with peer {}",
+        "Bucket {} shared with peer {} as {}",
         req.bucket_id,
-        req.peer_public_key
+        req.peer_public_key,
+        req.role
     );
 
     Ok((
@@ -62,6 +85,7 @@ pub async fn handler(
         Json(ShareResponse {
             bucket_id: req.bucket_id,
             peer_public_key: req.peer_public_key,
+            role: req.role,
             new_bucket_link: new_bucket_link.hash().to_string(),
         }),
     )
@@ -76,8 +100,12 @@ pub enum ShareError {
     InvalidPublicKey(String),
     #[error("Share not found")]
     ShareNotFound,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("Database error: {0}")]
     Database(String),
+    #[error("Bucket changed while it was being updated: {0}")]
+    Conflict(Uuid),
     #[error("Mount error: {0}")]
     Mount(String),
     #[error("Crypto error: {0}")]
@@ -90,10 +118,18 @@ impl From<MountOpsError> for ShareError {
             MountOpsError::BucketNotFound(id) => ShareError::BucketNotFound(id),
             MountOpsError::ShareNotFound => ShareError::ShareNotFound,
             MountOpsError::Database(msg) => ShareError::Database(msg),
+            MountOpsError::Conflict(id) => ShareError::Conflict(id),
+            MountOpsError::Mount(e @ (MountError::NotOwner | MountError::ReadOnly)) => {
+                ShareError::Forbidden(e.to_string())
+            }
             MountOpsError::Mount(e) => ShareError::Mount(e.to_string()),
             MountOpsError::CryptoError(msg) => ShareError::Crypto(msg),
             MountOpsError::ShareError(msg) => ShareError::Crypto(msg),
             MountOpsError::InvalidPath(msg) => ShareError::Mount(msg),
+            MountOpsError::InvalidArchive(msg) => ShareError::Mount(msg),
+            MountOpsError::VersionNotFound(hash) => {
+                ShareError::Mount(format!("Version not found: {}", hash))
+            }
         }
     }
 }
@@ -116,6 +152,15 @@ impl IntoResponse for ShareError {
                 "Share not found for this bucket".to_string(),
             )
                 .into_response(),
+            ShareError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            ShareError::Conflict(id) => (
+                http::StatusCode::CONFLICT,
+                format!(
+                    "Bucket {} changed while it was being updated, try again",
+                    id
+                ),
+            )
+                .into_response(),
             ShareError::Database(_) | ShareError::Mount(_) | ShareError::Crypto(_) => (
                 http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Unexpected error".to_string(),
@@ -0,0 +1,226 @@
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use http::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use common::bucket::{MountError, RekeyProgress};
+use common::crypto::PublicKey;
+
+use crate::http_server::api::client::ApiRequest;
+use crate::mount_ops::{self, MountOpsError};
+use crate::ServiceState;
+
+/// How often progress is reported while re-encrypting a bucket
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct UnshareRequest {
+    /// Bucket ID to stop sharing
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bucket_id: Uuid,
+
+    /// Public key of the peer to revoke (hex-encoded)
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub peer_public_key: String,
+
+    /// Also re-encrypt every file and directory under new keys
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub rekey: bool,
+
+    /// Message describing the change, recorded with the new version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnshareResponse {
+    pub bucket_id: Uuid,
+    pub peer_public_key: String,
+    pub rekeyed: bool,
+    pub new_bucket_link: String,
+}
+
+/// A line of the newline-delimited JSON response to an unshare request
+///
+/// Any number of `progress` events are sent while the bucket is
+///  re-encrypted, followed by either `done` or `error`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnshareEvent {
+    Progress(RekeyProgress),
+    Done(UnshareResponse),
+    Error(String),
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Json(req): Json<UnshareRequest>,
+) -> Result<impl IntoResponse, UnshareError> {
+    // Parse the peer's public key from hex
+    let peer_public_key = PublicKey::from_hex(&req.peer_public_key)
+        .map_err(|e| UnshareError::InvalidPublicKey(e.to_string()))?;
+
+    // Check what we can before responding, so these failures get a
+    //  status code rather than an error event in the stream
+    match mount_ops::get_bucket_role(req.bucket_id, &state).await? {
+        Some(role) if role.can_share() => {}
+        _ => return Err(UnshareError::Forbidden(MountError::NotOwner.to_string())),
+    }
+    let shares = mount_ops::get_bucket_shares(req.bucket_id, &state).await?;
+    if !shares
+        .iter()
+        .any(|share| share.public_key == peer_public_key.to_hex())
+    {
+        return Err(UnshareError::ShareNotFound);
+    }
+
+    // Run the revocation in a blocking task, streaming its progress back
+    let (tx, rx) = flume::unbounded();
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            let progress_tx = tx.clone();
+            let mut last_report: Option<Instant> = None;
+            let on_progress = |progress: &RekeyProgress| {
+                if last_report.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+                    last_report = Some(Instant::now());
+                    let _ = progress_tx.send(UnshareEvent::Progress(progress.clone()));
+                }
+            };
+            let result = mount_ops::unshare_bucket(
+                req.bucket_id,
+                peer_public_key,
+                req.rekey,
+                on_progress,
+                req.message,
+                &state,
+            )
+            .await;
+
+            let event = match result {
+                Ok(new_bucket_link) => {
+                    tracing::info!(
+                        "Bucket {} no longer shared with peer {}",
+                        req.bucket_id,
+                        req.peer_public_key
+                    );
+                    UnshareEvent::Done(UnshareResponse {
+                        bucket_id: req.bucket_id,
+                        peer_public_key: req.peer_public_key,
+                        rekeyed: req.rekey,
+                        new_bucket_link: new_bucket_link.hash().to_string(),
+                    })
+                }
+                Err(e) => {
+                    tracing::error!("Failed to unshare bucket {}: {}", req.bucket_id, e);
+                    UnshareEvent::Error(e.to_string())
+                }
+            };
+            let _ = tx.send(event);
+        })
+    });
+
+    let lines = rx
+        .into_stream()
+        .map(|event| serde_json::to_string(&event).map(|line| line + "\n"));
+    Ok((
+        http::StatusCode::OK,
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UnshareError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(Uuid),
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Share not found")]
+    ShareNotFound,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("Bucket changed while it was being updated: {0}")]
+    Conflict(Uuid),
+    #[error("Mount error: {0}")]
+    Mount(String),
+}
+
+impl From<MountOpsError> for UnshareError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => UnshareError::BucketNotFound(id),
+            MountOpsError::ShareNotFound => UnshareError::ShareNotFound,
+            MountOpsError::Database(msg) => UnshareError::Database(msg),
+            MountOpsError::Conflict(id) => UnshareError::Conflict(id),
+            MountOpsError::Mount(e) => UnshareError::Mount(e.to_string()),
+            MountOpsError::CryptoError(msg) => UnshareError::Mount(msg),
+            MountOpsError::ShareError(msg) => UnshareError::Mount(msg),
+            MountOpsError::InvalidPath(msg) => UnshareError::Mount(msg),
+            MountOpsError::InvalidArchive(msg) => UnshareError::Mount(msg),
+            MountOpsError::VersionNotFound(hash) => {
+                UnshareError::Mount(format!("Version not found: {}", hash))
+            }
+        }
+    }
+}
+
+impl IntoResponse for UnshareError {
+    fn into_response(self) -> Response {
+        match self {
+            UnshareError::BucketNotFound(id) => (
+                http::StatusCode::NOT_FOUND,
+                format!("Bucket not found: {}", id),
+            )
+                .into_response(),
+            UnshareError::InvalidPublicKey(msg) => (
+                http::StatusCode::BAD_REQUEST,
+                format!("Invalid public key: {}", msg),
+            )
+                .into_response(),
+            UnshareError::ShareNotFound => (
+                http::StatusCode::NOT_FOUND,
+                "Peer has no share in this bucket".to_string(),
+            )
+                .into_response(),
+            UnshareError::Forbidden(msg) => (http::StatusCode::FORBIDDEN, msg).into_response(),
+            UnshareError::Conflict(id) => (
+                http::StatusCode::CONFLICT,
+                format!(
+                    "Bucket {} changed while it was being updated, try again",
+                    id
+                ),
+            )
+                .into_response(),
+            UnshareError::Database(_) | UnshareError::Mount(_) => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Unexpected error".to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Client implementation - builds request for this operation
+// NOTE: the response is a stream of events, one per line, so use
+//  `ApiClient::call_lines` rather than `ApiClient::call`
+impl ApiRequest for UnshareRequest {
+    type Response = UnshareEvent;
+
+    fn build_request(self, base_url: &Url, client: &Client) -> RequestBuilder {
+        let full_url = base_url.join("/api/v0/bucket/unshare").unwrap();
+        client.post(full_url).json(&self)
+    }
+}
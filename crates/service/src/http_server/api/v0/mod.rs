@@ -3,6 +3,7 @@ use http::header::{ACCEPT, ORIGIN};
 use http::Method;
 use tower_http::cors::{Any, CorsLayer};
 
+pub mod admin;
 pub mod bucket;
 
 use crate::ServiceState;
@@ -16,6 +17,7 @@ pub fn router(state: ServiceState) -> Router<ServiceState> {
 
     Router::new()
         .nest("/bucket", bucket::router(state.clone()))
+        .nest("/admin", admin::router(state.clone()))
         .with_state(state)
         .layer(cors_layer)
 }
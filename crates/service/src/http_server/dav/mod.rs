@@ -0,0 +1,374 @@
+//! WebDAV access to buckets, so desktop file managers and tools like
+//! `rclone` can mount them
+//!
+//! Each bucket is a collection at `/dav/<bucket>/`, named by its name or
+//! its ID, and the root lists every bucket. Reads are served from the
+//! current version, and every change is saved as a new version and
+//! announced to peers, like changes made through the API.
+
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{HeaderMap, Method, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use axum::Router;
+use http::header::ALLOW;
+use http::{HeaderValue, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use uuid::Uuid;
+
+use common::prelude::MountError;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::mount_ops::{FileInfo, MountOpsError};
+use crate::ServiceState;
+
+mod read;
+mod write;
+
+use super::DAV_PREFIX;
+
+/// Methods served on every resource
+const ALLOWED_METHODS: &str = "OPTIONS, PROPFIND, GET, HEAD, PUT, DELETE, MKCOL, MOVE, COPY";
+
+/// Characters escaped in each segment of an href
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Routes under [`DAV_PREFIX`], merged rather than nested so the root
+///  is served with or without its trailing slash
+pub fn router(state: ServiceState) -> Router<ServiceState> {
+    Router::new()
+        .route(DAV_PREFIX, any(handler))
+        .route(&format!("{}/", DAV_PREFIX), any(handler))
+        .route(&format!("{}/*path", DAV_PREFIX), any(handler))
+        // Uploads are streamed into the bucket, so they aren't bound by the API body limit
+        .layer(DefaultBodyLimit::disable())
+        .with_state(state)
+}
+
+async fn handler(
+    State(state): State<ServiceState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let path = uri.path().strip_prefix(DAV_PREFIX).unwrap_or_default();
+    let result = match Target::parse(path) {
+        Ok(target) => match method.as_str() {
+            "OPTIONS" => Ok(read::options()),
+            "PROPFIND" => read::propfind(&state, &target, &headers).await,
+            "GET" => read::get(&state, &target, &headers, true).await,
+            "HEAD" => read::get(&state, &target, &headers, false).await,
+            "PUT" => write::put(&state, &target, body).await,
+            "DELETE" => write::delete(&state, &target).await,
+            "MKCOL" => write::mkcol(&state, &target, &headers).await,
+            "MOVE" => write::transfer(&state, &target, &headers, true).await,
+            "COPY" => write::transfer(&state, &target, &headers, false).await,
+            _ => Err(DavError::MethodNotAllowed),
+        },
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+/// What a request is about
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// The list of buckets
+    Root,
+    /// A path in a bucket, named as in the request
+    Bucket { bucket: String, path: String },
+}
+
+impl Target {
+    /// Parse the path of a request under the WebDAV prefix, decoding each
+    ///  segment
+    fn parse(path: &str) -> Result<Self, DavError> {
+        let mut segments = Vec::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            let segment = percent_decode_str(segment)
+                .decode_utf8()
+                .map_err(|_| DavError::BadRequest("Paths must be UTF-8".into()))?;
+            if segment == "." || segment == ".." || segment.contains('/') {
+                return Err(DavError::BadRequest(format!("Invalid path: {}", path)));
+            }
+            segments.push(segment.into_owned());
+        }
+
+        let mut segments = segments.into_iter();
+        let Some(bucket) = segments.next() else {
+            return Ok(Target::Root);
+        };
+        let path = format!("/{}", segments.collect::<Vec<_>>().join("/"));
+        Ok(Target::Bucket { bucket, path })
+    }
+
+    /// Parse a `Destination` header, which may be a full URL or just its
+    ///  path
+    fn destination(value: &str) -> Result<Self, DavError> {
+        let path = match url::Url::parse(value) {
+            Ok(url) => url.path().to_string(),
+            Err(_) if value.starts_with('/') => value.to_string(),
+            Err(_) => return Err(DavError::BadRequest("Invalid Destination".into())),
+        };
+        match path.strip_prefix(DAV_PREFIX) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => Self::parse(rest),
+            _ => Err(DavError::BadGateway(format!(
+                "{} is not served here",
+                value
+            ))),
+        }
+    }
+}
+
+/// A file or directory as it's described to clients
+struct Resource {
+    href: String,
+    name: String,
+    is_dir: bool,
+    size: Option<u64>,
+    /// Modification time in unix milliseconds
+    mtime: Option<u64>,
+    mime_type: Option<String>,
+    etag: Option<String>,
+}
+
+impl Resource {
+    fn collection(href: String, name: String) -> Self {
+        Resource {
+            href,
+            name,
+            is_dir: true,
+            size: None,
+            mtime: None,
+            mime_type: None,
+            etag: None,
+        }
+    }
+
+    fn from_info(bucket: &str, info: &FileInfo) -> Self {
+        if info.is_dir {
+            return Self::collection(href(bucket, &info.path, true), info.name.clone());
+        }
+        Resource {
+            href: href(bucket, &info.path, false),
+            name: info.name.clone(),
+            is_dir: false,
+            size: info.size,
+            mtime: info.mtime,
+            mime_type: Some(info.mime_type.clone()),
+            etag: Some(format!("\"{}\"", info.link.hash())),
+        }
+    }
+}
+
+/// The href of a path in a bucket, with a trailing slash for collections
+fn href(bucket: &str, path: &str, is_dir: bool) -> String {
+    let mut href = format!("{}/{}", DAV_PREFIX, utf8_percent_encode(bucket, SEGMENT));
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        href.push('/');
+        href.extend(utf8_percent_encode(segment, SEGMENT));
+    }
+    if is_dir {
+        href.push('/');
+    }
+    href
+}
+
+/// The parent of an absolute path in a bucket
+fn parent(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+/// Find a bucket by its name, or its ID
+async fn resolve_bucket(bucket: &str, state: &ServiceState) -> Result<Uuid, DavError> {
+    BucketModel::find(bucket, state.database())
+        .await
+        .map_err(|e| DavError::Database(e.to_string()))?
+        .map(|b| b.id)
+        .ok_or_else(|| DavError::BucketNotFound(bucket.to_string()))
+}
+
+/// Look up a path in a bucket, or `None` if nothing is there
+async fn stat(
+    bucket_id: Uuid,
+    path: &str,
+    state: &ServiceState,
+) -> Result<Option<FileInfo>, DavError> {
+    match crate::mount_ops::get_path_info(bucket_id, path.to_string(), None, state).await {
+        Ok(info) => Ok(Some(info)),
+        Err(MountOpsError::Mount(MountError::PathNotFound(_) | MountError::PathNotNode(_))) => {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Check the directory a new file or directory goes in exists
+async fn check_parent(bucket_id: Uuid, path: &str, state: &ServiceState) -> Result<(), DavError> {
+    let parent = parent(path);
+    if parent == "/" {
+        return Ok(());
+    }
+    match stat(bucket_id, parent, state).await? {
+        Some(info) if info.is_dir => Ok(()),
+        _ => Err(DavError::Conflict(format!("{} is not a directory", parent))),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DavError {
+    #[error("Bucket not found: {0}")]
+    BucketNotFound(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    #[error("Unsupported media type")]
+    UnsupportedMediaType,
+    #[error("Bad gateway: {0}")]
+    BadGateway(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("MountOps error: {0}")]
+    MountOps(String),
+}
+
+impl From<MountOpsError> for DavError {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => DavError::BucketNotFound(id.to_string()),
+            MountOpsError::InvalidPath(msg) => DavError::BadRequest(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                DavError::NotFound(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                DavError::Conflict(format!("{} is not a directory", path.display()))
+            }
+            MountOpsError::Mount(MountError::PathAlreadyExists(path)) => {
+                DavError::Conflict(format!("{} already exists", path.display()))
+            }
+            MountOpsError::Mount(MountError::ReadOnly) => {
+                DavError::Forbidden("This bucket is shared read-only".into())
+            }
+            MountOpsError::Conflict(id) => DavError::Conflict(format!(
+                "Bucket {} changed while it was being updated, try again",
+                id
+            )),
+            e => DavError::MountOps(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for DavError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            DavError::BucketNotFound(_) | DavError::NotFound(_) => StatusCode::NOT_FOUND,
+            DavError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            DavError::MethodNotAllowed => {
+                let mut response = StatusCode::METHOD_NOT_ALLOWED.into_response();
+                response
+                    .headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+                return response;
+            }
+            DavError::Forbidden(_) => StatusCode::FORBIDDEN,
+            DavError::Conflict(_) => StatusCode::CONFLICT,
+            DavError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            DavError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            DavError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            DavError::Database(_) | DavError::MountOps(_) => {
+                tracing::error!("WebDAV request failed: {}", self);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error").into_response();
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(Target::parse("/").unwrap(), Target::Root);
+        assert_eq!(Target::parse("").unwrap(), Target::Root);
+        assert_eq!(
+            Target::parse("/photos/").unwrap(),
+            Target::Bucket {
+                bucket: "photos".into(),
+                path: "/".into()
+            }
+        );
+        assert_eq!(
+            Target::parse("/my%20bucket/2024/beach%20day.jpg").unwrap(),
+            Target::Bucket {
+                bucket: "my bucket".into(),
+                path: "/2024/beach day.jpg".into()
+            }
+        );
+        assert!(Target::parse("/photos/../secrets").is_err());
+        assert!(Target::parse("/photos/a%2Fb").is_err());
+        assert!(Target::parse("/photos/%FF").is_err());
+    }
+
+    #[test]
+    fn test_destination() {
+        assert_eq!(
+            Target::destination("http://localhost:3000/dav/photos/new%20name.jpg").unwrap(),
+            Target::Bucket {
+                bucket: "photos".into(),
+                path: "/new name.jpg".into()
+            }
+        );
+        assert_eq!(
+            Target::destination("/dav/photos/a/").unwrap(),
+            Target::Bucket {
+                bucket: "photos".into(),
+                path: "/a".into()
+            }
+        );
+        assert!(matches!(
+            Target::destination("http://localhost:3000/elsewhere/a"),
+            Err(DavError::BadGateway(_))
+        ));
+        assert!(matches!(
+            Target::destination("/davx/a"),
+            Err(DavError::BadGateway(_))
+        ));
+    }
+
+    #[test]
+    fn test_href() {
+        assert_eq!(href("photos", "/", true), "/dav/photos/");
+        assert_eq!(
+            href("my bucket", "/2024/beach day.jpg", false),
+            "/dav/my%20bucket/2024/beach%20day.jpg"
+        );
+        assert_eq!(href("photos", "/a&b", true), "/dav/photos/a%26b/");
+    }
+
+    #[test]
+    fn test_parent() {
+        assert_eq!(parent("/a.txt"), "/");
+        assert_eq!(parent("/a/b/c.txt"), "/a/b");
+        assert_eq!(parent("/"), "/");
+    }
+}
@@ -0,0 +1,308 @@
+use std::fmt::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::response::{IntoResponse, Response};
+use headers::LastModified;
+use headers::{AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, IfNoneMatch};
+use http::header::{ALLOW, CONTENT_TYPE};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use tokio_util::io::ReaderStream;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::http_server::api::v0::bucket::cat::{resolve_range, RangeRequest};
+use crate::ServiceState;
+
+use super::{href, resolve_bucket, stat, DavError, Resource, Target, ALLOWED_METHODS};
+
+/// How far below a collection PROPFIND looks
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Depth {
+    Zero,
+    One,
+    Infinity,
+}
+
+impl Depth {
+    fn from_headers(headers: &HeaderMap) -> Result<Self, DavError> {
+        match headers.get("depth").map(|depth| depth.to_str()) {
+            // Missing means infinity
+            None | Some(Ok("infinity")) => Ok(Depth::Infinity),
+            Some(Ok("0")) => Ok(Depth::Zero),
+            Some(Ok("1")) => Ok(Depth::One),
+            _ => Err(DavError::BadRequest("Invalid Depth".into())),
+        }
+    }
+}
+
+pub fn options() -> Response {
+    let mut response = StatusCode::OK.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("dav"),
+        HeaderValue::from_static("1"),
+    );
+    headers.insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+    headers.insert(
+        HeaderName::from_static("ms-author-via"),
+        HeaderValue::from_static("DAV"),
+    );
+    response
+}
+
+/// Describe a resource, and what's under it if it's a collection
+///
+/// Every property known is returned whatever the request body asks for,
+///  which clients accept in place of the ones they named.
+pub async fn propfind(
+    state: &ServiceState,
+    target: &Target,
+    headers: &HeaderMap,
+) -> Result<Response, DavError> {
+    let depth = Depth::from_headers(headers)?;
+
+    let mut resources = Vec::new();
+    match target {
+        Target::Root => {
+            resources.push(Resource::collection(
+                format!("{}/", super::DAV_PREFIX),
+                String::new(),
+            ));
+            if depth != Depth::Zero {
+                let buckets = BucketModel::list_all(state.database())
+                    .await
+                    .map_err(|e| DavError::Database(e.to_string()))?;
+                resources.extend(buckets.into_iter().map(|bucket| {
+                    Resource::collection(href(&bucket.name, "/", true), bucket.name)
+                }));
+            }
+        }
+        Target::Bucket { bucket, path } => {
+            let bucket_id = resolve_bucket(bucket, state).await?;
+            let is_dir = if path == "/" {
+                resources.push(Resource::collection(
+                    href(bucket, "/", true),
+                    bucket.clone(),
+                ));
+                true
+            } else {
+                let info = stat(bucket_id, path, state)
+                    .await?
+                    .ok_or_else(|| DavError::NotFound(path.clone()))?;
+                resources.push(Resource::from_info(bucket, &info));
+                info.is_dir
+            };
+
+            if is_dir && depth != Depth::Zero {
+                let mut items = crate::mount_ops::list_bucket_contents(
+                    bucket_id,
+                    Some(path.clone()),
+                    depth == Depth::Infinity,
+                    None,
+                    state,
+                )
+                .await?;
+                // Deep listings are relative to the directory listed
+                if depth == Depth::Infinity && path != "/" {
+                    for info in &mut items {
+                        info.path = format!("{}{}", path, info.path);
+                    }
+                }
+                resources.extend(items.iter().map(|info| Resource::from_info(bucket, info)));
+            }
+        }
+    }
+
+    let mut response = (StatusCode::MULTI_STATUS, multistatus(&resources)).into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    Ok(response)
+}
+
+/// Serve a file, or part of it if a single range is asked for, as the
+///  download API does
+pub async fn get(
+    state: &ServiceState,
+    target: &Target,
+    headers: &HeaderMap,
+    with_body: bool,
+) -> Result<Response, DavError> {
+    let Target::Bucket { bucket, path } = target else {
+        return Err(DavError::MethodNotAllowed);
+    };
+    let bucket_id = resolve_bucket(bucket, state).await?;
+    if path == "/" {
+        return Err(DavError::MethodNotAllowed);
+    }
+    let info = stat(bucket_id, path, state)
+        .await?
+        .ok_or_else(|| DavError::NotFound(path.clone()))?;
+    if info.is_dir {
+        return Err(DavError::MethodNotAllowed);
+    }
+
+    let range_header = headers.typed_get::<headers::Range>();
+    let select_range = {
+        let range_header = range_header.clone();
+        move |size| match resolve_range(range_header.as_ref(), size) {
+            RangeRequest::Partial(range) if with_body => Some(range),
+            RangeRequest::Full if with_body => None,
+            // Nothing will be served, so don't read anything
+            _ => Some(size..size),
+        }
+    };
+    let file =
+        crate::mount_ops::get_file_reader(bucket_id, path.clone(), None, select_range, state)
+            .await?;
+
+    let etag: ETag = format!("\"{}\"", file.link.hash())
+        .parse()
+        .map_err(|_| DavError::MountOps("invalid etag".into()))?;
+    let size = file.reader.size();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.typed_insert(etag.clone());
+    response_headers.typed_insert(AcceptRanges::bytes());
+    if let Some(mtime) = info.mtime {
+        response_headers.typed_insert(LastModified::from(
+            UNIX_EPOCH + Duration::from_millis(mtime),
+        ));
+    }
+
+    if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
+        if !if_none_match.precondition_passes(&etag) {
+            return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+        }
+    }
+
+    let (status, len) = match resolve_range(range_header.as_ref(), size) {
+        RangeRequest::Full => (StatusCode::OK, size),
+        RangeRequest::Partial(range) => {
+            let len = range.end - range.start;
+            let content_range = ContentRange::bytes(range, size)
+                .map_err(|_| DavError::MountOps("invalid content range".into()))?;
+            response_headers.typed_insert(content_range);
+            (StatusCode::PARTIAL_CONTENT, len)
+        }
+        RangeRequest::Unsatisfiable => {
+            response_headers.typed_insert(ContentRange::unsatisfied_bytes(size));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+
+    response_headers.typed_insert(ContentLength(len));
+    if let Ok(content_type) = HeaderValue::from_str(&file.mime_type) {
+        response_headers.insert(CONTENT_TYPE, content_type);
+    }
+
+    let body = if with_body {
+        Body::from_stream(ReaderStream::new(file.reader))
+    } else {
+        Body::empty()
+    };
+    Ok((status, response_headers, body).into_response())
+}
+
+/// A `207 Multi-Status` body describing each resource
+fn multistatus(resources: &[Resource]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for resource in resources {
+        let _ = write!(
+            xml,
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>",
+            escape(&resource.href)
+        );
+        let _ = write!(
+            xml,
+            "<D:displayname>{}</D:displayname>",
+            escape(&resource.name)
+        );
+        if resource.is_dir {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            xml.push_str("<D:resourcetype/>");
+        }
+        if let Some(size) = resource.size {
+            let _ = write!(xml, "<D:getcontentlength>{}</D:getcontentlength>", size);
+        }
+        if let Some(mtime) = resource.mtime {
+            let modified = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_millis(mtime));
+            let _ = write!(xml, "<D:getlastmodified>{}</D:getlastmodified>", modified);
+        }
+        if let Some(mime_type) = &resource.mime_type {
+            let _ = write!(
+                xml,
+                "<D:getcontenttype>{}</D:getcontenttype>",
+                escape(mime_type)
+            );
+        }
+        if let Some(etag) = &resource.etag {
+            let _ = write!(xml, "<D:getetag>{}</D:getetag>", escape(etag));
+        }
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_depth() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Depth::from_headers(&headers).unwrap(), Depth::Infinity);
+        headers.insert("depth", HeaderValue::from_static("1"));
+        assert_eq!(Depth::from_headers(&headers).unwrap(), Depth::One);
+        headers.insert("depth", HeaderValue::from_static("0"));
+        assert_eq!(Depth::from_headers(&headers).unwrap(), Depth::Zero);
+        headers.insert("depth", HeaderValue::from_static("2"));
+        assert!(Depth::from_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn test_multistatus() {
+        let resources = [
+            Resource::collection("/dav/photos/".into(), "photos".into()),
+            Resource {
+                href: "/dav/photos/a%26b.txt".into(),
+                name: "a&b.txt".into(),
+                is_dir: false,
+                size: Some(3),
+                mtime: Some(784_111_777_000),
+                mime_type: Some("text/plain".into()),
+                etag: Some("\"abc\"".into()),
+            },
+        ];
+        let xml = multistatus(&resources);
+        assert!(xml.contains(
+            "<D:href>/dav/photos/</D:href><D:propstat><D:prop><D:displayname>photos</D:displayname><D:resourcetype><D:collection/></D:resourcetype></D:prop>"
+        ));
+        assert!(xml.contains("<D:displayname>a&amp;b.txt</D:displayname><D:resourcetype/>"));
+        assert!(xml.contains("<D:getcontentlength>3</D:getcontentlength>"));
+        assert!(
+            xml.contains("<D:getlastmodified>Sun, 06 Nov 1994 08:49:37 GMT</D:getlastmodified>")
+        );
+        assert!(xml.contains("<D:getetag>&quot;abc&quot;</D:getetag>"));
+    }
+}
@@ -0,0 +1,206 @@
+use std::future::Future;
+use std::path::PathBuf;
+
+use axum::body::{Body, Bytes};
+use axum::response::{IntoResponse, Response};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use http::header::CONTENT_LENGTH;
+use http::{HeaderMap, StatusCode};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use common::prelude::Link;
+
+use crate::mount_ops::MountOpsError;
+use crate::ServiceState;
+
+use super::{check_parent, resolve_bucket, stat, DavError, Target};
+
+/// Number of uploaded chunks buffered between the request and the mount
+const UPLOAD_CHANNEL_SIZE: usize = 16;
+
+/// Store the request body as a file, replacing the one there if any
+pub async fn put(state: &ServiceState, target: &Target, body: Body) -> Result<Response, DavError> {
+    let (bucket_id, path) = bucket_path(state, target).await?;
+    check_parent(bucket_id, &path, state).await?;
+    let existed = match stat(bucket_id, &path, state).await? {
+        Some(info) if info.is_dir => return Err(DavError::MethodNotAllowed),
+        Some(_) => true,
+        None => false,
+    };
+
+    // The body is forwarded to the mount through a bounded channel as it
+    //  arrives
+    let (mut sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(UPLOAD_CHANNEL_SIZE);
+    let reader = StreamReader::new(receiver);
+    let mount_path = PathBuf::from(&path);
+    let add_task = blocking(state, move |state| async move {
+        crate::mount_ops::add_data_to_bucket(bucket_id, mount_path, reader, None, &state).await
+    });
+
+    let forward = async move {
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            // Fail the add rather than letting it store a truncated file
+            let chunk = chunk.map_err(std::io::Error::other);
+            let failed = chunk.is_err();
+            // A closed channel means the add already failed; its error is returned instead
+            if sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    };
+    let (added, ()) = tokio::join!(add_task, forward);
+    added?;
+
+    Ok(created(existed))
+}
+
+pub async fn delete(state: &ServiceState, target: &Target) -> Result<Response, DavError> {
+    let (bucket_id, path) = bucket_path(state, target).await?;
+    let path = PathBuf::from(path);
+    blocking(state, move |state| async move {
+        crate::mount_ops::remove_path_from_bucket(bucket_id, path, None, &state).await
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+pub async fn mkcol(
+    state: &ServiceState,
+    target: &Target,
+    headers: &HeaderMap,
+) -> Result<Response, DavError> {
+    // Bodies describing what to create aren't supported
+    let has_body = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .is_some_and(|len| len != "0");
+    if has_body {
+        return Err(DavError::UnsupportedMediaType);
+    }
+
+    let (bucket_id, path) = match bucket_path(state, target).await {
+        // Buckets are only created through the API
+        Err(DavError::Forbidden(_)) => return Err(DavError::MethodNotAllowed),
+        result => result?,
+    };
+    check_parent(bucket_id, &path, state).await?;
+    if stat(bucket_id, &path, state).await?.is_some() {
+        return Err(DavError::MethodNotAllowed);
+    }
+
+    let dir = PathBuf::from(path);
+    blocking(state, move |state| async move {
+        crate::mount_ops::make_dir_in_bucket(bucket_id, dir, None, &state).await
+    })
+    .await?;
+    Ok(StatusCode::CREATED.into_response())
+}
+
+/// MOVE, or COPY if `remove_source` is false, within a bucket, saved as
+///  a single version even when it replaces what's at the destination
+pub async fn transfer(
+    state: &ServiceState,
+    target: &Target,
+    headers: &HeaderMap,
+    remove_source: bool,
+) -> Result<Response, DavError> {
+    let (bucket_id, from) = bucket_path(state, target).await?;
+    let destination = headers
+        .get("destination")
+        .and_then(|destination| destination.to_str().ok())
+        .ok_or_else(|| DavError::BadRequest("Destination is required".into()))?;
+    let Target::Bucket {
+        bucket: to_bucket,
+        path: to,
+    } = Target::destination(destination)?
+    else {
+        return Err(DavError::Forbidden(
+            "Can't replace the list of buckets".into(),
+        ));
+    };
+    if resolve_bucket(&to_bucket, state).await? != bucket_id {
+        return Err(DavError::BadGateway(
+            "Files can only be moved and copied within a bucket".into(),
+        ));
+    }
+    if to == "/" {
+        return Err(DavError::Forbidden(
+            "Can't replace the bucket itself".into(),
+        ));
+    }
+    if to == from || to.starts_with(&format!("{}/", from)) {
+        return Err(DavError::Forbidden(format!(
+            "Can't move or copy {} into itself",
+            from
+        )));
+    }
+    let overwrite = match headers
+        .get("overwrite")
+        .map(|overwrite| overwrite.as_bytes())
+    {
+        None | Some(b"T") => true,
+        Some(b"F") => false,
+        _ => return Err(DavError::BadRequest("Invalid Overwrite".into())),
+    };
+
+    if stat(bucket_id, &from, state).await?.is_none() {
+        return Err(DavError::NotFound(from));
+    }
+    check_parent(bucket_id, &to, state).await?;
+    let existed = stat(bucket_id, &to, state).await?.is_some();
+    if existed && !overwrite {
+        return Err(DavError::PreconditionFailed(format!(
+            "{} already exists",
+            to
+        )));
+    }
+
+    let (from, to) = (PathBuf::from(from), PathBuf::from(to));
+    blocking(state, move |state| async move {
+        crate::mount_ops::copy_path_in_bucket(bucket_id, from, to, remove_source, None, &state)
+            .await
+    })
+    .await?;
+    Ok(created(existed))
+}
+
+/// The bucket and path a change applies to, which can't be the bucket
+///  itself or the list of buckets
+async fn bucket_path(state: &ServiceState, target: &Target) -> Result<(Uuid, String), DavError> {
+    let Target::Bucket { bucket, path } = target else {
+        return Err(DavError::Forbidden(
+            "Buckets are managed through the API".into(),
+        ));
+    };
+    let bucket_id = resolve_bucket(bucket, state).await?;
+    if path == "/" {
+        return Err(DavError::Forbidden(
+            "Buckets are managed through the API".into(),
+        ));
+    }
+    Ok((bucket_id, path.clone()))
+}
+
+fn created(existed: bool) -> Response {
+    if existed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::CREATED.into_response()
+    }
+}
+
+/// Run a change to a bucket in a blocking task, as the API handlers do
+async fn blocking<F, Fut>(state: &ServiceState, change: F) -> Result<Link, DavError>
+where
+    F: FnOnce(ServiceState) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Link, MountOpsError>>,
+{
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || tokio::runtime::Handle::current().block_on(change(state)))
+        .await
+        .map_err(|e| DavError::MountOps(format!("Task join error: {}", e)))?
+        .map_err(DavError::from)
+}
@@ -4,7 +4,10 @@ use axum::extract::{Path, Query, State};
 use axum::http::HeaderMap;
 use axum::Extension;
 use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 use tracing::instrument;
+use url::Url;
 use uuid::Uuid;
 
 use common::bucket::Manifest;
@@ -36,17 +39,29 @@ pub struct BucketExplorerTemplate {
     pub previous_link_full: String,
     pub previous_link_short: String,
     pub bucket_data_formatted: String,
+    pub version: Option<VersionSummary>,
     pub current_path: String,
     pub path_segments: Vec<PathSegment>,
     pub parent_path_url: String,
     pub items: Vec<FileDisplayInfo>,
     pub read_only: bool,
     pub api_url: String,
+    /// Link to download the current directory as a zip archive
+    pub archive_url: Option<String>,
     pub sync_status: String,
     pub sync_status_class: String,
     pub sync_error: String,
 }
 
+/// When, by whom and why the current version was made
+#[derive(Debug, Clone)]
+pub struct VersionSummary {
+    pub time: String,
+    pub author: String,
+    pub author_short: String,
+    pub message: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PathSegment {
     pub name: String,
@@ -76,8 +91,13 @@ pub async fn handler(
     Path(bucket_id): Path<Uuid>,
     Query(query): Query<ExplorerQuery>,
 ) -> askama_axum::Response {
-    // Use the read_only flag from config
-    let read_only = config.read_only;
+    // Use the read_only flag from config, and switch it on for buckets
+    //  we may only read, since peers would reject our changes
+    let read_only = config.read_only
+        || match mount_ops::get_bucket_role(bucket_id, &state).await {
+            Ok(role) => !role.is_some_and(|role| role.can_write()),
+            Err(e) => return error_response(&format!("{}", e)),
+        };
 
     let current_path = query.path.unwrap_or_else(|| "/".to_string());
 
@@ -88,16 +108,21 @@ pub async fn handler(
     };
 
     // List bucket contents
-    let items =
-        match mount_ops::list_bucket_contents(bucket_id, Some(current_path.clone()), false, &state)
-            .await
-        {
-            Ok(items) => items,
-            Err(e) => {
-                tracing::error!("Failed to list bucket contents: {}", e);
-                return error_response("Failed to load bucket contents");
-            }
-        };
+    let items = match mount_ops::list_bucket_contents(
+        bucket_id,
+        Some(current_path.clone()),
+        false,
+        None,
+        &state,
+    )
+    .await
+    {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::error!("Failed to list bucket contents: {}", e);
+            return error_response("Failed to load bucket contents");
+        }
+    };
 
     // Build path segments for breadcrumb
     let path_segments = build_path_segments(&current_path);
@@ -122,6 +147,14 @@ pub async fn handler(
         .clone()
         .unwrap_or_else(|| "http://localhost:3000".to_string());
 
+    let archive_url = match build_archive_url(&api_url, &bucket_id, &current_path) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            tracing::warn!("Invalid API URL {}: {}", api_url, e);
+            None
+        }
+    };
+
     let (status_text, status_class) = status_badge_class(&bucket.sync_status);
 
     // Format bucket link for display
@@ -138,10 +171,11 @@ pub async fn handler(
 
     // Load the full bucket data from blobs to get previous link and format it
     let blobs = state.node().blobs();
-    let (previous_link, previous_link_full, previous_link_short, bucket_data_formatted) =
+    let (previous_link, previous_link_full, previous_link_short, bucket_data_formatted, version) =
         match blobs.get(bucket.link.hash()).await {
             Ok(data) => match Manifest::decode(&data) {
                 Ok(bucket_data) => {
+                    let version = Some(version_summary(&bucket_data));
                     // Format bucket data as pretty JSON
                     let formatted = serde_json::to_string_pretty(&bucket_data)
                         .unwrap_or_else(|_| format!("{:#?}", bucket_data));
@@ -164,7 +198,7 @@ pub async fn handler(
                             (None, String::new(), String::new())
                         };
 
-                    (prev_opt, prev_full, prev_short, formatted)
+                    (prev_opt, prev_full, prev_short, formatted, version)
                 }
                 Err(e) => {
                     tracing::warn!("Failed to decode bucket data: {}", e);
@@ -173,6 +207,7 @@ pub async fn handler(
                         String::new(),
                         String::new(),
                         format!("Error decoding bucket data: {}", e),
+                        None,
                     )
                 }
             },
@@ -183,6 +218,7 @@ pub async fn handler(
                     String::new(),
                     String::new(),
                     format!("Error loading bucket data: {}", e),
+                    None,
                 )
             }
         };
@@ -196,12 +232,14 @@ pub async fn handler(
         previous_link_full,
         previous_link_short,
         bucket_data_formatted,
+        version,
         current_path,
         path_segments,
         parent_path_url,
         items: display_items,
         read_only,
         api_url,
+        archive_url,
         sync_status: status_text.to_string(),
         sync_status_class: status_class.to_string(),
         sync_error: bucket.sync_error.unwrap_or_default(),
@@ -210,6 +248,21 @@ pub async fn handler(
     template.into_response()
 }
 
+/// Build the API URL that streams a directory as a zip archive
+fn build_archive_url(
+    api_url: &str,
+    bucket_id: &Uuid,
+    path: &str,
+) -> Result<String, url::ParseError> {
+    let mut archive_url = Url::parse(api_url)?.join("/api/v0/bucket/archive")?;
+    archive_url
+        .query_pairs_mut()
+        .append_pair("bucket_id", &bucket_id.to_string())
+        .append_pair("path", path)
+        .append_pair("format", "zip");
+    Ok(archive_url.to_string())
+}
+
 fn build_path_segments(path: &str) -> Vec<PathSegment> {
     if path == "/" {
         return vec![];
@@ -248,6 +301,26 @@ fn build_parent_path_url(current_path: &str, bucket_id: &Uuid) -> String {
     }
 }
 
+fn version_summary(manifest: &Manifest) -> VersionSummary {
+    let author = manifest
+        .author()
+        .map(|author| author.to_hex())
+        .unwrap_or_else(|| "unknown author".to_string());
+    VersionSummary {
+        time: manifest
+            .timestamp()
+            .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts as i64).ok())
+            .and_then(|time| time.format(&Rfc3339).ok())
+            .unwrap_or_else(|| "unknown time".to_string()),
+        author_short: match manifest.author() {
+            Some(_) => author.chars().take(8).collect(),
+            None => author.clone(),
+        },
+        author,
+        message: manifest.message().map(str::to_string),
+    }
+}
+
 fn error_response(message: &str) -> askama_axum::Response {
     (
         axum::http::StatusCode::INTERNAL_SERVER_ERROR,
@@ -0,0 +1,176 @@
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::extract::{Path, Query, State};
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::instrument;
+use uuid::Uuid;
+
+use common::linked_data::Hash;
+
+use crate::mount_ops::{self, MountOpsError};
+use crate::ServiceState;
+
+/// How many versions to offer for comparison below the diff
+const VERSIONS_SHOWN: usize = 50;
+
+#[derive(Template)]
+#[template(path = "diff_viewer.html")]
+pub struct DiffViewerTemplate {
+    pub bucket_id: String,
+    pub bucket_name: String,
+    pub from: String,
+    pub from_short: String,
+    pub to: String,
+    pub to_short: String,
+    pub changes: Vec<ChangeInfo>,
+    pub versions: Vec<VersionDisplayInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeInfo {
+    pub label: &'static str,
+    pub badge_class: &'static str,
+    pub path: String,
+    /// Where a moved path ended up
+    pub moved_to: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionDisplayInfo {
+    pub hash: String,
+    pub hash_short: String,
+    pub time: String,
+    pub author: String,
+    pub author_short: String,
+    pub message: String,
+    pub selected: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    /// Version to compare from (defaults to the version before `to`)
+    #[serde(default)]
+    pub from: Option<Hash>,
+    /// Version to compare to (defaults to the current version)
+    #[serde(default)]
+    pub to: Option<Hash>,
+}
+
+#[instrument(skip(state))]
+pub async fn handler(
+    State(state): State<ServiceState>,
+    Path(bucket_id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+) -> askama_axum::Response {
+    let bucket = match mount_ops::get_bucket_info(bucket_id, &state).await {
+        Ok(bucket) => bucket,
+        Err(e) => return error_response(&format!("Failed to load bucket: {}", e)),
+    };
+
+    let diff = match mount_ops::get_bucket_diff(bucket_id, query.from, query.to, &state).await {
+        Ok(diff) => diff,
+        Err(MountOpsError::VersionNotFound(hash)) => {
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                format!("Error: Version not found: {}", hash),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to diff bucket versions: {}", e);
+            return error_response("Failed to compare versions");
+        }
+    };
+
+    let history = match mount_ops::get_bucket_history(bucket_id, Some(VERSIONS_SHOWN), &state).await
+    {
+        Ok(history) => history,
+        Err(e) => {
+            tracing::error!("Failed to get bucket history: {}", e);
+            return error_response("Failed to load bucket history");
+        }
+    };
+
+    let change = |label, badge_class, path: &String| ChangeInfo {
+        label,
+        badge_class,
+        path: path.clone(),
+        moved_to: None,
+    };
+    let changes = diff
+        .added
+        .iter()
+        .map(|path| change("Added", "status-badge status-synced", path))
+        .chain(
+            diff.removed
+                .iter()
+                .map(|path| change("Removed", "status-badge status-failed", path)),
+        )
+        .chain(
+            diff.modified
+                .iter()
+                .map(|path| change("Modified", "status-badge status-out-of-sync", path)),
+        )
+        .chain(diff.moved.iter().map(|(from, to)| ChangeInfo {
+            moved_to: Some(to.clone()),
+            ..change("Moved", "status-badge status-syncing", from)
+        }))
+        .collect();
+
+    let versions = history
+        .into_iter()
+        .map(|version| {
+            let hash = version.link.hash().to_string();
+            let author = version.author.map(|author| author.to_hex());
+            VersionDisplayInfo {
+                hash_short: shorten(&hash),
+                time: version
+                    .timestamp
+                    .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts as i64).ok())
+                    .and_then(|time| time.format(&Rfc3339).ok())
+                    .unwrap_or_else(|| "unknown time".to_string()),
+                author_short: author
+                    .as_deref()
+                    .map(|author| author.chars().take(8).collect())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                author: author.unwrap_or_default(),
+                message: version.message.unwrap_or_default(),
+                selected: version.link == diff.to,
+                hash,
+            }
+        })
+        .collect();
+
+    let from = diff.from.hash().to_string();
+    let to = diff.to.hash().to_string();
+    let template = DiffViewerTemplate {
+        bucket_id: bucket_id.to_string(),
+        bucket_name: bucket.name,
+        from_short: shorten(&from),
+        from,
+        to_short: shorten(&to),
+        to,
+        changes,
+        versions,
+    };
+
+    template.into_response()
+}
+
+fn shorten(hash: &str) -> String {
+    if hash.len() > 16 {
+        format!("{}...{}", &hash[..8], &hash[hash.len() - 8..])
+    } else {
+        hash.to_string()
+    }
+}
+
+fn error_response(message: &str) -> askama_axum::Response {
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error: {}", message),
+    )
+        .into_response()
+}
@@ -1,10 +1,13 @@
 use askama::Template;
 use askama_axum::IntoResponse;
 use axum::extract::{Path, Query, State};
+use axum::Extension;
 use serde::Deserialize;
 use tracing::instrument;
+use url::Url;
 use uuid::Uuid;
 
+use crate::http_server::Config;
 use crate::mount_ops;
 use crate::ServiceState;
 
@@ -22,10 +25,13 @@ pub struct FileViewerTemplate {
     pub file_path: String,
     pub file_name: String,
     pub path_segments: Vec<PathSegment>,
-    pub file_size: usize,
+    pub file_size: u64,
     pub mime_type: String,
     pub is_text: bool,
     pub content: String,
+    /// Streaming URL for the file content, used by media elements
+    pub content_url: String,
+    pub download_url: String,
     pub back_url: String,
 }
 
@@ -34,9 +40,10 @@ pub struct ViewerQuery {
     pub path: String,
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, config))]
 pub async fn handler(
     State(state): State<ServiceState>,
+    Extension(config): Extension<Config>,
     Path(bucket_id): Path<Uuid>,
     Query(query): Query<ViewerQuery>,
 ) -> askama_axum::Response {
@@ -48,15 +55,33 @@ pub async fn handler(
         Err(e) => return error_response(&format!("{}", e)),
     };
 
-    // Get file content
-    let file_content = match mount_ops::get_file_content(bucket_id, file_path.clone(), &state).await
+    // Look up the file's size and MIME type without reading its content
+    let file_stream = match mount_ops::get_file_reader(
+        bucket_id,
+        file_path.clone(),
+        None,
+        |_| Some(0..0),
+        &state,
+    )
+    .await
     {
-        Ok(content) => content,
+        Ok(file_stream) => file_stream,
         Err(e) => {
-            tracing::error!("Failed to get file content: {}", e);
+            tracing::error!("Failed to open file: {}", e);
             return error_response("Failed to load file content");
         }
     };
+    let file_size = file_stream.reader.size();
+    let mime_type = file_stream.mime_type;
+
+    let api_url = config
+        .api_url
+        .clone()
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let (content_url, download_url) = match build_content_urls(&api_url, &bucket_id, &file_path) {
+        Ok(urls) => urls,
+        Err(e) => return error_response(&format!("Invalid API URL: {}", e)),
+    };
 
     // Extract file name
     let file_name = std::path::Path::new(&file_path)
@@ -65,20 +90,23 @@ pub async fn handler(
         .unwrap_or(&file_path)
         .to_string();
 
-    // Determine how to display content based on MIME type
-    let (is_text, content) = if file_content.mime_type.starts_with("image/")
-        || file_content.mime_type.starts_with("video/")
-        || file_content.mime_type.starts_with("audio/")
-    {
-        // Encode as base64 for embedded display
-        (
-            false,
-            base64::Engine::encode(
-                &base64::engine::general_purpose::STANDARD,
-                &file_content.data,
-            ),
-        )
+    // Media is streamed by the browser straight from the API, so only
+    //  text and other files need their content loaded here
+    let is_media = mime_type.starts_with("image/")
+        || mime_type.starts_with("video/")
+        || mime_type.starts_with("audio/");
+    let (is_text, content) = if is_media {
+        (false, String::new())
     } else {
+        let file_content =
+            match mount_ops::get_file_content(bucket_id, file_path.clone(), None, &state).await {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::error!("Failed to get file content: {}", e);
+                    return error_response("Failed to load file content");
+                }
+            };
+
         // Try to decode as UTF-8 text
         match String::from_utf8(file_content.data.clone()) {
             Ok(text) => (true, text),
@@ -125,10 +153,12 @@ pub async fn handler(
         file_path,
         file_name,
         path_segments,
-        file_size: file_content.data.len(),
-        mime_type: file_content.mime_type,
+        file_size,
+        mime_type,
         is_text,
         content,
+        content_url,
+        download_url,
         back_url,
     };
 
@@ -162,6 +192,26 @@ fn build_path_segments(file_path: &str) -> Vec<PathSegment> {
     segments
 }
 
+/// Build the API URLs that stream the file inline and as a download
+fn build_content_urls(
+    api_url: &str,
+    bucket_id: &Uuid,
+    file_path: &str,
+) -> Result<(String, String), url::ParseError> {
+    let mut content_url = Url::parse(api_url)?.join("/api/v0/bucket/cat")?;
+    content_url
+        .query_pairs_mut()
+        .append_pair("bucket_id", &bucket_id.to_string())
+        .append_pair("path", file_path);
+
+    let mut download_url = content_url.clone();
+    download_url
+        .query_pairs_mut()
+        .append_pair("download", "true");
+
+    Ok((content_url.to_string(), download_url.to_string()))
+}
+
 fn build_back_url(file_path: &str, bucket_id: &Uuid) -> String {
     let parent = std::path::Path::new(file_path)
         .parent()
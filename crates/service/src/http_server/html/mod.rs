@@ -6,6 +6,7 @@ use tower_http::cors::{Any, CorsLayer};
 
 mod bucket_explorer;
 mod buckets;
+mod diff_viewer;
 mod file_viewer;
 mod index;
 mod peers_explorer;
@@ -26,6 +27,7 @@ pub fn router(state: ServiceState) -> Router<ServiceState> {
         .route("/buckets/:bucket_id", get(bucket_explorer::handler))
         .route("/buckets/:bucket_id/view", get(file_viewer::handler))
         .route("/buckets/:bucket_id/pins", get(pins_explorer::handler))
+        .route("/buckets/:bucket_id/diff", get(diff_viewer::handler))
         .route("/buckets/:bucket_id/peers", get(peers_explorer::handler))
         .with_state(state)
         .layer(cors_layer)
@@ -11,15 +11,18 @@ use tower_http::LatencyUnit;
 
 pub mod api;
 mod config;
+mod dav;
 mod handlers;
 mod health;
 mod html;
+mod s3;
 
 pub use config::Config;
 
-use crate::ServiceState;
+use crate::{S3AccessKey, ServiceState};
 
 const API_PREFIX: &str = "/api";
+const DAV_PREFIX: &str = "/dav";
 const STATUS_PREFIX: &str = "/_status";
 
 #[derive(RustEmbed)]
@@ -118,8 +121,9 @@ pub async fn run_api(
     let api_router = Router::new()
         .nest(STATUS_PREFIX, health::router(state.clone()))
         .nest(API_PREFIX, api::router(state.clone()))
+        .merge(dav::router(state.clone()))
         .fallback(handlers::not_found_handler)
-        .layer(DefaultBodyLimit::max(500 * 1024 * 1024)) // 500MB limit for file uploads
+        .layer(DefaultBodyLimit::max(500 * 1024 * 1024)) // 500MB limit for request bodies (file uploads are streamed and exempt)
         .with_state(state)
         .layer(trace_layer);
 
@@ -135,6 +139,39 @@ pub async fn run_api(
     Ok(())
 }
 
+/// Run the S3-compatible API, accepting requests signed with any of
+///  `access_keys`
+pub async fn run_s3(
+    config: Config,
+    access_keys: Vec<S3AccessKey>,
+    state: ServiceState,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Result<(), HttpServerError> {
+    let listen_addr = config.listen_addr;
+    let log_level = config.log_level;
+    let trace_layer = TraceLayer::new_for_http()
+        .on_response(
+            DefaultOnResponse::new()
+                .include_headers(false)
+                .level(log_level)
+                .latency_unit(LatencyUnit::Micros),
+        )
+        .on_failure(DefaultOnFailure::new().latency_unit(LatencyUnit::Micros));
+
+    let s3_router = s3::router(state, access_keys).layer(trace_layer);
+
+    tracing::info!(addr = ?listen_addr, "S3 server listening");
+    let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+
+    axum::serve(listener, s3_router)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown_rx.changed().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum HttpServerError {
     #[error("an error occurred running the HTTP server: {0}")]
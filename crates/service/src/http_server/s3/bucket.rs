@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+
+use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use common::prelude::MountError;
+use http::StatusCode;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::database::models::Bucket as BucketModel;
+use crate::mount_ops::{FileInfo, MountOpsError};
+
+use super::xml::Writer;
+use super::{etag, iso8601, last_modified, resolve_bucket, xml_response, Params, S3Error, S3State};
+
+/// Most keys a listing returns at once
+const MAX_KEYS: usize = 1000;
+
+/// Characters percent-encoded in keys when a listing asks for
+///  `encoding-type=url`, which is all but the unreserved ones and `/`
+const KEY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+pub async fn list_buckets(s3: &S3State) -> Result<Response, S3Error> {
+    let buckets = BucketModel::list_all(s3.state.database())
+        .await
+        .map_err(|e| S3Error::Internal(e.to_string()))?;
+
+    let mut xml = Writer::new();
+    xml.root("ListAllMyBucketsResult");
+    owner(&mut xml, s3);
+    xml.open("Buckets");
+    for bucket in buckets {
+        xml.open("Bucket");
+        xml.element("Name", &bucket.name);
+        xml.element("CreationDate", &iso8601(bucket.created_at));
+        xml.close("Bucket");
+    }
+    xml.close("Buckets");
+    xml.close("ListAllMyBucketsResult");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+pub async fn head_bucket(s3: &S3State, bucket: &str) -> Result<Response, S3Error> {
+    resolve_bucket(bucket, &s3.state).await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Buckets aren't in any region, but clients ask before using them
+pub async fn location(s3: &S3State, bucket: &str) -> Result<Response, S3Error> {
+    resolve_bucket(bucket, &s3.state).await?;
+    let mut xml = Writer::new();
+    xml.root("LocationConstraint");
+    xml.close("LocationConstraint");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+/// Buckets are created through the API, so this only succeeds, as S3 does
+///  in us-east-1, when the bucket already exists
+pub async fn create_bucket(s3: &S3State, bucket: &str) -> Result<Response, S3Error> {
+    match resolve_bucket(bucket, &s3.state).await {
+        Ok(_) => Err(S3Error::BucketAlreadyOwnedByYou(format!(
+            "The bucket {} already exists",
+            bucket
+        ))),
+        Err(S3Error::NoSuchBucket(_)) => Err(S3Error::NotImplemented(
+            "Buckets are created with `jax bucket create`".into(),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// ListObjectsV2, or ListObjects (V1) without `list-type=2`
+///
+/// Keys are listed from the folder the prefix is in: only that folder with
+///  the usual `/` delimiter, everything below it otherwise.
+pub async fn list_objects(
+    s3: &S3State,
+    bucket: &str,
+    params: &Params,
+) -> Result<Response, S3Error> {
+    let v2 = match params.get("list-type") {
+        Some("2") => true,
+        None => false,
+        Some(_) => return Err(S3Error::InvalidArgument("Invalid list-type".into())),
+    };
+    if v2 {
+        params.only(&[
+            "list-type",
+            "prefix",
+            "delimiter",
+            "max-keys",
+            "encoding-type",
+            "continuation-token",
+            "start-after",
+            "fetch-owner",
+        ])?;
+    } else {
+        params.only(&["prefix", "delimiter", "max-keys", "encoding-type", "marker"])?;
+    }
+
+    let prefix = params.get("prefix").unwrap_or_default();
+    let delimiter = params
+        .get("delimiter")
+        .filter(|delimiter| !delimiter.is_empty());
+    let max_keys = match params.get("max-keys") {
+        Some(max_keys) => max_keys
+            .parse::<usize>()
+            .map_err(|_| S3Error::InvalidArgument("Invalid max-keys".into()))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+    let url_encoded = match params.get("encoding-type") {
+        Some("url") => true,
+        None => false,
+        Some(_) => return Err(S3Error::InvalidArgument("Invalid encoding-type".into())),
+    };
+    let continuation_token = params.get("continuation-token");
+    let after = match continuation_token {
+        Some(token) => Some(
+            URL_SAFE_NO_PAD
+                .decode(token)
+                .ok()
+                .and_then(|key| String::from_utf8(key).ok())
+                .ok_or_else(|| S3Error::InvalidArgument("Invalid continuation-token".into()))?,
+        ),
+        None if v2 => params.get("start-after").map(str::to_string),
+        None => params.get("marker").map(str::to_string),
+    };
+
+    let bucket_model = resolve_bucket(bucket, &s3.state).await?;
+    let entries = list_entries(s3, bucket_model.id, prefix, delimiter).await?;
+    let (mut page, truncated) = page(entries, after.as_deref(), max_keys);
+    fill_sizes(s3, bucket_model.id, &mut page).await?;
+    let encode = |key: &str| {
+        if url_encoded {
+            utf8_percent_encode(key, KEY_ENCODE).to_string()
+        } else {
+            key.to_string()
+        }
+    };
+
+    let mut xml = Writer::new();
+    xml.root("ListBucketResult");
+    xml.element("Name", bucket);
+    xml.element("Prefix", &encode(prefix));
+    if let Some(delimiter) = delimiter {
+        xml.element("Delimiter", &encode(delimiter));
+    }
+    xml.element("MaxKeys", &max_keys.to_string());
+    if url_encoded {
+        xml.element("EncodingType", "url");
+    }
+    xml.element("IsTruncated", &truncated.to_string());
+    let last_key = page.last().map(|(key, _)| key.clone());
+    if v2 {
+        xml.element("KeyCount", &page.len().to_string());
+        if let Some(token) = continuation_token {
+            xml.element("ContinuationToken", token);
+        }
+        if let Some(start_after) = params.get("start-after") {
+            xml.element("StartAfter", &encode(start_after));
+        }
+        if let (true, Some(last_key)) = (truncated, &last_key) {
+            xml.element("NextContinuationToken", &URL_SAFE_NO_PAD.encode(last_key));
+        }
+    } else {
+        xml.element("Marker", &encode(params.get("marker").unwrap_or_default()));
+        if let (true, Some(last_key)) = (truncated, &last_key) {
+            xml.element("NextMarker", &encode(last_key));
+        }
+    }
+
+    let mut common_prefixes = Vec::new();
+    for (key, info) in page {
+        let Some(info) = info else {
+            common_prefixes.push(key);
+            continue;
+        };
+        xml.open("Contents");
+        xml.element("Key", &encode(&key));
+        xml.element(
+            "LastModified",
+            &iso8601(last_modified(&info, &bucket_model)),
+        );
+        xml.element("ETag", &etag(&info));
+        xml.element("Size", &info.size.unwrap_or_default().to_string());
+        xml.element("StorageClass", "STANDARD");
+        if !v2 || params.get("fetch-owner") == Some("true") {
+            owner(&mut xml, s3);
+        }
+        xml.close("Contents");
+    }
+    for prefix in common_prefixes {
+        xml.open("CommonPrefixes");
+        xml.element("Prefix", &encode(&prefix));
+        xml.close("CommonPrefixes");
+    }
+    xml.close("ListBucketResult");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+/// Every file under `prefix` by key, and every common prefix by itself
+///  mapped to `None`
+async fn list_entries(
+    s3: &S3State,
+    bucket_id: uuid::Uuid,
+    prefix: &str,
+    delimiter: Option<&str>,
+) -> Result<BTreeMap<String, Option<FileInfo>>, S3Error> {
+    // The folder the prefix is in, which is all that has to be listed
+    let base = match prefix.rfind('/') {
+        Some(end) => &prefix[..=end],
+        None => "",
+    };
+    let shallow = delimiter == Some("/");
+    let listed = crate::mount_ops::list_bucket_contents(
+        bucket_id,
+        Some(format!("/{}", base)),
+        !shallow,
+        None,
+        &s3.state,
+    )
+    .await;
+    let items = match listed {
+        Ok(items) => items,
+        // Listing a folder that isn't there, or a file, finds nothing
+        Err(MountOpsError::Mount(MountError::PathNotFound(_) | MountError::PathNotNode(_))) => {
+            return Ok(BTreeMap::new())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut files = Vec::new();
+    let mut folders = Vec::new();
+    for mut info in items {
+        if shallow {
+            // Shallow listings give paths from the root of the bucket
+            let key = info.path.trim_start_matches('/').to_string();
+            if info.is_dir {
+                folders.push(format!("{}/", key));
+            } else {
+                files.push((key, info));
+            }
+        } else if !info.is_dir {
+            // Deep listings give paths from the folder listed
+            let key = format!("{}{}", base, info.path.trim_start_matches('/'));
+            info.path = format!("/{}", key);
+            files.push((key, info));
+        }
+    }
+
+    let mut entries = group(files, prefix, delimiter);
+    entries.extend(
+        folders
+            .into_iter()
+            .filter(|folder| folder.starts_with(prefix))
+            .map(|folder| (folder, None)),
+    );
+    Ok(entries)
+}
+
+/// Read the sizes of files that weren't recorded when they were added,
+///  since every object listed has to have one
+async fn fill_sizes(
+    s3: &S3State,
+    bucket_id: uuid::Uuid,
+    page: &mut [(String, Option<FileInfo>)],
+) -> Result<(), S3Error> {
+    let mut missing: Vec<&mut FileInfo> = page
+        .iter_mut()
+        .filter_map(|(_, info)| info.as_mut())
+        .filter(|info| info.size.is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let paths = missing.iter().map(|info| info.path.clone()).collect();
+    let sizes = crate::mount_ops::get_file_sizes(bucket_id, paths, &s3.state).await?;
+    for (info, size) in missing.iter_mut().zip(sizes) {
+        info.size = Some(size);
+    }
+    Ok(())
+}
+
+/// Keep the objects whose keys start with `prefix`, and roll up those with
+///  `delimiter` after the prefix into common prefixes, which map to `None`
+fn group<T>(
+    objects: impl IntoIterator<Item = (String, T)>,
+    prefix: &str,
+    delimiter: Option<&str>,
+) -> BTreeMap<String, Option<T>> {
+    let mut entries = BTreeMap::new();
+    for (key, object) in objects {
+        let Some(rest) = key.strip_prefix(prefix) else {
+            continue;
+        };
+        match delimiter.and_then(|delimiter| rest.find(delimiter).map(|i| i + delimiter.len())) {
+            Some(end) => {
+                entries.insert(format!("{}{}", prefix, &rest[..end]), None);
+            }
+            None => {
+                entries.insert(key, Some(object));
+            }
+        }
+    }
+    entries
+}
+
+/// The first `max_keys` entries after the key `after`, and whether there
+///  are more
+fn page<T>(
+    entries: BTreeMap<String, T>,
+    after: Option<&str>,
+    max_keys: usize,
+) -> (Vec<(String, T)>, bool) {
+    let mut remaining = entries
+        .into_iter()
+        .filter(|(key, _)| after.is_none_or(|after| key.as_str() > after))
+        .peekable();
+    let page: Vec<_> = remaining.by_ref().take(max_keys).collect();
+    // An empty page can't say where the next one starts
+    let truncated = !page.is_empty() && remaining.peek().is_some();
+    (page, truncated)
+}
+
+fn owner(xml: &mut Writer, s3: &S3State) {
+    let id = s3.state.node().id().to_string();
+    xml.open("Owner");
+    xml.element("ID", &id);
+    xml.element("DisplayName", &id);
+    xml.close("Owner");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn objects(keys: &[&str]) -> Vec<(String, ())> {
+        keys.iter().map(|key| (key.to_string(), ())).collect()
+    }
+
+    fn keys<T>(entries: &BTreeMap<String, Option<T>>) -> Vec<String> {
+        entries
+            .iter()
+            .map(|(key, object)| match object {
+                Some(_) => key.clone(),
+                None => format!("[{}]", key),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group() {
+        let objects = objects(&[
+            "a.txt",
+            "photos/2024/beach.jpg",
+            "photos/2024/city.jpg",
+            "photos/cover.jpg",
+            "photos-old/x.jpg",
+        ]);
+
+        assert_eq!(
+            keys(&group(objects.clone(), "", Some("/"))),
+            ["a.txt", "[photos-old/]", "[photos/]"]
+        );
+        assert_eq!(
+            keys(&group(objects.clone(), "photos/", Some("/"))),
+            ["[photos/2024/]", "photos/cover.jpg"]
+        );
+        assert_eq!(
+            keys(&group(objects.clone(), "photos", None)),
+            [
+                "photos-old/x.jpg",
+                "photos/2024/beach.jpg",
+                "photos/2024/city.jpg",
+                "photos/cover.jpg"
+            ]
+        );
+        assert_eq!(
+            keys(&group(objects, "photos/2024/b", Some("."))),
+            ["[photos/2024/beach.]"]
+        );
+    }
+
+    #[test]
+    fn test_page() {
+        let entries: BTreeMap<String, ()> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|key| (key.to_string(), ()))
+            .collect();
+        let page_keys = |after, max_keys| {
+            let (page, truncated) = page(entries.clone(), after, max_keys);
+            (
+                page.into_iter().map(|(key, _)| key).collect::<Vec<_>>(),
+                truncated,
+            )
+        };
+
+        assert_eq!(page_keys(None, 2), (vec!["a".into(), "b".into()], true));
+        assert_eq!(
+            page_keys(Some("b"), 2),
+            (vec!["c".into(), "d".into()], false)
+        );
+        assert_eq!(
+            page_keys(Some("bb"), 10),
+            (vec!["c".into(), "d".into()], false)
+        );
+        assert_eq!(page_keys(None, 0), (vec![], false));
+        assert_eq!(page_keys(Some("d"), 0), (vec![], false));
+    }
+}
@@ -0,0 +1,552 @@
+//! An S3-compatible API over buckets, for tools that only speak S3
+//!
+//! Requests are addressed path-style, `/<bucket>/<key>`, with buckets
+//! named by their name or their ID and keys being paths in the bucket
+//! without their leading `/`. Every request has to be signed (AWS
+//! Signature Version 4) with one of the node's access keys. Reads are
+//! served from the current version, and every change is saved as a new
+//! version and announced to peers, like changes made through the API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{HeaderMap, Method, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use http::header::CONTENT_TYPE;
+use http::{HeaderValue, StatusCode};
+use percent_encoding::percent_decode_str;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use common::prelude::{Link, MountError};
+
+use crate::database::models::Bucket as BucketModel;
+use crate::mount_ops::{FileInfo, MountOpsError};
+use crate::{S3AccessKey, ServiceState};
+
+mod auth;
+mod body;
+mod bucket;
+mod multipart;
+mod object;
+mod xml;
+
+use auth::Signed;
+
+/// Longest key S3 accepts, in bytes
+const MAX_KEY_LEN: usize = 1024;
+
+/// Query parameters any request may carry: presigned URL parameters, and
+///  the operation name some SDKs add
+const IGNORED_PARAMS: &[&str] = &[
+    "x-id",
+    "X-Amz-Algorithm",
+    "X-Amz-Credential",
+    "X-Amz-Date",
+    "X-Amz-Expires",
+    "X-Amz-SignedHeaders",
+    "X-Amz-Signature",
+    "X-Amz-Security-Token",
+];
+
+#[derive(Clone)]
+struct S3State {
+    state: ServiceState,
+    /// Secret access keys, by their access key ID
+    keys: Arc<HashMap<String, String>>,
+    /// Multipart uploads in progress, by their upload ID
+    uploads: Arc<Mutex<HashMap<String, multipart::Upload>>>,
+}
+
+pub fn router(state: ServiceState, access_keys: Vec<S3AccessKey>) -> Router {
+    let keys = access_keys
+        .into_iter()
+        .map(|key| (key.access_key_id, key.secret_access_key))
+        .collect();
+    let s3 = S3State {
+        state,
+        keys: Arc::new(keys),
+        uploads: Arc::default(),
+    };
+    Router::new()
+        .fallback(handler)
+        // Uploads are streamed into the bucket, so they aren't bound by a body limit
+        .layer(DefaultBodyLimit::disable())
+        .with_state(s3)
+}
+
+async fn handler(
+    State(s3): State<S3State>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let result = match auth::verify(&method, &uri, &headers, &s3.keys, OffsetDateTime::now_utc()) {
+        Ok(signed) => dispatch(&s3, signed, &method, &uri, &headers, body).await,
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn dispatch(
+    s3: &S3State,
+    signed: Signed,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let params = Params::parse(uri.query());
+    let target = Target::parse(uri.path())?;
+    let copy_source = headers.contains_key("x-amz-copy-source");
+
+    match (target, method.as_str()) {
+        (Target::Service, "GET") => {
+            params.only(&[])?;
+            bucket::list_buckets(s3).await
+        }
+        (Target::Bucket(bucket), "HEAD") => bucket::head_bucket(s3, &bucket).await,
+        (Target::Bucket(bucket), "GET") if params.has("location") => {
+            params.only(&["location"])?;
+            bucket::location(s3, &bucket).await
+        }
+        (Target::Bucket(bucket), "GET") => bucket::list_objects(s3, &bucket, &params).await,
+        (Target::Bucket(bucket), "PUT") => {
+            // Tools often create the bucket they're about to use, which
+            //  is fine as long as it exists
+            params.only(&[])?;
+            bucket::create_bucket(s3, &bucket).await
+        }
+        (Target::Object { bucket, key }, "GET") => {
+            params.only(&[])?;
+            object::get_object(s3, &bucket, &key, headers, true).await
+        }
+        (Target::Object { bucket, key }, "HEAD") => {
+            params.only(&[])?;
+            object::get_object(s3, &bucket, &key, headers, false).await
+        }
+        (Target::Object { bucket, key }, "PUT") if params.has("uploadId") => {
+            params.only(&["uploadId", "partNumber"])?;
+            if copy_source {
+                return Err(S3Error::NotImplemented(
+                    "Parts can't be copied from other objects".into(),
+                ));
+            }
+            multipart::upload_part(s3, signed, &bucket, &key, &params, headers, body).await
+        }
+        (Target::Object { bucket, key }, "PUT") if copy_source => {
+            params.only(&[])?;
+            object::copy_object(s3, &bucket, &key, headers).await
+        }
+        (Target::Object { bucket, key }, "PUT") => {
+            params.only(&[])?;
+            object::put_object(s3, signed, &bucket, &key, headers, body).await
+        }
+        (Target::Object { bucket, key }, "DELETE") if params.has("uploadId") => {
+            params.only(&["uploadId"])?;
+            multipart::abort_upload(s3, &bucket, &key, &params).await
+        }
+        (Target::Object { bucket, key }, "DELETE") => {
+            params.only(&[])?;
+            object::delete_object(s3, &bucket, &key).await
+        }
+        (Target::Object { bucket, key }, "POST") if params.has("uploads") => {
+            params.only(&["uploads"])?;
+            multipart::create_upload(s3, &bucket, &key).await
+        }
+        (Target::Object { bucket, key }, "POST") if params.has("uploadId") => {
+            params.only(&["uploadId"])?;
+            multipart::complete_upload(s3, signed, &bucket, &key, &params, headers, body).await
+        }
+        (_, "HEAD" | "PUT" | "POST" | "DELETE") => Err(S3Error::NotImplemented(format!(
+            "{} isn't supported here",
+            method
+        ))),
+        _ => Err(S3Error::MethodNotAllowed),
+    }
+}
+
+/// What a request is about
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// The list of buckets
+    Service,
+    /// A bucket, named as in the request
+    Bucket(String),
+    /// An object in a bucket, with its key as a path in the bucket
+    Object { bucket: String, key: String },
+}
+
+impl Target {
+    /// Parse the path of a path-style request
+    fn parse(path: &str) -> Result<Self, S3Error> {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+        let bucket = decode(bucket)?;
+        if bucket.is_empty() {
+            return Ok(Target::Service);
+        }
+        if key.is_empty() {
+            return Ok(Target::Bucket(bucket));
+        }
+
+        let key = decode(key)?;
+        if key.len() > MAX_KEY_LEN {
+            return Err(S3Error::KeyTooLong);
+        }
+        // A trailing slash marks a folder, but other empty segments have no
+        //  place in a bucket
+        let mut segments: Vec<_> = key.split('/').collect();
+        if segments.last() == Some(&"") {
+            segments.pop();
+        }
+        if segments
+            .iter()
+            .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+        {
+            return Err(S3Error::InvalidArgument(format!(
+                "{} can't be a path in a bucket",
+                key
+            )));
+        }
+        Ok(Target::Object {
+            bucket,
+            key: format!("/{}", key),
+        })
+    }
+}
+
+/// Decoded query parameters
+#[derive(Debug, Default)]
+struct Params(BTreeMap<String, String>);
+
+impl Params {
+    fn parse(query: Option<&str>) -> Self {
+        let params = query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+                let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+                (decode(name), decode(value))
+            })
+            .collect();
+        Params(params)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Refuse parameters other than `allowed`, which would make the request
+    ///  a different operation than the one that's supported
+    fn only(&self, allowed: &[&str]) -> Result<(), S3Error> {
+        match self.0.keys().find(|name| {
+            !allowed.contains(&name.as_str()) && !IGNORED_PARAMS.contains(&name.as_str())
+        }) {
+            Some(name) => Err(S3Error::NotImplemented(format!(
+                "The {} parameter isn't supported here",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn decode(text: &str) -> Result<String, S3Error> {
+    percent_decode_str(text)
+        .decode_utf8()
+        .map(|text| text.into_owned())
+        .map_err(|_| S3Error::InvalidArgument("Names must be UTF-8".into()))
+}
+
+/// Find a bucket by its name, or its ID
+async fn resolve_bucket(bucket: &str, state: &ServiceState) -> Result<BucketModel, S3Error> {
+    BucketModel::find(bucket, state.database())
+        .await
+        .map_err(|e| S3Error::Internal(e.to_string()))?
+        .ok_or_else(|| S3Error::NoSuchBucket(bucket.to_string()))
+}
+
+/// Look up a path in a bucket, or `None` if nothing is there
+async fn stat(
+    bucket_id: Uuid,
+    path: &str,
+    state: &ServiceState,
+) -> Result<Option<FileInfo>, S3Error> {
+    match crate::mount_ops::get_path_info(bucket_id, path.to_string(), None, state).await {
+        Ok(info) => Ok(Some(info)),
+        Err(MountOpsError::Mount(MountError::PathNotFound(_) | MountError::PathNotNode(_))) => {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Look up what a change saved, in the version it saved
+async fn stat_at(
+    bucket_id: Uuid,
+    path: &str,
+    link: &Link,
+    state: &ServiceState,
+) -> Result<FileInfo, S3Error> {
+    Ok(
+        crate::mount_ops::get_path_info(bucket_id, path.to_string(), Some(*link.hash()), state)
+            .await?,
+    )
+}
+
+/// The ETag of a file, which is the hash of its encrypted data, so it
+///  changes whenever the file does
+fn etag(info: &FileInfo) -> String {
+    format!("\"{}\"", info.link.hash())
+}
+
+/// A time as S3 writes them in XML
+fn iso8601(time: OffsetDateTime) -> String {
+    let format = time::format_description::parse(
+        "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z",
+    )
+    .expect("valid format description");
+    time.to_offset(time::UtcOffset::UTC)
+        .format(&format)
+        .unwrap_or_default()
+}
+
+/// When a file was last modified, or failing that when its bucket was
+fn last_modified(info: &FileInfo, bucket: &BucketModel) -> OffsetDateTime {
+    info.mtime
+        .and_then(|mtime| OffsetDateTime::from_unix_timestamp_nanos(mtime as i128 * 1_000_000).ok())
+        .unwrap_or(bucket.updated_at)
+}
+
+/// Run a change to a bucket in a blocking task, as the API handlers do
+async fn blocking<T, F, Fut>(state: &ServiceState, change: F) -> Result<T, S3Error>
+where
+    T: Send + 'static,
+    F: FnOnce(ServiceState) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<T, MountOpsError>>,
+{
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || tokio::runtime::Handle::current().block_on(change(state)))
+        .await
+        .map_err(|e| S3Error::Internal(format!("Task join error: {}", e)))?
+        .map_err(S3Error::from)
+}
+
+/// An XML response
+fn xml_response(status: StatusCode, body: String) -> Response {
+    let mut response = (status, body).into_response();
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+    response
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum S3Error {
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+    #[error("The access key ID isn't one of this node's")]
+    InvalidAccessKeyId,
+    #[error("The request signature doesn't match the one calculated with the access key")]
+    SignatureDoesNotMatch,
+    #[error("The request was signed too long ago, or too far in the future")]
+    RequestTimeTooSkewed,
+    #[error("Malformed authorization: {0}")]
+    AuthorizationHeaderMalformed(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("The key is longer than 1024 bytes")]
+    KeyTooLong,
+    #[error("The bucket {0} doesn't exist")]
+    NoSuchBucket(String),
+    #[error("The key {0} doesn't exist")]
+    NoSuchKey(String),
+    #[error("The upload {0} doesn't exist, it may have been completed or aborted")]
+    NoSuchUpload(String),
+    #[error("{0}")]
+    InvalidPart(String),
+    #[error("Parts must be listed in ascending order")]
+    InvalidPartOrder,
+    #[error("Malformed XML: {0}")]
+    MalformedXML(String),
+    #[error("The payload doesn't match x-amz-content-sha256")]
+    XAmzContentSHA256Mismatch,
+    #[error("The payload is incomplete: {0}")]
+    IncompleteBody(String),
+    #[error("The requested range isn't satisfiable")]
+    InvalidRange,
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    BucketAlreadyOwnedByYou(String),
+    #[error("{0}")]
+    OperationAborted(String),
+    #[error("{0}")]
+    NotImplemented(String),
+    #[error("The method isn't allowed on this resource")]
+    MethodNotAllowed,
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl S3Error {
+    fn code(&self) -> &'static str {
+        match self {
+            S3Error::AccessDenied(_) => "AccessDenied",
+            S3Error::InvalidAccessKeyId => "InvalidAccessKeyId",
+            S3Error::SignatureDoesNotMatch => "SignatureDoesNotMatch",
+            S3Error::RequestTimeTooSkewed => "RequestTimeTooSkewed",
+            S3Error::AuthorizationHeaderMalformed(_) => "AuthorizationHeaderMalformed",
+            S3Error::InvalidArgument(_) => "InvalidArgument",
+            S3Error::InvalidRequest(_) => "InvalidRequest",
+            S3Error::KeyTooLong => "KeyTooLongError",
+            S3Error::NoSuchBucket(_) => "NoSuchBucket",
+            S3Error::NoSuchKey(_) => "NoSuchKey",
+            S3Error::NoSuchUpload(_) => "NoSuchUpload",
+            S3Error::InvalidPart(_) => "InvalidPart",
+            S3Error::InvalidPartOrder => "InvalidPartOrder",
+            S3Error::MalformedXML(_) => "MalformedXML",
+            S3Error::XAmzContentSHA256Mismatch => "XAmzContentSHA256Mismatch",
+            S3Error::IncompleteBody(_) => "IncompleteBody",
+            S3Error::InvalidRange => "InvalidRange",
+            S3Error::PreconditionFailed(_) => "PreconditionFailed",
+            S3Error::BucketAlreadyOwnedByYou(_) => "BucketAlreadyOwnedByYou",
+            S3Error::OperationAborted(_) => "OperationAborted",
+            S3Error::NotImplemented(_) => "NotImplemented",
+            S3Error::MethodNotAllowed => "MethodNotAllowed",
+            S3Error::Internal(_) => "InternalError",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            S3Error::AccessDenied(_)
+            | S3Error::InvalidAccessKeyId
+            | S3Error::SignatureDoesNotMatch
+            | S3Error::RequestTimeTooSkewed => StatusCode::FORBIDDEN,
+            S3Error::AuthorizationHeaderMalformed(_)
+            | S3Error::InvalidArgument(_)
+            | S3Error::InvalidRequest(_)
+            | S3Error::KeyTooLong
+            | S3Error::InvalidPart(_)
+            | S3Error::InvalidPartOrder
+            | S3Error::MalformedXML(_)
+            | S3Error::XAmzContentSHA256Mismatch
+            | S3Error::IncompleteBody(_) => StatusCode::BAD_REQUEST,
+            S3Error::NoSuchBucket(_) | S3Error::NoSuchKey(_) | S3Error::NoSuchUpload(_) => {
+                StatusCode::NOT_FOUND
+            }
+            S3Error::InvalidRange => StatusCode::RANGE_NOT_SATISFIABLE,
+            S3Error::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            S3Error::BucketAlreadyOwnedByYou(_) | S3Error::OperationAborted(_) => {
+                StatusCode::CONFLICT
+            }
+            S3Error::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            S3Error::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            S3Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<MountOpsError> for S3Error {
+    fn from(err: MountOpsError) -> Self {
+        match err {
+            MountOpsError::BucketNotFound(id) => S3Error::NoSuchBucket(id.to_string()),
+            MountOpsError::InvalidPath(msg) => S3Error::InvalidArgument(msg),
+            MountOpsError::Mount(MountError::PathNotFound(path)) => {
+                S3Error::NoSuchKey(path.display().to_string())
+            }
+            MountOpsError::Mount(MountError::PathNotNode(path)) => {
+                S3Error::InvalidRequest(format!("{} is a file, not a folder", path.display()))
+            }
+            MountOpsError::Mount(MountError::PathAlreadyExists(path)) => {
+                S3Error::InvalidRequest(format!("{} already exists", path.display()))
+            }
+            MountOpsError::Mount(MountError::ReadOnly) => {
+                S3Error::AccessDenied("This bucket is shared read-only".into())
+            }
+            MountOpsError::Conflict(id) => S3Error::OperationAborted(format!(
+                "Bucket {} changed while it was being updated, try again",
+                id
+            )),
+            e => S3Error::Internal(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let message = match &self {
+            S3Error::Internal(_) => {
+                tracing::error!("S3 request failed: {}", self);
+                "Unexpected error".to_string()
+            }
+            _ => self.to_string(),
+        };
+        let mut body = xml::Writer::new();
+        body.open("Error");
+        body.element("Code", self.code());
+        body.element("Message", &message);
+        body.close("Error");
+        xml_response(self.status(), body.finish())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(Target::parse("/").unwrap(), Target::Service);
+        assert_eq!(
+            Target::parse("/photos").unwrap(),
+            Target::Bucket("photos".into())
+        );
+        assert_eq!(
+            Target::parse("/photos/").unwrap(),
+            Target::Bucket("photos".into())
+        );
+        assert_eq!(
+            Target::parse("/my%20photos/2024/beach%20day.jpg").unwrap(),
+            Target::Object {
+                bucket: "my photos".into(),
+                key: "/2024/beach day.jpg".into()
+            }
+        );
+        assert_eq!(
+            Target::parse("/photos/2024/").unwrap(),
+            Target::Object {
+                bucket: "photos".into(),
+                key: "/2024/".into()
+            }
+        );
+        assert!(Target::parse("/photos/a//b").is_err());
+        assert!(Target::parse("/photos/../secrets").is_err());
+        assert!(Target::parse(&format!("/photos/{}", "a".repeat(1025))).is_err());
+    }
+
+    #[test]
+    fn test_params() {
+        let params = Params::parse(Some(
+            "list-type=2&prefix=a%2Fb%20c&x-id=ListObjectsV2&uploads",
+        ));
+        assert_eq!(params.get("prefix"), Some("a/b c"));
+        assert!(params.has("uploads"));
+        assert!(params.only(&["list-type", "prefix", "uploads"]).is_ok());
+        assert!(params.only(&["list-type", "prefix"]).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+//! Multipart uploads, each part chunked, encrypted and stored as it
+//!  arrives, then linked into one file when the upload completes
+//!
+//! Uploads in progress are kept in memory, so they don't survive a
+//!  restart. Their parts are held from the garbage collector until the
+//!  upload completes or is aborted, or until it's been left alone for a
+//!  day and is dropped.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::response::{IntoResponse, Response};
+use common::bucket::Chunk;
+use common::crypto::Secret;
+use http::header::ETAG;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use uuid::Uuid;
+
+use super::auth::Signed;
+use super::body::{self, read_into};
+use super::xml::{self, Writer};
+use super::{blocking, etag, resolve_bucket, stat_at, xml_response, Params, S3Error, S3State};
+
+/// How long an upload is kept after anything last happened to it
+const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Highest part number S3 allows
+const MAX_PART_NUMBER: u32 = 10_000;
+/// Largest CompleteMultipartUpload body accepted, which is plenty for
+///  every part listed
+const MAX_COMPLETE_BODY: usize = 2 * 1024 * 1024;
+
+pub struct Upload {
+    bucket_id: Uuid,
+    /// The path the file will be at
+    key: String,
+    /// What every part is encrypted with
+    secret: Secret,
+    parts: BTreeMap<u32, Part>,
+    /// When the upload was last started, added to or completed
+    active: Instant,
+}
+
+struct Part {
+    chunks: Vec<Chunk>,
+    /// Quoted BLAKE3 hash of the part's data
+    etag: String,
+}
+
+/// CreateMultipartUpload
+pub async fn create_upload(s3: &S3State, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    if key.ends_with('/') {
+        return Err(S3Error::InvalidRequest(
+            "Folder markers can't be uploaded in parts".into(),
+        ));
+    }
+    let bucket_id = resolve_bucket(bucket, &s3.state).await?.id;
+    let secret =
+        crate::mount_ops::start_multipart_upload(bucket_id, PathBuf::from(key), &s3.state).await?;
+
+    let upload_id = Uuid::new_v4().to_string();
+    let expired = {
+        let mut uploads = s3.uploads.lock().expect("uploads lock poisoned");
+        let expired: Vec<String> = uploads
+            .iter()
+            .filter(|(_, upload)| upload.active.elapsed() >= UPLOAD_TTL)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            uploads.remove(id);
+        }
+        uploads.insert(
+            upload_id.clone(),
+            Upload {
+                bucket_id,
+                key: key.to_string(),
+                secret,
+                parts: BTreeMap::new(),
+                active: Instant::now(),
+            },
+        );
+        expired
+    };
+    // Abandoned uploads are aborted, releasing their parts
+    for id in expired {
+        if let Err(e) = crate::mount_ops::abort_multipart_upload(&id, &s3.state).await {
+            tracing::warn!("Failed to abort expired upload {}: {}", id, e);
+        }
+    }
+
+    let mut xml = Writer::new();
+    xml.root("InitiateMultipartUploadResult");
+    xml.element("Bucket", bucket);
+    xml.element("Key", key.trim_start_matches('/'));
+    xml.element("UploadId", &upload_id);
+    xml.close("InitiateMultipartUploadResult");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+/// UploadPart, storing the part as it arrives; uploading a part number
+///  again replaces the part
+pub async fn upload_part(
+    s3: &S3State,
+    signed: Signed,
+    bucket: &str,
+    key: &str,
+    params: &Params,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let upload_id = params.get("uploadId").unwrap_or_default();
+    let part_number = params
+        .get("partNumber")
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| (1..=MAX_PART_NUMBER).contains(number))
+        .ok_or_else(|| {
+            S3Error::InvalidArgument(format!(
+                "partNumber must be between 1 and {}",
+                MAX_PART_NUMBER
+            ))
+        })?;
+    let payload = body::payload(signed, headers, body)?;
+    let bucket_id = resolve_bucket(bucket, &s3.state).await?.id;
+    let secret = s3.with_upload(upload_id, bucket_id, key, |upload| {
+        Ok(upload.secret.clone())
+    })?;
+
+    let state = s3.state.clone();
+    let id = upload_id.to_string();
+    let (chunks, hash) = read_into(payload, move |reader| async move {
+        blocking(&state, move |state| async move {
+            crate::mount_ops::upload_part(&id, &secret, reader, &state).await
+        })
+        .await
+    })
+    .await?;
+
+    let etag = format!("\"{}\"", hash);
+    s3.with_upload(upload_id, bucket_id, key, |upload| {
+        upload.parts.insert(
+            part_number,
+            Part {
+                chunks,
+                etag: etag.clone(),
+            },
+        );
+        Ok(())
+    })?;
+
+    let mut response = StatusCode::OK.into_response();
+    let etag = HeaderValue::from_str(&etag).map_err(|e| S3Error::Internal(e.to_string()))?;
+    response.headers_mut().insert(ETAG, etag);
+    Ok(response)
+}
+
+/// CompleteMultipartUpload, joining the listed parts in order into the
+///  file, saved as a single version
+pub async fn complete_upload(
+    s3: &S3State,
+    signed: Signed,
+    bucket: &str,
+    key: &str,
+    params: &Params,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let upload_id = params.get("uploadId").unwrap_or_default();
+    let payload = body::payload(signed, headers, body)?;
+    let request = body::collect(payload, MAX_COMPLETE_BODY).await?;
+    let request = String::from_utf8(request)
+        .map_err(|_| S3Error::MalformedXML("The body isn't UTF-8".into()))?;
+    let listed = parse_parts(&request)?;
+
+    let bucket_id = resolve_bucket(bucket, &s3.state).await?.id;
+    let (secret, chunks) = s3.with_upload(upload_id, bucket_id, key, |upload| {
+        let mut chunks = Vec::new();
+        for (number, etag) in &listed {
+            let part = upload
+                .parts
+                .get(number)
+                .filter(|part| part.etag.trim_matches('"') == etag.trim_matches('"'))
+                .ok_or_else(|| {
+                    S3Error::InvalidPart(format!(
+                        "Part {} wasn't uploaded, or its ETag doesn't match",
+                        number
+                    ))
+                })?;
+            chunks.extend_from_slice(&part.chunks);
+        }
+        Ok((upload.secret.clone(), chunks))
+    })?;
+
+    let mount_path = PathBuf::from(key);
+    let id = upload_id.to_string();
+    let link = blocking(&s3.state, move |state| async move {
+        crate::mount_ops::complete_multipart_upload(
+            &id, bucket_id, mount_path, secret, chunks, None, &state,
+        )
+        .await
+    })
+    .await?;
+    s3.uploads
+        .lock()
+        .expect("uploads lock poisoned")
+        .remove(upload_id);
+
+    let info = stat_at(bucket_id, key, &link, &s3.state).await?;
+    let key = key.trim_start_matches('/');
+    let mut xml = Writer::new();
+    xml.root("CompleteMultipartUploadResult");
+    xml.element("Location", &format!("/{}/{}", bucket, key));
+    xml.element("Bucket", bucket);
+    xml.element("Key", key);
+    xml.element("ETag", &etag(&info));
+    xml.close("CompleteMultipartUploadResult");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+/// AbortMultipartUpload; the parts stored are left to the garbage collector
+pub async fn abort_upload(
+    s3: &S3State,
+    _bucket: &str,
+    key: &str,
+    params: &Params,
+) -> Result<Response, S3Error> {
+    let upload_id = params.get("uploadId").unwrap_or_default();
+    {
+        let mut uploads = s3.uploads.lock().expect("uploads lock poisoned");
+        match uploads.get(upload_id) {
+            Some(upload) if upload.key == key => uploads.remove(upload_id),
+            _ => return Err(S3Error::NoSuchUpload(upload_id.to_string())),
+        };
+    }
+    crate::mount_ops::abort_multipart_upload(upload_id, &s3.state).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+impl S3State {
+    /// Work on an upload in progress, as long as it's for `key` in the
+    ///  bucket
+    fn with_upload<T>(
+        &self,
+        upload_id: &str,
+        bucket_id: Uuid,
+        key: &str,
+        work: impl FnOnce(&mut Upload) -> Result<T, S3Error>,
+    ) -> Result<T, S3Error> {
+        let mut uploads = self.uploads.lock().expect("uploads lock poisoned");
+        match uploads.get_mut(upload_id) {
+            Some(upload) if upload.bucket_id == bucket_id && upload.key == key => {
+                upload.active = Instant::now();
+                work(upload)
+            }
+            _ => Err(S3Error::NoSuchUpload(upload_id.to_string())),
+        }
+    }
+}
+
+/// The part numbers and ETags listed in a CompleteMultipartUpload body, in
+///  ascending order
+fn parse_parts(request: &str) -> Result<Vec<(u32, String)>, S3Error> {
+    let mut parts: Vec<(u32, String)> = Vec::new();
+    for part in xml::elements(request, "Part")? {
+        let number = xml::element(part, "PartNumber")?
+            .trim()
+            .parse()
+            .map_err(|_| S3Error::MalformedXML("Invalid PartNumber".into()))?;
+        let etag = xml::unescape(xml::element(part, "ETag")?.trim());
+        if parts.last().is_some_and(|(last, _)| *last >= number) {
+            return Err(S3Error::InvalidPartOrder);
+        }
+        parts.push((number, etag));
+    }
+    if parts.is_empty() {
+        return Err(S3Error::MalformedXML("No parts are listed".into()));
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_parts() {
+        let request = "<CompleteMultipartUpload xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+            <Part><ETag>&quot;aa&quot;</ETag><PartNumber>1</PartNumber></Part>\
+            <Part><PartNumber>3</PartNumber><ETag>\"bb\"</ETag></Part>\
+            </CompleteMultipartUpload>";
+        assert_eq!(
+            parse_parts(request).unwrap(),
+            vec![(1, "\"aa\"".to_string()), (3, "\"bb\"".to_string())]
+        );
+
+        let out_of_order = "<CompleteMultipartUpload>\
+            <Part><ETag>\"bb\"</ETag><PartNumber>2</PartNumber></Part>\
+            <Part><ETag>\"aa\"</ETag><PartNumber>1</PartNumber></Part>\
+            </CompleteMultipartUpload>";
+        assert!(matches!(
+            parse_parts(out_of_order),
+            Err(S3Error::InvalidPartOrder)
+        ));
+        assert!(parse_parts("<CompleteMultipartUpload/>").is_err());
+        assert!(parse_parts("<Part><PartNumber>x</PartNumber><ETag>a</ETag></Part>").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use axum::body::Body;
+use axum::response::{IntoResponse, Response};
+use headers::{
+    AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, IfMatch, IfModifiedSince,
+    IfNoneMatch, IfUnmodifiedSince, LastModified,
+};
+use http::header::CONTENT_TYPE;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use tokio_util::io::ReaderStream;
+
+use crate::http_server::api::v0::bucket::cat::{resolve_range, RangeRequest};
+use crate::mount_ops::FileInfo;
+
+use super::auth::Signed;
+use super::body::{self, read_into};
+use super::xml::Writer;
+use super::{
+    blocking, etag, iso8601, last_modified, resolve_bucket, stat, stat_at, xml_response, S3Error,
+    S3State, Target,
+};
+
+/// GetObject, or HeadObject without `with_body`, serving part of the file
+///  if a single range is asked for, as the download API does
+///
+/// A key ending in `/` is a folder marker, found if the folder exists.
+pub async fn get_object(
+    s3: &S3State,
+    bucket: &str,
+    key: &str,
+    headers: &HeaderMap,
+    with_body: bool,
+) -> Result<Response, S3Error> {
+    let bucket_model = resolve_bucket(bucket, &s3.state).await?;
+    let folder_marker = key.ends_with('/');
+    let info = stat(bucket_model.id, key.trim_end_matches('/'), &s3.state)
+        .await?
+        .filter(|info| info.is_dir == folder_marker)
+        .ok_or_else(|| S3Error::NoSuchKey(key.trim_start_matches('/').to_string()))?;
+    let modified = SystemTime::from(last_modified(&info, &bucket_model));
+
+    if folder_marker {
+        let mut response_headers = HeaderMap::new();
+        response_headers.typed_insert(parse_etag(&etag(&info))?);
+        response_headers.typed_insert(LastModified::from(modified));
+        response_headers.typed_insert(ContentLength(0));
+        return Ok((StatusCode::OK, response_headers).into_response());
+    }
+
+    let range_header = headers.typed_get::<headers::Range>();
+    let select_range = {
+        let range_header = range_header.clone();
+        move |size| match resolve_range(range_header.as_ref(), size) {
+            RangeRequest::Partial(range) if with_body => Some(range),
+            RangeRequest::Full if with_body => None,
+            // Nothing will be served, so don't read anything
+            _ => Some(size..size),
+        }
+    };
+    let file = crate::mount_ops::get_file_reader(
+        bucket_model.id,
+        key.to_string(),
+        None,
+        select_range,
+        &s3.state,
+    )
+    .await?;
+
+    let etag = parse_etag(&format!("\"{}\"", file.link.hash()))?;
+    let size = file.reader.size();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.typed_insert(etag.clone());
+    response_headers.typed_insert(AcceptRanges::bytes());
+    response_headers.typed_insert(LastModified::from(modified));
+
+    // If-Match and If-None-Match take precedence over the dates, as in S3
+    match headers.typed_get::<IfMatch>() {
+        Some(if_match) if !if_match.precondition_passes(&etag) => {
+            return Err(S3Error::PreconditionFailed(
+                "The object's ETag doesn't match If-Match".into(),
+            ));
+        }
+        Some(_) => {}
+        None => {
+            if let Some(since) = headers.typed_get::<IfUnmodifiedSince>() {
+                if !since.precondition_passes(modified) {
+                    return Err(S3Error::PreconditionFailed(
+                        "The object was modified after If-Unmodified-Since".into(),
+                    ));
+                }
+            }
+        }
+    }
+    let not_modified = match headers.typed_get::<IfNoneMatch>() {
+        Some(if_none_match) => !if_none_match.precondition_passes(&etag),
+        None => headers
+            .typed_get::<IfModifiedSince>()
+            .is_some_and(|since| !since.is_modified(modified)),
+    };
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let (status, len) = match resolve_range(range_header.as_ref(), size) {
+        RangeRequest::Full => (StatusCode::OK, size),
+        RangeRequest::Partial(range) => {
+            let len = range.end - range.start;
+            let content_range = ContentRange::bytes(range, size)
+                .map_err(|_| S3Error::Internal("invalid content range".into()))?;
+            response_headers.typed_insert(content_range);
+            (StatusCode::PARTIAL_CONTENT, len)
+        }
+        RangeRequest::Unsatisfiable => return Err(S3Error::InvalidRange),
+    };
+
+    response_headers.typed_insert(ContentLength(len));
+    if let Ok(content_type) = HeaderValue::from_str(&file.mime_type) {
+        response_headers.insert(CONTENT_TYPE, content_type);
+    }
+
+    let body = if with_body {
+        Body::from_stream(ReaderStream::new(file.reader))
+    } else {
+        Body::empty()
+    };
+    Ok((status, response_headers, body).into_response())
+}
+
+/// PutObject, streaming the payload into the file as it arrives
+///
+/// A key ending in `/` is a folder marker, which creates the folder.
+pub async fn put_object(
+    s3: &S3State,
+    signed: Signed,
+    bucket: &str,
+    key: &str,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let payload = body::payload(signed, headers, body)?;
+    let bucket_id = resolve_bucket(bucket, &s3.state).await?.id;
+
+    if key.ends_with('/') {
+        body::collect(payload, 0)
+            .await
+            .map_err(|_| S3Error::InvalidRequest("Folder markers can't have content".into()))?;
+        let path = key.trim_end_matches('/').to_string();
+        let info = match stat(bucket_id, &path, &s3.state).await? {
+            Some(info) if info.is_dir => info,
+            Some(_) => return Err(is_a_file(&path)),
+            None => {
+                let dir = PathBuf::from(&path);
+                let link = blocking(&s3.state, move |state| async move {
+                    crate::mount_ops::make_dir_in_bucket(bucket_id, dir, None, &state).await
+                })
+                .await?;
+                stat_at(bucket_id, &path, &link, &s3.state).await?
+            }
+        };
+        return etag_response(&info);
+    }
+
+    if stat(bucket_id, key, &s3.state)
+        .await?
+        .is_some_and(|info| info.is_dir)
+    {
+        return Err(is_a_folder(key));
+    }
+
+    let mount_path = PathBuf::from(key);
+    let state = s3.state.clone();
+    let link = read_into(payload, move |reader| async move {
+        blocking(&state, move |state| async move {
+            crate::mount_ops::add_data_to_bucket(bucket_id, mount_path, reader, None, &state).await
+        })
+        .await
+    })
+    .await?;
+
+    etag_response(&stat_at(bucket_id, key, &link, &s3.state).await?)
+}
+
+/// CopyObject within a bucket, which shares the original's data rather than
+///  copying it
+pub async fn copy_object(
+    s3: &S3State,
+    bucket: &str,
+    key: &str,
+    headers: &HeaderMap,
+) -> Result<Response, S3Error> {
+    let source = headers
+        .get("x-amz-copy-source")
+        .and_then(|source| source.to_str().ok())
+        .ok_or_else(|| S3Error::InvalidArgument("Invalid x-amz-copy-source".into()))?;
+    if source.contains("?versionId=") {
+        return Err(S3Error::NotImplemented(
+            "Objects can only be copied from the current version".into(),
+        ));
+    }
+    let Target::Object {
+        bucket: source_bucket,
+        key: source_key,
+    } = Target::parse(&format!("/{}", source.trim_start_matches('/')))?
+    else {
+        return Err(S3Error::InvalidArgument(
+            "x-amz-copy-source must name an object".into(),
+        ));
+    };
+
+    let bucket_model = resolve_bucket(bucket, &s3.state).await?;
+    if resolve_bucket(&source_bucket, &s3.state).await?.id != bucket_model.id {
+        return Err(S3Error::NotImplemented(
+            "Objects can only be copied within a bucket".into(),
+        ));
+    }
+    let info = stat(bucket_model.id, &source_key, &s3.state)
+        .await?
+        .filter(|info| !info.is_dir && !source_key.ends_with('/'))
+        .ok_or_else(|| S3Error::NoSuchKey(source_key.trim_start_matches('/').to_string()))?;
+    if key.ends_with('/') {
+        return Err(S3Error::InvalidRequest(
+            "Objects can't be copied to folder markers".into(),
+        ));
+    }
+    if stat(bucket_model.id, key, &s3.state)
+        .await?
+        .is_some_and(|info| info.is_dir)
+    {
+        return Err(is_a_folder(key));
+    }
+
+    // Copying onto itself, to replace its metadata, leaves it as it is
+    if source_key != key {
+        let bucket_id = bucket_model.id;
+        let (from, to) = (PathBuf::from(&source_key), PathBuf::from(key));
+        blocking(&s3.state, move |state| async move {
+            crate::mount_ops::copy_path_in_bucket(bucket_id, from, to, false, None, &state).await
+        })
+        .await?;
+    }
+
+    let mut xml = Writer::new();
+    xml.root("CopyObjectResult");
+    xml.element(
+        "LastModified",
+        &iso8601(last_modified(&info, &bucket_model)),
+    );
+    xml.element("ETag", &etag(&info));
+    xml.close("CopyObjectResult");
+    Ok(xml_response(StatusCode::OK, xml.finish()))
+}
+
+/// DeleteObject, which succeeds whether or not the key exists, as in S3
+///
+/// A folder marker removes its folder only when it's empty, since S3
+///  wouldn't remove the keys under it either.
+pub async fn delete_object(s3: &S3State, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let bucket_id = resolve_bucket(bucket, &s3.state).await?.id;
+    let path = key.trim_end_matches('/').to_string();
+    let remove = match stat(bucket_id, &path, &s3.state).await? {
+        Some(info) if info.is_dir && key.ends_with('/') => crate::mount_ops::list_bucket_contents(
+            bucket_id,
+            Some(path.clone()),
+            false,
+            None,
+            &s3.state,
+        )
+        .await?
+        .is_empty(),
+        Some(info) => !info.is_dir && !key.ends_with('/'),
+        None => false,
+    };
+
+    if remove {
+        let path = PathBuf::from(path);
+        let removed = blocking(&s3.state, move |state| async move {
+            crate::mount_ops::remove_path_from_bucket(bucket_id, path, None, &state).await
+        })
+        .await;
+        match removed {
+            // Removed by another request since it was looked up
+            Ok(_) | Err(S3Error::NoSuchKey(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+fn etag_response(info: &FileInfo) -> Result<Response, S3Error> {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(parse_etag(&etag(info))?);
+    Ok((StatusCode::OK, headers).into_response())
+}
+
+fn parse_etag(etag: &str) -> Result<ETag, S3Error> {
+    etag.parse()
+        .map_err(|_| S3Error::Internal("invalid etag".into()))
+}
+
+fn is_a_folder(key: &str) -> S3Error {
+    S3Error::InvalidRequest(format!("{} is a folder", key.trim_start_matches('/')))
+}
+
+fn is_a_file(key: &str) -> S3Error {
+    S3Error::InvalidRequest(format!("{} is a file", key.trim_start_matches('/')))
+}
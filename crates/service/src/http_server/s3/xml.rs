@@ -0,0 +1,116 @@
+//! Just enough XML for S3: writing responses, and reading the few request
+//!  bodies that are XML
+
+use std::fmt::Write;
+
+use super::S3Error;
+
+/// Namespace of every S3 response
+const NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+pub struct Writer(String);
+
+impl Writer {
+    pub fn new() -> Self {
+        Writer(String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"))
+    }
+
+    /// Open the root element of a response, in the S3 namespace
+    pub fn root(&mut self, name: &str) {
+        let _ = write!(self.0, "<{} xmlns=\"{}\">", name, NAMESPACE);
+    }
+
+    pub fn open(&mut self, name: &str) {
+        let _ = write!(self.0, "<{}>", name);
+    }
+
+    pub fn close(&mut self, name: &str) {
+        let _ = write!(self.0, "</{}>", name);
+    }
+
+    /// An element holding text
+    pub fn element(&mut self, name: &str, text: &str) {
+        let _ = write!(self.0, "<{}>{}</{}>", name, escape(text), name);
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+    }
+}
+
+/// The text of every `name` element in `xml`, in order, without looking at
+///  where they are nested
+pub fn elements<'a>(xml: &'a str, name: &str) -> Result<Vec<&'a str>, S3Error> {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let end = rest
+            .find(&close)
+            .ok_or_else(|| S3Error::MalformedXML(format!("{} isn't closed", name)))?;
+        found.push(&rest[..end]);
+        rest = &rest[end + close.len()..];
+    }
+    Ok(found)
+}
+
+/// The text of the one `name` element in `xml`
+pub fn element<'a>(xml: &'a str, name: &str) -> Result<&'a str, S3Error> {
+    match elements(xml, name)?.as_slice() {
+        [text] => Ok(text),
+        _ => Err(S3Error::MalformedXML(format!("Expected one {}", name))),
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_writer() {
+        let mut xml = Writer::new();
+        xml.root("ListAllMyBucketsResult");
+        xml.element("Name", "a&b");
+        xml.close("ListAllMyBucketsResult");
+        assert_eq!(
+            xml.finish(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListAllMyBucketsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>a&amp;b</Name></ListAllMyBucketsResult>"
+        );
+    }
+
+    #[test]
+    fn test_elements() {
+        let xml = "<CompleteMultipartUpload><Part><ETag>&quot;a&quot;</ETag><PartNumber>1</PartNumber></Part><Part><ETag>\"b\"</ETag><PartNumber>2</PartNumber></Part></CompleteMultipartUpload>";
+        let parts = elements(xml, "Part").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(element(parts[0], "PartNumber").unwrap(), "1");
+        assert_eq!(unescape(element(parts[0], "ETag").unwrap()), "\"a\"");
+        assert_eq!(unescape(element(parts[1], "ETag").unwrap()), "\"b\"");
+        assert!(element(xml, "PartNumber").is_err());
+        assert!(elements("<Part><ETag>a</Part>", "ETag").is_err());
+    }
+}
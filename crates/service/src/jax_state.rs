@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use flume::Sender;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::sync::{Arc, OnceLock};
 use uuid::Uuid;
 
@@ -71,6 +71,11 @@ impl JaxState {
 
     /// Check if a target link is in the bucket's history
     ///
+    /// Follows both parents of merge versions, visiting versions closest to
+    /// `current_link` first.
+    /// Versions we no longer have, having dropped them to a retention policy
+    /// or garbage collection, end the branch of history they're on.
+    ///
     /// Returns:
     /// - Some(true) if the link is found (target is an ancestor)
     /// - Some(false) if we reached max depth without finding it
@@ -81,7 +86,7 @@ impl JaxState {
         target_link: &Link,
     ) -> Result<Option<bool>, anyhow::Error> {
         let mut seen_links = HashSet::new();
-        let mut current = current_link.clone();
+        let mut queue = VecDeque::from([current_link.clone()]);
         let mut depth = 0;
 
         tracing::info!(
@@ -90,53 +95,50 @@ impl JaxState {
             current_link
         );
 
-        seen_links.insert(current.clone());
+        seen_links.insert(current_link.clone());
 
-        while depth < MAX_HISTORY_DEPTH {
+        while let Some(current) = queue.pop_front() {
+            if depth >= MAX_HISTORY_DEPTH {
+                // Hit max depth
+                return Ok(Some(false));
+            }
             tracing::info!(
                 "Checking if link {:?} is in history of {:?} -- depth: {}",
                 target_link,
                 current_link,
                 depth
             );
-            // Load the bucket data
+            // Load the bucket data, history we dropped ends this branch
             let bucket_data = match self.load_bucket_data(&current).await {
                 Ok(data) => data,
                 Err(e) => {
-                    tracing::warn!("Failed to load bucket data at link {:?}: {}", current, e);
-                    return Ok(Some(false));
+                    tracing::debug!("Failed to load bucket data at link {:?}: {}", current, e);
+                    depth += 1;
+                    continue;
                 }
             };
             tracing::info!("Loaded bucket data @ {:?}", current);
 
-            // Check if there's a previous version
-            let Some(previous_link) = bucket_data.previous().clone() else {
-                tracing::info!("No more history after {:?}", current);
-                // No more history
-                return Ok(None);
-            };
-
-            tracing::info!("Previous link: {:?}", previous_link);
+            for parent_link in bucket_data.parents() {
+                tracing::info!("Parent link: {:?}", parent_link);
 
-            // Check if we've found the target
-            if &previous_link == target_link {
-                tracing::info!("Found target link in history, we are ahead");
-                return Ok(Some(true));
-            }
+                // Check if we've found the target
+                if parent_link == target_link {
+                    tracing::info!("Found target link in history, we are ahead");
+                    return Ok(Some(true));
+                }
 
-            // Avoid cycles
-            if seen_links.contains(&previous_link) {
-                tracing::warn!("Cycle detected in bucket history");
-                return Ok(Some(false));
+                // Merges make the history a DAG, so versions can be reached twice
+                if seen_links.insert(parent_link.clone()) {
+                    queue.push_back(parent_link.clone());
+                }
             }
-
-            seen_links.insert(previous_link.clone());
-            current = previous_link;
             depth += 1;
         }
 
-        // Hit max depth
-        Ok(Some(false))
+        // No more history
+        tracing::info!("No more history after {:?}", current_link);
+        Ok(None)
     }
 }
 
@@ -8,7 +8,7 @@ mod sync_manager;
 mod config;
 mod state;
 
-pub use config::Config as ServiceConfig;
+pub use config::{Config as ServiceConfig, S3AccessKey};
 pub use mount_ops::{BucketInfo, FileInfo, MountOpsError};
 pub use process::spawn_service;
 pub use state::{State as ServiceState, StateSetupError as ServiceStateSetupError};
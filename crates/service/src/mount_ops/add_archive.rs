@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_zip::base::read::stream::ZipFileReader;
+use common::bucket::NodeLink;
+use common::peer::BlobsStore;
+use common::prelude::{Link, Mount};
+use futures::StreamExt;
+use tokio::io::{AsyncRead, BufReader};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::ServiceState;
+
+use super::add_files::{join_relative, save_staged};
+use super::error::MountOpsError;
+use super::types::{ArchiveFormat, UploadInfo};
+
+/// Size of the buffer between an archive entry and the mount
+const ENTRY_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Unix file type bits of a symlink, as stored in zip external attributes
+const ZIP_SYMLINK_MODE: u16 = 0o120000;
+const ZIP_FILE_TYPE_MASK: u16 = 0o170000;
+
+/// Unpack a tar or zip archive into the directory `dir` of a bucket,
+/// streaming it from `reader`
+/// Every file is stored before any is added to the tree, so the whole
+/// archive lands as a single version. Directories are implied by the files in
+/// them, and links and other special entries are skipped.
+pub async fn add_archive_to_bucket<R>(
+    bucket_id: Uuid,
+    dir: PathBuf,
+    format: ArchiveFormat,
+    reader: R,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<UploadInfo, MountOpsError>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    if !dir.is_absolute() {
+        return Err(MountOpsError::InvalidPath(
+            "Mount path must be absolute".into(),
+        ));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    let (staged, skipped) = match format {
+        ArchiveFormat::Tar => stage_tar(&mut mount, &dir, reader, blobs).await?,
+        ArchiveFormat::Zip => stage_zip(&mut mount, &dir, reader, blobs).await?,
+    };
+    if staged.is_empty() {
+        return Err(MountOpsError::InvalidArchive(
+            "Archive has no files in it".into(),
+        ));
+    }
+
+    let added = staged
+        .keys()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let changes = staged
+        .into_iter()
+        .map(|(path, link)| (path, Some(link)))
+        .collect();
+    let new_bucket_link = save_staged(&mut mount, bucket, changes, message, state).await?;
+
+    Ok(UploadInfo {
+        link: new_bucket_link,
+        added,
+        removed: Vec::new(),
+        moved: Vec::new(),
+        skipped,
+    })
+}
+
+/// Files staged from an archive, by their path in the bucket, and the
+/// names of the entries skipped
+type Staged = (BTreeMap<PathBuf, NodeLink>, Vec<String>);
+
+/// Stage every regular file in a tar archive under `dir`
+async fn stage_tar<R>(
+    mount: &mut Mount,
+    dir: &Path,
+    reader: R,
+    blobs: &BlobsStore,
+) -> Result<Staged, MountOpsError>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut archive = tokio_tar::Archive::new(reader);
+    let mut entries = archive.entries().map_err(invalid_archive)?;
+
+    let mut staged = BTreeMap::new();
+    let mut skipped = Vec::new();
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry.map_err(invalid_archive)?;
+        let name = entry.path().map_err(invalid_archive)?.to_path_buf();
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            continue;
+        }
+        if !entry_type.is_file() {
+            skipped.push(name.to_string_lossy().to_string());
+            continue;
+        }
+        let Some(path) = join_relative(dir, &name)? else {
+            continue;
+        };
+        let link = stage_entry(mount, &path, &mut entry, blobs).await?;
+        staged.insert(path, link);
+    }
+    Ok((staged, skipped))
+}
+
+/// Stage every file in a zip archive under `dir`
+/// The archive is read as a stream, relying on the local header of each
+/// entry rather than the central directory at its end
+async fn stage_zip<R>(
+    mount: &mut Mount,
+    dir: &Path,
+    reader: R,
+    blobs: &BlobsStore,
+) -> Result<Staged, MountOpsError>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut zip = ZipFileReader::with_tokio(BufReader::new(reader));
+
+    let mut staged = BTreeMap::new();
+    let mut skipped = Vec::new();
+    while let Some(mut reading) = zip.next_with_entry().await.map_err(invalid_archive)? {
+        let entry = reading.reader().entry();
+        let name = entry
+            .filename()
+            .as_str()
+            .map_err(invalid_archive)?
+            .to_string();
+        let is_dir = entry.dir().map_err(invalid_archive)?;
+        let is_symlink = entry
+            .unix_permissions()
+            .is_some_and(|mode| mode & ZIP_FILE_TYPE_MASK == ZIP_SYMLINK_MODE);
+        let crc32 = entry.crc32();
+
+        let path = if is_dir {
+            None
+        } else if is_symlink {
+            skipped.push(name.clone());
+            None
+        } else {
+            join_relative(dir, Path::new(&name))?
+        };
+        let Some(path) = path else {
+            zip = reading.skip().await.map_err(invalid_archive)?;
+            continue;
+        };
+
+        let link = stage_entry(mount, &path, &mut reading.reader_mut().compat(), blobs).await?;
+        // Entries written with a data descriptor don't know their checksum
+        //  up front, and record it as zero
+        if crc32 != 0 && reading.reader_mut().compute_hash() != crc32 {
+            return Err(MountOpsError::InvalidArchive(format!(
+                "Checksum mismatch for {}",
+                name
+            )));
+        }
+        zip = reading.done().await.map_err(invalid_archive)?;
+        staged.insert(path, link);
+    }
+    Ok((staged, skipped))
+}
+
+/// Stage the contents of one archive entry to go at `path`
+/// The mount needs a reader it can own, so the entry is forwarded to it
+/// through an in-memory pipe
+async fn stage_entry<R>(
+    mount: &mut Mount,
+    path: &Path,
+    data: &mut R,
+    blobs: &BlobsStore,
+) -> Result<NodeLink, MountOpsError>
+where
+    R: AsyncRead + Unpin,
+{
+    let (mut writer, reader) = tokio::io::duplex(ENTRY_BUFFER_SIZE);
+    let copy = async {
+        let result = tokio::io::copy(data, &mut writer).await;
+        drop(writer);
+        result
+    };
+    let (copied, staged) = tokio::join!(copy, mount.stage(path, reader, blobs));
+    // A failed stage breaks the pipe, so its error comes first, while a
+    //  failed read leaves a truncated file that is never added
+    let link = staged?;
+    copied.map_err(invalid_archive)?;
+    Ok(link)
+}
+
+fn invalid_archive(err: impl std::fmt::Display) -> MountOpsError {
+    MountOpsError::InvalidArchive(err.to_string())
+}
@@ -1,7 +1,7 @@
-use std::io::Read;
 use std::path::PathBuf;
 
 use common::prelude::{Link, Mount};
+use tokio::io::AsyncRead;
 use uuid::Uuid;
 
 use crate::database::models::Bucket as BucketModel;
@@ -10,17 +10,19 @@ use crate::ServiceState;
 
 use super::error::MountOpsError;
 
-/// Share a bucket with a peer by adding them to the bucket's shares
-/// Returns the new bucket link after adding the share
+/// Add data to a bucket at the given path, streaming it from `reader`
+/// Returns the new bucket link after adding the data
 pub async fn add_data_to_bucket<R>(
     bucket_id: Uuid,
     mount_path: PathBuf,
     reader: R,
+    message: Option<String>,
     state: &ServiceState,
 ) -> Result<Link, MountOpsError>
 where
-    R: Read + Send + Sync + 'static + Unpin,
+    R: AsyncRead + Send + Sync + 'static + Unpin,
 {
+    let _lock = state.lock_bucket(bucket_id).await;
     // Get bucket from database
     let bucket = BucketModel::get_by_id(&bucket_id, state.database())
         .await
@@ -38,13 +40,13 @@ where
 
     mount.add(&mount_path, reader, blobs).await?;
 
+    mount.set_message(message);
     let new_bucket_link = mount.save(blobs).await?;
 
     // Update bucket link in database
     bucket
         .update_link(new_bucket_link.clone(), state.database())
-        .await
-        .map_err(|e| MountOpsError::Database(e.to_string()))?;
+        .await?;
 
     // Trigger push sync to announce the new share to all peers
     tracing::debug!(
@@ -77,3 +79,36 @@ where
 
     Ok(new_bucket_link)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use futures::future::join_all;
+
+    use super::super::test_utils::{create_bucket, load, setup};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_adds_are_kept() {
+        let (state, _temp) = setup().await;
+        let bucket_id = create_bucket(&state).await;
+
+        let adds = (0..8).map(|i| {
+            let path = PathBuf::from(format!("/{}.txt", i));
+            let data = Cursor::new(format!("file {}", i).into_bytes());
+            add_data_to_bucket(bucket_id, path, data, None, &state)
+        });
+        for result in join_all(adds).await {
+            result.unwrap();
+        }
+
+        let mount = load(bucket_id, &state).await;
+        let blobs = state.node().blobs();
+        for i in 0..8 {
+            let path = PathBuf::from(format!("/{}.txt", i));
+            let data = mount.cat(&path, blobs).await.unwrap();
+            assert_eq!(data, format!("file {}", i).into_bytes());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use common::bucket::NodeLink;
+use common::prelude::{Link, Mount};
+use tokio::io::AsyncRead;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::types::{UploadFile, UploadInfo};
+
+/// Add files to the directory `dir` of a bucket as they arrive on `files`,
+/// each named by its path relative to `dir`, removing the relative paths
+/// in `removed` and moving the relative paths in `moved` along with them
+/// A move replaces whatever was at its destination, and is resolved
+/// against the bucket as it was before any of the changes
+/// Files are encrypted and stored one after another, then added to the
+/// tree together and saved as a single version, so each directory is
+/// only written once however many files go in it
+pub async fn add_files_to_bucket<R>(
+    bucket_id: Uuid,
+    dir: PathBuf,
+    files: flume::Receiver<UploadFile<R>>,
+    removed: Vec<String>,
+    moved: Vec<(String, String)>,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<UploadInfo, MountOpsError>
+where
+    R: AsyncRead + Send + Sync + 'static + Unpin,
+{
+    if !dir.is_absolute() {
+        return Err(MountOpsError::InvalidPath(
+            "Mount path must be absolute".into(),
+        ));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    // Removals go in first, so a file uploaded to the same path replaces them
+    let mut changes = BTreeMap::new();
+    for name in removed {
+        let Some(path) = join_relative(&dir, Path::new(&name))? else {
+            return Err(MountOpsError::InvalidPath(format!(
+                "Can't remove {} itself",
+                dir.display()
+            )));
+        };
+        changes.insert(path, None);
+    }
+
+    let mut moved_paths = Vec::new();
+    for (from, to) in moved {
+        let (Some(from_path), Some(to_path)) = (
+            join_relative(&dir, Path::new(&from))?,
+            join_relative(&dir, Path::new(&to))?,
+        ) else {
+            return Err(MountOpsError::InvalidPath(format!(
+                "Can't move {} itself",
+                dir.display()
+            )));
+        };
+        if to_path.starts_with(&from_path) {
+            return Err(MountOpsError::InvalidPath(format!(
+                "Can't move {} into itself",
+                from
+            )));
+        }
+        let link = mount.get(&from_path, blobs).await?;
+        moved_paths.push((from_path, to_path, link));
+    }
+    // Sources are only removed if nothing moves in their place, so paths
+    //  can be swapped
+    for (from, _, _) in &moved_paths {
+        changes.entry(from.clone()).or_insert(None);
+    }
+    for (_, to, link) in &moved_paths {
+        changes.insert(to.clone(), Some(link.clone()));
+    }
+    let moved_paths: Vec<(String, String)> = moved_paths
+        .into_iter()
+        .map(|(from, to, _)| {
+            (
+                from.to_string_lossy().to_string(),
+                to.to_string_lossy().to_string(),
+            )
+        })
+        .collect();
+
+    while let Ok(file) = files.recv_async().await {
+        let Some(path) = join_relative(&dir, Path::new(&file.name))? else {
+            return Err(MountOpsError::InvalidPath(format!(
+                "{} is not a file",
+                file.name
+            )));
+        };
+        let mut link = mount.stage(&path, file.reader, blobs).await?;
+        if let (Some(mtime), Some(data)) = (file.mtime, link.data_mut()) {
+            data.set_mtime(mtime);
+        }
+        changes.insert(path, Some(link));
+    }
+
+    // Removing a directory removes everything under it
+    let removed_dirs: Vec<PathBuf> = changes
+        .iter()
+        .filter(|(_, link)| link.is_none())
+        .map(|(path, _)| path.clone())
+        .collect();
+    changes.retain(|path, link| {
+        link.is_some()
+            || !removed_dirs
+                .iter()
+                .any(|dir| path != dir && path.starts_with(dir))
+    });
+
+    if changes.is_empty() {
+        return Err(MountOpsError::InvalidPath("No files to add".into()));
+    }
+
+    // Moves are reported on their own rather than as additions and removals
+    let (mut added, mut removed) = (Vec::new(), Vec::new());
+    for (path, link) in &changes {
+        let path = path.to_string_lossy().to_string();
+        let is_move = moved_paths
+            .iter()
+            .any(|(from, to)| *from == path || *to == path);
+        match link {
+            _ if is_move => {}
+            Some(_) => added.push(path),
+            None => removed.push(path),
+        }
+    }
+    let new_bucket_link = save_staged(&mut mount, bucket, changes, message, state).await?;
+
+    Ok(UploadInfo {
+        link: new_bucket_link,
+        added,
+        removed,
+        moved: moved_paths,
+        skipped: Vec::new(),
+    })
+}
+
+/// Apply staged files, and removals (`None`), to the mount's tree in one
+/// pass and save it, pushing the new version to peers
+pub(super) async fn save_staged(
+    mount: &mut Mount,
+    bucket: BucketModel,
+    changes: BTreeMap<PathBuf, Option<NodeLink>>,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    let bucket_id = bucket.id;
+    let blobs = state.node().blobs();
+
+    tracing::info!("Applying {} changes to bucket {}", changes.len(), bucket_id);
+    mount.apply_staged(changes, blobs).await?;
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the files were added successfully
+    }
+
+    Ok(new_bucket_link)
+}
+
+/// Resolve a relative `name`, from an upload or an archive, to a path
+/// under `dir`
+/// Returns None for names with nothing left once `.` components are
+/// dropped, and refuses names that would escape `dir`
+pub(super) fn join_relative(dir: &Path, name: &Path) -> Result<Option<PathBuf>, MountOpsError> {
+    let mut path = dir.to_path_buf();
+    let mut empty = true;
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => {
+                path.push(part);
+                empty = false;
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(MountOpsError::InvalidPath(format!(
+                    "{} is outside of {}",
+                    name.display(),
+                    dir.display()
+                )));
+            }
+        }
+    }
+    Ok((!empty).then_some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_relative() {
+        let dir = Path::new("/photos");
+        let resolve = |name: &str| join_relative(dir, Path::new(name));
+
+        assert_eq!(
+            resolve("2024/beach.jpg").unwrap(),
+            Some(PathBuf::from("/photos/2024/beach.jpg"))
+        );
+        assert_eq!(
+            resolve("./2024/./beach.jpg").unwrap(),
+            Some(PathBuf::from("/photos/2024/beach.jpg"))
+        );
+        assert_eq!(resolve("./").unwrap(), None);
+
+        // Names may not escape the directory they're added to
+        assert!(resolve("../secrets").is_err());
+        assert!(resolve("2024/../../secrets").is_err());
+        assert!(resolve("/etc/passwd").is_err());
+    }
+}
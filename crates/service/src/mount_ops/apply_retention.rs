@@ -0,0 +1,125 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use uuid::Uuid;
+
+use common::prelude::{Link, Mount, MountError};
+
+use crate::database::models::{Bucket as BucketModel, RetentionPolicy};
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::collect_garbage::collect_garbage;
+use super::error::MountOpsError;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Drop the history of a bucket its retention policy no longer keeps
+/// Returns the new bucket link, or None if there was nothing to drop
+pub async fn apply_retention(
+    policy: &RetentionPolicy,
+    state: &ServiceState,
+) -> Result<Option<Link>, MountOpsError> {
+    let bucket_id = policy.bucket_id;
+    // Keep writes made while the history is squashed from being lost
+    let _lock = state.lock_bucket(bucket_id).await;
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+    let blobs = state.node().blobs();
+    let mut mount = Mount::load(&bucket.link.into(), state.node().secret(), blobs).await?;
+
+    // Count the versions before the current one that the policy keeps
+    let max_previous = policy
+        .max_versions
+        .map_or(usize::MAX, |max| (max.max(1) - 1) as usize);
+    let cutoff = policy.max_age_days.map(|days| {
+        let age = Duration::from_secs(days.max(0) as u64 * SECONDS_PER_DAY);
+        SystemTime::now()
+            .checked_sub(age)
+            .unwrap_or(UNIX_EPOCH)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    });
+    let mut keep = 0;
+    let mut history = Box::pin(mount.history(blobs).skip(1));
+    while keep < max_previous {
+        let Some(entry) = history.next().await else {
+            break;
+        };
+        let entry = match entry {
+            Ok(entry) => entry,
+            // History that isn't available locally is dropped regardless
+            Err(MountError::LinkNotFound(_)) => break,
+            Err(e) => return Err(MountOpsError::Mount(e)),
+        };
+        // Versions from before timestamps were recorded count as old
+        if cutoff.is_some_and(|cutoff| entry.timestamp().is_none_or(|at| at < cutoff)) {
+            break;
+        }
+        keep += 1;
+    }
+
+    mount.set_message(Some(format!(
+        "Drop history before the last {} versions",
+        keep + 1
+    )));
+    let Some(new_bucket_link) = mount.squash(keep, blobs).await? else {
+        return Ok(None);
+    };
+
+    // Update bucket link in database, unless it moved on since it was read
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the squashed history to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after applying its retention policy",
+        bucket_id
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the history was dropped successfully
+    }
+
+    Ok(Some(new_bucket_link))
+}
+
+/// Apply every bucket's retention policy, then remove the blobs only
+/// dropped versions referenced
+/// Returns the buckets whose history was dropped
+pub async fn apply_retention_policies(state: &ServiceState) -> Result<Vec<Uuid>, MountOpsError> {
+    let policies = RetentionPolicy::list(state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?;
+
+    let mut squashed = Vec::new();
+    for policy in &policies {
+        // A bucket failing to apply its policy shouldn't hold up the rest
+        match apply_retention(policy, state).await {
+            Ok(Some(_)) => squashed.push(policy.bucket_id),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                "Failed to apply retention policy of bucket {}: {}",
+                policy.bucket_id,
+                e
+            ),
+        }
+    }
+
+    if !squashed.is_empty() {
+        collect_garbage(None, false, state).await?;
+    }
+
+    Ok(squashed)
+}
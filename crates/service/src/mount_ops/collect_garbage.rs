@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use common::prelude::{Link, Mount};
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::types::GcReport;
+
+/// How long a blob is kept after it was added, even if nothing references it,
+///  so that blobs of changes which aren't saved yet aren't collected
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Prune the pin set of every bucket to its current version and `depth`
+/// previous versions, and remove every blob none of them reference
+/// Without a `depth` every bucket's whole history is kept, and only blobs
+/// no version references are removed
+/// With `dry_run` nothing is changed, and the report describes what would be
+pub async fn collect_garbage(
+    depth: Option<usize>,
+    dry_run: bool,
+    state: &ServiceState,
+) -> Result<GcReport, MountOpsError> {
+    let buckets = BucketModel::list_all(state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?;
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut live = HashSet::new();
+    let mut pruned = Vec::new();
+    for bucket in &buckets {
+        let mut bucket_link: Link = bucket.link.into();
+
+        // Only buckets we can write to get a new, smaller pin set. The
+        //  history of the rest is still limited to `depth` locally.
+        if let (Some(depth), false) = (depth, dry_run) {
+            // Hold the bucket while its pins are pruned, and prune the version
+            //  it's at by then, so writes made since it was listed aren't lost
+            let _lock = state.lock_bucket(bucket.id).await;
+            let Some(bucket) = BucketModel::get_by_id(&bucket.id, state.database())
+                .await
+                .map_err(|e| MountOpsError::Database(e.to_string()))?
+            else {
+                continue;
+            };
+            bucket_link = bucket.link.into();
+            let mut mount = Mount::load(&bucket_link, secret_key, blobs).await?;
+            if mount.role().is_some_and(|role| role.can_write())
+                && mount.prune_pins(depth, blobs).await?
+            {
+                mount.set_message(Some(format!(
+                    "Prune history to the last {} versions",
+                    depth
+                )));
+                bucket_link = mount.save(blobs).await?;
+                bucket
+                    .update_link(bucket_link.clone(), state.database())
+                    .await?;
+
+                // Trigger push sync so peers see the pruned pin set
+                tracing::debug!(
+                    "Triggering push sync for bucket {} after pruning its pins",
+                    bucket.id
+                );
+                if let Err(e) = state.send_sync_event(SyncEvent::Push {
+                    bucket_id: bucket.id,
+                    new_link: bucket_link.clone(),
+                }) {
+                    tracing::warn!(
+                        "Failed to trigger push sync for bucket {}: {:?}",
+                        bucket.id,
+                        e
+                    );
+                    // Don't fail the collection if sync event fails - the pins were pruned successfully
+                }
+                pruned.push(bucket.id);
+            }
+        }
+
+        // Pin sets are only pruned once they hold more than one version
+        //  beyond `depth`, so keep everything they may still hold
+        let depth = depth.map_or(usize::MAX, |depth| depth + 1);
+        let retained = Mount::retained(&bucket_link, depth, secret_key, blobs).await?;
+        live.extend(retained.iter());
+    }
+
+    let removed = blobs
+        .collect_garbage(&live, GC_GRACE_PERIOD, dry_run)
+        .await
+        .map_err(|e| MountOpsError::Mount(e.into()))?;
+    tracing::info!(
+        "Garbage collection {} {} blobs ({} bytes) across {} buckets",
+        if dry_run { "would remove" } else { "removed" },
+        removed.blobs,
+        removed.bytes,
+        buckets.len()
+    );
+
+    Ok(GcReport {
+        buckets: buckets.len(),
+        pruned,
+        retained: live.len(),
+        removed,
+    })
+}
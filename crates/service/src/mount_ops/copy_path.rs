@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use common::prelude::{Link, Mount};
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Copy a file or directory within a bucket, replacing whatever is at `to`
+/// The copy shares the original's encrypted data, so nothing is uploaded
+/// again. With `remove_source` the original is removed in the same
+/// version, which makes it a move that may replace its destination.
+/// Returns the new bucket link after the copy
+pub async fn copy_path_in_bucket(
+    bucket_id: Uuid,
+    from: PathBuf,
+    to: PathBuf,
+    remove_source: bool,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    if !from.is_absolute() || !to.is_absolute() {
+        return Err(MountOpsError::InvalidPath(
+            "Source and destination paths must be absolute".into(),
+        ));
+    }
+    if to.starts_with(&from) {
+        return Err(MountOpsError::InvalidPath(format!(
+            "Can't copy {} into itself",
+            from.display()
+        )));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    let link = mount.get(&from, blobs).await?;
+    let mut changes = BTreeMap::new();
+    if remove_source {
+        changes.insert(from.clone(), None);
+    }
+    changes.insert(to.clone(), Some(link));
+    mount.apply_staged(changes, blobs).await?;
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the copy to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after copying {:?} to {:?}",
+        bucket_id,
+        from,
+        to
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the copy was applied successfully
+    }
+
+    Ok(new_bucket_link)
+}
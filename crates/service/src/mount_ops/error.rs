@@ -1,13 +1,19 @@
 use uuid::Uuid;
 
+use crate::database::models::bucket::BucketError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum MountOpsError {
     #[error("Bucket not found: {0}")]
     BucketNotFound(Uuid),
+    #[error("Version not found: {0}")]
+    VersionNotFound(common::linked_data::Hash),
     #[error("Invalid path: {0}")]
     InvalidPath(String),
     #[error("Database error: {0}")]
     Database(String),
+    #[error("Bucket changed while it was being updated: {0}")]
+    Conflict(Uuid),
     #[error("Mount error: {0}")]
     Mount(#[from] common::prelude::MountError),
     #[error("Share not found")]
@@ -16,4 +22,15 @@ pub enum MountOpsError {
     CryptoError(String),
     #[error("Share error: {0}")]
     ShareError(String),
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+}
+
+impl From<BucketError> for MountOpsError {
+    fn from(e: BucketError) -> Self {
+        match e {
+            BucketError::Conflict(id) => MountOpsError::Conflict(id),
+            e => MountOpsError::Database(e.to_string()),
+        }
+    }
 }
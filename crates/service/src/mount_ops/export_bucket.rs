@@ -0,0 +1,59 @@
+use common::prelude::{Link, Mount, MountError};
+use tokio::io::DuplexStream;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Size of the buffer between the archive being written and read
+const ARCHIVE_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug)]
+pub struct BucketArchive {
+    /// The CAR archive, written as it's read
+    pub reader: DuplexStream,
+    /// Resolves once the archive is written, with the error that cut it
+    ///  short if there was one
+    pub done: oneshot::Receiver<Result<(), MountError>>,
+    pub name: String,
+}
+
+/// Export the current version of a bucket as a CAR archive, along with
+/// every blob pinned for its history that is still stored
+/// Which blobs go in the archive is worked out up front, so a bucket that
+/// is missing part of its current version fails before anything is written
+pub async fn export_bucket(
+    bucket_id: Uuid,
+    state: &ServiceState,
+) -> Result<BucketArchive, MountOpsError> {
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs().clone();
+    let blocks = Mount::car_blocks(&bucket_link, secret_key, &blobs).await?;
+
+    let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_SIZE);
+    let (done_tx, done) = oneshot::channel();
+    tokio::spawn(async move {
+        let result = Mount::export_car(&bucket_link, &blocks, &blobs, writer)
+            .await
+            .map(|_| ());
+        if let Err(e) = &result {
+            tracing::error!("Failed to export bucket {}: {}", bucket_id, e);
+        }
+        let _ = done_tx.send(result);
+    });
+
+    Ok(BucketArchive {
+        reader,
+        done,
+        name: bucket.name,
+    })
+}
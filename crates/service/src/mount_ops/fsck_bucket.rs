@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use common::linked_data::{BlockEncoded, Hash};
+use common::prelude::{Link, Manifest, Mount, MountError};
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::types::FsckInfo;
+
+/// Check every blob the current version of a bucket is made of
+/// With `repair`, blobs that are missing or corrupt are fetched again from
+/// the bucket's peers until everything is repaired, or nothing more can be
+/// A blob is only fetched once, so a peer that sends a corrupt copy again
+/// doesn't keep the repair going
+pub async fn fsck_bucket(
+    bucket_id: Uuid,
+    repair: bool,
+    state: &ServiceState,
+) -> Result<FsckInfo, MountOpsError> {
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+    let endpoint = state.node().endpoint();
+
+    let report = Mount::fsck(&bucket_link, secret_key, blobs).await?;
+    let mut remaining = report.problems.clone();
+    let mut repaired = Vec::new();
+    if !repair {
+        return Ok(FsckInfo {
+            link: bucket_link,
+            report,
+            repaired,
+            remaining,
+        });
+    }
+
+    // Peers are whoever the bucket is shared with, other than ourselves.
+    //  Without a manifest there's nobody to ask; syncing the bucket fetches
+    //  it again.
+    let peer_ids: Vec<_> = match blobs.get(bucket_link.hash()).await {
+        Ok(data) => match Manifest::decode(&data) {
+            Ok(manifest) => manifest
+                .shares()
+                .values()
+                .map(|share| share.principal().identity)
+                .filter(|identity| *identity != secret_key.public())
+                .map(|identity| *identity)
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    };
+    if peer_ids.is_empty() {
+        tracing::warn!("No peers to repair bucket {} from", bucket_id);
+        return Ok(FsckInfo {
+            link: bucket_link,
+            report,
+            repaired,
+            remaining,
+        });
+    }
+
+    // Fetching a directory can turn up problems beneath it, so check again
+    //  after each round of fetches
+    let mut attempted = HashSet::new();
+    loop {
+        let fetch: HashSet<Hash> = remaining
+            .iter()
+            .filter(|problem| problem.issue.is_refetchable())
+            .map(|problem| problem.hash)
+            .filter(|hash| !attempted.contains(hash))
+            .collect();
+        if fetch.is_empty() {
+            break;
+        }
+        attempted.extend(fetch.iter().copied());
+
+        // Corrupt blobs have to go before they can be downloaded again
+        blobs.evict(&fetch).await.map_err(MountError::from)?;
+        for hash in fetch {
+            match blobs.download_hash(hash, peer_ids.clone(), endpoint).await {
+                Ok(()) => repaired.push(hash),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to fetch blob {} of bucket {}: {}",
+                        hash,
+                        bucket_id,
+                        e
+                    );
+                }
+            }
+        }
+
+        remaining = Mount::fsck(&bucket_link, secret_key, blobs).await?.problems;
+    }
+
+    // A fetched blob that is still broken wasn't repaired
+    let broken: HashSet<Hash> = remaining.iter().map(|problem| problem.hash).collect();
+    repaired.retain(|hash| !broken.contains(hash));
+    tracing::info!(
+        "Repaired {} blobs of bucket {}, {} problems remain",
+        repaired.len(),
+        bucket_id,
+        remaining.len()
+    );
+
+    Ok(FsckInfo {
+        link: bucket_link,
+        report,
+        repaired,
+        remaining,
+    })
+}
@@ -0,0 +1,200 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use common::linked_data::Hash;
+use common::peer::BlobsStore;
+use common::prelude::{Mount, MountError};
+use tokio::io::{AsyncWrite, DuplexStream};
+use tokio::sync::oneshot;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_at_version;
+use super::types::ArchiveFormat;
+
+/// Size of the buffer between the archive being written and read
+const ARCHIVE_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug)]
+pub struct PathArchive {
+    /// The archive, written as it's read
+    pub reader: DuplexStream,
+    /// Resolves once the archive is written, with the error that cut it
+    ///  short if there was one
+    pub done: oneshot::Receiver<io::Result<()>>,
+    /// Name of the file or directory archived, or of the bucket for its root
+    pub name: String,
+}
+
+/// A file or directory in the archive, relative to the path archived
+struct ArchiveEntry {
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/// Pack the decrypted contents of `path` in a bucket, optionally at a past
+/// version, into a tar or zip archive
+/// Entries are named relative to `path`, and files are decrypted as the
+/// archive is read, so nothing is held in memory
+pub async fn get_bucket_archive(
+    bucket_id: Uuid,
+    path: String,
+    version: Option<Hash>,
+    format: ArchiveFormat,
+    state: &ServiceState,
+) -> Result<PathArchive, MountOpsError> {
+    let mount = load_mount_at_version(bucket_id, version, state).await?;
+
+    let path_buf = PathBuf::from(&path);
+    if !path_buf.is_absolute() {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let inner = mount.inner();
+    let mtime = inner.manifest().timestamp().unwrap_or(0);
+    let blobs = state.node().blobs().clone();
+
+    // Work out what goes in the archive up front, so a missing path is an
+    //  error rather than an empty archive
+    let (base, name, entries) = tokio::task::spawn_blocking({
+        let mount = mount.clone();
+        let blobs = blobs.clone();
+        let bucket_name = inner.manifest().name().to_string();
+        move || {
+            tokio::runtime::Handle::current().block_on(async move {
+                let file_name = path_buf
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string());
+                if let Some(name) = &file_name {
+                    if !mount.get(&path_buf, &blobs).await?.is_dir() {
+                        // A single file is archived under its own name
+                        let entry = ArchiveEntry {
+                            path: PathBuf::from(name),
+                            is_dir: false,
+                        };
+                        let base = path_buf.parent().unwrap_or(Path::new("/")).to_path_buf();
+                        return Ok::<_, MountError>((base, name.clone(), vec![entry]));
+                    }
+                }
+
+                let name = file_name.unwrap_or(bucket_name);
+                let entries = mount
+                    .ls_deep(&path_buf, &blobs)
+                    .await?
+                    .into_iter()
+                    .map(|(path, link)| ArchiveEntry {
+                        path,
+                        is_dir: link.is_dir(),
+                    })
+                    .collect();
+                Ok((path_buf, name, entries))
+            })
+        }
+    })
+    .await
+    .map_err(|e| MountOpsError::Mount(MountError::Default(anyhow::anyhow!(e))))??;
+
+    let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_SIZE);
+    let (done_tx, done) = oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async move {
+            let result = match format {
+                ArchiveFormat::Tar => {
+                    write_tar(&mount, &base, &entries, mtime, &blobs, writer).await
+                }
+                ArchiveFormat::Zip => {
+                    write_zip(&mount, &base, &entries, mtime, &blobs, writer).await
+                }
+            };
+            if let Err(e) = &result {
+                tracing::error!("Failed to archive {} in bucket {}: {}", path, bucket_id, e);
+            }
+            let _ = done_tx.send(result);
+        })
+    });
+
+    Ok(PathArchive { reader, done, name })
+}
+
+async fn write_tar(
+    mount: &Mount,
+    base: &Path,
+    entries: &[ArchiveEntry],
+    mtime: u64,
+    blobs: &BlobsStore,
+    writer: DuplexStream,
+) -> io::Result<()> {
+    let mut builder = tokio_tar::Builder::new(writer);
+    for entry in entries {
+        let mut header = tokio_tar::Header::new_gnu();
+        header.set_mtime(mtime);
+        if entry.is_dir {
+            header.set_entry_type(tokio_tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder
+                .append_data(&mut header, &entry.path, tokio::io::empty())
+                .await?;
+        } else {
+            let reader = mount
+                .cat_reader(&base.join(&entry.path), None, blobs)
+                .await
+                .map_err(io::Error::other)?;
+            header.set_entry_type(tokio_tar::EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_size(reader.len());
+            builder
+                .append_data(&mut header, &entry.path, reader)
+                .await?;
+        }
+    }
+    builder.into_inner().await?;
+    Ok(())
+}
+
+async fn write_zip<W: AsyncWrite + Unpin>(
+    mount: &Mount,
+    base: &Path,
+    entries: &[ArchiveEntry],
+    mtime: u64,
+    blobs: &BlobsStore,
+    writer: W,
+) -> io::Result<()> {
+    let date = chrono::DateTime::from_timestamp(mtime as i64, 0)
+        .map(|date| ZipDateTime::from_chrono(&date))
+        .unwrap_or_default();
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    for entry in entries {
+        // Directories are told apart from files by a trailing slash
+        let name = entry.path.to_string_lossy().to_string();
+        if entry.is_dir {
+            let builder = ZipEntryBuilder::new(format!("{}/", name).into(), Compression::Stored)
+                .last_modification_date(date)
+                .unix_permissions(0o755);
+            zip.write_entry_whole(builder, &[])
+                .await
+                .map_err(io::Error::other)?;
+        } else {
+            let reader = mount
+                .cat_reader(&base.join(&entry.path), None, blobs)
+                .await
+                .map_err(io::Error::other)?;
+            let builder = ZipEntryBuilder::new(name.into(), Compression::Deflate)
+                .last_modification_date(date)
+                .unix_permissions(0o644);
+            let mut entry_writer = zip
+                .write_entry_stream(builder)
+                .await
+                .map_err(io::Error::other)?;
+            futures::io::copy(&mut reader.compat(), &mut entry_writer).await?;
+            entry_writer.close().await.map_err(io::Error::other)?;
+        }
+    }
+    zip.close().await.map_err(io::Error::other)?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use common::linked_data::Hash;
+use common::prelude::{Mount, MountError};
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_at_version;
+use super::types::DiffInfo;
+
+/// Compare two versions of a bucket, given as hashes of their manifests
+///
+/// `to` defaults to the current version, and `from` to the version before
+/// `to`, so leaving both out shows what the latest change did.
+pub async fn get_bucket_diff(
+    bucket_id: Uuid,
+    from: Option<Hash>,
+    to: Option<Hash>,
+    state: &ServiceState,
+) -> Result<DiffInfo, MountOpsError> {
+    let to_mount = load_mount_at_version(bucket_id, to, state).await?;
+    let to_link = to_mount.link();
+    let from_link = match from {
+        Some(from) => load_mount_at_version(bucket_id, Some(from), state)
+            .await?
+            .link(),
+        // The first version of a bucket has nothing before it to compare against
+        None => to_mount
+            .inner()
+            .manifest()
+            .previous()
+            .clone()
+            .unwrap_or_else(|| to_link.clone()),
+    };
+
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+    let diff = match Mount::diff(&from_link, &to_link, secret_key, blobs).await {
+        Ok(diff) => diff,
+        Err(MountError::LinkNotFound(link)) if link == from_link => {
+            return Err(MountOpsError::VersionNotFound(*link.hash()))
+        }
+        Err(e) => return Err(MountOpsError::Mount(e)),
+    };
+
+    // Mount returns relative paths, prepend "/" to make them absolute
+    let absolute = |path: &Path| Path::new("/").join(path).to_string_lossy().to_string();
+    let absolute_all =
+        |paths: &[std::path::PathBuf]| paths.iter().map(|path| absolute(path)).collect::<Vec<_>>();
+    Ok(DiffInfo {
+        added: absolute_all(&diff.added),
+        removed: absolute_all(&diff.removed),
+        modified: absolute_all(&diff.modified),
+        moved: diff
+            .moved
+            .iter()
+            .map(|moved| (absolute(&moved.from), absolute(&moved.to)))
+            .collect(),
+        from: from_link,
+        to: to_link,
+    })
+}
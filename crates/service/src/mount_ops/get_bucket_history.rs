@@ -0,0 +1,49 @@
+use futures::StreamExt;
+use uuid::Uuid;
+
+use common::prelude::MountError;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+use super::types::VersionInfo;
+
+/// Get the version history of a bucket, newest first
+///
+/// At most `limit` versions are returned when set. The walk stops early at a
+/// version whose manifest isn't available locally, e.g. history from before
+/// this node started syncing the bucket.
+pub async fn get_bucket_history(
+    bucket_id: Uuid,
+    limit: Option<usize>,
+    state: &ServiceState,
+) -> Result<Vec<VersionInfo>, MountOpsError> {
+    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    let blobs = state.node().blobs();
+
+    let mut history = Box::pin(mount.history(blobs).take(limit.unwrap_or(usize::MAX)));
+    let mut versions = Vec::new();
+    while let Some(entry) = history.next().await {
+        match entry {
+            Ok(entry) => versions.push(VersionInfo {
+                timestamp: entry.timestamp(),
+                author: entry.author().copied(),
+                message: entry.message().map(str::to_string),
+                previous: entry.manifest.previous().clone(),
+                link: entry.link,
+            }),
+            Err(MountError::LinkNotFound(link)) => {
+                tracing::warn!(
+                    "History of bucket {} is incomplete, version {} is not available locally",
+                    bucket_id,
+                    link.hash()
+                );
+                break;
+            }
+            Err(e) => return Err(MountOpsError::Mount(e)),
+        }
+    }
+
+    Ok(versions)
+}
@@ -0,0 +1,16 @@
+use uuid::Uuid;
+
+use crate::database::models::RetentionPolicy;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Get the retention policy of a bucket, if it has one
+pub async fn get_bucket_retention(
+    bucket_id: Uuid,
+    state: &ServiceState,
+) -> Result<Option<RetentionPolicy>, MountOpsError> {
+    RetentionPolicy::get_by_bucket_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))
+}
@@ -0,0 +1,15 @@
+use common::bucket::PrincipalRole;
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Get the role this node has on a bucket
+pub async fn get_bucket_role(
+    bucket_id: Uuid,
+    state: &ServiceState,
+) -> Result<Option<PrincipalRole>, MountOpsError> {
+    let bucket_mount = super::load_mount::load_mount_for_bucket(bucket_id, state).await?;
+    Ok(bucket_mount.role())
+}
@@ -1,9 +1,10 @@
+use common::linked_data::Hash;
 use uuid::Uuid;
 
 use crate::ServiceState;
 
 use super::error::MountOpsError;
-use super::load_mount::load_mount_for_bucket;
+use super::load_mount::load_mount_at_version;
 
 #[derive(Debug, Clone)]
 pub struct FileContent {
@@ -11,13 +12,14 @@ pub struct FileContent {
     pub mime_type: String,
 }
 
-/// Get file content from a bucket
+/// Get file content from a bucket, optionally at a past version
 pub async fn get_file_content(
     bucket_id: Uuid,
     path: String,
+    version: Option<Hash>,
     state: &ServiceState,
 ) -> Result<FileContent, MountOpsError> {
-    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    let mount = load_mount_at_version(bucket_id, version, state).await?;
 
     let path_buf = std::path::PathBuf::from(&path);
     if !path_buf.is_absolute() {
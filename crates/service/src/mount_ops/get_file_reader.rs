@@ -0,0 +1,63 @@
+use std::ops::Range;
+
+use common::bucket::FileReader;
+use common::linked_data::Hash;
+use common::prelude::Link;
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_at_version;
+
+#[derive(Debug)]
+pub struct FileStream {
+    pub reader: FileReader,
+    /// Link to the file's encrypted content, which changes whenever the content does
+    pub link: Link,
+    pub mime_type: String,
+}
+
+/// Open a streaming reader over a file in a bucket, optionally at a past version
+///
+/// `select_range` is called with the size of the file and returns the byte range
+/// to read, or `None` for the whole file. This lets callers resolve ranges that
+/// are relative to the end of the file before anything is decrypted.
+pub async fn get_file_reader<F>(
+    bucket_id: Uuid,
+    path: String,
+    version: Option<Hash>,
+    select_range: F,
+    state: &ServiceState,
+) -> Result<FileStream, MountOpsError>
+where
+    F: FnOnce(u64) -> Option<Range<u64>> + Send + 'static,
+{
+    let mount = load_mount_at_version(bucket_id, version, state).await?;
+
+    let path_buf = std::path::PathBuf::from(&path);
+    if !path_buf.is_absolute() {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let blobs = state.node().blobs();
+    let node_link = mount.get(&path_buf, blobs).await?;
+    let link = node_link.link().clone();
+    let mime_type = node_link
+        .data()
+        .and_then(|data| data.mime())
+        .map(|mime| mime.to_string())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    // An empty range only reads the header, which is enough to learn the size
+    let size = mount.cat_reader(&path_buf, Some(0..0), blobs).await?.size();
+    let reader = mount
+        .cat_reader(&path_buf, select_range(size), blobs)
+        .await?;
+
+    Ok(FileStream {
+        reader,
+        link,
+        mime_type,
+    })
+}
@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use common::prelude::MountError;
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Read the plaintext size of each of several files in a bucket, for files
+/// whose size wasn't recorded when they were added
+pub async fn get_file_sizes(
+    bucket_id: Uuid,
+    paths: Vec<String>,
+    state: &ServiceState,
+) -> Result<Vec<u64>, MountOpsError> {
+    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    let blobs = state.node().blobs().clone();
+
+    let sizes = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            let mut sizes = Vec::with_capacity(paths.len());
+            for path in paths {
+                // An empty range only reads the header, which is enough to learn the size
+                let size = mount
+                    .cat_reader(&PathBuf::from(path), Some(0..0), &blobs)
+                    .await?
+                    .size();
+                sizes.push(size);
+            }
+            Ok::<_, MountError>(sizes)
+        })
+    })
+    .await
+    .map_err(|e| MountOpsError::Mount(MountError::Default(anyhow::anyhow!(e))))??;
+
+    Ok(sizes)
+}
@@ -0,0 +1,40 @@
+use common::linked_data::Hash;
+use uuid::Uuid;
+
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_at_version;
+use super::types::FileInfo;
+
+/// Look up a single file or directory of a bucket, optionally at a past
+/// version
+pub async fn get_path_info(
+    bucket_id: Uuid,
+    path: String,
+    version: Option<Hash>,
+    state: &ServiceState,
+) -> Result<FileInfo, MountOpsError> {
+    let mount = load_mount_at_version(bucket_id, version, state).await?;
+
+    let path_buf = std::path::PathBuf::from(&path);
+    if !path_buf.is_absolute() {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let blobs = state.node().blobs().clone();
+    let path_buf_clone = path_buf.clone();
+
+    // Resolve the path in blocking task
+    let node_link = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current()
+            .block_on(async { mount.get(&path_buf_clone, &blobs).await })
+    })
+    .await
+    .map_err(|e| {
+        MountOpsError::Mount(common::prelude::MountError::Default(anyhow::anyhow!(e)))
+    })??;
+
+    let relative = path_buf.strip_prefix("/").unwrap_or(&path_buf);
+    Ok(FileInfo::from_node_link(relative, &node_link))
+}
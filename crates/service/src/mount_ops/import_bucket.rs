@@ -0,0 +1,90 @@
+use common::prelude::{Link, Mount};
+use tokio::io::AsyncRead;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::types::BucketImport;
+
+/// Import a CAR archive of a bucket, as written by `export_bucket`
+/// A bucket we don't have yet is created at the archive's version, then
+/// pulled from peers to catch up on anything newer. For a bucket we have,
+/// the archive's version is handled like an announcement from its author,
+/// so it's only adopted if it passes the checks a synced version would.
+pub async fn import_bucket<R>(
+    reader: R,
+    state: &ServiceState,
+) -> Result<BucketImport, MountOpsError>
+where
+    R: AsyncRead + Unpin,
+{
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mount = Mount::import_car(reader, secret_key, blobs).await?;
+    let link = mount.link();
+    let manifest = mount.inner().manifest().clone();
+    let bucket_id = *manifest.id();
+
+    let existing = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?;
+    let created = match existing {
+        None => {
+            BucketModel::create(
+                bucket_id,
+                manifest.name().to_string(),
+                link.clone(),
+                state.database(),
+            )
+            .await
+            .map_err(|e| MountOpsError::Database(e.to_string()))?;
+            tracing::info!("Imported bucket {} at {:?}", bucket_id, link);
+            if let Err(e) = state.send_sync_event(SyncEvent::Pull { bucket_id }) {
+                tracing::warn!(
+                    "Failed to trigger pull sync for bucket {}: {:?}",
+                    bucket_id,
+                    e
+                );
+            }
+            true
+        }
+        Some(bucket) => {
+            let current: Link = bucket.link.into();
+            if current != link {
+                match manifest.author() {
+                    Some(author) => {
+                        if let Err(e) = state.send_sync_event(SyncEvent::PeerAnnounce {
+                            bucket_id,
+                            peer_id: author.to_hex(),
+                            new_link: link.clone(),
+                            previous_link: manifest.previous().clone(),
+                        }) {
+                            tracing::warn!(
+                                "Failed to apply imported version of bucket {}: {:?}",
+                                bucket_id,
+                                e
+                            );
+                        }
+                    }
+                    None => tracing::warn!(
+                        "Imported version {:?} of bucket {} is unsigned, keeping {:?}",
+                        link,
+                        bucket_id,
+                        current
+                    ),
+                }
+            }
+            false
+        }
+    };
+
+    Ok(BucketImport {
+        bucket_id,
+        name: manifest.name().to_string(),
+        link,
+        created,
+    })
+}
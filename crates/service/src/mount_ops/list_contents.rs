@@ -1,19 +1,21 @@
+use common::linked_data::Hash;
 use uuid::Uuid;
 
 use crate::ServiceState;
 
 use super::error::MountOpsError;
-use super::load_mount::load_mount_for_bucket;
+use super::load_mount::load_mount_at_version;
 use super::types::FileInfo;
 
-/// List contents of a bucket at a specific path
+/// List contents of a bucket at a specific path, optionally at a past version
 pub async fn list_bucket_contents(
     bucket_id: Uuid,
     path: Option<String>,
     deep: bool,
+    version: Option<Hash>,
     state: &ServiceState,
 ) -> Result<Vec<FileInfo>, MountOpsError> {
-    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    let mount = load_mount_at_version(bucket_id, version, state).await?;
 
     let path_str = path.as_deref().unwrap_or("/");
     let path_buf = std::path::PathBuf::from(path_str);
@@ -37,36 +39,8 @@ pub async fn list_bucket_contents(
         MountOpsError::Mount(common::prelude::MountError::Default(anyhow::anyhow!(e)))
     })??;
 
-    // Convert to FileInfo - paths from mount are relative, make them absolute
     Ok(items
         .into_iter()
-        .map(|(path, node_link)| {
-            // Mount returns relative paths, prepend "/" to make them absolute
-            let absolute_path = std::path::Path::new("/").join(&path);
-            let path_str = absolute_path.to_string_lossy().to_string();
-            let name = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| path.to_string_lossy().to_string());
-
-            let mime_type = if node_link.is_dir() {
-                "inode/directory".to_string()
-            } else {
-                // Get MIME type from node data if available
-                node_link
-                    .data()
-                    .and_then(|data| data.mime())
-                    .map(|mime| mime.to_string())
-                    .unwrap_or_else(|| "application/octet-stream".to_string())
-            };
-
-            FileInfo {
-                path: path_str,
-                name,
-                link: node_link.link().clone(),
-                is_dir: node_link.is_dir(),
-                mime_type,
-            }
-        })
+        .map(|(path, node_link)| FileInfo::from_node_link(&path, &node_link))
         .collect())
 }
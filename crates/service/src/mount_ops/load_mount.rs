@@ -1,4 +1,5 @@
-use common::prelude::{Link, Mount};
+use common::linked_data::Hash;
+use common::prelude::{Link, Mount, MountError};
 use uuid::Uuid;
 
 use crate::database::models::Bucket as BucketModel;
@@ -26,3 +27,40 @@ pub async fn load_mount_for_bucket(
         .await
         .map_err(MountOpsError::Mount)
 }
+
+/// Load a mount for a bucket, either at its current version or at a past
+/// `version`, given as the hash of that version's manifest
+pub async fn load_mount_at_version(
+    bucket_id: Uuid,
+    version: Option<Hash>,
+    state: &ServiceState,
+) -> Result<Mount, MountOpsError> {
+    let Some(version) = version else {
+        return load_mount_for_bucket(bucket_id, state).await;
+    };
+
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Manifests are all stored the same way, so the version's link only
+    //  differs from the current one by its hash
+    let current: Link = bucket.link.into();
+    let version_link = Link::new(*current.codec(), version, *current.format());
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mount = match Mount::load(&version_link, secret_key, blobs).await {
+        Ok(mount) => mount,
+        Err(MountError::LinkNotFound(_)) => return Err(MountOpsError::VersionNotFound(version)),
+        Err(e) => return Err(MountOpsError::Mount(e)),
+    };
+
+    // Don't serve versions of other buckets through this one
+    if *mount.inner().manifest().id() != bucket_id {
+        return Err(MountOpsError::VersionNotFound(version));
+    }
+
+    Ok(mount)
+}
@@ -0,0 +1,60 @@
+use common::bucket::{RekeyProgress, MANIFEST_FORMAT};
+use common::prelude::Link;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Rewrite a bucket in the newest format, calling `on_progress` as every
+/// directory and file is re-encrypted
+/// Returns the new bucket link, or None if the bucket was already in the
+/// newest format
+pub async fn migrate_bucket(
+    bucket_id: Uuid,
+    on_progress: impl FnMut(&RekeyProgress),
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Option<Link>, MountOpsError> {
+    let _lock = state.lock_bucket(bucket_id).await;
+    let mut mount = load_mount_for_bucket(bucket_id, state).await?;
+    let blobs = state.node().blobs();
+
+    if !mount.migrate(blobs, on_progress).await? {
+        return Ok(None);
+    }
+
+    mount.set_message(message.or_else(|| Some(format!("Migrate to format {}", MANIFEST_FORMAT))));
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the migrated version to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after migrating it",
+        bucket_id
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the bucket was migrated successfully
+    }
+
+    Ok(Some(new_bucket_link))
+}
@@ -1,25 +1,78 @@
+mod add_archive;
 mod add_data;
+mod add_files;
+mod apply_retention;
+mod collect_garbage;
+mod copy_path;
 mod error;
+mod export_bucket;
+mod fsck_bucket;
+mod get_archive;
+mod get_bucket_diff;
+mod get_bucket_history;
 mod get_bucket_info;
 mod get_bucket_pins;
+mod get_bucket_retention;
+mod get_bucket_role;
 mod get_bucket_shares;
 mod get_file_content;
+mod get_file_reader;
+mod get_file_sizes;
+mod get_path_info;
+mod import_bucket;
 mod list_buckets;
 mod list_contents;
 mod load_mount;
+mod make_dir;
+mod migrate_bucket;
+mod move_path;
+mod multipart_upload;
+mod remove_path;
+mod revert_bucket;
+mod set_bucket_retention;
 mod share_bucket;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod types;
+mod unshare_bucket;
 
 // Re-export types
 pub use error::MountOpsError;
-pub use types::{BucketInfo, FileInfo};
+pub use types::{ArchiveFormat, BucketInfo, FileInfo, GcReport, UploadFile, UploadInfo};
 
 // Re-export functions
+pub use add_archive::add_archive_to_bucket;
 pub use add_data::add_data_to_bucket;
+pub use add_files::add_files_to_bucket;
+pub use apply_retention::apply_retention_policies;
+pub use collect_garbage::collect_garbage;
+pub use copy_path::copy_path_in_bucket;
+pub use export_bucket::export_bucket;
+pub use fsck_bucket::fsck_bucket;
+pub use get_archive::get_bucket_archive;
+pub use get_bucket_diff::get_bucket_diff;
+pub use get_bucket_history::get_bucket_history;
 pub use get_bucket_info::get_bucket_info;
 pub use get_bucket_pins::get_bucket_pins;
+pub use get_bucket_retention::get_bucket_retention;
+pub use get_bucket_role::get_bucket_role;
 pub use get_bucket_shares::get_bucket_shares;
 pub use get_file_content::get_file_content;
+pub use get_file_reader::get_file_reader;
+pub use get_file_sizes::get_file_sizes;
+pub use get_path_info::get_path_info;
+pub use import_bucket::import_bucket;
 pub use list_buckets::list_buckets;
 pub use list_contents::list_bucket_contents;
+pub use make_dir::make_dir_in_bucket;
+pub use migrate_bucket::migrate_bucket;
+pub use move_path::move_path_in_bucket;
+pub use multipart_upload::{
+    abort_multipart_upload, abort_multipart_uploads, complete_multipart_upload,
+    start_multipart_upload, upload_part,
+};
+pub use remove_path::remove_path_from_bucket;
+pub use revert_bucket::revert_bucket;
+pub use set_bucket_retention::set_bucket_retention;
 pub use share_bucket::share_bucket;
+pub use unshare_bucket::unshare_bucket;
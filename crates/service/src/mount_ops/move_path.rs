@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use common::prelude::{Link, Mount};
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Move (or rename) a file or directory within a bucket
+/// Returns the new bucket link after the move
+pub async fn move_path_in_bucket(
+    bucket_id: Uuid,
+    from: PathBuf,
+    to: PathBuf,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    if !from.is_absolute() || !to.is_absolute() {
+        return Err(MountOpsError::InvalidPath(
+            "Source and destination paths must be absolute".into(),
+        ));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    mount.mv(&from, &to, blobs).await?;
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the move to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after moving {:?} to {:?}",
+        bucket_id,
+        from,
+        to
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the move was applied successfully
+    }
+
+    Ok(new_bucket_link)
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use common::bucket::{Chunk, NodeLink};
+use common::crypto::Secret;
+use common::linked_data::Hash;
+use common::prelude::{Link, Mount, MountError};
+use tokio::io::AsyncRead;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Name the parts of every upload are held under until it completes or is
+/// aborted
+const UPLOADS_HOLD: &str = "multipart";
+
+/// Name the parts of one upload are held under
+fn upload_hold(upload_id: &str) -> String {
+    format!("{}/{}", UPLOADS_HOLD, upload_id)
+}
+
+/// Start uploading a file to a bucket in parts
+/// Returns the secret every part is encrypted with, which is that of the
+/// file already at `mount_path` if there is one, so the chunks it shares
+/// with the upload aren't stored twice
+pub async fn start_multipart_upload(
+    bucket_id: Uuid,
+    mount_path: PathBuf,
+    state: &ServiceState,
+) -> Result<Secret, MountOpsError> {
+    if !mount_path.is_absolute() {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    // Refuse now rather than after every part is uploaded
+    if !mount.role().is_some_and(|role| role.can_write()) {
+        return Err(MountOpsError::Mount(MountError::ReadOnly));
+    }
+
+    let blobs = state.node().blobs().clone();
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            match mount.get(&mount_path, &blobs).await {
+                Ok(NodeLink::Dir(..)) => Err(MountOpsError::InvalidPath(format!(
+                    "{} is a directory",
+                    mount_path.display()
+                ))),
+                Ok(NodeLink::Data(_, secret, _)) => Ok(secret),
+                _ => Ok(Secret::generate()),
+            }
+        })
+    })
+    .await
+    .map_err(|e| MountOpsError::Mount(MountError::Default(anyhow::anyhow!(e))))?
+}
+
+/// Encrypt and store one part of a file uploaded in parts, streaming it
+/// from `reader`
+/// Returns the part's chunks, to be passed to
+/// [`complete_multipart_upload`] with the rest, and the BLAKE3 hash of
+/// the part. The chunks are kept from garbage collection until the upload
+/// completes or is aborted.
+pub async fn upload_part<R>(
+    upload_id: &str,
+    secret: &Secret,
+    reader: R,
+    state: &ServiceState,
+) -> Result<(Vec<Chunk>, Hash), MountOpsError>
+where
+    R: AsyncRead + Unpin,
+{
+    let hold = upload_hold(upload_id);
+    Ok(Mount::put_part(reader, secret, &hold, state.node().blobs()).await?)
+}
+
+/// Finish uploading a file in parts, joining the chunks of every part in
+/// order into the file at `mount_path`
+/// Returns the new bucket link after adding the file
+pub async fn complete_multipart_upload(
+    upload_id: &str,
+    bucket_id: Uuid,
+    mount_path: PathBuf,
+    secret: Secret,
+    chunks: Vec<Chunk>,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    let _lock = state.lock_bucket(bucket_id).await;
+
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    let link = mount
+        .stage_parts(&mount_path, secret, chunks, blobs)
+        .await?;
+    mount
+        .add_staged(BTreeMap::from([(mount_path.clone(), link)]), blobs)
+        .await?;
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // The file's pinned now, its parts needn't be held any more
+    if let Err(e) = blobs.release(&upload_hold(upload_id)).await {
+        tracing::warn!("Failed to release the parts of upload {}: {}", upload_id, e);
+    }
+
+    // Trigger push sync to announce the new file to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after uploading {:?} in parts",
+        bucket_id,
+        mount_path
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the file was added successfully
+    }
+
+    Ok(new_bucket_link)
+}
+
+/// Abort an upload in parts, leaving what it stored to the garbage
+/// collector
+pub async fn abort_multipart_upload(
+    upload_id: &str,
+    state: &ServiceState,
+) -> Result<(), MountOpsError> {
+    state
+        .node()
+        .blobs()
+        .release(&upload_hold(upload_id))
+        .await
+        .map_err(|e| MountOpsError::Mount(e.into()))
+}
+
+/// Abort every upload in parts, as none survive a restart
+pub async fn abort_multipart_uploads(state: &ServiceState) -> Result<(), MountOpsError> {
+    state
+        .node()
+        .blobs()
+        .release(UPLOADS_HOLD)
+        .await
+        .map_err(|e| MountOpsError::Mount(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::super::add_data::add_data_to_bucket;
+    use super::super::test_utils::{create_bucket, load, setup};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_upload_over_file_keeps_secret() {
+        let (state, _temp) = setup().await;
+        let bucket_id = create_bucket(&state).await;
+        let path = PathBuf::from("/clips/video.bin");
+        let data = Cursor::new(b"old".to_vec());
+        add_data_to_bucket(bucket_id, path.clone(), data, None, &state)
+            .await
+            .unwrap();
+        let mount = load(bucket_id, &state).await;
+        let existing = mount.get(&path, state.node().blobs()).await.unwrap();
+
+        let secret = start_multipart_upload(bucket_id, path, &state)
+            .await
+            .unwrap();
+        assert_eq!(&secret, existing.secret());
+
+        assert!(matches!(
+            start_multipart_upload(bucket_id, PathBuf::from("/clips"), &state).await,
+            Err(MountOpsError::InvalidPath(_))
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use common::prelude::{Link, Mount};
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+
+/// Remove a file or directory from a bucket
+/// Returns the new bucket link after the removal
+pub async fn remove_path_from_bucket(
+    bucket_id: Uuid,
+    path: PathBuf,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    if !path.is_absolute() {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    // Get bucket from database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+
+    // Load mount
+    let bucket_link: Link = bucket.link.into();
+    let secret_key = state.node().secret();
+    let blobs = state.node().blobs();
+
+    let mut mount = Mount::load(&bucket_link, secret_key, blobs)
+        .await
+        .map_err(MountOpsError::Mount)?;
+
+    mount.rm(&path, blobs).await?;
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the removal to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after removing {:?}",
+        bucket_id,
+        path
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the removal was applied successfully
+    }
+
+    Ok(new_bucket_link)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use common::prelude::MountError;
+
+    use super::super::add_data::add_data_to_bucket;
+    use super::super::test_utils::{create_bucket, load, setup};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rm_records_message() {
+        let (state, _temp) = setup().await;
+        let bucket_id = create_bucket(&state).await;
+        let path = PathBuf::from("/drafts/old.txt");
+        let data = Cursor::new(b"stale".to_vec());
+        add_data_to_bucket(bucket_id, path.clone(), data, None, &state)
+            .await
+            .unwrap();
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let message = "Remove the stale draft".to_string();
+        let link = remove_path_from_bucket(bucket_id, path.clone(), Some(message), &state)
+            .await
+            .unwrap();
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mount = load(bucket_id, &state).await;
+        assert_eq!(mount.link(), link);
+        let manifest = mount.inner().manifest().clone();
+        assert_eq!(manifest.message(), Some("Remove the stale draft"));
+        let timestamp = manifest.timestamp().unwrap();
+        assert!((before..=after).contains(&timestamp));
+        assert!(matches!(
+            mount.get(&path, state.node().blobs()).await,
+            Err(MountError::PathNotFound(_))
+        ));
+    }
+}
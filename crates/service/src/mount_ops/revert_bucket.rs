@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use common::linked_data::Hash;
+use common::prelude::{Link, MountError};
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Revert a bucket, or a single path within it, to an earlier version
+/// given as the hash of that version's manifest
+/// Returns the new bucket link, which has the current version as its previous
+pub async fn revert_bucket(
+    bucket_id: Uuid,
+    version: Hash,
+    path: Option<PathBuf>,
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    if path.as_ref().is_some_and(|path| !path.is_absolute()) {
+        return Err(MountOpsError::InvalidPath("Path must be absolute".into()));
+    }
+
+    let _lock = state.lock_bucket(bucket_id).await;
+    let mut mount = load_mount_for_bucket(bucket_id, state).await?;
+
+    // Manifests are all stored the same way, so the version's link only
+    //  differs from the current one by its hash
+    let current = mount.link();
+    let version_link = Link::new(*current.codec(), version, *current.format());
+    let blobs = state.node().blobs();
+
+    let reverted = match &path {
+        Some(path) => mount.revert_path(&version_link, path, blobs).await,
+        None => mount.revert_to(&version_link, blobs).await,
+    };
+    match reverted {
+        Ok(()) => {}
+        Err(MountError::LinkNotFound(link)) if link == version_link => {
+            return Err(MountOpsError::VersionNotFound(version))
+        }
+        Err(e) => return Err(MountOpsError::Mount(e)),
+    }
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync to announce the revert to all peers
+    tracing::debug!(
+        "Triggering push sync for bucket {} after reverting to {}",
+        bucket_id,
+        version
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the revert was applied successfully
+    }
+
+    Ok(new_bucket_link)
+}
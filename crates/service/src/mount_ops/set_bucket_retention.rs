@@ -0,0 +1,43 @@
+use common::prelude::MountError;
+use uuid::Uuid;
+
+use crate::database::models::RetentionPolicy;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Set how much history of a bucket to keep, or keep all of it if neither
+/// limit is given
+/// Returns the bucket's policy, if it still has one
+pub async fn set_bucket_retention(
+    bucket_id: Uuid,
+    max_versions: Option<u32>,
+    max_age_days: Option<u32>,
+    state: &ServiceState,
+) -> Result<Option<RetentionPolicy>, MountOpsError> {
+    // Dropping history rewrites the versions that are kept, which only
+    //  owners may do
+    let mount = load_mount_for_bucket(bucket_id, state).await?;
+    if !mount.role().is_some_and(|role| role.can_share()) {
+        return Err(MountOpsError::Mount(MountError::NotOwner));
+    }
+
+    if max_versions.is_none() && max_age_days.is_none() {
+        RetentionPolicy::clear(&bucket_id, state.database())
+            .await
+            .map_err(|e| MountOpsError::Database(e.to_string()))?;
+        return Ok(None);
+    }
+
+    let policy = RetentionPolicy::set(
+        bucket_id,
+        max_versions.map(i64::from),
+        max_age_days.map(i64::from),
+        state.database(),
+    )
+    .await
+    .map_err(|e| MountOpsError::Database(e.to_string()))?;
+
+    Ok(Some(policy))
+}
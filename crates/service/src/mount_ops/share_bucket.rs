@@ -1,3 +1,4 @@
+use common::bucket::PrincipalRole;
 use common::crypto::PublicKey;
 use common::prelude::{Link, Mount};
 use uuid::Uuid;
@@ -8,13 +9,17 @@ use crate::ServiceState;
 
 use super::error::MountOpsError;
 
-/// Share a bucket with a peer by adding them to the bucket's shares
+/// Share a bucket with a peer by adding them to the bucket's shares as `role`
+/// (or changing their role if they already have a share)
 /// Returns the new bucket link after adding the share
 pub async fn share_bucket(
     bucket_id: Uuid,
     peer_public_key: PublicKey,
+    role: PrincipalRole,
+    message: Option<String>,
     state: &ServiceState,
 ) -> Result<Link, MountOpsError> {
+    let _lock = state.lock_bucket(bucket_id).await;
     // Get bucket from database
     let bucket = BucketModel::get_by_id(&bucket_id, state.database())
         .await
@@ -30,15 +35,15 @@ pub async fn share_bucket(
         .await
         .map_err(MountOpsError::Mount)?;
 
-    mount.share(peer_public_key).await?;
+    mount.share(peer_public_key, role).await?;
 
+    mount.set_message(message);
     let new_bucket_link = mount.save(blobs).await?;
 
     // Update bucket link in database
     bucket
         .update_link(new_bucket_link.clone(), state.database())
-        .await
-        .map_err(|e| MountOpsError::Database(e.to_string()))?;
+        .await?;
 
     // Trigger push sync to announce the new share to all peers
     tracing::debug!(
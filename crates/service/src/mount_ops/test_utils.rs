@@ -0,0 +1,46 @@
+use common::prelude::{Link, Mount};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::{ServiceConfig, ServiceState};
+
+/// A node backed by a database and blob store in a temporary directory,
+///  which is removed once it's dropped
+pub async fn setup() -> (ServiceState, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let sqlite_path = temp_dir.path().join("jax.db");
+    std::fs::File::create(&sqlite_path).unwrap();
+    let config = ServiceConfig {
+        sqlite_path: Some(sqlite_path),
+        node_blobs_store_path: Some(temp_dir.path().join("blobs")),
+        ..Default::default()
+    };
+    let state = ServiceState::from_config(&config).await.unwrap();
+    (state, temp_dir)
+}
+
+/// Create an empty bucket owned by the node, returning its ID
+pub async fn create_bucket(state: &ServiceState) -> Uuid {
+    let id = Uuid::new_v4();
+    let blobs = state.node().blobs();
+    let mount = Mount::init(id, "test".to_string(), state.node().secret(), blobs)
+        .await
+        .unwrap();
+    BucketModel::create(id, "test".to_string(), mount.link(), state.database())
+        .await
+        .unwrap();
+    id
+}
+
+/// Load the bucket at its current version
+pub async fn load(id: Uuid, state: &ServiceState) -> Mount {
+    let bucket = BucketModel::get_by_id(&id, state.database())
+        .await
+        .unwrap()
+        .unwrap();
+    let link: Link = bucket.link.into();
+    Mount::load(&link, state.node().secret(), state.node().blobs())
+        .await
+        .unwrap()
+}
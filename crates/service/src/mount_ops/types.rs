@@ -1,4 +1,13 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use common::bucket::{FsckProblem, FsckReport, NodeLink};
+use common::crypto::PublicKey;
+use common::linked_data::Hash;
+use common::peer::GcStats;
 use common::prelude::Link;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -22,4 +31,185 @@ pub struct FileInfo {
     pub link: Link,
     pub is_dir: bool,
     pub mime_type: String,
+    /// Size of a file's plaintext, if recorded when it was added
+    pub size: Option<u64>,
+    /// Modification time of a file in unix milliseconds, if its uploader gave one
+    pub mtime: Option<u64>,
+    /// BLAKE3 hash of a file's plaintext, if recorded when it was added
+    pub hash: Option<Hash>,
+}
+
+impl FileInfo {
+    /// Describe what `node_link` points to, at `path` relative to the root
+    ///  of the bucket as the mount gives it
+    pub(crate) fn from_node_link(path: &Path, node_link: &NodeLink) -> Self {
+        // Mount returns relative paths, prepend "/" to make them absolute
+        let absolute_path = Path::new("/").join(path);
+        let path_str = absolute_path.to_string_lossy().to_string();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string());
+
+        let mime_type = if node_link.is_dir() {
+            "inode/directory".to_string()
+        } else {
+            // Get MIME type from node data if available
+            node_link
+                .data()
+                .and_then(|data| data.mime())
+                .map(|mime| mime.to_string())
+                .unwrap_or_else(|| "application/octet-stream".to_string())
+        };
+
+        let data = node_link.data();
+        FileInfo {
+            path: path_str,
+            name,
+            link: node_link.link().clone(),
+            is_dir: node_link.is_dir(),
+            mime_type,
+            size: data.and_then(|data| data.size()),
+            mtime: data.and_then(|data| data.mtime()),
+            hash: data.and_then(|data| data.plaintext_hash()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionInfo {
+    /// Link to the version's manifest
+    pub link: Link,
+    pub previous: Option<Link>,
+    /// Unix timestamp (seconds), missing on versions written before timestamps were recorded
+    pub timestamp: Option<u64>,
+    /// The node that created the version, missing on unsigned versions
+    pub author: Option<PublicKey>,
+    /// The author's description of the change
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffInfo {
+    /// Link to the manifest of the version compared from
+    pub from: Link,
+    /// Link to the manifest of the version compared to
+    pub to: Link,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    /// (from, to) pairs of moved paths
+    pub moved: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BucketImport {
+    pub bucket_id: Uuid,
+    pub name: String,
+    /// Link to the manifest of the version imported
+    pub link: Link,
+    /// Whether the bucket was new to us, rather than already synced
+    pub created: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GcReport {
+    /// Number of buckets whose history was walked
+    pub buckets: usize,
+    /// Buckets whose pin set was pruned, saving a new version of each
+    pub pruned: Vec<Uuid>,
+    /// Number of blobs still referenced by a bucket
+    pub retained: usize,
+    /// What was, or with a dry run would be, removed from the blob store
+    pub removed: GcStats,
+}
+
+#[derive(Debug, Clone)]
+pub struct FsckInfo {
+    /// Link to the manifest of the version checked
+    pub link: Link,
+    /// What the check found, before any repair
+    pub report: FsckReport,
+    /// Blobs fetched again from peers and now intact
+    pub repaired: Vec<Hash>,
+    /// Problems left after repairing, or all of them without a repair
+    pub remaining: Vec<FsckProblem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadInfo {
+    /// Link to the manifest of the version with the uploaded files
+    pub link: Link,
+    /// Paths of the files added
+    pub added: Vec<String>,
+    /// Paths removed along with the files being added
+    pub removed: Vec<String>,
+    /// Paths moved along with the files being added, from and to
+    pub moved: Vec<(String, String)>,
+    /// Names of what wasn't added, such as links in an archive
+    pub skipped: Vec<String>,
+}
+
+/// A file being uploaded into a directory of a bucket
+#[derive(Debug)]
+pub struct UploadFile<R> {
+    /// Path of the file relative to the directory
+    pub name: String,
+    /// Modification time of the file, as a unix timestamp in milliseconds
+    pub mtime: Option<u64>,
+    pub reader: R,
+}
+
+/// Formats directories of a bucket can be packed into, and unpacked from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Guess the format of an archive from its file name
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "application/x-tar",
+            ArchiveFormat::Zip => "application/zip",
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(format!("unknown archive format {}, expected tar or zip", s)),
+        }
+    }
 }
@@ -0,0 +1,68 @@
+use common::bucket::{MountError, RekeyProgress};
+use common::crypto::PublicKey;
+use common::prelude::Link;
+use uuid::Uuid;
+
+use crate::database::models::Bucket as BucketModel;
+use crate::sync_manager::SyncEvent;
+use crate::ServiceState;
+
+use super::error::MountOpsError;
+use super::load_mount::load_mount_for_bucket;
+
+/// Revoke a peer's access to a bucket by removing its share
+/// With `rekey`, every directory and file is also re-encrypted under new
+/// secrets, calling `on_progress` as it goes
+/// Returns the new bucket link after removing the share
+pub async fn unshare_bucket(
+    bucket_id: Uuid,
+    peer_public_key: PublicKey,
+    rekey: bool,
+    on_progress: impl FnMut(&RekeyProgress),
+    message: Option<String>,
+    state: &ServiceState,
+) -> Result<Link, MountOpsError> {
+    let _lock = state.lock_bucket(bucket_id).await;
+    let mut mount = load_mount_for_bucket(bucket_id, state).await?;
+    let blobs = state.node().blobs();
+
+    match mount
+        .revoke(&peer_public_key, rekey, blobs, on_progress)
+        .await
+    {
+        Ok(()) => {}
+        Err(MountError::NotPrincipal(_)) => return Err(MountOpsError::ShareNotFound),
+        Err(e) => return Err(MountOpsError::Mount(e)),
+    }
+
+    mount.set_message(message);
+    let new_bucket_link = mount.save(blobs).await?;
+
+    // Update bucket link in database
+    let bucket = BucketModel::get_by_id(&bucket_id, state.database())
+        .await
+        .map_err(|e| MountOpsError::Database(e.to_string()))?
+        .ok_or(MountOpsError::BucketNotFound(bucket_id))?;
+    bucket
+        .update_link(new_bucket_link.clone(), state.database())
+        .await?;
+
+    // Trigger push sync so the remaining peers pick up the new shares
+    tracing::debug!(
+        "Triggering push sync for bucket {} after removing a share",
+        bucket_id
+    );
+    if let Err(e) = state.send_sync_event(SyncEvent::Push {
+        bucket_id,
+        new_link: new_bucket_link.clone(),
+    }) {
+        tracing::warn!(
+            "Failed to trigger push sync for bucket {}: {:?}",
+            bucket_id,
+            e
+        );
+        // Don't fail the request if sync event fails - the share was removed successfully
+    }
+
+    Ok(new_bucket_link)
+}
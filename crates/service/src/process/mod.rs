@@ -12,6 +12,9 @@ use tracing_subscriber::{EnvFilter, Layer};
 
 const FINAL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// How often buckets' retention policies are applied
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 use crate::http_server;
 use crate::sync_manager::SyncManager;
 use crate::{ServiceConfig, ServiceState};
@@ -42,6 +45,12 @@ pub async fn spawn_service(service_config: &ServiceConfig) {
         }
     };
 
+    // Uploads in parts don't survive a restart, so release what the last
+    //  run was holding for them
+    if let Err(e) = crate::mount_ops::abort_multipart_uploads(state.as_ref()).await {
+        tracing::warn!("Failed to release the parts of unfinished uploads: {}", e);
+    }
+
     let mut handles = Vec::new();
 
     // Create sync manager
@@ -87,6 +96,24 @@ pub async fn spawn_service(service_config: &ServiceConfig) {
     });
     handles.push(api_handle);
 
+    // Start the S3-compatible API, if it's configured
+    if let Some(s3_listen_addr) = service_config.s3_listen_addr {
+        if service_config.s3_access_keys.is_empty() {
+            tracing::warn!("No S3 access keys are configured, every S3 request will be refused");
+        }
+        let s3_state = state.as_ref().clone();
+        let s3_config = http_server::Config::new(s3_listen_addr, None, false);
+        let s3_access_keys = service_config.s3_access_keys.clone();
+        let s3_rx = shutdown_rx.clone();
+        let s3_handle = tokio::spawn(async move {
+            tracing::info!("Starting S3 server on {}", s3_listen_addr);
+            if let Err(e) = http_server::run_s3(s3_config, s3_access_keys, s3_state, s3_rx).await {
+                tracing::error!("S3 server error: {}", e);
+            }
+        });
+        handles.push(s3_handle);
+    }
+
     // spawn a router for the node
     let node_state = state.clone();
     let node_rx = shutdown_rx.clone();
@@ -151,6 +178,48 @@ pub async fn spawn_service(service_config: &ServiceConfig) {
     });
     handles.push(periodic_handle);
 
+    // Spawn retention policy enforcer
+    let retention_state = state.clone();
+    let mut retention_rx = shutdown_rx.clone();
+    let retention_handle = tokio::spawn(async move {
+        use crate::mount_ops;
+        use tokio::time::interval;
+
+        let mut interval_timer = interval(RETENTION_INTERVAL);
+        interval_timer.tick().await; // Skip first immediate tick
+
+        tracing::info!("Retention policy enforcer started");
+
+        loop {
+            tokio::select! {
+                _ = interval_timer.tick() => {
+                    tracing::debug!("Applying retention policies");
+
+                    // Mount operations aren't Send, run them in a blocking task
+                    let state = retention_state.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        tokio::runtime::Handle::current()
+                            .block_on(mount_ops::apply_retention_policies(&state))
+                    })
+                    .await;
+                    match result {
+                        Ok(Ok(squashed)) if !squashed.is_empty() => {
+                            tracing::info!("Dropped expired history of {} buckets", squashed.len());
+                        }
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => tracing::error!("Failed to apply retention policies: {}", e),
+                        Err(e) => tracing::error!("Retention policy task failed: {}", e),
+                    }
+                }
+                _ = retention_rx.changed() => {
+                    tracing::info!("Retention policy enforcer shutting down");
+                    break;
+                }
+            }
+        }
+    });
+    handles.push(retention_handle);
+
     let _ = graceful_waiter.await;
 
     if timeout(FINAL_SHUTDOWN_TIMEOUT, join_all(handles))
@@ -1,5 +1,8 @@
-use std::sync::{Arc, OnceLock};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::OwnedMutexGuard;
 use url::Url;
+use uuid::Uuid;
 
 use super::config::Config;
 use super::database::{Database, DatabaseSetupError};
@@ -14,6 +17,8 @@ pub struct State {
     database: Database,
     jax_state: Arc<JaxState>,
     sync_sender: Arc<OnceLock<flume::Sender<SyncEvent>>>,
+    /// Held while a bucket is loaded, changed and saved, one lock per bucket
+    bucket_locks: Arc<Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>>,
 }
 
 impl State {
@@ -43,8 +48,8 @@ impl State {
         let mut node_builder = Peer::builder().protocol_state(jax_state.clone());
 
         // set the socket addr if specified
-        if config.node_listen_addr.is_some() {
-            node_builder = node_builder.socket_addr(config.node_listen_addr.unwrap());
+        if let Some(node_listen_addr) = config.node_listen_addr {
+            node_builder = node_builder.socket_addr(node_listen_addr);
         }
         // attempt to read the secret key if specified
         if config.node_secret.is_some() {
@@ -72,6 +77,7 @@ impl State {
             database,
             jax_state,
             sync_sender: Arc::new(OnceLock::new()),
+            bucket_locks: Arc::default(),
         })
     }
 
@@ -87,6 +93,22 @@ impl State {
         &self.jax_state
     }
 
+    /// Wait until nothing else on this node is changing the bucket, and keep
+    ///  others from changing it until the guard is dropped
+    ///
+    /// Hold it from reading the bucket's link to updating it, so that writes
+    ///  on this node follow one another rather than overwrite one another.
+    pub async fn lock_bucket(&self, bucket_id: Uuid) -> OwnedMutexGuard<()> {
+        let lock = self
+            .bucket_locks
+            .lock()
+            .expect("bucket locks poisoned")
+            .entry(bucket_id)
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
     /// Set the sync event sender (called once during initialization)
     pub fn set_sync_sender(&self, sender: flume::Sender<SyncEvent>) {
         let _ = self.sync_sender.set(sender.clone());
@@ -1,15 +1,17 @@
 use flume::{Receiver, Sender};
 use futures::future::join_all;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::database::models::bucket::BucketError;
 use crate::database::models::{Bucket, SyncStatus};
 use crate::jax_state::MAX_HISTORY_DEPTH;
 use crate::mount_ops;
 use crate::ServiceState;
-use common::bucket::Manifest;
+use common::bucket::{Manifest, Mount};
 use common::crypto::PublicKey;
-use common::linked_data::{BlockEncoded, Link};
+use common::linked_data::{BlockEncoded, CodecError, Link};
 use common::peer::{
     announce_to_peer, fetch_bucket, ping_peer, NodeAddr, SyncStatus as PeerSyncStatus,
 };
@@ -44,12 +46,17 @@ pub struct SyncManager {
 
 /// Result of multi-hop verification when walking a peer's chain
 enum MultiHopOutcome {
-    /// Found a manifest whose previous equals our current link
+    /// The peer's history builds on our current link, walking `depth` versions
     Verified { depth: usize },
-    /// Chain terminated without including our current link
-    Fork,
+    /// The peer's history builds on `base`, an earlier version of ours, but
+    ///  not on our current link
+    Fork { base: Link },
     /// Walk exceeded the configured maximum depth
     DepthExceeded,
+    /// A manifest wasn't signed by a principal of the version it builds on
+    Unauthorized(String),
+    /// A manifest couldn't be decoded, such as one in a newer format
+    Unreadable(String),
 }
 
 impl SyncManager {
@@ -130,13 +137,44 @@ impl SyncManager {
         Ok(shares.iter().any(|share| share.public_key == peer_hex))
     }
 
-    /// Iteratively verify that a peer's latest link chains back to our current link.
+    /// Check the version a bucket we don't have yet is created from
     ///
-    /// Walks the manifest chain from `latest_link` backwards by following `previous`,
-    /// downloading only manifests from the specified peer, until it finds a manifest
-    /// whose `previous` equals `our_current_link`. Returns true if such a link is
-    /// found within `MAX_MULTI_HOP_DEPTH`; returns false on fork/mismatch or when
-    /// the chain terminates without reaching our current link.
+    /// There's no version of the bucket we trust yet, so the first version must
+    /// be signed by one of its own owners, and later versions by a writer or owner
+    /// of the version they build on, downloaded from the peer.
+    async fn verify_genesis(
+        &self,
+        manifest: &Manifest,
+        peer_pub_key: &PublicKey,
+    ) -> anyhow::Result<()> {
+        if let Some(previous) = manifest.previous() {
+            let previous = self.download_from_peer(previous, peer_pub_key).await?;
+            return check_author(manifest, &previous, None, false);
+        }
+        let author = manifest.verify()?;
+        match manifest.role(author) {
+            Some(role) if role.can_share() => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "signed by {}, who is not an owner of the bucket",
+                author.to_hex()
+            )),
+        }
+    }
+
+    /// Verify a peer's latest link against our current link
+    ///
+    /// Walks the manifest history from `latest_link` backwards by following `previous`
+    /// (and `merged`, for merge versions), downloading only manifests from the specified
+    /// peer, until every branch reaches a version in our own history, which we trust.
+    /// History the peer no longer has, having dropped it, ends the branch it's on, but
+    /// a manifest that can't be decoded ends the walk as `Unreadable`.
+    ///
+    /// The versions walked are then checked from the oldest on with [`check_author`],
+    /// each against the parents it builds on that were themselves verified, so no
+    /// version vouches for its own principals. Returns `Verified` if the latest
+    /// version checks out and builds on our current link, `Fork` with the closest
+    /// version of ours it builds on if it checks out but doesn't, and `Unauthorized`
+    /// if it doesn't check out.
     async fn verify_multi_hop(
         &self,
         peer_pub_key: &PublicKey,
@@ -144,32 +182,222 @@ impl SyncManager {
         our_current_link: &Link,
         first_manifest: Option<Manifest>,
     ) -> anyhow::Result<MultiHopOutcome> {
-        let mut cursor = latest_link.clone();
+        let ours = self.our_history(our_current_link).await;
+        let mut queue = VecDeque::from([latest_link.clone()]);
+        let mut seen = HashSet::from([latest_link.clone()]);
         let mut cached = first_manifest;
 
-        for depth in 0..MAX_HISTORY_DEPTH {
+        let mut history = History::default();
+        let mut base = None;
+        while let Some(cursor) = queue.pop_front() {
+            if history.walked.len() >= MAX_HISTORY_DEPTH {
+                return Ok(MultiHopOutcome::DepthExceeded);
+            }
+            // Versions we have were checked when we got them, and
+            //  the history before them is ours
+            if ours.contains(&cursor) {
+                if let Ok(manifest) = self.get_bucket(&cursor).await {
+                    history.trusted.insert(cursor.clone(), manifest);
+                    base.get_or_insert(cursor);
+                }
+                continue;
+            }
+
             // Download or reuse the manifest at the current cursor from the specific peer
             let manifest = match cached.take() {
                 Some(m) => m,
                 None => match self.download_from_peer(&cursor, peer_pub_key).await {
                     Ok(m) => m,
-                    Err(e) => return Err(e),
+                    Err(e) if e.downcast_ref::<CodecError>().is_some() => {
+                        return Ok(MultiHopOutcome::Unreadable(format!(
+                            "{}: {}",
+                            cursor.hash(),
+                            e
+                        )));
+                    }
+                    // History dropped by retention or garbage collection
+                    //  ends this branch of the walk
+                    Err(e) => {
+                        tracing::debug!(
+                            "History ends before {:?}, it isn't available from the peer: {}",
+                            cursor,
+                            e
+                        );
+                        continue;
+                    }
                 },
             };
 
-            match manifest.previous() {
-                Some(prev) if prev == our_current_link => {
-                    return Ok(MultiHopOutcome::Verified { depth })
+            // Continue walking backwards
+            for parent in manifest.parents() {
+                if seen.insert(parent.clone()) {
+                    queue.push_back(parent.clone());
+                }
+            }
+            history.walked.push((cursor, manifest));
+        }
+
+        if let Err(e) = history.verify(latest_link) {
+            return Ok(MultiHopOutcome::Unauthorized(e));
+        }
+        if history.trusted.contains_key(our_current_link) {
+            return Ok(MultiHopOutcome::Verified {
+                depth: history.walked.len(),
+            });
+        }
+        match base {
+            Some(base) => Ok(MultiHopOutcome::Fork { base }),
+            None => Err(anyhow::anyhow!("no common version with the peer")),
+        }
+    }
+
+    /// Collect our own history, up to `MAX_HISTORY_DEPTH` versions, from the local store
+    async fn our_history(&self, our_current_link: &Link) -> HashSet<Link> {
+        let mut ours = HashSet::from([our_current_link.clone()]);
+        let mut queue = VecDeque::from([our_current_link.clone()]);
+        while let Some(cursor) = queue.pop_front() {
+            if ours.len() >= MAX_HISTORY_DEPTH {
+                break;
+            }
+            // History we dropped ends this branch of the walk
+            let Ok(manifest) = self.get_bucket(&cursor).await else {
+                continue;
+            };
+            for parent in manifest.parents() {
+                if ours.insert(parent.clone()) {
+                    queue.push_back(parent.clone());
                 }
-                Some(prev) => {
-                    // Continue walking backwards
-                    cursor = prev.clone();
+            }
+        }
+        ours
+    }
+
+    /// Merge a peer's version that forked from our current one
+    ///
+    /// Applies what the peer changed since `base`, the latest version of ours it
+    /// builds on, on top of our version and saves the result as a merge version,
+    /// which the peer accepts as a successor of its own. Returns the link to the
+    /// merge version, or `None` if the peer's version has nothing we don't already
+    /// have.
+    async fn merge_fork(
+        &self,
+        bucket_id: Uuid,
+        current_link: &Link,
+        new_link: &Link,
+        base: Link,
+        peer_pub_key: &PublicKey,
+        peer_label: &str,
+    ) -> anyhow::Result<Option<Link>> {
+        // The peer is behind us rather than forked
+        if &base == new_link {
+            return Ok(None);
+        }
+
+        // We need the peer's nodes to merge them
+        let their_manifest = self.download_from_peer(new_link, peer_pub_key).await?;
+        self.download_pinset(bucket_id, &their_manifest, peer_pub_key, peer_label)
+            .await;
+
+        // Conflict copies are named after the peer that made them
+        let their_label = short_id(peer_pub_key);
+        let state = self.state.clone();
+        let current_link = current_link.clone();
+        let new_link = new_link.clone();
+
+        // Run mount operations in blocking task
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Option<Link>> {
+            tokio::runtime::Handle::current().block_on(async {
+                let secret_key = state.node().secret();
+                let blobs = state.node().blobs();
+                let mut mount = Mount::load(&current_link, secret_key, blobs).await?;
+                let our_label = short_id(&secret_key.public());
+                if !mount
+                    .merge(&base, &new_link, &our_label, &their_label, blobs)
+                    .await?
+                {
+                    return Ok(None);
                 }
-                None => return Ok(MultiHopOutcome::Fork),
+                Ok(Some(mount.save(blobs).await?))
+            })
+        })
+        .await?
+    }
+
+    /// Best-effort download of the pinset of a peer's manifest
+    async fn download_pinset(
+        &self,
+        bucket_id: Uuid,
+        bucket_data: &Manifest,
+        peer_pub_key: &PublicKey,
+        peer_label: &str,
+    ) {
+        let pins_link = bucket_data.pins();
+        let blobs = self.state.node().blobs();
+        let endpoint = self.state.node().endpoint();
+        let pins_hash = *pins_link.hash();
+        let peer_ids = vec![(*peer_pub_key).into()];
+
+        match blobs
+            .download_hash_list(pins_hash, peer_ids, endpoint)
+            .await
+        {
+            Ok(()) => {
+                tracing::info!(
+                    "Successfully downloaded pinset for bucket {} from peer {}",
+                    bucket_id,
+                    peer_label
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to download pinset for bucket {} from peer {}: {}",
+                    bucket_id,
+                    peer_label,
+                    e
+                );
             }
         }
+    }
 
-        Ok(MultiHopOutcome::DepthExceeded)
+    /// Move the bucket from `current_link`, which the update was verified
+    ///  against, to `new_link` and mark it as synced
+    ///
+    /// If the bucket was changed on this node in the meantime, it's left as
+    ///  it is and pulled again, so the change isn't lost. Returns whether the
+    ///  bucket was updated.
+    async fn advance_link(
+        &self,
+        bucket_id: Uuid,
+        current_link: &Link,
+        new_link: &Link,
+    ) -> anyhow::Result<bool> {
+        let _lock = self.state.lock_bucket(bucket_id).await;
+        let Some(bucket) = Bucket::get_by_id(&bucket_id, self.state.database()).await? else {
+            return Ok(false);
+        };
+        if Link::from(bucket.link) == *current_link {
+            match bucket
+                .update_link_and_sync(new_link.clone(), self.state.database())
+                .await
+            {
+                Ok(()) => return Ok(true),
+                Err(BucketError::Conflict(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        tracing::info!(
+            "Bucket {} changed while it was being synced, pulling it again",
+            bucket_id
+        );
+        if let Err(e) = self.sender.send(SyncEvent::Pull { bucket_id }) {
+            tracing::warn!(
+                "Failed to trigger pull sync for bucket {}: {:?}",
+                bucket_id,
+                e
+            );
+        }
+        Ok(false)
     }
 
     /// Download the peer's latest manifest, verify the chain back to our current link,
@@ -223,18 +451,110 @@ impl SyncManager {
                     depth
                 );
             }
-            Ok(MultiHopOutcome::Fork) => {
+            Ok(MultiHopOutcome::Fork { base }) => {
+                tracing::info!(
+                    "Bucket {} forked from peer {}, merging their version into ours",
+                    bucket_id,
+                    peer_label
+                );
+                let Some(bucket) = Bucket::get_by_id(&bucket_id, self.state.database()).await?
+                else {
+                    return Ok(());
+                };
+                match self
+                    .merge_fork(
+                        bucket_id,
+                        current_link,
+                        new_link,
+                        base,
+                        peer_pub_key,
+                        peer_label,
+                    )
+                    .await
+                {
+                    Ok(Some(merge_link)) => {
+                        if !self
+                            .advance_link(bucket_id, current_link, &merge_link)
+                            .await?
+                        {
+                            return Ok(());
+                        }
+                        tracing::info!(
+                            "Merged fork of bucket {} from peer {} as {:?}",
+                            bucket_id,
+                            peer_label,
+                            merge_link
+                        );
+                        // Announce the merge so the peer can pick it up
+                        if let Err(e) = self.sender.send(SyncEvent::Push {
+                            bucket_id,
+                            new_link: merge_link,
+                        }) {
+                            tracing::warn!(
+                                "Failed to trigger push sync for bucket {}: {:?}",
+                                bucket_id,
+                                e
+                            );
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::info!(
+                            "Peer {} has nothing to merge into bucket {}",
+                            peer_label,
+                            bucket_id
+                        );
+                        bucket
+                            .update_sync_status(SyncStatus::Synced, None, self.state.database())
+                            .await?;
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to merge fork of bucket {} from peer {}: {}",
+                            bucket_id,
+                            peer_label,
+                            e
+                        );
+                        bucket
+                            .update_sync_status(
+                                SyncStatus::Failed,
+                                Some(format!("Failed to merge fork: {}", e)),
+                                self.state.database(),
+                            )
+                            .await?;
+                    }
+                }
+                return Ok(());
+            }
+            Ok(MultiHopOutcome::Unauthorized(reason)) => {
                 tracing::error!(
-                    "Multi-hop verification failed (fork or mismatch) for bucket {}",
-                    bucket_id
+                    "Rejected update for bucket {} from peer {}: {}",
+                    bucket_id,
+                    peer_label,
+                    reason
+                );
+                if let Some(bucket) = Bucket::get_by_id(&bucket_id, self.state.database()).await? {
+                    bucket
+                        .update_sync_status(
+                            SyncStatus::Failed,
+                            Some(format!("Unauthorized manifest {}", reason)),
+                            self.state.database(),
+                        )
+                        .await?;
+                }
+                return Ok(());
+            }
+            Ok(MultiHopOutcome::Unreadable(reason)) => {
+                tracing::error!(
+                    "Can't read update for bucket {} from peer {}: {}",
+                    bucket_id,
+                    peer_label,
+                    reason
                 );
                 if let Some(bucket) = Bucket::get_by_id(&bucket_id, self.state.database()).await? {
                     bucket
                         .update_sync_status(
                             SyncStatus::Failed,
-                            Some(
-                                "Multi-hop verification failed: chain mismatch or fork".to_string(),
-                            ),
+                            Some(format!("Unreadable manifest {}", reason)),
                             self.state.database(),
                         )
                         .await?;
@@ -277,39 +597,13 @@ impl SyncManager {
         }
 
         // 3) Download the pinset for the verified latest
-        let pins_link = bucket_data.pins();
-        let blobs = self.state.node().blobs();
-        let endpoint = self.state.node().endpoint();
-        let pins_hash = *pins_link.hash();
-        let peer_ids = vec![(*peer_pub_key).into()];
-
-        match blobs
-            .download_hash_list(pins_hash, peer_ids, endpoint)
-            .await
-        {
-            Ok(()) => {
-                tracing::info!(
-                    "Successfully downloaded pinset for bucket {} from peer {}",
-                    bucket_id,
-                    peer_label
-                );
-            }
-            Err(e) => {
-                tracing::error!(
-                    "Failed to download pinset for bucket {} from peer {}: {}",
-                    bucket_id,
-                    peer_label,
-                    e
-                );
-                // Do not fail the overall operation on pinset errors
-            }
-        }
+        // Do not fail the overall operation on pinset errors
+        self.download_pinset(bucket_id, &bucket_data, peer_pub_key, peer_label)
+            .await;
 
         // 4) Update the bucket's link and mark as synced
-        if let Some(bucket) = Bucket::get_by_id(&bucket_id, self.state.database()).await? {
-            bucket
-                .update_link_and_sync(new_link.clone(), self.state.database())
-                .await?;
+        if !self.advance_link(bucket_id, current_link, new_link).await? {
+            return Ok(());
         }
 
         tracing::info!(
@@ -345,6 +639,16 @@ impl SyncManager {
             }
         };
 
+        if let Err(e) = self.verify_genesis(&bucket_data, peer_pub_key).await {
+            tracing::error!(
+                "Refusing to create bucket {} from peer {}: {}",
+                bucket_id,
+                peer_label,
+                e
+            );
+            return Ok(());
+        }
+
         let bucket_name = bucket_data.name().to_string();
 
         // Create the bucket
@@ -752,3 +1056,524 @@ impl SyncManager {
             .await
     }
 }
+
+/// The part of a peer's history walked to verify its latest version
+#[derive(Default)]
+struct History {
+    /// Versions of the peer's, closest to its latest first
+    walked: Vec<(Link, Manifest)>,
+    /// Versions of ours the peer's build on
+    trusted: HashMap<Link, Manifest>,
+}
+
+impl History {
+    /// Check every version walked, oldest first, against the parents it builds
+    ///  on that are verified, returning why `latest` doesn't check out if it
+    ///  doesn't
+    fn verify(&self, latest: &Link) -> Result<(), String> {
+        let mut verified: HashMap<&Link, &Manifest> = self.trusted.iter().collect();
+        let mut failed: HashMap<&Link, String> = HashMap::new();
+        let walked: HashSet<&Link> = self.walked.iter().map(|(link, _)| link).collect();
+
+        // Parents come later in the walk than at least one of their children,
+        //  but not necessarily all of them, so go over it until it settles
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for (link, manifest) in self.walked.iter().rev() {
+                if verified.contains_key(link) || failed.contains_key(link) {
+                    continue;
+                }
+                // Wait for the parents that are still to be checked
+                if manifest.parents().any(|parent| {
+                    walked.contains(parent)
+                        && !verified.contains_key(parent)
+                        && !failed.contains_key(parent)
+                }) {
+                    continue;
+                }
+                let parent = |parent: &Option<Link>| {
+                    parent
+                        .as_ref()
+                        .and_then(|parent| verified.get(parent).copied())
+                };
+                let previous = parent(manifest.previous());
+                let merged = parent(manifest.merged());
+                // A version that doesn't build on a previous one can't be
+                //  vouched for by anything but the version it merges in
+                let unverified = manifest.previous().is_none()
+                    || manifest.parents().count() > previous.iter().chain(merged.iter()).count();
+                let result = match previous.or(merged) {
+                    Some(parent) => {
+                        // The author's role comes from the version they built
+                        //  on, or failing that the one they merged in
+                        let other = previous.and(merged);
+                        check_author(manifest, parent, other, unverified)
+                    }
+                    None => Err(anyhow::anyhow!(
+                        "doesn't build on a version that could be verified"
+                    )),
+                };
+                match result {
+                    Ok(()) => {
+                        verified.insert(link, manifest);
+                    }
+                    Err(e) => {
+                        failed.insert(link, format!("{}: {}", link.hash(), e));
+                    }
+                }
+                progress = true;
+            }
+        }
+
+        if verified.contains_key(latest) {
+            return Ok(());
+        }
+        // Blame the oldest version that doesn't check out
+        Err(self
+            .walked
+            .iter()
+            .rev()
+            .find_map(|(link, _)| failed.remove(link))
+            .unwrap_or_else(|| format!("{}: couldn't be verified", latest.hash())))
+    }
+}
+
+/// Check that a manifest is signed, and that its author may make it
+///
+/// The author must be a writer or owner of `parent`, the verified version it builds
+/// on, and only owners may add, remove or change the role of principals. A merge
+/// made by a writer may still carry over principal changes from `other`, the other
+/// verified version it builds on. Building on versions that couldn't be verified,
+/// or on no previous version at all, as `unverified` says the manifest does, is
+/// only allowed to owners, who squash history by rewriting it.
+fn check_author(
+    manifest: &Manifest,
+    parent: &Manifest,
+    other: Option<&Manifest>,
+    unverified: bool,
+) -> anyhow::Result<()> {
+    let author = manifest.verify()?;
+    let Some(role) = parent.role(author) else {
+        return Err(anyhow::anyhow!(
+            "signed by {}, who is not a principal of the previous version",
+            author.to_hex()
+        ));
+    };
+    if !role.can_write() {
+        return Err(anyhow::anyhow!(
+            "signed by {}, who is a {} of the previous version",
+            author.to_hex(),
+            role
+        ));
+    }
+    if role.can_share() {
+        return Ok(());
+    }
+    if unverified {
+        return Err(anyhow::anyhow!(
+            "signed by {}, who is not an owner but builds on history that can't be verified",
+            author.to_hex()
+        ));
+    }
+
+    // every principal change must come from a verified parent
+    let roles = manifest.roles();
+    let parent_roles = parent.roles();
+    let other_roles = other
+        .map(Manifest::roles)
+        .unwrap_or_else(|| parent_roles.clone());
+    let from_a_parent = roles
+        .keys()
+        .chain(parent_roles.keys())
+        .chain(other_roles.keys())
+        .all(|key| {
+            let role = roles.get(key);
+            role == parent_roles.get(key) || role == other_roles.get(key)
+        });
+    if !from_a_parent {
+        return Err(anyhow::anyhow!(
+            "signed by {}, who is not an owner but changed the principals",
+            author.to_hex()
+        ));
+    }
+    Ok(())
+}
+
+/// Short form of a peer's id, used to name conflict copies
+fn short_id(public_key: &PublicKey) -> String {
+    public_key.to_hex().chars().take(8).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::bucket::PrincipalRole;
+    use common::crypto::SecretKey;
+    use std::io::Cursor;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    async fn setup() -> (SyncManager, Receiver<SyncEvent>, TempDir) {
+        let (state, temp_dir) = mount_ops::test_utils::setup().await;
+        let (manager, receiver) = SyncManager::new(Arc::new(state));
+        (manager, receiver, temp_dir)
+    }
+
+    /// Create a bucket owned by the manager's node, shared with `peer` as
+    ///  `role`, returning its ID and current link
+    async fn create_bucket(
+        manager: &SyncManager,
+        peer: &SecretKey,
+        role: PrincipalRole,
+    ) -> (Uuid, Link) {
+        let state = &manager.state;
+        let id = Uuid::new_v4();
+        let blobs = state.node().blobs();
+        let mut mount = Mount::init(id, "test".to_string(), state.node().secret(), blobs)
+            .await
+            .unwrap();
+        mount.share(peer.public(), role).await.unwrap();
+        let link = mount.save(blobs).await.unwrap();
+        Bucket::create(id, "test".to_string(), link.clone(), state.database())
+            .await
+            .unwrap();
+        (id, link)
+    }
+
+    /// Add a file as `author` on top of `link`, returning the new version
+    async fn add_file(manager: &SyncManager, link: &Link, author: &SecretKey, path: &str) -> Link {
+        let blobs = manager.state.node().blobs();
+        let mut mount = Mount::load(link, author, blobs).await.unwrap();
+        mount
+            .add(
+                Path::new(path),
+                Cursor::new(path.as_bytes().to_vec()),
+                blobs,
+            )
+            .await
+            .unwrap();
+        mount.save(blobs).await.unwrap()
+    }
+
+    /// Store a copy of the manifest at `link`, changed by `edit` and signed
+    ///  by `signer`, as a peer making up a version would
+    async fn forge(
+        manager: &SyncManager,
+        link: &Link,
+        signer: &SecretKey,
+        edit: impl FnOnce(&mut Manifest),
+    ) -> Link {
+        let blobs = manager.state.node().blobs();
+        let mut manifest = manager.get_bucket(link).await.unwrap();
+        edit(&mut manifest);
+        manifest.sign(signer).unwrap();
+        Mount::_put_manifest_in_blobs(&manifest, blobs)
+            .await
+            .unwrap()
+    }
+
+    /// Apply a peer's version as if `peer` announced it, returning the
+    ///  bucket afterwards
+    async fn apply(manager: &SyncManager, id: Uuid, new_link: &Link, peer: &SecretKey) -> Bucket {
+        let database = manager.state.database();
+        let current: Link = Bucket::get_by_id(&id, database)
+            .await
+            .unwrap()
+            .unwrap()
+            .link
+            .into();
+        manager
+            .verify_and_apply_update(id, &current, new_link, &peer.public(), "peer")
+            .await
+            .unwrap();
+        Bucket::get_by_id(&id, database).await.unwrap().unwrap()
+    }
+
+    async fn set_link(manager: &SyncManager, id: Uuid, link: &Link) {
+        let database = manager.state.database();
+        let bucket = Bucket::get_by_id(&id, database).await.unwrap().unwrap();
+        bucket.update_link(link.clone(), database).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fast_forward() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        let first = add_file(&manager, &link, &writer, "/a.txt").await;
+        let second = add_file(&manager, &first, &writer, "/b.txt").await;
+
+        let bucket = apply(&manager, id, &second, &writer).await;
+        assert_eq!(Link::from(bucket.link), second);
+        assert_eq!(bucket.sync_status, SyncStatus::Synced);
+    }
+
+    #[tokio::test]
+    async fn test_local_write_during_sync_is_kept() {
+        let (manager, receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        let theirs = add_file(&manager, &link, &writer, "/theirs.txt").await;
+        // Written here after the peer's version was announced against `link`
+        let ours = add_file(&manager, &link, manager.state.node().secret(), "/ours.txt").await;
+        set_link(&manager, id, &ours).await;
+
+        manager
+            .verify_and_apply_update(id, &link, &theirs, &writer.public(), "peer")
+            .await
+            .unwrap();
+        let bucket = Bucket::get_by_id(&id, manager.state.database())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Link::from(bucket.link), ours);
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(SyncEvent::Pull { bucket_id }) if bucket_id == id
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fork_is_merged() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, base) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        let ours = add_file(&manager, &base, manager.state.node().secret(), "/ours.txt").await;
+        set_link(&manager, id, &ours).await;
+        let theirs = add_file(&manager, &base, &writer, "/theirs.txt").await;
+
+        let bucket = apply(&manager, id, &theirs, &writer).await;
+        let merged: Link = bucket.link.into();
+        assert_eq!(bucket.sync_status, SyncStatus::Synced);
+        let manifest = manager.get_bucket(&merged).await.unwrap();
+        assert_eq!(manifest.previous(), &Some(ours));
+        assert_eq!(manifest.merged(), &Some(theirs));
+
+        let blobs = manager.state.node().blobs();
+        let mount = Mount::load(&merged, manager.state.node().secret(), blobs)
+            .await
+            .unwrap();
+        assert_eq!(
+            mount.cat(Path::new("/ours.txt"), blobs).await.unwrap(),
+            b"/ours.txt"
+        );
+        assert_eq!(
+            mount.cat(Path::new("/theirs.txt"), blobs).await.unwrap(),
+            b"/theirs.txt"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_author_rejected() {
+        let (manager, _receiver, _temp) = setup().await;
+        let reader = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &reader, PrincipalRole::Reader).await;
+
+        // Readers can't save versions, so make one up the way a peer could
+        let version = add_file(&manager, &link, manager.state.node().secret(), "/a.txt").await;
+        let forged = forge(&manager, &version, &reader, |_| {}).await;
+
+        let bucket = apply(&manager, id, &forged, &reader).await;
+        assert_eq!(Link::from(bucket.link), link);
+        assert_eq!(bucket.sync_status, SyncStatus::Failed);
+    }
+
+    /// Make up a version that starts a new history, with `peer` as its
+    ///  owner, and merges in the bucket's current one
+    async fn forge_root(manager: &SyncManager, head: &Link, peer: &SecretKey) -> Link {
+        forge(manager, head, peer, |manifest| {
+            manifest
+                .add_share(peer.public(), PrincipalRole::Owner, Default::default())
+                .unwrap();
+            manifest.set_previous(None);
+            manifest.set_merged(Some(head.clone()));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_reader_cannot_make_themselves_owner() {
+        let (manager, _receiver, _temp) = setup().await;
+        let reader = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &reader, PrincipalRole::Reader).await;
+
+        let forged = forge_root(&manager, &link, &reader).await;
+
+        let bucket = apply(&manager, id, &forged, &reader).await;
+        assert_eq!(Link::from(bucket.link), link);
+        assert_eq!(bucket.sync_status, SyncStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_writer_cannot_make_themselves_owner() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        let forged = forge_root(&manager, &link, &writer).await;
+        let bucket = apply(&manager, id, &forged, &writer).await;
+        assert_eq!(Link::from(bucket.link), link);
+        assert_eq!(bucket.sync_status, SyncStatus::Failed);
+
+        // Nor by building on the bucket and claiming to be an owner
+        let forged = forge(&manager, &link, &writer, |manifest| {
+            manifest
+                .add_share(writer.public(), PrincipalRole::Owner, Default::default())
+                .unwrap();
+            manifest.set_previous(Some(link.clone()));
+        })
+        .await;
+        let bucket = apply(&manager, id, &forged, &writer).await;
+        assert_eq!(Link::from(bucket.link), link);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_must_be_signed_by_an_owner() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (_, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+        let peer = manager.state.node().secret().public();
+
+        let genesis = forge(&manager, &link, &writer, |manifest| {
+            manifest.set_previous(None);
+        })
+        .await;
+        let manifest = manager.get_bucket(&genesis).await.unwrap();
+        assert!(manager.verify_genesis(&manifest, &peer).await.is_err());
+
+        let owned = forge(&manager, &genesis, manager.state.node().secret(), |_| {}).await;
+        let manifest = manager.get_bucket(&owned).await.unwrap();
+        manager.verify_genesis(&manifest, &peer).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_cannot_merge_in_forged_owner() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        // A made up history where the writer is an owner, merged into a
+        //  version building on ours to carry over its principals
+        let root = forge_root(&manager, &link, &writer).await;
+        let forged = forge(&manager, &link, &writer, |manifest| {
+            manifest
+                .add_share(writer.public(), PrincipalRole::Owner, Default::default())
+                .unwrap();
+            manifest.set_previous(Some(link.clone()));
+            manifest.set_merged(Some(root.clone()));
+        })
+        .await;
+
+        let bucket = apply(&manager, id, &forged, &writer).await;
+        assert_eq!(Link::from(bucket.link), link);
+        assert_eq!(bucket.sync_status, SyncStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_writer_merge_carries_owner_changes() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let owner = SecretKey::generate();
+        let reader = SecretKey::generate();
+        let (id, base) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+        let base = {
+            let blobs = manager.state.node().blobs();
+            let mut mount = Mount::load(&base, manager.state.node().secret(), blobs)
+                .await
+                .unwrap();
+            mount
+                .share(owner.public(), PrincipalRole::Owner)
+                .await
+                .unwrap();
+            mount.save(blobs).await.unwrap()
+        };
+        set_link(&manager, id, &base).await;
+
+        // Another owner shares the bucket while the writer adds a file,
+        //  then the writer merges the two
+        let blobs = manager.state.node().blobs();
+        let mut mount = Mount::load(&base, &owner, blobs).await.unwrap();
+        mount
+            .share(reader.public(), PrincipalRole::Reader)
+            .await
+            .unwrap();
+        let shared = mount.save(blobs).await.unwrap();
+        let written = add_file(&manager, &base, &writer, "/a.txt").await;
+        let mut mount = Mount::load(&written, &writer, blobs).await.unwrap();
+        assert!(mount
+            .merge(&base, &shared, "writer", "owner", blobs)
+            .await
+            .unwrap());
+        let merged = mount.save(blobs).await.unwrap();
+
+        let bucket = apply(&manager, id, &merged, &writer).await;
+        assert_eq!(Link::from(bucket.link), merged);
+        assert_eq!(bucket.sync_status, SyncStatus::Synced);
+        let manifest = manager.get_bucket(&merged).await.unwrap();
+        assert_eq!(manifest.role(&reader.public()), Some(PrincipalRole::Reader));
+    }
+
+    #[tokio::test]
+    async fn test_squashed_history_accepted() {
+        let (manager, _receiver, _temp) = setup().await;
+        let owner = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &owner, PrincipalRole::Owner).await;
+        let first = add_file(&manager, &link, &owner, "/a.txt").await;
+        let second = add_file(&manager, &first, &owner, "/b.txt").await;
+        set_link(&manager, id, &second).await;
+
+        let blobs = manager.state.node().blobs();
+        let mut mount = Mount::load(&second, &owner, blobs).await.unwrap();
+        let squashed = mount.squash(1, blobs).await.unwrap().unwrap();
+        let bucket = apply(&manager, id, &squashed, &owner).await;
+        assert_eq!(Link::from(bucket.link), squashed);
+        assert_eq!(bucket.sync_status, SyncStatus::Synced);
+
+        // Keeping no history at all leaves nothing before the new version
+        let squashed = mount.squash(0, blobs).await.unwrap().unwrap();
+        assert_eq!(
+            manager.get_bucket(&squashed).await.unwrap().previous(),
+            &None
+        );
+        let bucket = apply(&manager, id, &squashed, &owner).await;
+        assert_eq!(Link::from(bucket.link), squashed);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_history() {
+        let (manager, _receiver, _temp) = setup().await;
+        let writer = SecretKey::generate();
+        let (id, link) = create_bucket(&manager, &writer, PrincipalRole::Writer).await;
+
+        // A manifest in a format newer than we read, `{"format": 99}`
+        let blobs = manager.state.node().blobs();
+        let hash = blobs.put(b"\xa1\x66format\x18\x63".to_vec()).await.unwrap();
+        let newer = Link::new(*link.codec(), hash, *link.format());
+        let version = add_file(&manager, &link, &writer, "/a.txt").await;
+        let forged = forge(&manager, &version, &writer, |manifest| {
+            manifest.set_previous(Some(newer.clone()));
+        })
+        .await;
+
+        let outcome = manager
+            .verify_multi_hop(&writer.public(), &forged, &link, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcome,
+            MultiHopOutcome::Unreadable(reason) if reason.contains("unsupported format 99")
+        ));
+
+        let bucket = apply(&manager, id, &forged, &writer).await;
+        assert_eq!(Link::from(bucket.link), link);
+        assert_eq!(bucket.sync_status, SyncStatus::Failed);
+        assert!(bucket
+            .sync_error
+            .unwrap()
+            .starts_with("Unreadable manifest"));
+    }
+}
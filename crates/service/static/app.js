@@ -58,6 +58,7 @@ const FileUpload = {
 
             const fileInput = document.getElementById('fileInput');
             const pathInput = document.getElementById('pathInput');
+            const messageInput = document.getElementById('messageInput');
             const status = document.getElementById('uploadStatus');
 
             if (!fileInput.files.length) {
@@ -74,6 +75,10 @@ const FileUpload = {
             const formData = new FormData();
             formData.append('bucket_id', bucketId);
             formData.append('mount_path', mountPath);
+            // Fields are read in order, so the message has to come before the file
+            if (messageInput && messageInput.value.trim()) {
+                formData.append('message', messageInput.value.trim());
+            }
             formData.append('file', file);
 
             this.showStatus(status, 'Uploading...', 'info');